@@ -0,0 +1,133 @@
+//! HTML の字句解析・ツリー構築のスループット
+//!
+//! `cargo bench --bench html_parser`で実行します。入力の大きさを`Throughput::Bytes`で
+//! 指定しているので、結果は 1 秒あたりに処理したバイト数でも表示されます。
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use orinium_browser::engine::html::parser::Parser;
+use orinium_browser::engine::html::tokenizer::Tokenizer;
+
+/// 各入力のおおよその大きさ
+const TARGET_LEN: usize = 1 << 20;
+
+/// `unit`を`TARGET_LEN`に届くまで繰り返した文書
+fn repeat_to_target(head: &str, unit: &str) -> String {
+    let mut html = String::from(head);
+    while html.len() < TARGET_LEN {
+        html.push_str(unit);
+    }
+    html
+}
+
+/// ベンチマークに使う入力（名前と HTML）
+fn inputs() -> Vec<(&'static str, String)> {
+    let test_page = include_str!("../contents/pages/test/testpage.html");
+    let (head, body) = test_page
+        .split_once("<body>")
+        .expect("testpage.html has <body>");
+    let body = body.split("</body>").next().unwrap_or(body);
+
+    vec![
+        // 実際のページに近い、タグ・テキスト・スクリプトが混ざった文書
+        ("page", repeat_to_target(&format!("{head}<body>"), body)),
+        // 長いテキストの段落
+        (
+            "text",
+            repeat_to_target(
+                "<!DOCTYPE html><body>",
+                &format!(
+                    "<p>{}</p>\n",
+                    "The quick brown fox jumps over the lazy dog. ".repeat(40)
+                ),
+            ),
+        ),
+        // 属性の多い要素
+        (
+            "attributes",
+            repeat_to_target(
+                "<!DOCTYPE html><body>",
+                "<div id=item class=\"card card-large\" data-index=42 style='color: red' \
+                 title=\"An item\" hidden><a href=\"https://example.com/path?a=1&b=2\" \
+                 target=_blank rel=\"noopener noreferrer\">link</a><img src=a.png alt=\"\" \
+                 width=100 height=100 loading=lazy></div>\n",
+            ),
+        ),
+        // 文字参照の多いテキスト
+        (
+            "entities",
+            repeat_to_target(
+                "<!DOCTYPE html><body>",
+                "<p>&lt;tag&gt; &amp; &quot;quoted&quot; &copy; 2024 &#x41;&#66; &nbsp;&hellip; \
+                 &notin; &eacute;t&eacute;</p>\n",
+            ),
+        ),
+        // 改行を取り除いた（ミニファイされた）1 行だけの文書
+        (
+            "minified",
+            repeat_to_target(&format!("{head}<body>"), body).replace('\n', ""),
+        ),
+        // RAWTEXT / script data として読む要素
+        (
+            "scripts",
+            repeat_to_target(
+                "<!DOCTYPE html><head>",
+                "<script>for (let i = 0; i < items.length; i++) { if (a < b && c > d) \
+                 { render('<p>' + items[i] + '</p>'); } }</script>\n<style>.a > .b { color: red; }\
+                 </style>\n",
+            ),
+        ),
+    ]
+}
+
+fn tokenizer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tokenizer");
+    for (name, html) in inputs() {
+        group.throughput(Throughput::Bytes(html.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &html, |b, html| {
+            b.iter(|| {
+                let mut tokenizer = Tokenizer::new(black_box(html));
+                let mut count = 0;
+                while tokenizer.next_token().is_some() {
+                    count += 1;
+                }
+                count
+            })
+        });
+    }
+    group.finish();
+}
+
+fn parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+    group.sample_size(20);
+    for (name, html) in inputs() {
+        group.throughput(Throughput::Bytes(html.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &html, |b, html| {
+            b.iter(|| Parser::new(black_box(html)).parse())
+        });
+    }
+    group.finish();
+}
+
+/// ネットワークから届くように、入力を小さな塊に分けて渡す場合
+fn streaming_parser(c: &mut Criterion) {
+    const CHUNK_SIZE: usize = 4096;
+
+    let mut group = c.benchmark_group("streaming_parser");
+    group.sample_size(20);
+    let (name, html) = inputs().swap_remove(0);
+    group.throughput(Throughput::Bytes(html.len() as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(name), &html, |b, html| {
+        b.iter(|| {
+            let mut parser = Parser::new_streaming();
+            for chunk in black_box(html).as_bytes().chunks(CHUNK_SIZE) {
+                parser.feed(chunk);
+            }
+            parser.finish()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, tokenizer, parser, streaming_parser);
+criterion_main!(benches);
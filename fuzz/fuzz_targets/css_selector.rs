@@ -0,0 +1,21 @@
+//! CSS セレクターの構文解析と照合のファジング
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orinium_browser::engine::css::selector::SelectorList;
+use orinium_browser::engine::html::parser::Parser;
+
+const DOCUMENT: &str = "<!DOCTYPE html><div id=a class='b c'><p lang=en-US>x</p>\
+    <ul><li><a href=/>1</a><li><input type=checkbox checked disabled></ul>\
+    <svg><rect/></svg></div>";
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(list) = SelectorList::parse(&String::from_utf8_lossy(data)) else {
+        return;
+    };
+    let document = Parser::new(DOCUMENT).parse();
+    for element in document.borrow().descendant_elements() {
+        list.matches(&element.borrow());
+    }
+});
@@ -0,0 +1,40 @@
+//! 字句解析器のファジング
+//!
+//! 先頭の1バイトで開始状態と区切り方を決め、残りを入力として、一度に与えた場合と
+//! 少しずつ`feed`した場合の両方でトークンを最後まで取り出します。
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orinium_browser::engine::html::tokenizer::{Tokenizer, TokenizerState};
+
+const STATES: [TokenizerState; 5] = [
+    TokenizerState::Data,
+    TokenizerState::Rcdata,
+    TokenizerState::Rawtext,
+    TokenizerState::ScriptData,
+    TokenizerState::Plaintext,
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&control, input)) = data.split_first() else {
+        return;
+    };
+    let state = STATES[usize::from(control) % STATES.len()];
+    let chunk_size = usize::from(control >> 3) + 1;
+
+    let text = String::from_utf8_lossy(input);
+    let mut tokenizer = Tokenizer::new(&text);
+    tokenizer.switch_to(state);
+    tokenizer.set_last_start_tag("title");
+    while tokenizer.next_token().is_some() {}
+
+    let mut tokenizer = Tokenizer::new_streaming();
+    tokenizer.switch_to(state);
+    for chunk in input.chunks(chunk_size) {
+        tokenizer.feed(chunk);
+        while tokenizer.next_token().is_some() {}
+    }
+    tokenizer.finish();
+    while tokenizer.next_token().is_some() {}
+});
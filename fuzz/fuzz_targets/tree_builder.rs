@@ -0,0 +1,33 @@
+//! ツリー構築のファジング
+//!
+//! 入力を少しずつ`feed`して文書を組み立て、書き出した HTML をもう一度解析します。
+//! 先頭の1バイトで区切り方と、断片として解析する場合の文脈要素を決めます。
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orinium_browser::engine::html::namespace::Namespace;
+use orinium_browser::engine::html::parser::{Node, Parser};
+
+const CONTEXTS: [&str; 8] = [
+    "body", "table", "tr", "select", "template", "textarea", "script", "html",
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&control, input)) = data.split_first() else {
+        return;
+    };
+    let chunk_size = usize::from(control >> 3) + 1;
+
+    let mut parser = Parser::new_streaming();
+    for chunk in input.chunks(chunk_size) {
+        parser.feed(chunk);
+    }
+    let document = parser.finish();
+    let html = document.borrow().inner_html();
+    Parser::new(&html).parse();
+
+    let context = CONTEXTS[usize::from(control) % CONTEXTS.len()];
+    let context = Node::new_element(Namespace::Html, context, vec![]);
+    Parser::new_fragment(&String::from_utf8_lossy(input), &context).parse_fragment();
+});
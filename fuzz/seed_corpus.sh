@@ -0,0 +1,19 @@
+#!/bin/sh
+# contents/pages の HTML をファジングの初期コーパスにコピーします
+# 使い方: リポジトリのルートで `sh fuzz/seed_corpus.sh`
+set -eu
+
+for target in tokenizer tree_builder; do
+    mkdir -p "fuzz/corpus/$target"
+    find contents/pages -name '*.html' | while read -r page; do
+        # 先頭の1バイトは制御に使われるので、データ状態・body 文脈・31 バイトずつの区切りになる値を付ける
+        name=$(echo "$page" | tr '/' '_')
+        { printf '\360'; cat "$page"; } > "fuzz/corpus/$target/$name"
+    done
+done
+
+mkdir -p fuzz/corpus/css_selector
+for selector in 'div > p' '#a.b:not(.c)' 'li:nth-child(2n+1) a[href^="/"]' \
+    ':is(p, ul) ~ svg rect' 'div:has(> p[lang|=en i])' 'input:checked:disabled'; do
+    printf '%s' "$selector" > "fuzz/corpus/css_selector/$(printf '%s' "$selector" | cksum | cut -d' ' -f1)"
+done
@@ -0,0 +1,902 @@
+//! CSS セレクター
+//!
+//! Selectors Level 4 のうち、DOM の検索（`querySelector`）に必要な範囲を解析し、要素と照合します。
+//! 対応するのは型・全称・ID・クラス・属性セレクター、4種類の結合子、構造擬似クラスと
+//! `:not()`・`:is()`・`:has()`などの論理擬似クラスです。擬似要素には対応しません。
+//! https://drafts.csswg.org/selectors-4/
+
+use std::error::Error;
+use std::fmt;
+
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::parser::{Node, NodeRef, NodeType};
+
+/// セレクターの構文エラーの種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorErrorKind {
+    /// セレクターが空（`a,,b`など）
+    EmptySelector,
+    /// セレクターの途中で入力が終わった
+    UnexpectedEnd,
+    /// その位置に置けない文字
+    UnexpectedCharacter(char),
+    /// `:nth-child()`などの引数が`an+b`の形式でない
+    InvalidNth,
+    /// 対応していない擬似クラス
+    UnsupportedPseudoClass(String),
+    /// 擬似要素（`::before`など）。要素の検索では使えない
+    UnsupportedPseudoElement(String),
+    /// `:not()`などの入れ子が深すぎる
+    NestingTooDeep,
+}
+
+impl fmt::Display for SelectorErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectorErrorKind::EmptySelector => f.write_str("empty selector"),
+            SelectorErrorKind::UnexpectedEnd => f.write_str("unexpected end of selector"),
+            SelectorErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character {c:?}"),
+            SelectorErrorKind::InvalidNth => f.write_str("invalid an+b expression"),
+            SelectorErrorKind::UnsupportedPseudoClass(name) => {
+                write!(f, "unsupported pseudo-class :{name}")
+            }
+            SelectorErrorKind::UnsupportedPseudoElement(name) => {
+                write!(f, "unsupported pseudo-element ::{name}")
+            }
+            SelectorErrorKind::NestingTooDeep => f.write_str("selector nesting too deep"),
+        }
+    }
+}
+
+/// 位置つきのセレクターの構文エラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorError {
+    pub kind: SelectorErrorKind,
+    /// エラーを検出した位置（文字単位のオフセット）
+    pub position: usize,
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.kind)
+    }
+}
+
+impl Error for SelectorError {}
+
+/// セレクターリスト（`h1, .title > a`など、カンマ区切りのセレクター）
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorList {
+    selectors: Vec<ComplexSelector>,
+}
+
+/// 結合子でつないだセレクター
+///
+/// 照合は右端から行うので、`compounds`は右から左の順に並べます。結合子は左隣の複合セレクター
+/// との関係で、左端の要素の結合子は使いません。
+#[derive(Debug, Clone, PartialEq)]
+struct ComplexSelector {
+    compounds: Vec<(Combinator, CompoundSelector)>,
+}
+
+/// 結合子（左側の複合セレクターとの関係）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// 空白（子孫）
+    Descendant,
+    /// `>`（子）
+    Child,
+    /// `+`（直後の兄弟）
+    NextSibling,
+    /// `~`（後続の兄弟）
+    SubsequentSibling,
+}
+
+/// 複合セレクター（`a.link[href]`など、間に結合子を含まない単純セレクターの並び）
+#[derive(Debug, Clone, PartialEq)]
+struct CompoundSelector {
+    simple: Vec<SimpleSelector>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SimpleSelector {
+    Universal,
+    Type(String),
+    Id(String),
+    Class(String),
+    Attribute {
+        name: String,
+        /// 値の条件。`[name]`のように存在だけを調べる場合は`None`
+        matcher: Option<(AttributeOperator, String)>,
+        /// `i`フラグ（値を ASCII の大文字小文字を区別せずに比べる）
+        case_insensitive: bool,
+    },
+    PseudoClass(PseudoClass),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeOperator {
+    /// `=`
+    Equals,
+    /// `~=`（空白区切りの語のいずれか）
+    Includes,
+    /// `|=`（値そのもの、または値に`-`が続くもの）
+    DashMatch,
+    /// `^=`
+    Prefix,
+    /// `$=`
+    Suffix,
+    /// `*=`
+    Substring,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PseudoClass {
+    Root,
+    Empty,
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    OnlyOfType,
+    NthChild(Nth),
+    NthLastChild(Nth),
+    NthOfType(Nth),
+    NthLastOfType(Nth),
+    /// `:not()`
+    Not(SelectorList),
+    /// `:is()`と`:where()`（詳細度を扱わないため同じもの）
+    Is(SelectorList),
+    /// `:has()`（引数は相対セレクター。結合子を省略した場合は子孫）
+    Has(Vec<(Combinator, ComplexSelector)>),
+    /// `:link`と`:any-link`（`href`を持つ`a`と`area`）
+    Link,
+    Checked,
+    Disabled,
+    Enabled,
+}
+
+/// `an+b`（1から数えた位置`i`が`i = a*n + b`（`n >= 0`）を満たすか）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Nth {
+    a: i32,
+    b: i32,
+}
+
+impl Nth {
+    fn matches(&self, index: i32) -> bool {
+        if self.a == 0 {
+            return index == self.b;
+        }
+        let diff = index - self.b;
+        diff % self.a == 0 && diff / self.a >= 0
+    }
+}
+
+impl SelectorList {
+    /// セレクターリストを解析します
+    ///
+    /// # 引数
+    /// * `input` - セレクターの文字列（`div > p.note, #main a[href^="https:"]`など）
+    pub fn parse(input: &str) -> Result<Self, SelectorError> {
+        let mut parser = SelectorParser {
+            input: input.chars().collect(),
+            pos: 0,
+            depth: 0,
+        };
+        let list = parser.parse_selector_list()?;
+        if let Some(c) = parser.peek() {
+            return Err(parser.error(SelectorErrorKind::UnexpectedCharacter(c)));
+        }
+        Ok(list)
+    }
+
+    /// 要素がいずれかのセレクターに一致するかを返します（要素以外のノードは一致しません）
+    pub fn matches(&self, node: &Node) -> bool {
+        is_element(node) && self.selectors.iter().any(|s| s.matches(node))
+    }
+}
+
+impl ComplexSelector {
+    fn matches(&self, node: &Node) -> bool {
+        matches_from(&self.compounds, node, None)
+    }
+}
+
+/// 右から左に並んだ複合セレクターを`node`から照合します
+///
+/// 子孫・後続の兄弟の結合子は、一致する候補が見つかるまで左側をたどり直します。
+///
+/// # 引数
+/// * `anchor` - `:has()`の照合で、左端（空の複合セレクター）に当たるべき要素
+fn matches_from(
+    compounds: &[(Combinator, CompoundSelector)],
+    node: &Node,
+    anchor: Option<&Node>,
+) -> bool {
+    let Some(((combinator, compound), rest)) = compounds.split_first() else {
+        return true;
+    };
+    if let (true, Some(anchor)) = (rest.is_empty(), anchor) {
+        return std::ptr::eq(node, anchor);
+    }
+    if !compound.matches(node) {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+    let matches_rest = |next: &NodeRef| matches_from(rest, &next.borrow(), anchor);
+    match combinator {
+        Combinator::Child => parent_element(node).is_some_and(|parent| matches_rest(&parent)),
+        Combinator::Descendant => {
+            let mut ancestor = parent_element(node);
+            while let Some(current) = ancestor {
+                if matches_rest(&current) {
+                    return true;
+                }
+                ancestor = parent_element(&current.borrow());
+            }
+            false
+        }
+        Combinator::NextSibling => {
+            previous_element_sibling(node).is_some_and(|sibling| matches_rest(&sibling))
+        }
+        Combinator::SubsequentSibling => {
+            let mut sibling = previous_element_sibling(node);
+            while let Some(current) = sibling {
+                if matches_rest(&current) {
+                    return true;
+                }
+                sibling = previous_element_sibling(&current.borrow());
+            }
+            false
+        }
+    }
+}
+
+impl CompoundSelector {
+    fn matches(&self, node: &Node) -> bool {
+        self.simple.iter().all(|simple| simple.matches(node))
+    }
+}
+
+impl SimpleSelector {
+    fn matches(&self, node: &Node) -> bool {
+        let NodeType::Element {
+            tag_name,
+            namespace,
+            ..
+        } = &node.node_type
+        else {
+            return false;
+        };
+        match self {
+            SimpleSelector::Universal => true,
+            SimpleSelector::Type(name) => name_matches(*namespace, name, tag_name),
+            SimpleSelector::Id(id) => node.attribute("id") == Some(id.as_str()),
+            SimpleSelector::Class(class) => node
+                .attribute("class")
+                .is_some_and(|value| value.split_ascii_whitespace().any(|c| c == class)),
+            SimpleSelector::Attribute {
+                name,
+                matcher,
+                case_insensitive,
+            } => {
+                let Some(value) = attribute(node, name) else {
+                    return false;
+                };
+                let Some((operator, expected)) = matcher else {
+                    return true;
+                };
+                let (value, expected) = if *case_insensitive {
+                    (value.to_ascii_lowercase(), expected.to_ascii_lowercase())
+                } else {
+                    (value.to_string(), expected.clone())
+                };
+                match operator {
+                    AttributeOperator::Equals => value == expected,
+                    AttributeOperator::Includes => {
+                        !expected.is_empty()
+                            && value.split_ascii_whitespace().any(|word| word == expected)
+                    }
+                    AttributeOperator::DashMatch => {
+                        value == expected
+                            || value
+                                .strip_prefix(expected.as_str())
+                                .is_some_and(|rest| rest.starts_with('-'))
+                    }
+                    AttributeOperator::Prefix => {
+                        !expected.is_empty() && value.starts_with(&expected)
+                    }
+                    AttributeOperator::Suffix => !expected.is_empty() && value.ends_with(&expected),
+                    AttributeOperator::Substring => {
+                        !expected.is_empty() && value.contains(&expected)
+                    }
+                }
+            }
+            SimpleSelector::PseudoClass(pseudo) => pseudo.matches(node, *namespace, tag_name),
+        }
+    }
+}
+
+impl PseudoClass {
+    fn matches(&self, node: &Node, namespace: Namespace, tag_name: &str) -> bool {
+        let is_html = |names: &[&str]| namespace == Namespace::Html && names.contains(&tag_name);
+        match self {
+            PseudoClass::Root => node
+                .parent()
+                .is_some_and(|parent| matches!(parent.borrow().node_type, NodeType::Document)),
+            PseudoClass::Empty => {
+                node.children
+                    .iter()
+                    .all(|child| match &child.borrow().node_type {
+                        NodeType::Element { .. } => false,
+                        NodeType::Text(text) => text.is_empty(),
+                        _ => true,
+                    })
+            }
+            PseudoClass::FirstChild => previous_element_sibling(node).is_none(),
+            PseudoClass::LastChild => next_element_sibling(node).is_none(),
+            PseudoClass::OnlyChild => {
+                previous_element_sibling(node).is_none() && next_element_sibling(node).is_none()
+            }
+            PseudoClass::FirstOfType => count_siblings(node, previous_element_sibling, true) == 0,
+            PseudoClass::LastOfType => count_siblings(node, next_element_sibling, true) == 0,
+            PseudoClass::OnlyOfType => {
+                count_siblings(node, previous_element_sibling, true) == 0
+                    && count_siblings(node, next_element_sibling, true) == 0
+            }
+            PseudoClass::NthChild(nth) => {
+                nth.matches(count_siblings(node, previous_element_sibling, false) + 1)
+            }
+            PseudoClass::NthLastChild(nth) => {
+                nth.matches(count_siblings(node, next_element_sibling, false) + 1)
+            }
+            PseudoClass::NthOfType(nth) => {
+                nth.matches(count_siblings(node, previous_element_sibling, true) + 1)
+            }
+            PseudoClass::NthLastOfType(nth) => {
+                nth.matches(count_siblings(node, next_element_sibling, true) + 1)
+            }
+            PseudoClass::Not(list) => !list.matches(node),
+            PseudoClass::Is(list) => list.matches(node),
+            PseudoClass::Has(relative) => relative
+                .iter()
+                .any(|(combinator, selector)| has_relative(node, *combinator, selector)),
+            PseudoClass::Link => is_html(&["a", "area"]) && node.attribute("href").is_some(),
+            PseudoClass::Checked => {
+                (is_html(&["input"])
+                    && node.attribute("checked").is_some()
+                    && node.attribute("type").is_some_and(|t| {
+                        t.eq_ignore_ascii_case("checkbox") || t.eq_ignore_ascii_case("radio")
+                    }))
+                    || (is_html(&["option"]) && node.attribute("selected").is_some())
+            }
+            PseudoClass::Disabled => is_html(FORM_CONTROLS) && node.attribute("disabled").is_some(),
+            PseudoClass::Enabled => is_html(FORM_CONTROLS) && node.attribute("disabled").is_none(),
+        }
+    }
+}
+
+/// `:disabled`・`:enabled`の対象になる要素
+const FORM_CONTROLS: &[&str] = &[
+    "button", "input", "select", "textarea", "optgroup", "option", "fieldset",
+];
+
+/// `:has()`の相対セレクターに一致する要素が`node`から見た位置にあるか
+fn has_relative(node: &Node, combinator: Combinator, selector: &ComplexSelector) -> bool {
+    // 相対セレクターの左端を結合子で`node`につなぎ、候補の要素から照合する
+    let mut compounds = selector.compounds.clone();
+    if let Some(leftmost) = compounds.last_mut() {
+        leftmost.0 = combinator;
+    }
+    compounds.push((combinator, CompoundSelector { simple: vec![] }));
+
+    // 候補は、子孫の結合子なら`node`の子孫、兄弟の結合子なら後続の兄弟とその子孫
+    let mut stack: Vec<NodeRef> = match combinator {
+        Combinator::Descendant | Combinator::Child => node.children.iter().rev().cloned().collect(),
+        Combinator::NextSibling | Combinator::SubsequentSibling => {
+            let mut siblings = vec![];
+            let mut sibling = next_element_sibling(node);
+            while let Some(current) = sibling {
+                sibling = next_element_sibling(&current.borrow());
+                siblings.push(current);
+            }
+            siblings.reverse();
+            siblings
+        }
+    };
+    while let Some(current) = stack.pop() {
+        let current = current.borrow();
+        if is_element(&current) && matches_from(&compounds, &current, Some(node)) {
+            return true;
+        }
+        stack.extend(current.children.iter().rev().cloned());
+    }
+    false
+}
+
+fn is_element(node: &Node) -> bool {
+    matches!(node.node_type, NodeType::Element { .. })
+}
+
+/// 型セレクターの名前がタグ名に一致するか（HTMLの要素は大文字小文字を区別しない）
+fn name_matches(namespace: Namespace, name: &str, tag_name: &str) -> bool {
+    if namespace == Namespace::Html {
+        name.eq_ignore_ascii_case(tag_name)
+    } else {
+        name == tag_name
+    }
+}
+
+/// 属性値を返します（HTMLの要素は属性名の大文字小文字を区別しない）
+fn attribute<'a>(node: &'a Node, name: &str) -> Option<&'a str> {
+    let NodeType::Element {
+        attributes,
+        namespace,
+        ..
+    } = &node.node_type
+    else {
+        return None;
+    };
+    attributes
+        .iter()
+        .find(|attr| {
+            if *namespace == Namespace::Html {
+                attr.name.eq_ignore_ascii_case(name)
+            } else {
+                attr.name == name
+            }
+        })
+        .map(|attr| attr.value.as_ref())
+}
+
+fn parent_element(node: &Node) -> Option<NodeRef> {
+    node.parent().filter(|parent| is_element(&parent.borrow()))
+}
+
+fn previous_element_sibling(node: &Node) -> Option<NodeRef> {
+    let mut sibling = node.previous_sibling();
+    while let Some(current) = sibling {
+        if is_element(&current.borrow()) {
+            return Some(current);
+        }
+        sibling = current.borrow().previous_sibling();
+    }
+    None
+}
+
+fn next_element_sibling(node: &Node) -> Option<NodeRef> {
+    let mut sibling = node.next_sibling();
+    while let Some(current) = sibling {
+        if is_element(&current.borrow()) {
+            return Some(current);
+        }
+        sibling = current.borrow().next_sibling();
+    }
+    None
+}
+
+/// `step`の方向にある兄弟要素を数えます
+///
+/// # 引数
+/// * `same_type` - `node`と同じタグ名・名前空間の要素だけを数えるか
+fn count_siblings(node: &Node, step: fn(&Node) -> Option<NodeRef>, same_type: bool) -> i32 {
+    let mut count = 0;
+    let mut sibling = step(node);
+    while let Some(current) = sibling {
+        let current = current.borrow();
+        if !same_type
+            || (current.tag_name() == node.tag_name() && current.namespace() == node.namespace())
+        {
+            count += 1;
+        }
+        sibling = step(&current);
+    }
+    count
+}
+
+// -------------------------------------------------------------------------
+// 構文解析
+// -------------------------------------------------------------------------
+
+/// `:not()`などの引数にセレクターを入れ子にできる深さの上限
+///
+/// 構文解析と照合はどちらも入れ子を再帰でたどるので、深すぎる入力でスタックを使い切らないようにします。
+const MAX_NESTING_DEPTH: usize = 32;
+
+struct SelectorParser {
+    input: Vec<char>,
+    pos: usize,
+    /// 解析中の擬似クラスの入れ子の深さ
+    depth: usize,
+}
+
+impl SelectorParser {
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    fn error(&self, kind: SelectorErrorKind) -> SelectorError {
+        SelectorError {
+            kind,
+            position: self.pos,
+        }
+    }
+
+    fn unexpected(&self) -> SelectorError {
+        match self.peek() {
+            Some(c) => self.error(SelectorErrorKind::UnexpectedCharacter(c)),
+            None => self.error(SelectorErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SelectorError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// 空白を読み飛ばし、読み飛ばしたかを返します
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    /// カンマ区切りのセレクターを、入力の終わりか`)`の手前まで読みます
+    fn parse_selector_list(&mut self) -> Result<SelectorList, SelectorError> {
+        let mut selectors = vec![];
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.parse_complex_selector()?);
+            self.skip_whitespace();
+            if self.peek() != Some(',') {
+                return Ok(SelectorList { selectors });
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn parse_complex_selector(&mut self) -> Result<ComplexSelector, SelectorError> {
+        let mut compounds = vec![(Combinator::Descendant, self.parse_compound_selector()?)];
+        loop {
+            let whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                None | Some(',') | Some(')') => break,
+                Some(_) if whitespace => Combinator::Descendant,
+                Some(_) => return Err(self.unexpected()),
+            };
+            if combinator != Combinator::Descendant {
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+            compounds.push((combinator, self.parse_compound_selector()?));
+        }
+        Ok(into_right_to_left(compounds))
+    }
+
+    /// `:has()`の引数（先頭に結合子を置ける相対セレクターのリスト）を読みます
+    fn parse_relative_selector_list(
+        &mut self,
+    ) -> Result<Vec<(Combinator, ComplexSelector)>, SelectorError> {
+        let mut selectors = vec![];
+        loop {
+            self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                _ => Combinator::Descendant,
+            };
+            if combinator != Combinator::Descendant {
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+            selectors.push((combinator, self.parse_complex_selector()?));
+            self.skip_whitespace();
+            if self.peek() != Some(',') {
+                return Ok(selectors);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn parse_compound_selector(&mut self) -> Result<CompoundSelector, SelectorError> {
+        let mut simple = vec![];
+        if self.peek() == Some('*') {
+            self.pos += 1;
+            simple.push(SimpleSelector::Universal);
+        } else if self.starts_identifier() {
+            simple.push(SimpleSelector::Type(self.parse_identifier()?));
+        }
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.pos += 1;
+                    simple.push(SimpleSelector::Id(self.parse_identifier()?));
+                }
+                Some('.') => {
+                    self.pos += 1;
+                    simple.push(SimpleSelector::Class(self.parse_identifier()?));
+                }
+                Some('[') => simple.push(self.parse_attribute_selector()?),
+                Some(':') => simple.push(self.parse_pseudo_class()?),
+                _ => break,
+            }
+        }
+        if simple.is_empty() {
+            return Err(match self.peek() {
+                None | Some(',') | Some(')') => self.error(SelectorErrorKind::EmptySelector),
+                Some(_) => self.unexpected(),
+            });
+        }
+        Ok(CompoundSelector { simple })
+    }
+
+    fn parse_attribute_selector(&mut self) -> Result<SimpleSelector, SelectorError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let name = self.parse_identifier()?;
+        self.skip_whitespace();
+        let operator = match (self.peek(), self.peek_at(1)) {
+            (Some(']'), _) => {
+                self.pos += 1;
+                return Ok(SimpleSelector::Attribute {
+                    name,
+                    matcher: None,
+                    case_insensitive: false,
+                });
+            }
+            (Some('='), _) => AttributeOperator::Equals,
+            (Some('~'), Some('=')) => AttributeOperator::Includes,
+            (Some('|'), Some('=')) => AttributeOperator::DashMatch,
+            (Some('^'), Some('=')) => AttributeOperator::Prefix,
+            (Some('$'), Some('=')) => AttributeOperator::Suffix,
+            (Some('*'), Some('=')) => AttributeOperator::Substring,
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += if operator == AttributeOperator::Equals {
+            1
+        } else {
+            2
+        };
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => self.parse_string(quote)?,
+            _ => self.parse_identifier()?,
+        };
+        self.skip_whitespace();
+        let case_insensitive = match self.peek() {
+            Some('i' | 'I') => {
+                self.pos += 1;
+                self.skip_whitespace();
+                true
+            }
+            Some('s' | 'S') => {
+                self.pos += 1;
+                self.skip_whitespace();
+                false
+            }
+            _ => false,
+        };
+        self.expect(']')?;
+        Ok(SimpleSelector::Attribute {
+            name,
+            matcher: Some((operator, value)),
+            case_insensitive,
+        })
+    }
+
+    fn parse_pseudo_class(&mut self) -> Result<SimpleSelector, SelectorError> {
+        self.expect(':')?;
+        if self.peek() == Some(':') {
+            self.pos += 1;
+            let start = self.pos;
+            let name = self.parse_identifier()?;
+            self.pos = start;
+            return Err(self.error(SelectorErrorKind::UnsupportedPseudoElement(name)));
+        }
+        let start = self.pos;
+        let name = self.parse_identifier()?.to_ascii_lowercase();
+        let takes_arguments = self.peek() == Some('(');
+        let pseudo = if takes_arguments {
+            self.pos += 1;
+            self.skip_whitespace();
+            if self.depth == MAX_NESTING_DEPTH {
+                return Err(self.error(SelectorErrorKind::NestingTooDeep));
+            }
+            self.depth += 1;
+            let pseudo = match name.as_str() {
+                "not" => PseudoClass::Not(self.parse_selector_list()?),
+                "is" | "where" | "matches" => PseudoClass::Is(self.parse_selector_list()?),
+                "has" => PseudoClass::Has(self.parse_relative_selector_list()?),
+                "nth-child" => PseudoClass::NthChild(self.parse_nth()?),
+                "nth-last-child" => PseudoClass::NthLastChild(self.parse_nth()?),
+                "nth-of-type" => PseudoClass::NthOfType(self.parse_nth()?),
+                "nth-last-of-type" => PseudoClass::NthLastOfType(self.parse_nth()?),
+                _ => {
+                    self.pos = start;
+                    return Err(self.error(SelectorErrorKind::UnsupportedPseudoClass(name)));
+                }
+            };
+            self.depth -= 1;
+            self.skip_whitespace();
+            self.expect(')')?;
+            pseudo
+        } else {
+            match name.as_str() {
+                "root" => PseudoClass::Root,
+                "empty" => PseudoClass::Empty,
+                "first-child" => PseudoClass::FirstChild,
+                "last-child" => PseudoClass::LastChild,
+                "only-child" => PseudoClass::OnlyChild,
+                "first-of-type" => PseudoClass::FirstOfType,
+                "last-of-type" => PseudoClass::LastOfType,
+                "only-of-type" => PseudoClass::OnlyOfType,
+                "link" | "any-link" => PseudoClass::Link,
+                "checked" => PseudoClass::Checked,
+                "disabled" => PseudoClass::Disabled,
+                "enabled" => PseudoClass::Enabled,
+                _ => {
+                    self.pos = start;
+                    return Err(self.error(SelectorErrorKind::UnsupportedPseudoClass(name)));
+                }
+            }
+        };
+        Ok(SimpleSelector::PseudoClass(pseudo))
+    }
+
+    /// `:nth-child()`などの引数（`odd`、`even`、`an+b`）を読みます
+    fn parse_nth(&mut self) -> Result<Nth, SelectorError> {
+        let start = self.pos;
+        let mut text = String::new();
+        while let Some(c) = self.peek().filter(|&c| c != ')') {
+            if !is_whitespace(c) {
+                text.push(c.to_ascii_lowercase());
+            }
+            self.pos += 1;
+        }
+        let invalid = SelectorError {
+            kind: SelectorErrorKind::InvalidNth,
+            position: start,
+        };
+        let parse_int = |s: &str| s.parse::<i32>().map_err(|_| invalid.clone());
+        match text.as_str() {
+            "odd" => return Ok(Nth { a: 2, b: 1 }),
+            "even" => return Ok(Nth { a: 2, b: 0 }),
+            _ => {}
+        }
+        let Some((a, b)) = text.split_once('n') else {
+            return Ok(Nth {
+                a: 0,
+                b: parse_int(&text)?,
+            });
+        };
+        let a = match a {
+            "" | "+" => 1,
+            "-" => -1,
+            a => parse_int(a)?,
+        };
+        let b = match b {
+            "" => 0,
+            b if b.starts_with(['+', '-']) && b[1..].chars().all(|c| c.is_ascii_digit()) => {
+                parse_int(b)?
+            }
+            _ => return Err(invalid),
+        };
+        Ok(Nth { a, b })
+    }
+
+    fn starts_identifier(&self) -> bool {
+        match (self.peek(), self.peek_at(1)) {
+            (Some('-'), Some(c)) => is_name_start(c) || c == '-' || c == '\\',
+            (Some(c), _) => is_name_start(c) || c == '\\',
+            _ => false,
+        }
+    }
+
+    /// 識別子を読みます（`\`によるエスケープを解釈します）
+    fn parse_identifier(&mut self) -> Result<String, SelectorError> {
+        if !self.starts_identifier() {
+            return Err(self.unexpected());
+        }
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.pos += 1;
+                name.push(self.parse_escape()?);
+            } else if is_name_start(c) || c.is_ascii_digit() || c == '-' {
+                self.pos += 1;
+                name.push(c);
+            } else {
+                break;
+            }
+        }
+        Ok(name)
+    }
+
+    /// 引用符で囲まれた文字列を読みます
+    fn parse_string(&mut self, quote: char) -> Result<String, SelectorError> {
+        self.expect(quote)?;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.unexpected()),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    // 行末のエスケープは改行を取り除くだけ
+                    if self.peek() == Some('\n') {
+                        self.pos += 1;
+                    } else {
+                        value.push(self.parse_escape()?);
+                    }
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    value.push(c);
+                }
+            }
+        }
+    }
+
+    /// `\`の後のエスケープされた文字を読みます（16進数は最大6桁と後続の空白1つ）
+    fn parse_escape(&mut self) -> Result<char, SelectorError> {
+        let Some(c) = self.peek() else {
+            return Ok('\u{FFFD}');
+        };
+        if !c.is_ascii_hexdigit() {
+            self.pos += 1;
+            return Ok(c);
+        }
+        let mut code = 0;
+        let mut digits = 0;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(16)) {
+            if digits == 6 {
+                break;
+            }
+            code = code * 16 + digit;
+            digits += 1;
+            self.pos += 1;
+        }
+        if self.peek().is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        Ok(match char::from_u32(code) {
+            Some('\0') | None => '\u{FFFD}',
+            Some(c) => c,
+        })
+    }
+}
+
+/// 左から右に読んだ複合セレクターを、照合用の右から左の並びにします
+fn into_right_to_left(mut compounds: Vec<(Combinator, CompoundSelector)>) -> ComplexSelector {
+    compounds.reverse();
+    ComplexSelector { compounds }
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C')
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
@@ -0,0 +1,253 @@
+//! 文書の URL とサブリソース
+//!
+//! 文書ノードに文書の URL を持たせ、`<base href>`を考慮した基底 URL で相対 URL を解決します。
+//! また、スタイルシートやスクリプト、画像など、文書が読み込むリソースを絶対 URL で列挙し、
+//! ネットワーク層で読み込みを予定できるようにします。
+//! https://html.spec.whatwg.org/multipage/urls-and-fetching.html#document-base-url
+
+use url::Url;
+
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::parser::{Node, NodeRef};
+
+/// サブリソースの種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubresourceKind {
+    /// `<link rel=stylesheet>`
+    Stylesheet,
+    /// `<script src>`
+    Script,
+    /// `<img>`と`<input type=image>`
+    Image,
+    /// `<link rel=icon>`
+    Icon,
+    /// `<iframe src>`
+    Iframe,
+    /// `<link rel=preload>`と`<link rel=modulepreload>`
+    Preload {
+        /// 先読みするリソースの種類（`as`属性の値。`modulepreload`は`script`）
+        destination: String,
+    },
+}
+
+/// 文書が読み込むリソース
+#[derive(Debug, Clone)]
+pub struct Subresource {
+    pub kind: SubresourceKind,
+    /// 基底 URL で解決した絶対 URL
+    pub url: Url,
+    /// リソースを参照している要素（`crossorigin`などの属性を読むために使う）
+    pub element: NodeRef,
+}
+
+impl Node {
+    /// 文書ノードの場合は文書の URL を返します
+    pub fn url(&self) -> Option<&Url> {
+        self.document_state()?.url.as_ref()
+    }
+
+    /// 文書ノードに文書の URL を設定します（文書ノード以外では何もしません）
+    pub fn set_url(&mut self, url: Url) {
+        if let Some(state) = self.document_state_mut() {
+            state.url = Some(url);
+        }
+    }
+
+    /// 文書の基底 URL を返します（document base URL）
+    ///
+    /// `href`属性を持つ最初の`<base>`要素があれば、その値を文書の URL で解決したもの、
+    /// なければ文書の URL です。`<base>`はツリーの変更に追従するよう、呼び出すたびに探します。
+    pub fn base_url(&self) -> Option<Url> {
+        let url = self.url();
+        let base = self.descendant_elements().find_map(|node| {
+            let node = node.borrow();
+            if node.namespace() != Some(Namespace::Html) || node.tag_name() != Some("base") {
+                return None;
+            }
+            node.attribute("href").map(str::to_string)
+        });
+        match base {
+            Some(href) => Url::options()
+                .base_url(url)
+                .parse(&href)
+                .ok()
+                .or_else(|| url.cloned()),
+            None => url.cloned(),
+        }
+    }
+
+    /// 文書の基底 URL で`href`などの値を絶対 URL に解決します
+    ///
+    /// 基底 URL がない文書では、絶対 URL だけを解決できます。
+    ///
+    /// # 戻り値
+    /// * 解決した URL（URL として解釈できない場合は`None`）
+    pub fn resolve_url(&self, href: &str) -> Option<Url> {
+        resolve(self.base_url().as_ref(), href)
+    }
+
+    /// 文書が読み込むサブリソースを文書順に列挙します
+    ///
+    /// 値が空の属性や、解決できない URL は含みません。`<template>`の内容は対象外です。
+    pub fn subresources(&self) -> Vec<Subresource> {
+        let base = self.base_url();
+        let mut resources = vec![];
+        for element in self.descendant_elements() {
+            let node = element.borrow();
+            if node.namespace() != Some(Namespace::Html) {
+                continue;
+            }
+            let found: Vec<(SubresourceKind, &str)> = match node.tag_name().unwrap_or_default() {
+                "link" => link_resources(&node),
+                "script" if is_javascript_type(&node) => attribute_resource(&node, "src")
+                    .map(|src| vec![(SubresourceKind::Script, src)])
+                    .unwrap_or_default(),
+                "img" => image_source(&node)
+                    .map(|src| vec![(SubresourceKind::Image, src)])
+                    .unwrap_or_default(),
+                "input"
+                    if node
+                        .attribute("type")
+                        .is_some_and(|value| value.eq_ignore_ascii_case("image")) =>
+                {
+                    attribute_resource(&node, "src")
+                        .map(|src| vec![(SubresourceKind::Image, src)])
+                        .unwrap_or_default()
+                }
+                // srcdoc がある場合は src を読み込まない
+                "iframe" if !node.has_attribute("srcdoc") => attribute_resource(&node, "src")
+                    .map(|src| vec![(SubresourceKind::Iframe, src)])
+                    .unwrap_or_default(),
+                _ => vec![],
+            };
+            for (kind, value) in found {
+                if let Some(url) = resolve(base.as_ref(), value) {
+                    resources.push(Subresource {
+                        kind,
+                        url,
+                        element: element.clone(),
+                    });
+                }
+            }
+        }
+        resources
+    }
+}
+
+fn resolve(base: Option<&Url>, href: &str) -> Option<Url> {
+    Url::options().base_url(base).parse(href).ok()
+}
+
+/// 属性の値を返します（空白だけの値は`None`）
+fn attribute_resource<'a>(node: &'a Node, name: &str) -> Option<&'a str> {
+    node.attribute(name)
+        .filter(|value| !value.trim_matches(is_ascii_whitespace).is_empty())
+}
+
+/// `<link>`の`rel`に応じたサブリソース
+///
+/// `rel`は空白区切りのキーワードで、1つの要素が複数の種類を持つことがあります。
+fn link_resources(node: &Node) -> Vec<(SubresourceKind, &str)> {
+    let Some(href) = attribute_resource(node, "href") else {
+        return vec![];
+    };
+    let rel: Vec<String> = node
+        .attribute("rel")
+        .unwrap_or_default()
+        .split_ascii_whitespace()
+        .map(str::to_ascii_lowercase)
+        .collect();
+    let has = |keyword: &str| rel.iter().any(|token| token == keyword);
+
+    let mut resources = vec![];
+    // 代替スタイルシートと無効にされたスタイルシートは読み込まない
+    if has("stylesheet") && !has("alternate") && !node.has_attribute("disabled") {
+        resources.push((SubresourceKind::Stylesheet, href));
+    }
+    if has("icon") {
+        resources.push((SubresourceKind::Icon, href));
+    }
+    if has("preload") {
+        // as が指定されていない preload は何も読み込まない
+        if let Some(destination) = node.attribute("as").filter(|value| !value.is_empty()) {
+            resources.push((
+                SubresourceKind::Preload {
+                    destination: destination.to_ascii_lowercase(),
+                },
+                href,
+            ));
+        }
+    }
+    if has("modulepreload") {
+        resources.push((
+            SubresourceKind::Preload {
+                destination: "script".to_string(),
+            },
+            href,
+        ));
+    }
+    resources
+}
+
+/// `<img>`が読み込む URL（`src`がなければ`srcset`の最初の候補）
+fn image_source(node: &Node) -> Option<&str> {
+    attribute_resource(node, "src").or_else(|| {
+        let srcset = node.attribute("srcset")?;
+        let candidate = srcset
+            .trim_start_matches(|c| is_ascii_whitespace(c) || c == ',')
+            .split(is_ascii_whitespace)
+            .next()?
+            .trim_end_matches(',');
+        (!candidate.is_empty()).then_some(candidate)
+    })
+}
+
+/// `<script>`の`type`がスクリプトとして実行される種類か
+///
+/// https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element
+fn is_javascript_type(node: &Node) -> bool {
+    let script_type = match node.attribute("type") {
+        Some(value) => value,
+        None => match node.attribute("language") {
+            Some(language) if !language.is_empty() => {
+                return is_javascript_mime(&format!("text/{language}"))
+            }
+            _ => return true,
+        },
+    };
+    let script_type = script_type.trim_matches(is_ascii_whitespace);
+    script_type.is_empty()
+        || script_type.eq_ignore_ascii_case("module")
+        || is_javascript_mime(script_type)
+}
+
+/// JavaScript の MIME タイプの本質か
+///
+/// https://mimesniff.spec.whatwg.org/#javascript-mime-type
+fn is_javascript_mime(value: &str) -> bool {
+    const JAVASCRIPT_MIME_TYPES: [&str; 16] = [
+        "application/ecmascript",
+        "application/javascript",
+        "application/x-ecmascript",
+        "application/x-javascript",
+        "text/ecmascript",
+        "text/javascript",
+        "text/javascript1.0",
+        "text/javascript1.1",
+        "text/javascript1.2",
+        "text/javascript1.3",
+        "text/javascript1.4",
+        "text/javascript1.5",
+        "text/jscript",
+        "text/livescript",
+        "text/x-ecmascript",
+        "text/x-javascript",
+    ];
+    JAVASCRIPT_MIME_TYPES
+        .iter()
+        .any(|mime| mime.eq_ignore_ascii_case(value))
+}
+
+fn is_ascii_whitespace(c: char) -> bool {
+    c.is_ascii_whitespace()
+}
@@ -0,0 +1,180 @@
+//! HTMLのパースエラー
+//!
+//! HTMLのパースはエラーで止まらず、仕様の回復処理に従って常にDOMを作ります。ここでは
+//! 回復処理を行った箇所を、ソース表示やバリデーター向けに名前と位置つきで記録します。
+//! 字句解析のエラー名は仕様の
+//! https://html.spec.whatwg.org/multipage/parsing.html#parse-errors に従います。
+
+use std::error::Error;
+use std::fmt;
+
+use crate::engine::html::span::SourcePosition;
+
+/// パースエラーの種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    // 字句解析のエラー
+    UnexpectedNullCharacter,
+    UnexpectedQuestionMarkInsteadOfTagName,
+    InvalidFirstCharacterOfTagName,
+    MissingEndTagName,
+    EofBeforeTagName,
+    EofInTag,
+    EofInComment,
+    EofInDoctype,
+    EofInScriptHtmlCommentLikeText,
+    EofInCdata,
+    IncorrectlyOpenedComment,
+    AbruptClosingOfEmptyComment,
+    NestedComment,
+    IncorrectlyClosedComment,
+    CdataInHtmlContent,
+    UnexpectedCharacterInAttributeName,
+    UnexpectedEqualsSignBeforeAttributeName,
+    MissingAttributeValue,
+    DuplicateAttribute,
+    EndTagWithAttributes,
+    EndTagWithTrailingSolidus,
+    UnexpectedSolidusInTag,
+    MissingWhitespaceBeforeDoctypeName,
+    MissingDoctypeName,
+    InvalidCharacterSequenceAfterDoctypeName,
+    MissingDoctypePublicIdentifier,
+    MissingDoctypeSystemIdentifier,
+    MissingQuoteBeforeDoctypePublicIdentifier,
+    MissingQuoteBeforeDoctypeSystemIdentifier,
+    AbruptDoctypePublicIdentifier,
+    AbruptDoctypeSystemIdentifier,
+    UnexpectedCharacterAfterDoctypeSystemIdentifier,
+    MissingSemicolonAfterCharacterReference,
+    UnknownNamedCharacterReference,
+    AbsenceOfDigitsInNumericCharacterReference,
+    NullCharacterReference,
+    CharacterReferenceOutsideUnicodeRange,
+    SurrogateCharacterReference,
+    NoncharacterCharacterReference,
+    ControlCharacterReference,
+
+    // ツリー構築のエラー（仕様には名前がないため、このクレート独自の名前を使う）
+    /// DOCTYPEより前に内容がある
+    MissingDoctype,
+    /// `<!DOCTYPE html>`以外のDOCTYPE
+    NonConformingDoctype,
+    /// 文書の途中のDOCTYPE
+    UnexpectedDoctype,
+    /// 文脈に合わない開始タグ（無視、または別の要素として扱った）
+    UnexpectedStartTag(String),
+    /// 対応する要素が開いていない終了タグ
+    UnexpectedEndTag(String),
+    /// 入れ子が正しくない終了タグ（開いている要素を暗黙に閉じた）
+    MisnestedTag(String),
+    /// テーブルの中に置けない内容をテーブルの前に移動した
+    FosterParenting,
+    /// 要素が閉じられないまま入力が終わった
+    EofWithOpenElements,
+}
+
+impl ParseErrorKind {
+    /// ケバブケースのエラー名を返します（`unexpected-null-character`など）
+    pub fn code(&self) -> &'static str {
+        use ParseErrorKind as K;
+        match self {
+            K::UnexpectedNullCharacter => "unexpected-null-character",
+            K::UnexpectedQuestionMarkInsteadOfTagName => {
+                "unexpected-question-mark-instead-of-tag-name"
+            }
+            K::InvalidFirstCharacterOfTagName => "invalid-first-character-of-tag-name",
+            K::MissingEndTagName => "missing-end-tag-name",
+            K::EofBeforeTagName => "eof-before-tag-name",
+            K::EofInTag => "eof-in-tag",
+            K::EofInComment => "eof-in-comment",
+            K::EofInDoctype => "eof-in-doctype",
+            K::EofInScriptHtmlCommentLikeText => "eof-in-script-html-comment-like-text",
+            K::EofInCdata => "eof-in-cdata",
+            K::IncorrectlyOpenedComment => "incorrectly-opened-comment",
+            K::AbruptClosingOfEmptyComment => "abrupt-closing-of-empty-comment",
+            K::NestedComment => "nested-comment",
+            K::IncorrectlyClosedComment => "incorrectly-closed-comment",
+            K::CdataInHtmlContent => "cdata-in-html-content",
+            K::UnexpectedCharacterInAttributeName => "unexpected-character-in-attribute-name",
+            K::UnexpectedEqualsSignBeforeAttributeName => {
+                "unexpected-equals-sign-before-attribute-name"
+            }
+            K::MissingAttributeValue => "missing-attribute-value",
+            K::DuplicateAttribute => "duplicate-attribute",
+            K::EndTagWithAttributes => "end-tag-with-attributes",
+            K::EndTagWithTrailingSolidus => "end-tag-with-trailing-solidus",
+            K::UnexpectedSolidusInTag => "unexpected-solidus-in-tag",
+            K::MissingWhitespaceBeforeDoctypeName => "missing-whitespace-before-doctype-name",
+            K::MissingDoctypeName => "missing-doctype-name",
+            K::InvalidCharacterSequenceAfterDoctypeName => {
+                "invalid-character-sequence-after-doctype-name"
+            }
+            K::MissingDoctypePublicIdentifier => "missing-doctype-public-identifier",
+            K::MissingDoctypeSystemIdentifier => "missing-doctype-system-identifier",
+            K::MissingQuoteBeforeDoctypePublicIdentifier => {
+                "missing-quote-before-doctype-public-identifier"
+            }
+            K::MissingQuoteBeforeDoctypeSystemIdentifier => {
+                "missing-quote-before-doctype-system-identifier"
+            }
+            K::AbruptDoctypePublicIdentifier => "abrupt-doctype-public-identifier",
+            K::AbruptDoctypeSystemIdentifier => "abrupt-doctype-system-identifier",
+            K::UnexpectedCharacterAfterDoctypeSystemIdentifier => {
+                "unexpected-character-after-doctype-system-identifier"
+            }
+            K::MissingSemicolonAfterCharacterReference => {
+                "missing-semicolon-after-character-reference"
+            }
+            K::UnknownNamedCharacterReference => "unknown-named-character-reference",
+            K::AbsenceOfDigitsInNumericCharacterReference => {
+                "absence-of-digits-in-numeric-character-reference"
+            }
+            K::NullCharacterReference => "null-character-reference",
+            K::CharacterReferenceOutsideUnicodeRange => "character-reference-outside-unicode-range",
+            K::SurrogateCharacterReference => "surrogate-character-reference",
+            K::NoncharacterCharacterReference => "noncharacter-character-reference",
+            K::ControlCharacterReference => "control-character-reference",
+            K::MissingDoctype => "missing-doctype",
+            K::NonConformingDoctype => "non-conforming-doctype",
+            K::UnexpectedDoctype => "unexpected-doctype",
+            K::UnexpectedStartTag(_) => "unexpected-start-tag",
+            K::UnexpectedEndTag(_) => "unexpected-end-tag",
+            K::MisnestedTag(_) => "misnested-tag",
+            K::FosterParenting => "foster-parenting",
+            K::EofWithOpenElements => "eof-with-open-elements",
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedStartTag(name) => write!(f, "{} <{name}>", self.code()),
+            ParseErrorKind::UnexpectedEndTag(name) | ParseErrorKind::MisnestedTag(name) => {
+                write!(f, "{} </{name}>", self.code())
+            }
+            _ => f.write_str(self.code()),
+        }
+    }
+}
+
+/// 位置つきのパースエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// エラーを検出した位置（ツリー構築のエラーは原因となったトークンの先頭）
+    pub position: SourcePosition,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.position.line, self.position.column, self.kind
+        )
+    }
+}
+
+impl Error for ParseError {}
@@ -0,0 +1,394 @@
+//! DOM のイベント
+//!
+//! ノードに登録したリスナーへ、DOM Standard の dispatch に従ってイベントを届けます。
+//! イベントは対象から文書ノードまでの経路を、キャプチャ・ターゲット・バブリングの順にたどります。
+//! `click`が取り消されなかった場合は、リンクをたどるなどの既定の動作を返します。
+//! https://dom.spec.whatwg.org/#concept-event-dispatch
+
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::parser::{NodeRef, NodeType};
+
+/// イベントが経路のどこにいるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPhase {
+    /// 配送していない
+    None,
+    /// 文書ノードから対象の親へ向かっている
+    Capturing,
+    /// 対象のリスナーを呼んでいる
+    AtTarget,
+    /// 対象の親から文書ノードへ向かっている
+    Bubbling,
+}
+
+/// 修飾キーの状態
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+/// マウスのボタン（MouseEvent の`button`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MouseButton {
+    #[default]
+    Primary,
+    Auxiliary,
+    Secondary,
+    Back,
+    Forward,
+}
+
+/// マウスイベントの情報
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MouseEventData {
+    /// ビューポート上の位置
+    pub client_x: f64,
+    pub client_y: f64,
+    pub button: MouseButton,
+    pub modifiers: Modifiers,
+}
+
+/// キーボードイベントの情報
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyboardEventData {
+    /// 入力された文字、または`Enter`や`Escape`などのキーの名前
+    pub key: String,
+    /// 物理的なキーの位置（`KeyA`、`Enter`など）
+    pub code: String,
+    /// キーを押し続けたことによる繰り返しか
+    pub repeat: bool,
+    pub modifiers: Modifiers,
+}
+
+/// イベントの種類ごとの情報
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum EventData {
+    #[default]
+    None,
+    Mouse(MouseEventData),
+    Keyboard(KeyboardEventData),
+}
+
+/// イベント
+#[derive(Debug, Clone)]
+pub struct Event {
+    event_type: String,
+    bubbles: bool,
+    cancelable: bool,
+    /// マウスやキーボードの情報
+    pub data: EventData,
+    target: Option<NodeRef>,
+    current_target: Option<NodeRef>,
+    phase: EventPhase,
+    propagation_stopped: bool,
+    immediate_propagation_stopped: bool,
+    canceled: bool,
+    in_passive_listener: bool,
+}
+
+impl Event {
+    /// イベントを作成します
+    ///
+    /// # 引数
+    /// * `event_type` - 種類（`click`、`load`など）
+    /// * `bubbles` - バブリングするか
+    /// * `cancelable` - `prevent_default`で取り消せるか
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            bubbles,
+            cancelable,
+            data: EventData::None,
+            target: None,
+            current_target: None,
+            phase: EventPhase::None,
+            propagation_stopped: false,
+            immediate_propagation_stopped: false,
+            canceled: false,
+            in_passive_listener: false,
+        }
+    }
+
+    /// マウスイベント（`click`、`mousedown`など）を作成します。バブリングし、取り消せます
+    pub fn mouse(event_type: &str, data: MouseEventData) -> Self {
+        let mut event = Self::new(event_type, true, true);
+        event.data = EventData::Mouse(data);
+        event
+    }
+
+    /// キーボードイベント（`keydown`、`keyup`）を作成します。バブリングし、取り消せます
+    pub fn keyboard(event_type: &str, data: KeyboardEventData) -> Self {
+        let mut event = Self::new(event_type, true, true);
+        event.data = EventData::Keyboard(data);
+        event
+    }
+
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    pub fn bubbles(&self) -> bool {
+        self.bubbles
+    }
+
+    pub fn cancelable(&self) -> bool {
+        self.cancelable
+    }
+
+    /// イベントを配送した対象
+    pub fn target(&self) -> Option<&NodeRef> {
+        self.target.as_ref()
+    }
+
+    /// 現在リスナーを呼んでいるノード（配送中以外は`None`）
+    pub fn current_target(&self) -> Option<&NodeRef> {
+        self.current_target.as_ref()
+    }
+
+    pub fn phase(&self) -> EventPhase {
+        self.phase
+    }
+
+    /// これより先のノードへの配送を止めます（同じノードの残りのリスナーは呼ばれます）
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
+
+    /// 同じノードの残りのリスナーも含めて、配送を止めます
+    pub fn stop_immediate_propagation(&mut self) {
+        self.propagation_stopped = true;
+        self.immediate_propagation_stopped = true;
+    }
+
+    /// 既定の動作を取り消します（取り消せないイベントと passive なリスナーの中では無視されます）
+    pub fn prevent_default(&mut self) {
+        if self.cancelable && !self.in_passive_listener {
+            self.canceled = true;
+        }
+    }
+
+    /// 既定の動作が取り消されたか
+    pub fn default_prevented(&self) -> bool {
+        self.canceled
+    }
+}
+
+/// リスナーの登録時のオプション
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListenerOptions {
+    /// キャプチャフェーズで呼ぶか（`false`の場合はターゲットとバブリングのフェーズ）
+    pub capture: bool,
+    /// 一度呼んだら登録を解除するか
+    pub once: bool,
+    /// `prevent_default`を呼ばないことを宣言するか（呼んでも無視されます）
+    pub passive: bool,
+}
+
+/// 登録したリスナーの識別子（登録の解除に使います）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+type Callback = Rc<dyn Fn(&mut Event)>;
+
+/// ノードに登録されたリスナー
+#[derive(Clone)]
+pub(crate) struct EventListener {
+    id: ListenerId,
+    event_type: String,
+    options: ListenerOptions,
+    callback: Callback,
+}
+
+impl fmt::Debug for EventListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventListener")
+            .field("id", &self.id)
+            .field("event_type", &self.event_type)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+/// `click`の既定の動作（activation behavior）
+#[derive(Debug, Clone)]
+pub enum DefaultAction {
+    /// リンクをたどる（`href`は解決前の属性値）
+    FollowLink { element: NodeRef, href: String },
+    /// フォームを送信する
+    SubmitForm { form: NodeRef, submitter: NodeRef },
+}
+
+static NEXT_LISTENER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// ノードにリスナーを登録します（`addEventListener`）
+///
+/// # 引数
+/// * `node` - 登録先のノード
+/// * `event_type` - イベントの種類
+/// * `options` - キャプチャ・once・passive の指定
+/// * `callback` - 呼び出す関数。配送中のノードは`Event::current_target`で参照できます
+pub fn add_event_listener(
+    node: &NodeRef,
+    event_type: &str,
+    options: ListenerOptions,
+    callback: impl Fn(&mut Event) + 'static,
+) -> ListenerId {
+    let id = ListenerId(NEXT_LISTENER_ID.fetch_add(1, Ordering::Relaxed));
+    node.borrow_mut().event_listeners.push(EventListener {
+        id,
+        event_type: event_type.to_string(),
+        options,
+        callback: Rc::new(callback),
+    });
+    id
+}
+
+/// リスナーの登録を解除します（`removeEventListener`）
+///
+/// # 戻り値
+/// * 登録されていたか
+pub fn remove_event_listener(node: &NodeRef, id: ListenerId) -> bool {
+    let listeners = &mut node.borrow_mut().event_listeners;
+    let len = listeners.len();
+    listeners.retain(|listener| listener.id != id);
+    listeners.len() != len
+}
+
+/// イベントを`target`に配送します（`dispatchEvent`）
+///
+/// 経路は`target`から親をたどった祖先です。配送中にリスナーがツリーを変更しても、経路は
+/// 配送を始めた時点のものを使います。
+///
+/// # 戻り値
+/// * 取り消されなかった`click`の既定の動作。それ以外は`None`
+pub fn dispatch_event(target: &NodeRef, event: &mut Event) -> Option<DefaultAction> {
+    let mut path = vec![Rc::clone(target)];
+    let mut ancestor = target.borrow().parent();
+    while let Some(node) = ancestor {
+        ancestor = node.borrow().parent();
+        path.push(node);
+    }
+
+    // click の場合は、既定の動作を持つ最も近い要素（バブリングしない場合は対象自身のみ）
+    let activation_target = (event.event_type == "click")
+        .then(|| {
+            let candidates = if event.bubbles { &path[..] } else { &path[..1] };
+            candidates.iter().find_map(activation_behavior)
+        })
+        .flatten();
+
+    event.target = Some(Rc::clone(target));
+    event.propagation_stopped = false;
+    event.immediate_propagation_stopped = false;
+
+    for node in path[1..].iter().rev() {
+        invoke(node, event, EventPhase::Capturing);
+    }
+    invoke(target, event, EventPhase::AtTarget);
+    if event.bubbles {
+        for node in &path[1..] {
+            invoke(node, event, EventPhase::Bubbling);
+        }
+    }
+
+    event.phase = EventPhase::None;
+    event.current_target = None;
+    activation_target.filter(|_| !event.canceled)
+}
+
+/// ノードのリスナーのうち、フェーズに合うものを登録順に呼びます
+fn invoke(node: &NodeRef, event: &mut Event, phase: EventPhase) {
+    if event.propagation_stopped {
+        return;
+    }
+    event.phase = phase;
+    event.current_target = Some(Rc::clone(node));
+
+    // リスナーの中で登録が変わっても影響しないよう、呼ぶ前に一覧を複製する
+    let listeners: Vec<EventListener> = node
+        .borrow()
+        .event_listeners
+        .iter()
+        .filter(|listener| listener.event_type == event.event_type)
+        .filter(|listener| match phase {
+            EventPhase::Capturing => listener.options.capture,
+            EventPhase::Bubbling => !listener.options.capture,
+            _ => true,
+        })
+        .cloned()
+        .collect();
+    // ターゲットではキャプチャのリスナーを先に呼ぶ
+    let (capturing, rest): (Vec<_>, Vec<_>) = listeners
+        .into_iter()
+        .partition(|listener| listener.options.capture);
+
+    for listener in capturing.into_iter().chain(rest) {
+        // 配送中に登録を解除されたリスナーは呼ばない
+        let registered = node
+            .borrow()
+            .event_listeners
+            .iter()
+            .any(|l| l.id == listener.id);
+        if !registered {
+            continue;
+        }
+        if listener.options.once {
+            remove_event_listener(node, listener.id);
+        }
+        event.in_passive_listener = listener.options.passive;
+        (listener.callback)(event);
+        event.in_passive_listener = false;
+        if event.immediate_propagation_stopped {
+            return;
+        }
+    }
+}
+
+/// 要素が`click`の既定の動作を持つ場合は、その動作を返します
+fn activation_behavior(node: &NodeRef) -> Option<DefaultAction> {
+    let element = node.borrow();
+    if element.namespace() != Some(Namespace::Html) {
+        return None;
+    }
+    match element.tag_name()? {
+        "a" | "area" => Some(DefaultAction::FollowLink {
+            element: Rc::clone(node),
+            href: element.attribute("href")?.to_string(),
+        }),
+        "button" | "input" => {
+            let kind = element
+                .attribute("type")
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let submits = match element.tag_name()? {
+                "button" => !matches!(kind.as_str(), "button" | "reset"),
+                _ => matches!(kind.as_str(), "submit" | "image"),
+            };
+            if !submits || element.attribute("disabled").is_some() {
+                return None;
+            }
+            let form = element
+                .ancestors()
+                .find(|ancestor| is_html_element(ancestor, "form"))?;
+            Some(DefaultAction::SubmitForm {
+                form,
+                submitter: Rc::clone(node),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn is_html_element(node: &NodeRef, name: &str) -> bool {
+    matches!(
+        &node.borrow().node_type,
+        NodeType::Element { tag_name, namespace: Namespace::Html, .. } if tag_name == name
+    )
+}
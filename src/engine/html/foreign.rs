@@ -0,0 +1,225 @@
+//! 外部コンテンツ（SVG / MathML）のための名前の補正
+//!
+//! 字句解析はタグ名と属性名を小文字にするため、SVGの`foreignObject`や`viewBox`のような
+//! 大文字を含む名前を元に戻します。
+//! https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::tokenizer::Attribute;
+
+/// 小文字にされたSVGの要素名と、正しい名前
+const SVG_TAG_NAMES: &[(&str, &str)] = &[
+    ("altglyph", "altGlyph"),
+    ("altglyphdef", "altGlyphDef"),
+    ("altglyphitem", "altGlyphItem"),
+    ("animatecolor", "animateColor"),
+    ("animatemotion", "animateMotion"),
+    ("animatetransform", "animateTransform"),
+    ("clippath", "clipPath"),
+    ("feblend", "feBlend"),
+    ("fecolormatrix", "feColorMatrix"),
+    ("fecomponenttransfer", "feComponentTransfer"),
+    ("fecomposite", "feComposite"),
+    ("feconvolvematrix", "feConvolveMatrix"),
+    ("fediffuselighting", "feDiffuseLighting"),
+    ("fedisplacementmap", "feDisplacementMap"),
+    ("fedistantlight", "feDistantLight"),
+    ("fedropshadow", "feDropShadow"),
+    ("feflood", "feFlood"),
+    ("fefunca", "feFuncA"),
+    ("fefuncb", "feFuncB"),
+    ("fefuncg", "feFuncG"),
+    ("fefuncr", "feFuncR"),
+    ("fegaussianblur", "feGaussianBlur"),
+    ("feimage", "feImage"),
+    ("femerge", "feMerge"),
+    ("femergenode", "feMergeNode"),
+    ("femorphology", "feMorphology"),
+    ("feoffset", "feOffset"),
+    ("fepointlight", "fePointLight"),
+    ("fespecularlighting", "feSpecularLighting"),
+    ("fespotlight", "feSpotLight"),
+    ("fetile", "feTile"),
+    ("feturbulence", "feTurbulence"),
+    ("foreignobject", "foreignObject"),
+    ("glyphref", "glyphRef"),
+    ("lineargradient", "linearGradient"),
+    ("radialgradient", "radialGradient"),
+    ("textpath", "textPath"),
+];
+
+/// 小文字にされたSVGの属性名と、正しい名前
+const SVG_ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("attributename", "attributeName"),
+    ("attributetype", "attributeType"),
+    ("basefrequency", "baseFrequency"),
+    ("baseprofile", "baseProfile"),
+    ("calcmode", "calcMode"),
+    ("clippathunits", "clipPathUnits"),
+    ("diffuseconstant", "diffuseConstant"),
+    ("edgemode", "edgeMode"),
+    ("filterunits", "filterUnits"),
+    ("glyphref", "glyphRef"),
+    ("gradienttransform", "gradientTransform"),
+    ("gradientunits", "gradientUnits"),
+    ("kernelmatrix", "kernelMatrix"),
+    ("kernelunitlength", "kernelUnitLength"),
+    ("keypoints", "keyPoints"),
+    ("keysplines", "keySplines"),
+    ("keytimes", "keyTimes"),
+    ("lengthadjust", "lengthAdjust"),
+    ("limitingconeangle", "limitingConeAngle"),
+    ("markerheight", "markerHeight"),
+    ("markerunits", "markerUnits"),
+    ("markerwidth", "markerWidth"),
+    ("maskcontentunits", "maskContentUnits"),
+    ("maskunits", "maskUnits"),
+    ("numoctaves", "numOctaves"),
+    ("pathlength", "pathLength"),
+    ("patterncontentunits", "patternContentUnits"),
+    ("patterntransform", "patternTransform"),
+    ("patternunits", "patternUnits"),
+    ("pointsatx", "pointsAtX"),
+    ("pointsaty", "pointsAtY"),
+    ("pointsatz", "pointsAtZ"),
+    ("preservealpha", "preserveAlpha"),
+    ("preserveaspectratio", "preserveAspectRatio"),
+    ("primitiveunits", "primitiveUnits"),
+    ("refx", "refX"),
+    ("refy", "refY"),
+    ("repeatcount", "repeatCount"),
+    ("repeatdur", "repeatDur"),
+    ("requiredextensions", "requiredExtensions"),
+    ("requiredfeatures", "requiredFeatures"),
+    ("specularconstant", "specularConstant"),
+    ("specularexponent", "specularExponent"),
+    ("spreadmethod", "spreadMethod"),
+    ("startoffset", "startOffset"),
+    ("stddeviation", "stdDeviation"),
+    ("stitchtiles", "stitchTiles"),
+    ("surfacescale", "surfaceScale"),
+    ("systemlanguage", "systemLanguage"),
+    ("tablevalues", "tableValues"),
+    ("targetx", "targetX"),
+    ("targety", "targetY"),
+    ("textlength", "textLength"),
+    ("viewbox", "viewBox"),
+    ("viewtarget", "viewTarget"),
+    ("xchannelselector", "xChannelSelector"),
+    ("ychannelselector", "yChannelSelector"),
+    ("zoomandpan", "zoomAndPan"),
+];
+
+/// 名前空間を持つ属性
+const FOREIGN_ATTRIBUTES: &[(&str, Namespace)] = &[
+    ("xlink:actuate", Namespace::XLink),
+    ("xlink:arcrole", Namespace::XLink),
+    ("xlink:href", Namespace::XLink),
+    ("xlink:role", Namespace::XLink),
+    ("xlink:show", Namespace::XLink),
+    ("xlink:title", Namespace::XLink),
+    ("xlink:type", Namespace::XLink),
+    ("xml:lang", Namespace::Xml),
+    ("xml:space", Namespace::Xml),
+    ("xmlns", Namespace::XmlNs),
+    ("xmlns:xlink", Namespace::XmlNs),
+];
+
+/// 外部コンテンツの中でHTMLに戻る開始タグ（`font`は属性によって判定する）
+const BREAKOUT_ELEMENTS: &[&str] = &[
+    "b",
+    "big",
+    "blockquote",
+    "body",
+    "br",
+    "center",
+    "code",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "embed",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "listing",
+    "menu",
+    "meta",
+    "nobr",
+    "ol",
+    "p",
+    "pre",
+    "ruby",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "strike",
+    "sub",
+    "sup",
+    "table",
+    "tt",
+    "u",
+    "ul",
+    "var",
+];
+
+fn lookup(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(lower, _)| *lower == name)
+        .map(|(_, name)| *name)
+}
+
+/// SVGの要素名を正しい大文字小文字に直します
+pub(crate) fn adjust_svg_tag_name(name: &str) -> String {
+    lookup(SVG_TAG_NAMES, name).unwrap_or(name).to_string()
+}
+
+/// SVGの属性名を正しい大文字小文字に直します
+pub(crate) fn adjust_svg_attributes(attributes: &mut [Attribute]) {
+    for attribute in attributes {
+        if let Some(name) = lookup(SVG_ATTRIBUTE_NAMES, &attribute.name) {
+            attribute.name = name.into();
+        }
+    }
+}
+
+/// MathMLの属性名を直します
+pub(crate) fn adjust_mathml_attributes(attributes: &mut [Attribute]) {
+    for attribute in attributes {
+        if attribute.name == "definitionurl" {
+            attribute.name = "definitionURL".into();
+        }
+    }
+}
+
+/// `xlink:href`などの属性に名前空間を設定します
+pub(crate) fn adjust_foreign_attributes(attributes: &mut [Attribute]) {
+    for attribute in attributes {
+        if let Some((_, namespace)) = FOREIGN_ATTRIBUTES
+            .iter()
+            .find(|(name, _)| *name == attribute.name)
+        {
+            attribute.namespace = Some(*namespace);
+        }
+    }
+}
+
+/// 外部コンテンツを抜けてHTMLとして処理する開始タグかどうか
+pub(crate) fn is_breakout_start_tag(name: &str, attributes: &[Attribute]) -> bool {
+    BREAKOUT_ELEMENTS.contains(&name)
+        || (name == "font"
+            && attributes
+                .iter()
+                .any(|a| matches!(&*a.name, "color" | "face" | "size")))
+}
@@ -0,0 +1,91 @@
+//! 文書ノードが持つ ID とクラスの索引
+//!
+//! 文書につながっている要素を`id`属性とクラス名から引けるようにします。ツリー構築やDOM操作で
+//! 要素が文書に出入りするたびに更新されます。`<template>`の内容のように文書につながっていない
+//! ノードは含みません。
+
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::engine::html::parser::{NodeRef, NodeType, WeakNodeRef};
+
+#[derive(Debug, Default)]
+pub(crate) struct DocumentIndex {
+    ids: HashMap<String, Vec<WeakNodeRef>>,
+    classes: HashMap<String, Vec<WeakNodeRef>>,
+}
+
+impl DocumentIndex {
+    /// 要素を索引に加えます（子孫は含みません）
+    pub(crate) fn insert(&mut self, node: &NodeRef) {
+        let (id, classes) = keys(node);
+        if let Some(id) = id {
+            self.ids.entry(id).or_default().push(Rc::downgrade(node));
+        }
+        for class in classes {
+            self.classes
+                .entry(class)
+                .or_default()
+                .push(Rc::downgrade(node));
+        }
+    }
+
+    /// 要素を索引から取り除きます（子孫は含みません）
+    pub(crate) fn remove(&mut self, node: &NodeRef) {
+        let (id, classes) = keys(node);
+        if let Some(id) = id {
+            remove_entry(&mut self.ids, &id, node);
+        }
+        for class in classes {
+            remove_entry(&mut self.classes, &class, node);
+        }
+    }
+
+    /// `id`を持つ要素を返します（順序は文書順とは限りません）
+    pub(crate) fn elements_with_id(&self, id: &str) -> Vec<NodeRef> {
+        upgrade_all(self.ids.get(id))
+    }
+
+    /// クラス名`class`を持つ要素を返します（順序は文書順とは限りません）
+    pub(crate) fn elements_with_class(&self, class: &str) -> Vec<NodeRef> {
+        upgrade_all(self.classes.get(class))
+    }
+}
+
+/// 要素の ID と、重複を除いたクラス名を返します
+fn keys(node: &NodeRef) -> (Option<String>, Vec<String>) {
+    let node = node.borrow();
+    if !matches!(node.node_type, NodeType::Element { .. }) {
+        return (None, vec![]);
+    }
+    let id = node
+        .attribute("id")
+        .filter(|id| !id.is_empty())
+        .map(str::to_string);
+    let mut classes: Vec<String> = vec![];
+    for class in node
+        .attribute("class")
+        .unwrap_or("")
+        .split_ascii_whitespace()
+    {
+        if !classes.iter().any(|c| c == class) {
+            classes.push(class.to_string());
+        }
+    }
+    (id, classes)
+}
+
+fn remove_entry(map: &mut HashMap<String, Vec<WeakNodeRef>>, key: &str, node: &NodeRef) {
+    if let Some(nodes) = map.get_mut(key) {
+        nodes.retain(|weak| !std::ptr::eq(weak.as_ptr(), Rc::as_ptr(node)));
+        if nodes.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+fn upgrade_all(nodes: Option<&Vec<WeakNodeRef>>) -> Vec<NodeRef> {
+    nodes
+        .map(|nodes| nodes.iter().filter_map(Weak::upgrade).collect())
+        .unwrap_or_default()
+}
@@ -1,2 +1,15 @@
+pub mod document;
+pub mod entities;
+pub mod error;
+pub mod event;
+pub(crate) mod foreign;
+pub(crate) mod index;
+pub mod mutation;
+pub(crate) mod names;
+pub mod namespace;
 pub mod parser;
+pub mod query;
+pub mod sanitizer;
+pub mod serializer;
+pub mod span;
 pub mod tokenizer;
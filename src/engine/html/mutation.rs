@@ -0,0 +1,446 @@
+//! DOM の変更
+//!
+//! DOM Standard の挿入・削除・置換と属性・テキストの変更を行います。親子と兄弟のリンク、
+//! 文書の ID とクラスの索引を保ったまま変更し、文書ノードで記録を有効にしている場合は
+//! 変更の記録（MutationRecord）を残します。レイアウトの差分更新や、将来の MutationObserver が
+//! 記録を使います。
+//! https://dom.spec.whatwg.org/#mutation-algorithms
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::engine::html::names;
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::parser::{self, Node, NodeRef, NodeType};
+use crate::engine::html::tokenizer::Attribute;
+
+/// DOM の変更に失敗した理由（DOMException の名前に対応）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomError {
+    /// 子を持てないノードへの挿入や、自身の祖先の挿入など、ツリーの構造に反する（HierarchyRequestError）
+    HierarchyRequest,
+    /// 基準のノードが親の子でない（NotFoundError）
+    NotFound,
+    /// 属性名に使えない文字を含む（InvalidCharacterError）
+    InvalidCharacter,
+}
+
+impl fmt::Display for DomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DomError::HierarchyRequest => "HierarchyRequestError",
+            DomError::NotFound => "NotFoundError",
+            DomError::InvalidCharacter => "InvalidCharacterError",
+        })
+    }
+}
+
+impl Error for DomError {}
+
+/// 変更の記録
+#[derive(Debug, Clone)]
+pub enum MutationRecord {
+    /// 子の追加・削除（`childList`）
+    ChildList {
+        /// 子が変わったノード
+        target: NodeRef,
+        added_nodes: Vec<NodeRef>,
+        removed_nodes: Vec<NodeRef>,
+        /// 追加・削除した位置の直前の兄弟
+        previous_sibling: Option<NodeRef>,
+        /// 追加・削除した位置の直後の兄弟
+        next_sibling: Option<NodeRef>,
+    },
+    /// 属性の変更（`attributes`）
+    Attributes {
+        target: NodeRef,
+        name: String,
+        /// 変更前の値（属性がなかった場合は`None`）
+        old_value: Option<String>,
+    },
+    /// テキスト・コメントの内容の変更（`characterData`）
+    CharacterData { target: NodeRef, old_value: String },
+}
+
+impl MutationRecord {
+    /// 変更されたノードを返します
+    pub fn target(&self) -> &NodeRef {
+        match self {
+            MutationRecord::ChildList { target, .. }
+            | MutationRecord::Attributes { target, .. }
+            | MutationRecord::CharacterData { target, .. } => target,
+        }
+    }
+}
+
+impl Node {
+    /// 文書ノードで、以降の変更を記録するかを設定します（既定では記録しません）
+    ///
+    /// 文書につながっているノードへの変更だけが記録されます。
+    pub fn set_mutation_recording(&mut self, enabled: bool) {
+        if let Some(state) = self.document_state_mut() {
+            state.record_mutations = enabled;
+            if !enabled {
+                state.mutation_records.clear();
+            }
+        }
+    }
+
+    /// 文書ノードに溜まった変更の記録を、変更した順に取り出します
+    pub fn take_mutation_records(&mut self) -> Vec<MutationRecord> {
+        self.document_state_mut()
+            .map(|state| std::mem::take(&mut state.mutation_records))
+            .unwrap_or_default()
+    }
+}
+
+/// ノードを親の末尾に追加します（`appendChild`）
+///
+/// すでに別の場所にあるノードは移動します。DocumentFragment の場合は、その子をすべて移します。
+///
+/// # 戻り値
+/// * 追加したノード
+pub fn append_child(parent: &NodeRef, node: &NodeRef) -> Result<NodeRef, DomError> {
+    insert_before(parent, node, None)
+}
+
+/// ノードを`reference`の直前に挿入します（`insertBefore`）
+///
+/// # 引数
+/// * `parent` - 挿入先の親
+/// * `node` - 挿入するノード
+/// * `reference` - 基準の子。`None`の場合は末尾に追加します
+///
+/// # 戻り値
+/// * 挿入したノード
+pub fn insert_before(
+    parent: &NodeRef,
+    node: &NodeRef,
+    reference: Option<&NodeRef>,
+) -> Result<NodeRef, DomError> {
+    ensure_pre_insertion_validity(parent, node, reference)?;
+    let mut reference = reference.cloned();
+    if reference.as_ref().is_some_and(|r| Rc::ptr_eq(r, node)) {
+        reference = node.borrow().next_sibling();
+    }
+    let added_nodes = take_nodes_to_insert(node);
+    let previous_sibling = match &reference {
+        Some(reference) => reference.borrow().previous_sibling(),
+        None => parent.borrow().last_child(),
+    };
+    for child in &added_nodes {
+        insert_node(parent, child, reference.as_ref());
+    }
+    if !added_nodes.is_empty() {
+        queue_record(
+            parent,
+            MutationRecord::ChildList {
+                target: Rc::clone(parent),
+                added_nodes,
+                removed_nodes: vec![],
+                previous_sibling,
+                next_sibling: reference,
+            },
+        );
+    }
+    Ok(Rc::clone(node))
+}
+
+/// 親から子を取り除きます（`removeChild`）
+///
+/// # 戻り値
+/// * 取り除いたノード
+pub fn remove_child(parent: &NodeRef, child: &NodeRef) -> Result<NodeRef, DomError> {
+    if !is_child_of(child, parent) {
+        return Err(DomError::NotFound);
+    }
+    let (previous_sibling, next_sibling) = {
+        let child = child.borrow();
+        (child.previous_sibling(), child.next_sibling())
+    };
+    remove_node(child);
+    queue_record(
+        parent,
+        MutationRecord::ChildList {
+            target: Rc::clone(parent),
+            added_nodes: vec![],
+            removed_nodes: vec![Rc::clone(child)],
+            previous_sibling,
+            next_sibling,
+        },
+    );
+    Ok(Rc::clone(child))
+}
+
+/// ノードを親から取り除きます（`ChildNode.remove`）。親がない場合は何もしません
+pub fn remove(node: &NodeRef) {
+    let parent = node.borrow().parent();
+    if let Some(parent) = parent {
+        let _ = remove_child(&parent, node);
+    }
+}
+
+/// 子`old`を`node`で置き換えます（`replaceChild`）
+///
+/// # 戻り値
+/// * 取り除いた`old`
+pub fn replace_child(parent: &NodeRef, node: &NodeRef, old: &NodeRef) -> Result<NodeRef, DomError> {
+    if !is_child_of(old, parent) {
+        return Err(DomError::NotFound);
+    }
+    ensure_pre_insertion_validity(parent, node, None)?;
+    if Rc::ptr_eq(node, old) {
+        return Ok(Rc::clone(old));
+    }
+    let mut reference = old.borrow().next_sibling();
+    if reference.as_ref().is_some_and(|r| Rc::ptr_eq(r, node)) {
+        reference = node.borrow().next_sibling();
+    }
+    let added_nodes = take_nodes_to_insert(node);
+    let previous_sibling = old.borrow().previous_sibling();
+    remove_node(old);
+    for child in &added_nodes {
+        insert_node(parent, child, reference.as_ref());
+    }
+    queue_record(
+        parent,
+        MutationRecord::ChildList {
+            target: Rc::clone(parent),
+            added_nodes,
+            removed_nodes: vec![Rc::clone(old)],
+            previous_sibling,
+            next_sibling: reference,
+        },
+    );
+    Ok(Rc::clone(old))
+}
+
+/// 要素の属性を設定します（`setAttribute`）
+///
+/// HTMLの要素では属性名を小文字にします。要素でない場合は`DomError::HierarchyRequest`を返します。
+pub fn set_attribute(element: &NodeRef, name: &str, value: &str) -> Result<(), DomError> {
+    if !is_valid_attribute_name(name) {
+        return Err(DomError::InvalidCharacter);
+    }
+    let old_value = change_attribute(element, name, |attributes, name| {
+        match attributes.iter_mut().find(|attr| attr.name == name) {
+            Some(attr) => Some(std::mem::replace(&mut attr.value, value.to_string().into())),
+            None => {
+                attributes.push(Attribute {
+                    name: names::intern(name),
+                    value: value.to_string().into(),
+                    namespace: None,
+                });
+                None
+            }
+        }
+    })
+    .ok_or(DomError::HierarchyRequest)?;
+    queue_record(
+        element,
+        MutationRecord::Attributes {
+            target: Rc::clone(element),
+            name: attribute_name(element, name),
+            old_value: old_value.map(Cow::into_owned),
+        },
+    );
+    Ok(())
+}
+
+/// 要素の属性を取り除きます（`removeAttribute`）
+///
+/// # 戻り値
+/// * 取り除いた属性の値（属性がなかった場合は`None`）
+pub fn remove_attribute(element: &NodeRef, name: &str) -> Option<String> {
+    let old_value = change_attribute(element, name, |attributes, name| {
+        let index = attributes.iter().position(|attr| attr.name == name)?;
+        Some(attributes.remove(index).value.into_owned())
+    })
+    .flatten()?;
+    queue_record(
+        element,
+        MutationRecord::Attributes {
+            target: Rc::clone(element),
+            name: attribute_name(element, name),
+            old_value: Some(old_value.clone()),
+        },
+    );
+    Some(old_value)
+}
+
+/// テキストの内容を設定します（`textContent`への代入）
+///
+/// テキストとコメントは内容を置き換えます。要素と DocumentFragment は子をすべて取り除き、
+/// `text`が空でなければ1つのテキストノードを子にします。文書ノードと DOCTYPE は変更しません。
+pub fn set_text_content(node: &NodeRef, text: &str) {
+    let old_value = match &mut node.borrow_mut().node_type {
+        NodeType::Text(data) | NodeType::Comment(data) => {
+            Some(std::mem::replace(data, text.to_string()))
+        }
+        NodeType::Element { .. } | NodeType::DocumentFragment => None,
+        NodeType::Document | NodeType::Doctype { .. } => return,
+    };
+    if let Some(old_value) = old_value {
+        queue_record(
+            node,
+            MutationRecord::CharacterData {
+                target: Rc::clone(node),
+                old_value,
+            },
+        );
+        return;
+    }
+
+    let removed_nodes = node.borrow().children.clone();
+    let added_nodes = if text.is_empty() {
+        vec![]
+    } else {
+        vec![Node::new_text(text)]
+    };
+    if removed_nodes.is_empty() && added_nodes.is_empty() {
+        return;
+    }
+    queue_record(
+        node,
+        MutationRecord::ChildList {
+            target: Rc::clone(node),
+            added_nodes: added_nodes.clone(),
+            removed_nodes: removed_nodes.clone(),
+            previous_sibling: None,
+            next_sibling: None,
+        },
+    );
+    for child in &removed_nodes {
+        remove_node(child);
+    }
+    for child in added_nodes {
+        insert_node(node, &child, None);
+    }
+}
+
+/// 挿入の前提条件を確かめます（ensure pre-insertion validity）
+fn ensure_pre_insertion_validity(
+    parent: &NodeRef,
+    node: &NodeRef,
+    reference: Option<&NodeRef>,
+) -> Result<(), DomError> {
+    if !matches!(
+        parent.borrow().node_type,
+        NodeType::Document | NodeType::DocumentFragment | NodeType::Element { .. }
+    ) {
+        return Err(DomError::HierarchyRequest);
+    }
+    // 自身や祖先を子孫に挿入することはできない
+    let mut ancestor = Some(Rc::clone(parent));
+    while let Some(current) = ancestor {
+        if Rc::ptr_eq(&current, node) {
+            return Err(DomError::HierarchyRequest);
+        }
+        ancestor = current.borrow().parent();
+    }
+    if reference.is_some_and(|reference| !is_child_of(reference, parent)) {
+        return Err(DomError::NotFound);
+    }
+    let parent_is_document = matches!(parent.borrow().node_type, NodeType::Document);
+    match node.borrow().node_type {
+        NodeType::Document => Err(DomError::HierarchyRequest),
+        NodeType::Text(_) if parent_is_document => Err(DomError::HierarchyRequest),
+        NodeType::Doctype { .. } if !parent_is_document => Err(DomError::HierarchyRequest),
+        _ => Ok(()),
+    }
+}
+
+/// 挿入するノードを元の場所から取り除いて返します
+///
+/// DocumentFragment の場合はその子を返します。別の親から移動するノードは、元の親での削除を記録します。
+fn take_nodes_to_insert(node: &NodeRef) -> Vec<NodeRef> {
+    if !matches!(node.borrow().node_type, NodeType::DocumentFragment) {
+        remove(node);
+        return vec![Rc::clone(node)];
+    }
+    let children = node.borrow().children.clone();
+    for child in &children {
+        remove_node(child);
+    }
+    children
+}
+
+fn insert_node(parent: &NodeRef, node: &NodeRef, reference: Option<&NodeRef>) {
+    match reference {
+        Some(reference) => parser::insert_before(parent, Rc::clone(node), reference),
+        None => parser::append_child(parent, Rc::clone(node)),
+    }
+}
+
+fn remove_node(node: &NodeRef) {
+    parser::detach(node);
+}
+
+fn is_child_of(node: &NodeRef, parent: &NodeRef) -> bool {
+    node.borrow()
+        .parent()
+        .is_some_and(|p| Rc::ptr_eq(&p, parent))
+}
+
+/// 属性名として使えるか（空白や`/`、`>`、`=`を含まない空でない名前）
+fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_ascii_whitespace() || matches!(c, '/' | '>' | '=' | '"' | '\'' | '\0'))
+}
+
+/// 要素に合わせた属性名（HTMLの要素では小文字）
+fn attribute_name(element: &NodeRef, name: &str) -> String {
+    if element.borrow().namespace() == Some(Namespace::Html) {
+        name.to_ascii_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+/// 要素の属性を変更し、文書の索引を更新します
+///
+/// # 戻り値
+/// * `change`の戻り値。要素でない場合は`None`
+fn change_attribute<T>(
+    element: &NodeRef,
+    name: &str,
+    change: impl FnOnce(&mut Vec<Attribute<'static>>, &str) -> T,
+) -> Option<T> {
+    let name = attribute_name(element, name);
+    let document = parser::owner_document(element);
+    let reindex = |insert: bool| {
+        if let Some(document) = &document {
+            if let Some(state) = document.borrow_mut().document_state_mut() {
+                if insert {
+                    state.index.insert(element);
+                } else {
+                    state.index.remove(element);
+                }
+            }
+        }
+    };
+    reindex(false);
+    let result = match &mut element.borrow_mut().node_type {
+        NodeType::Element { attributes, .. } => Some(change(attributes, &name)),
+        _ => None,
+    };
+    reindex(true);
+    result
+}
+
+/// `target`がつながっている文書で記録が有効なら、変更を記録します
+fn queue_record(target: &NodeRef, record: MutationRecord) {
+    let Some(document) = parser::owner_document(target) else {
+        return;
+    };
+    let mut document = document.borrow_mut();
+    if let Some(state) = document.document_state_mut() {
+        if state.record_mutations {
+            state.mutation_records.push(record);
+        }
+    }
+}
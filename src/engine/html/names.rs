@@ -0,0 +1,337 @@
+//! タグ名・属性名のインターン
+//!
+//! 字句解析器はタグ名と属性名を小文字にして出力します。よく使われる名前は静的な文字列を
+//! 借用して返し、要素や属性ごとに名前の`String`を確保しないようにします。
+
+use std::borrow::Cow;
+
+/// インターンする名前（小文字、二分探索のため昇順）
+const KNOWN_NAMES: &[&str] = &[
+    "a",
+    "abbr",
+    "accept",
+    "accept-charset",
+    "accesskey",
+    "action",
+    "address",
+    "align",
+    "allow",
+    "alt",
+    "annotation",
+    "annotation-xml",
+    "applet",
+    "area",
+    "article",
+    "as",
+    "aside",
+    "async",
+    "audio",
+    "autocomplete",
+    "autofocus",
+    "autoplay",
+    "b",
+    "background",
+    "base",
+    "basefont",
+    "bdi",
+    "bdo",
+    "bgcolor",
+    "bgsound",
+    "big",
+    "blink",
+    "blockquote",
+    "body",
+    "border",
+    "br",
+    "button",
+    "canvas",
+    "caption",
+    "center",
+    "charset",
+    "checked",
+    "circle",
+    "cite",
+    "class",
+    "clippath",
+    "code",
+    "col",
+    "colgroup",
+    "color",
+    "cols",
+    "colspan",
+    "content",
+    "contenteditable",
+    "controls",
+    "coords",
+    "crossorigin",
+    "d",
+    "data",
+    "datalist",
+    "datetime",
+    "dd",
+    "decoding",
+    "default",
+    "defer",
+    "defs",
+    "del",
+    "desc",
+    "details",
+    "dfn",
+    "dialog",
+    "dir",
+    "disabled",
+    "div",
+    "dl",
+    "download",
+    "draggable",
+    "dt",
+    "ellipse",
+    "em",
+    "embed",
+    "enctype",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "fill",
+    "filter",
+    "font",
+    "footer",
+    "for",
+    "foreignobject",
+    "form",
+    "formaction",
+    "frame",
+    "frameset",
+    "g",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "headers",
+    "height",
+    "hgroup",
+    "hidden",
+    "high",
+    "hr",
+    "href",
+    "hreflang",
+    "html",
+    "http-equiv",
+    "i",
+    "id",
+    "iframe",
+    "image",
+    "img",
+    "input",
+    "ins",
+    "integrity",
+    "is",
+    "isindex",
+    "itemprop",
+    "kbd",
+    "keygen",
+    "kind",
+    "label",
+    "lang",
+    "language",
+    "legend",
+    "li",
+    "line",
+    "lineargradient",
+    "link",
+    "list",
+    "listing",
+    "loading",
+    "loop",
+    "low",
+    "main",
+    "malignmark",
+    "map",
+    "mark",
+    "marquee",
+    "mask",
+    "math",
+    "max",
+    "maxlength",
+    "media",
+    "menu",
+    "menuitem",
+    "meta",
+    "meter",
+    "method",
+    "mglyph",
+    "mi",
+    "min",
+    "minlength",
+    "mn",
+    "mo",
+    "mrow",
+    "ms",
+    "mtext",
+    "multiple",
+    "muted",
+    "name",
+    "nav",
+    "nobr",
+    "noembed",
+    "noframes",
+    "nomodule",
+    "noscript",
+    "novalidate",
+    "object",
+    "ol",
+    "open",
+    "optgroup",
+    "optimum",
+    "option",
+    "output",
+    "p",
+    "param",
+    "path",
+    "pattern",
+    "picture",
+    "ping",
+    "placeholder",
+    "plaintext",
+    "playsinline",
+    "polygon",
+    "polyline",
+    "poster",
+    "pre",
+    "preload",
+    "progress",
+    "q",
+    "radialgradient",
+    "rb",
+    "readonly",
+    "rect",
+    "referrerpolicy",
+    "rel",
+    "required",
+    "reversed",
+    "role",
+    "rows",
+    "rowspan",
+    "rp",
+    "rt",
+    "rtc",
+    "ruby",
+    "s",
+    "samp",
+    "sandbox",
+    "scope",
+    "script",
+    "search",
+    "section",
+    "select",
+    "selected",
+    "semantics",
+    "shape",
+    "size",
+    "sizes",
+    "slot",
+    "small",
+    "source",
+    "span",
+    "spellcheck",
+    "src",
+    "srcdoc",
+    "srclang",
+    "srcset",
+    "start",
+    "step",
+    "stop",
+    "strike",
+    "stroke",
+    "strong",
+    "style",
+    "sub",
+    "summary",
+    "sup",
+    "svg",
+    "symbol",
+    "tabindex",
+    "table",
+    "target",
+    "tbody",
+    "td",
+    "template",
+    "text",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "title",
+    "tr",
+    "track",
+    "translate",
+    "tspan",
+    "tt",
+    "type",
+    "u",
+    "ul",
+    "use",
+    "usemap",
+    "value",
+    "var",
+    "video",
+    "viewbox",
+    "wbr",
+    "width",
+    "wrap",
+    "x",
+    "xmlns",
+    "xmp",
+    "y",
+];
+
+/// 小文字の名前を、既知の名前なら静的な文字列として、そうでなければ複製して返します
+pub(crate) fn intern(name: &str) -> Cow<'static, str> {
+    match KNOWN_NAMES.binary_search(&name) {
+        Ok(index) => Cow::Borrowed(KNOWN_NAMES[index]),
+        Err(_) => Cow::Owned(name.to_string()),
+    }
+}
+
+/// 名前を ASCII の小文字にしてから`intern`します
+pub(crate) fn intern_lowercase(name: &str) -> Cow<'static, str> {
+    if !name.bytes().any(|b| b.is_ascii_uppercase()) {
+        return intern(name);
+    }
+    let lower = name.to_ascii_lowercase();
+    match KNOWN_NAMES.binary_search(&lower.as_str()) {
+        Ok(index) => Cow::Borrowed(KNOWN_NAMES[index]),
+        Err(_) => Cow::Owned(lower),
+    }
+}
+
+/// 組み立て中の名前を、既知の名前なら静的な文字列に置き換えます
+pub(crate) fn intern_in_place(name: &mut Cow<'static, str>) {
+    if let Cow::Owned(owned) = name {
+        if let Ok(index) = KNOWN_NAMES.binary_search(&owned.as_str()) {
+            *name = Cow::Borrowed(KNOWN_NAMES[index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_names_are_sorted_and_unique() {
+        // 二分探索で引くため、昇順で重複がないこと
+        assert!(KNOWN_NAMES.windows(2).all(|w| w[0] < w[1]));
+        for name in KNOWN_NAMES {
+            assert_eq!(*name, name.to_ascii_lowercase());
+            assert!(matches!(intern(name), Cow::Borrowed(_)), "{name}");
+        }
+        assert!(matches!(intern_lowercase("DIV"), Cow::Borrowed("div")));
+        assert!(matches!(intern("x-custom"), Cow::Owned(_)));
+    }
+}
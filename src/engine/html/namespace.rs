@@ -0,0 +1,42 @@
+//! 要素と属性の名前空間
+//!
+//! HTML文書の中の`<svg>`や`<math>`以下の要素（外部コンテンツ）は、それぞれSVGとMathMLの
+//! 名前空間に属します。属性は`xlink:href`などの一部だけが名前空間を持ちます。
+//! https://infra.spec.whatwg.org/#namespaces
+
+/// 名前空間
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+    XLink,
+    Xml,
+    XmlNs,
+}
+
+impl Namespace {
+    /// 名前空間のURL
+    pub fn url(&self) -> &'static str {
+        match self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+            Namespace::XLink => "http://www.w3.org/1999/xlink",
+            Namespace::Xml => "http://www.w3.org/XML/1998/namespace",
+            Namespace::XmlNs => "http://www.w3.org/2000/xmlns/",
+        }
+    }
+
+    /// 慣例的な接頭辞（`svg`、`math`、`xlink`など）
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Namespace::Html => "html",
+            Namespace::Svg => "svg",
+            Namespace::MathMl => "math",
+            Namespace::XLink => "xlink",
+            Namespace::Xml => "xml",
+            Namespace::XmlNs => "xmlns",
+        }
+    }
+}
@@ -1,243 +1,3346 @@
-use crate::engine::html::tokenizer::{Attribute, Token, Tokenizer};
+//! HTML のツリー構築
+//!
+//! WHATWG HTML Standard の tree construction に従って、トークン列から DOM ツリーを構築します。
+//! https://html.spec.whatwg.org/multipage/parsing.html#tree-construction
+
+use crate::engine::html::error::{ParseError, ParseErrorKind};
+use crate::engine::html::event::EventListener;
+use crate::engine::html::foreign;
+use crate::engine::html::index::DocumentIndex;
+use crate::engine::html::mutation::MutationRecord;
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::span::{SourcePosition, Span};
+use crate::engine::html::tokenizer::{Attribute, Token, Tokenizer, TokenizerState};
+use std::borrow::Cow;
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
+use url::Url;
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum NodeType {
+    Document,
+    /// 文書に属さないノードの入れ物（`<template>`の内容など）
+    DocumentFragment,
+    Element {
+        tag_name: String,
+        attributes: Vec<Attribute<'static>>,
+        /// HTML、または`<svg>`・`<math>`以下の外部コンテンツの名前空間
+        namespace: Namespace,
+        /// `<template>`要素の内容（DocumentFragment）。それ以外の要素は`None`
+        ///
+        /// テンプレートの中身は`children`ではなく、こちらの子として構築されます。
+        template_content: Option<NodeRef>,
+    },
+    Text(String),
+    Comment(String),
+    Doctype {
+        name: Option<String>,
+        public_id: Option<String>,
+        system_id: Option<String>,
+    },
+}
+
+/// DOM のノード
+///
+/// 子は親が所有し、親と兄弟へのリンクは弱参照で持ちます。文書ノードを手放せばツリー全体が解放されます。
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Node {
+    /// ノードの種類と内容（変更は`mutation`の関数で行い、索引や変更の記録と食い違わないようにする）
+    pub(crate) node_type: NodeType,
+    /// 子ノード（変更は`mutation`の関数で行い、親・兄弟へのリンクと食い違わないようにする）
+    pub(crate) children: Vec<Rc<RefCell<Node>>>,
+    parent: Option<WeakNodeRef>,
+    previous_sibling: Option<WeakNodeRef>,
+    next_sibling: Option<WeakNodeRef>,
+    /// 文書ノードだけが持つ状態
+    document_state: Option<Box<DocumentState>>,
+    /// 登録されたイベントリスナー（複製したノードには引き継がない）
+    pub(crate) event_listeners: Vec<EventListener>,
+    /// ノードの元になったソース上の範囲
+    ///
+    /// 要素は開始タグ（対応する開始タグがない場合は終了タグ）の範囲、テキストは元になった
+    /// テキストトークン全体の範囲です。暗黙に補われた要素や文書ノードは`None`です。
+    pub span: Option<Span>,
+}
+
+pub type NodeRef = Rc<RefCell<Node>>;
+
+/// 文書ノードだけが持つ状態
+#[derive(Debug, Default)]
+pub(crate) struct DocumentState {
+    /// 文書につながっている要素の ID とクラスの索引
+    pub(crate) index: DocumentIndex,
+    /// 変更を記録するか
+    pub(crate) record_mutations: bool,
+    /// 記録した変更（`take_mutation_records`で取り出すまで溜まる）
+    pub(crate) mutation_records: Vec<MutationRecord>,
+    /// 文書の URL（`set_url`で設定するまでは`None`）
+    pub(crate) url: Option<Url>,
+}
+
+/// 所有しないノードへの参照（親・兄弟へのリンク）
+pub type WeakNodeRef = Weak<RefCell<Node>>;
+
+impl Node {
+    /// 親ノードを返します（親がない場合や、親がすでに解放されている場合は`None`）
+    pub fn parent(&self) -> Option<NodeRef> {
+        self.parent.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// 直前の兄弟ノードを返します
+    pub fn previous_sibling(&self) -> Option<NodeRef> {
+        self.previous_sibling.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// 直後の兄弟ノードを返します
+    pub fn next_sibling(&self) -> Option<NodeRef> {
+        self.next_sibling.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// ノードの種類と内容を返します
+    pub fn node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+
+    /// 子ノードを返します
+    pub fn children(&self) -> &[NodeRef] {
+        &self.children
+    }
+
+    /// 最初の子ノードを返します
+    pub fn first_child(&self) -> Option<NodeRef> {
+        self.children.first().cloned()
+    }
+
+    /// 最後の子ノードを返します
+    pub fn last_child(&self) -> Option<NodeRef> {
+        self.children.last().cloned()
+    }
+
+    /// 文書ノードの場合は ID とクラスの索引を返します
+    pub(crate) fn document_index(&self) -> Option<&DocumentIndex> {
+        self.document_state.as_ref().map(|state| &state.index)
+    }
+
+    /// 文書ノードの場合は文書の状態を返します
+    pub(crate) fn document_state(&self) -> Option<&DocumentState> {
+        self.document_state.as_deref()
+    }
+
+    /// 文書ノードの場合は文書の状態を返します
+    pub(crate) fn document_state_mut(&mut self) -> Option<&mut DocumentState> {
+        self.document_state.as_deref_mut()
+    }
+
+    /// 要素の場合はタグ名を返します
+    pub fn tag_name(&self) -> Option<&str> {
+        match &self.node_type {
+            NodeType::Element { tag_name, .. } => Some(tag_name),
+            _ => None,
+        }
+    }
+
+    /// 要素の場合は名前空間を返します
+    pub fn namespace(&self) -> Option<Namespace> {
+        match &self.node_type {
+            NodeType::Element { namespace, .. } => Some(*namespace),
+            _ => None,
+        }
+    }
+
+    /// 親を持たない要素を作成します
+    ///
+    /// `<template>`要素には空の内容（DocumentFragment）を持たせます。
+    ///
+    /// # 引数
+    /// * `namespace` - 要素の名前空間
+    /// * `tag_name` - タグ名（SVGの`foreignObject`などは正しい大文字小文字で指定する）
+    /// * `attributes` - 属性
+    pub fn new_element(
+        namespace: Namespace,
+        tag_name: &str,
+        attributes: Vec<Attribute>,
+    ) -> NodeRef {
+        new_element_in(namespace, tag_name, attributes)
+    }
+
+    /// 親を持たないテキストノードを作成します
+    pub fn new_text(text: &str) -> NodeRef {
+        new_node(NodeType::Text(text.to_string()))
+    }
+
+    /// 空の DocumentFragment を作成します
+    pub fn new_document_fragment() -> NodeRef {
+        new_node(NodeType::DocumentFragment)
+    }
+
+    /// `<template>`要素の場合は内容の DocumentFragment を返します
+    pub fn template_content(&self) -> Option<NodeRef> {
+        match &self.node_type {
+            NodeType::Element {
+                template_content, ..
+            } => template_content.clone(),
+            _ => None,
+        }
+    }
+
+    /// ノードを複製します（cloning a node）
+    ///
+    /// 複製は親を持ちません。`deep`が`true`の場合は子孫と`<template>`の内容も複製します。
+    ///
+    /// # 引数
+    /// * `deep` - 子孫も複製するか
+    pub fn clone_node(&self, deep: bool) -> NodeRef {
+        let node_type = match &self.node_type {
+            NodeType::Document => NodeType::Document,
+            NodeType::DocumentFragment => NodeType::DocumentFragment,
+            NodeType::Element {
+                tag_name,
+                attributes,
+                namespace,
+                template_content,
+            } => NodeType::Element {
+                tag_name: tag_name.clone(),
+                attributes: attributes.clone(),
+                namespace: *namespace,
+                template_content: template_content.as_ref().map(|content| {
+                    if deep {
+                        content.borrow().clone_node(true)
+                    } else {
+                        new_node(NodeType::DocumentFragment)
+                    }
+                }),
+            },
+            NodeType::Text(text) => NodeType::Text(text.clone()),
+            NodeType::Comment(data) => NodeType::Comment(data.clone()),
+            NodeType::Doctype {
+                name,
+                public_id,
+                system_id,
+            } => NodeType::Doctype {
+                name: name.clone(),
+                public_id: public_id.clone(),
+                system_id: system_id.clone(),
+            },
+        };
+        let clone = new_node(node_type);
+        clone.borrow_mut().span = self.span;
+        // 文書の複製は URL を引き継ぐ
+        if let (Some(state), Some(cloned)) = (
+            self.document_state(),
+            clone.borrow_mut().document_state_mut(),
+        ) {
+            cloned.url = state.url.clone();
+        }
+        if deep {
+            for child in &self.children {
+                append_child(&clone, child.borrow().clone_node(true));
+            }
+        }
+        clone
+    }
+
+    /// 要素の属性値を返します
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        match &self.node_type {
+            NodeType::Element { attributes, .. } => attributes
+                .iter()
+                .find(|attr| attr.name == name)
+                .map(|attr| attr.value.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Node {
+    /// 子孫を再帰せずに解放します（深く入れ子になった文書でスタックを使い切らないように）
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.children);
+        while let Some(child) = pending.pop() {
+            if let Ok(child) = Rc::try_unwrap(child) {
+                pending.append(&mut child.borrow_mut().children);
+            }
+        }
+    }
+}
+
+/// DOCTYPE から決まる文書のモード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirksMode {
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
+}
+
+/// ツリー構築の挿入モード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertionMode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    InHeadNoscript,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InTableText,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InSelect,
+    InSelectInTable,
+    InTemplate,
+    AfterBody,
+    InFrameset,
+    AfterFrameset,
+    AfterAfterBody,
+    AfterAfterFrameset,
+}
+
+/// 活性書式要素リスト（list of active formatting elements）の項目
+#[derive(Debug, Clone)]
+enum FormattingEntry {
+    Marker,
+    Element {
+        node: NodeRef,
+        name: Cow<'static, str>,
+        attributes: Vec<Attribute<'static>>,
+    },
+}
+
+/// スコープの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+    Select,
+}
+
+/// ノードを挿入する位置（`before`が`None`の場合は`parent`の末尾）
+struct InsertionPoint {
+    parent: NodeRef,
+    before: Option<NodeRef>,
+}
+
+/// パーサーが作る木の深さの上限
+///
+/// 深く入れ子になった入力で、木を再帰的にたどる処理（書き出しや描画）がスタックを
+/// 使い果たさないよう、これより深い要素は上限の深さの要素の兄弟にします（Blink と同じ値）。
+const MAX_TREE_DEPTH: usize = 512;
+
+/// 特別な要素（special）の一覧
+const SPECIAL_ELEMENTS: &[&str] = &[
+    "address",
+    "applet",
+    "area",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "bgsound",
+    "blockquote",
+    "body",
+    "br",
+    "button",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "embed",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "iframe",
+    "img",
+    "input",
+    "keygen",
+    "li",
+    "link",
+    "listing",
+    "main",
+    "marquee",
+    "menu",
+    "meta",
+    "nav",
+    "noembed",
+    "noframes",
+    "noscript",
+    "object",
+    "ol",
+    "p",
+    "param",
+    "plaintext",
+    "pre",
+    "script",
+    "search",
+    "section",
+    "select",
+    "source",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+    "wbr",
+    "xmp",
+    // MathML と SVG の特別な要素
+    "math mi",
+    "math mo",
+    "math mn",
+    "math ms",
+    "math mtext",
+    "math annotation-xml",
+    "svg foreignObject",
+    "svg desc",
+    "svg title",
+];
+
+/// 暗黙に閉じられる要素（generate implied end tags の対象）
+const IMPLIED_END_TAG_ELEMENTS: &[&str] = &[
+    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+];
+
+/// 表の要素も含めて暗黙に閉じられる要素（generate all implied end tags thoroughly の対象）
+const IMPLIED_END_TAG_ELEMENTS_THOROUGHLY: &[&str] = &[
+    "caption", "colgroup", "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+    "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+const HEADING_ELEMENTS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// 空要素（void elements）の一覧
+///
+/// 後半の5つは仕様上廃止された要素ですが、同じく子を持たない要素として解析します。
+pub const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr", "basefont", "bgsound", "frame", "keygen", "param",
+];
+
+/// 空要素（子を持たず、終了タグを書かない要素）かどうか
+pub fn is_void_element(tag_name: &str) -> bool {
+    VOID_ELEMENTS.contains(&tag_name)
+}
+
+/// HTMLのパーサー
+///
+/// `new`で入力全体を渡して`parse`するか、`new_streaming`で作って`feed`で少しずつ渡します。
+/// 入力の途中でも`document`で構築中の DOM を参照できます。
+/// 要素の中身（`innerHTML`）としての断片は`new_fragment`で作って`parse_fragment`で解析します。
+pub struct Parser<'a> {
+    tokenizer: Tokenizer<'a>,
+    document: NodeRef,
+    /// 開いている要素のスタック（stack of open elements）
+    stack: Vec<NodeRef>,
+    insertion_mode: InsertionMode,
+    /// Text / InTableText モードを抜けた後に戻る挿入モード
+    original_insertion_mode: InsertionMode,
+    /// `<template>`の中の挿入モードのスタック（stack of template insertion modes）
+    template_insertion_modes: Vec<InsertionMode>,
+    active_formatting_elements: Vec<FormattingEntry>,
+    head_element: Option<NodeRef>,
+    form_element: Option<NodeRef>,
+    frameset_ok: bool,
+    /// テーブル内の不正な内容をテーブルの前に移動するか（foster parenting）
+    foster_parenting: bool,
+    /// 次のトークンが改行で始まる場合に読み飛ばすか（`<pre>`などの直後）
+    ignore_next_line_feed: bool,
+    pending_table_text: String,
+    quirks_mode: QuirksMode,
+    /// 処理中のトークンの範囲
+    token_span: Option<Span>,
+    /// 最後に読んだテキストトークンの範囲
+    text_span: Option<Span>,
+    /// 処理中のタグの名前と範囲（同じ名前の要素を作るときに取り出す）
+    element_span: Option<(Cow<'static, str>, Span)>,
+    errors: Vec<ParseError>,
+    /// 入力の終わりまで処理したか
+    finished: bool,
+    /// 断片の解析の文脈要素（context element）
+    context_element: Option<NodeRef>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_tokenizer(Tokenizer::new(input))
+    }
+
+    /// 入力を`feed`で少しずつ受け取るパーサーを作ります
+    pub fn new_streaming() -> Self {
+        Self::with_tokenizer(Tokenizer::new_streaming())
+    }
+
+    fn with_tokenizer(tokenizer: Tokenizer<'a>) -> Self {
+        let document = new_node(NodeType::Document);
+
+        Self {
+            tokenizer,
+            document,
+            stack: vec![],
+            insertion_mode: InsertionMode::Initial,
+            original_insertion_mode: InsertionMode::Initial,
+            template_insertion_modes: vec![],
+            active_formatting_elements: vec![],
+            head_element: None,
+            form_element: None,
+            frameset_ok: true,
+            foster_parenting: false,
+            ignore_next_line_feed: false,
+            pending_table_text: String::new(),
+            quirks_mode: QuirksMode::NoQuirks,
+            token_span: None,
+            text_span: None,
+            element_span: None,
+            errors: vec![],
+            finished: false,
+            context_element: None,
+        }
+    }
+
+    /// `context`要素の中身として断片を解析するパーサーを作ります
+    ///
+    /// HTML fragment parsing algorithm に従い、文脈要素から字句解析の状態と挿入モードを決めます。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+    ///
+    /// # 引数
+    /// * `input` - 断片のHTML
+    /// * `context` - 断片を入れる要素（`<tr>`の中身なら`tr`要素）。要素以外は`body`として扱う
+    pub fn new_fragment(input: &'a str, context: &NodeRef) -> Self {
+        let context = if context.borrow().tag_name().is_some() {
+            Rc::clone(context)
+        } else {
+            new_element("body", vec![])
+        };
+        let mut parser = Self::with_tokenizer(Tokenizer::new(input));
+
+        if is_html_element(&context) {
+            let name = element_name(&context);
+            let state = match name.as_str() {
+                "title" | "textarea" => TokenizerState::Rcdata,
+                "style" | "xmp" | "iframe" | "noembed" | "noframes" => TokenizerState::Rawtext,
+                "script" => TokenizerState::ScriptData,
+                "plaintext" => TokenizerState::Plaintext,
+                // スクリプトは無効として扱うので noscript の中身も通常どおり解析する
+                _ => TokenizerState::Data,
+            };
+            parser.tokenizer.switch_to(state);
+            parser.tokenizer.set_last_start_tag(&name);
+            if name == "template" {
+                parser
+                    .template_insertion_modes
+                    .push(InsertionMode::InTemplate);
+            }
+        }
+
+        let root = new_element("html", vec![]);
+        append_child(&parser.document, Rc::clone(&root));
+        parser.stack.push(root);
+
+        // 文脈要素を含む祖先の form 要素
+        let mut ancestor = Some(Rc::clone(&context));
+        while let Some(node) = ancestor {
+            if is_element(&node, "form") {
+                parser.form_element = Some(node);
+                break;
+            }
+            ancestor = node.borrow().parent();
+        }
+
+        parser.context_element = Some(context);
+        parser.reset_insertion_mode();
+        parser
+    }
+
+    /// 入力の続きを渡し、届いた分までツリーを構築します
+    ///
+    /// # 引数
+    /// * `chunk` - UTF-8 のバイト列（文字の途中で区切られていてもよい）
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.tokenizer.feed(chunk);
+        self.process_available_tokens();
+    }
+
+    /// 構築中（または構築済み）の文書ノードを返します
+    pub fn document(&self) -> NodeRef {
+        Rc::clone(&self.document)
+    }
+
+    /// 残りの入力を処理して文書を完成させます
+    pub fn finish(&mut self) -> NodeRef {
+        if self.finished {
+            return self.document();
+        }
+        self.tokenizer.finish();
+        self.process_available_tokens();
+        self.finished = true;
+
+        let end = self.tokenizer.position();
+        self.token_span = Some(Span { start: end, end });
+        self.element_span = None;
+        self.dispatch(Token::Eof);
+
+        // 字句解析とツリー構築のエラーをソース上の順に並べる
+        let mut errors = self.tokenizer.take_errors();
+        errors.append(&mut self.errors);
+        errors.sort_by_key(|error| error.position.offset);
+        self.errors = errors;
+
+        self.document()
+    }
+
+    pub fn parse(&mut self) -> NodeRef {
+        self.finish()
+    }
+
+    fn process_available_tokens(&mut self) {
+        loop {
+            // CDATA セクションは外部コンテンツの中でのみ有効
+            let in_foreign_content = self
+                .adjusted_current_node()
+                .is_some_and(|node| !is_html_element(&node));
+            self.tokenizer.set_cdata_allowed(in_foreign_content);
+            let Some((token, span)) = self.tokenizer.next_token_with_span() else {
+                break;
+            };
+            self.set_token_span(&token, span);
+            self.dispatch(token);
+        }
+    }
+
+    /// 断片を解析し、文脈要素の子になるノードを返します
+    ///
+    /// 返すノードは親を持ちません。パースエラーは`errors`で参照できます。
+    pub fn parse_fragment(&mut self) -> Vec<NodeRef> {
+        let document = self.finish();
+        let Some(root) = document.borrow().children.first().cloned() else {
+            return vec![];
+        };
+        let children = root.borrow().children.clone();
+        for child in &children {
+            detach(child);
+        }
+        children
+    }
+
+    /// 文書を構築し、パースエラーの一覧とともに返します
+    ///
+    /// # 戻り値
+    /// * 文書ノードと、ソース上の順に並んだパースエラー
+    pub fn parse_with_errors(&mut self) -> (NodeRef, Vec<ParseError>) {
+        let document = self.parse();
+        (document, self.errors.clone())
+    }
+
+    /// `parse`（または`finish`）で検出したパースエラーを返します
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// DOCTYPE から判定された文書のモードを返します
+    pub fn quirks_mode(&self) -> QuirksMode {
+        self.quirks_mode
+    }
+
+    /// 処理するトークンの範囲を記録します（ノードの`span`やエラーの位置に使う）
+    fn set_token_span(&mut self, token: &Token, span: Span) {
+        self.token_span = Some(span);
+        self.element_span = match token {
+            Token::StartTag { name, .. } | Token::EndTag { name } => Some((name.clone(), span)),
+            _ => None,
+        };
+        if let Token::Text(_) = token {
+            self.text_span = Some(span);
+        }
+    }
+
+    /// 処理中のタグの位置でパースエラーを記録します
+    fn error(&mut self, kind: ParseErrorKind) {
+        let position = self.token_span.map(|span| span.start).unwrap_or_default();
+        self.errors.push(ParseError { kind, position });
+    }
+
+    /// トークンを無視して、パースエラーを記録します
+    fn ignore_token(&mut self, token: &Token) {
+        let kind = match token {
+            Token::StartTag { name, .. } => ParseErrorKind::UnexpectedStartTag(name.to_string()),
+            Token::EndTag { name } => ParseErrorKind::UnexpectedEndTag(name.to_string()),
+            Token::Doctype { .. } => ParseErrorKind::UnexpectedDoctype,
+            _ => return,
+        };
+        self.error(kind);
+    }
+
+    /// 処理中のタグが`name`のものであれば、その範囲を取り出します
+    fn take_element_span(&mut self, name: &str) -> Option<Span> {
+        match self.element_span.take() {
+            Some((tag_name, span)) if tag_name == name => Some(span),
+            other => {
+                self.element_span = other;
+                None
+            }
+        }
+    }
+
+    fn dispatch(&mut self, token: Token) {
+        let token = match token {
+            Token::Text(text) if self.ignore_next_line_feed => {
+                self.ignore_next_line_feed = false;
+                match text.strip_prefix('\n') {
+                    None => Token::Text(text),
+                    Some("") => return,
+                    Some(rest) => {
+                        // 読み飛ばした改行はテキストノードの範囲に含めない
+                        if let Some(span) = &mut self.text_span {
+                            span.start = SourcePosition {
+                                offset: span.start.offset + 1,
+                                line: span.start.line + 1,
+                                column: 1,
+                            };
+                        }
+                        Token::Text(rest.to_string().into())
+                    }
+                }
+            }
+            token => {
+                self.ignore_next_line_feed = false;
+                token
+            }
+        };
+        if self.is_foreign_content_token(&token) {
+            self.process_foreign_content(token);
+        } else {
+            self.process_token(token);
+        }
+    }
+
+    /// 調整済みの現在のノード（adjusted current node）を返します
+    ///
+    /// 断片の解析でスタックに html 要素しかない場合は、文脈要素を返します。
+    fn adjusted_current_node(&self) -> Option<NodeRef> {
+        match &self.context_element {
+            Some(context) if self.stack.len() == 1 => Some(Rc::clone(context)),
+            _ => self.stack.last().cloned(),
+        }
+    }
+
+    /// トークンを挿入モードではなく外部コンテンツの規則で処理するかどうか
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#tree-construction-dispatcher
+    fn is_foreign_content_token(&self, token: &Token) -> bool {
+        let Some(node) = self.adjusted_current_node() else {
+            return false;
+        };
+        if is_html_element(&node) {
+            return false;
+        }
+        match token {
+            Token::Eof => false,
+            Token::StartTag { name, .. }
+                if is_mathml_text_integration_point(&node)
+                    && !matches!(name.as_ref(), "mglyph" | "malignmark") =>
+            {
+                false
+            }
+            Token::Text(_) if is_mathml_text_integration_point(&node) => false,
+            Token::StartTag { name, .. }
+                if name == "svg" && is_element(&node, "math annotation-xml") =>
+            {
+                false
+            }
+            Token::StartTag { .. } | Token::Text(_) => !is_html_integration_point(&node),
+            _ => true,
+        }
+    }
+
+    fn process_token(&mut self, token: Token) {
+        self.process_using(self.insertion_mode, token);
+    }
+
+    /// 指定した挿入モードの規則でトークンを処理します
+    fn process_using(&mut self, mode: InsertionMode, token: Token) {
+        match mode {
+            InsertionMode::Initial => self.handle_initial(token),
+            InsertionMode::BeforeHtml => self.handle_before_html(token),
+            InsertionMode::BeforeHead => self.handle_before_head(token),
+            InsertionMode::InHead => self.handle_in_head(token),
+            InsertionMode::InHeadNoscript => self.handle_in_head_noscript(token),
+            InsertionMode::AfterHead => self.handle_after_head(token),
+            InsertionMode::InBody => self.handle_in_body(token),
+            InsertionMode::Text => self.handle_text(token),
+            InsertionMode::InTable => self.handle_in_table(token),
+            InsertionMode::InTableText => self.handle_in_table_text(token),
+            InsertionMode::InCaption => self.handle_in_caption(token),
+            InsertionMode::InColumnGroup => self.handle_in_column_group(token),
+            InsertionMode::InTableBody => self.handle_in_table_body(token),
+            InsertionMode::InRow => self.handle_in_row(token),
+            InsertionMode::InCell => self.handle_in_cell(token),
+            InsertionMode::InSelect => self.handle_in_select(token),
+            InsertionMode::InSelectInTable => self.handle_in_select_in_table(token),
+            InsertionMode::InTemplate => self.handle_in_template(token),
+            InsertionMode::AfterBody => self.handle_after_body(token),
+            InsertionMode::InFrameset => self.handle_in_frameset(token),
+            InsertionMode::AfterFrameset => self.handle_after_frameset(token),
+            InsertionMode::AfterAfterBody => self.handle_after_after_body(token),
+            InsertionMode::AfterAfterFrameset => self.handle_after_after_frameset(token),
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    // 挿入モードごとの処理
+    // ---------------------------------------------------------------------
+
+    fn handle_initial(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let rest = trim_leading_whitespace(&text);
+                if !rest.is_empty() {
+                    self.initial_anything_else(Token::Text(rest.to_string().into()));
+                }
+            }
+            Token::Comment(data) => self.append_comment_to(&Rc::clone(&self.document), data),
+            Token::Doctype {
+                name,
+                public_id,
+                system_id,
+                force_quirks,
+            } => {
+                let conforming = name.as_deref() == Some("html")
+                    && public_id.is_none()
+                    && system_id
+                        .as_deref()
+                        .is_none_or(|id| id == "about:legacy-compat");
+                if !conforming {
+                    self.error(ParseErrorKind::NonConformingDoctype);
+                }
+                self.quirks_mode = quirks_mode_for_doctype(
+                    name.as_deref(),
+                    public_id.as_deref(),
+                    system_id.as_deref(),
+                    force_quirks,
+                );
+                let doctype = new_node(NodeType::Doctype {
+                    name: name.map(Cow::into_owned),
+                    public_id: public_id.map(Cow::into_owned),
+                    system_id: system_id.map(Cow::into_owned),
+                });
+                doctype.borrow_mut().span = self.token_span;
+                append_child(&self.document, doctype);
+                self.insertion_mode = InsertionMode::BeforeHtml;
+            }
+            token => self.initial_anything_else(token),
+        }
+    }
+
+    fn initial_anything_else(&mut self, token: Token) {
+        // DOCTYPE がない文書は互換モード
+        self.error(ParseErrorKind::MissingDoctype);
+        self.quirks_mode = QuirksMode::Quirks;
+        self.insertion_mode = InsertionMode::BeforeHtml;
+        self.process_token(token);
+    }
+
+    fn handle_before_html(&mut self, token: Token) {
+        match token {
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::Comment(data) => self.append_comment_to(&Rc::clone(&self.document), data),
+            Token::Text(text) => {
+                let rest = trim_leading_whitespace(&text);
+                if !rest.is_empty() {
+                    self.before_html_anything_else(Token::Text(rest.to_string().into()));
+                }
+            }
+            Token::StartTag {
+                name, attributes, ..
+            } if name == "html" => {
+                let html = new_element(&name, attributes);
+                html.borrow_mut().span = self.take_element_span(&name);
+                append_child(&self.document, Rc::clone(&html));
+                self.stack.push(html);
+                self.insertion_mode = InsertionMode::BeforeHead;
+            }
+            Token::EndTag { ref name }
+                if !matches!(name.as_ref(), "head" | "body" | "html" | "br") =>
+            {
+                self.ignore_token(&token)
+            }
+            token => self.before_html_anything_else(token),
+        }
+    }
+
+    fn before_html_anything_else(&mut self, token: Token) {
+        let html = new_element("html", vec![]);
+        append_child(&self.document, Rc::clone(&html));
+        self.stack.push(html);
+        self.insertion_mode = InsertionMode::BeforeHead;
+        self.process_token(token);
+    }
+
+    fn handle_before_head(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let rest = trim_leading_whitespace(&text);
+                if !rest.is_empty() {
+                    self.before_head_anything_else(Token::Text(rest.to_string().into()));
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag { ref name, .. } if name == "html" => self.handle_in_body(token),
+            Token::StartTag {
+                name, attributes, ..
+            } if name == "head" => {
+                let head = self.insert_html_element(&name, attributes);
+                self.head_element = Some(head);
+                self.insertion_mode = InsertionMode::InHead;
+            }
+            Token::EndTag { ref name }
+                if !matches!(name.as_ref(), "head" | "body" | "html" | "br") =>
+            {
+                self.ignore_token(&token)
+            }
+            token => self.before_head_anything_else(token),
+        }
+    }
+
+    fn before_head_anything_else(&mut self, token: Token) {
+        let head = self.insert_html_element("head", vec![]);
+        self.head_element = Some(head);
+        self.insertion_mode = InsertionMode::InHead;
+        self.process_token(token);
+    }
+
+    fn handle_in_head(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let rest = self.insert_leading_whitespace(&text);
+                if !rest.is_empty() {
+                    self.in_head_anything_else(Token::Text(rest.into()));
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag {
+                name, attributes, ..
+            } => match name.as_ref() {
+                "html" => self.handle_in_body(Token::StartTag {
+                    name,
+                    attributes,
+                    self_closing: false,
+                }),
+                "base" | "basefont" | "bgsound" | "link" | "meta" => {
+                    self.insert_void_element(&name, attributes);
+                }
+                "title" => {
+                    self.parse_generic_text_element(&name, attributes, TokenizerState::Rcdata)
+                }
+                "noscript" => {
+                    // スクリプトは無効として扱う
+                    self.insert_html_element(&name, attributes);
+                    self.insertion_mode = InsertionMode::InHeadNoscript;
+                }
+                "noframes" | "style" => {
+                    self.parse_generic_text_element(&name, attributes, TokenizerState::Rawtext)
+                }
+                "script" => {
+                    self.parse_generic_text_element(&name, attributes, TokenizerState::ScriptData)
+                }
+                "template" => {
+                    self.insert_html_element(&name, attributes);
+                    self.active_formatting_elements
+                        .push(FormattingEntry::Marker);
+                    self.frameset_ok = false;
+                    self.insertion_mode = InsertionMode::InTemplate;
+                    self.template_insertion_modes
+                        .push(InsertionMode::InTemplate);
+                }
+                "head" => self.error(ParseErrorKind::UnexpectedStartTag(name.to_string())),
+                _ => self.in_head_anything_else(Token::StartTag {
+                    name,
+                    attributes,
+                    self_closing: false,
+                }),
+            },
+            Token::EndTag { ref name } => match name.as_ref() {
+                "head" => {
+                    self.stack.pop();
+                    self.insertion_mode = InsertionMode::AfterHead;
+                }
+                "body" | "html" | "br" => self.in_head_anything_else(token),
+                "template" => self.close_template(),
+                _ => self.ignore_token(&token),
+            },
+            Token::Eof => self.in_head_anything_else(token),
+        }
+    }
+
+    fn in_head_anything_else(&mut self, token: Token) {
+        self.stack.pop();
+        self.insertion_mode = InsertionMode::AfterHead;
+        self.process_token(token);
+    }
+
+    /// `</template>`を処理します
+    fn close_template(&mut self) {
+        if !self.has_template_on_stack() {
+            self.error(ParseErrorKind::UnexpectedEndTag("template".to_string()));
+            return;
+        }
+        self.generate_all_implied_end_tags_thoroughly();
+        self.pop_until_closing("template");
+        self.clear_active_formatting_elements_to_last_marker();
+        self.template_insertion_modes.pop();
+        self.reset_insertion_mode();
+    }
+
+    fn handle_in_head_noscript(&mut self, token: Token) {
+        match token {
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag { ref name, .. } if name == "html" => self.handle_in_body(token),
+            Token::EndTag { ref name } if name == "noscript" => {
+                self.stack.pop();
+                self.insertion_mode = InsertionMode::InHead;
+            }
+            Token::Text(text) => {
+                let (whitespace, rest) = split_leading_whitespace(&text);
+                if !whitespace.is_empty() {
+                    self.handle_in_head(Token::Text(whitespace.to_string().into()));
+                }
+                if !rest.is_empty() {
+                    self.in_head_noscript_anything_else(Token::Text(rest.to_string().into()));
+                }
+            }
+            Token::Comment(_) => self.handle_in_head(token),
+            Token::StartTag { ref name, .. }
+                if matches!(
+                    name.as_ref(),
+                    "basefont" | "bgsound" | "link" | "meta" | "noframes" | "style"
+                ) =>
+            {
+                self.handle_in_head(token)
+            }
+            Token::StartTag { ref name, .. } if matches!(name.as_ref(), "head" | "noscript") => {
+                self.ignore_token(&token)
+            }
+            Token::EndTag { ref name } if name != "br" => self.ignore_token(&token),
+            token => self.in_head_noscript_anything_else(token),
+        }
+    }
+
+    fn in_head_noscript_anything_else(&mut self, token: Token) {
+        self.stack.pop();
+        self.insertion_mode = InsertionMode::InHead;
+        self.process_token(token);
+    }
+
+    fn handle_after_head(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let rest = self.insert_leading_whitespace(&text);
+                if !rest.is_empty() {
+                    self.after_head_anything_else(Token::Text(rest.into()));
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag {
+                name, attributes, ..
+            } => match name.as_ref() {
+                "html" => self.handle_in_body(Token::StartTag {
+                    name,
+                    attributes,
+                    self_closing: false,
+                }),
+                "body" => {
+                    self.insert_html_element(&name, attributes);
+                    self.frameset_ok = false;
+                    self.insertion_mode = InsertionMode::InBody;
+                }
+                "frameset" => {
+                    self.insert_html_element(&name, attributes);
+                    self.insertion_mode = InsertionMode::InFrameset;
+                }
+                "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script"
+                | "style" | "template" | "title" => {
+                    // head の外に書かれた head 用の要素は head に入れる
+                    let Some(head) = self.head_element.clone() else {
+                        return;
+                    };
+                    self.stack.push(Rc::clone(&head));
+                    self.handle_in_head(Token::StartTag {
+                        name,
+                        attributes,
+                        self_closing: false,
+                    });
+                    self.remove_from_stack(&head);
+                }
+                "head" => self.error(ParseErrorKind::UnexpectedStartTag(name.to_string())),
+                _ => self.after_head_anything_else(Token::StartTag {
+                    name,
+                    attributes,
+                    self_closing: false,
+                }),
+            },
+            Token::EndTag { ref name } if name == "template" => self.handle_in_head(token),
+            Token::EndTag { ref name } if !matches!(name.as_ref(), "body" | "html" | "br") => {
+                self.ignore_token(&token)
+            }
+            token => self.after_head_anything_else(token),
+        }
+    }
+
+    fn after_head_anything_else(&mut self, token: Token) {
+        self.insert_html_element("body", vec![]);
+        self.insertion_mode = InsertionMode::InBody;
+        self.process_token(token);
+    }
+
+    fn handle_in_body(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let text = text.replace('\0', "");
+                if text.is_empty() {
+                    return;
+                }
+                self.reconstruct_active_formatting_elements();
+                self.insert_characters(&text);
+                if !is_all_whitespace(&text) {
+                    self.frameset_ok = false;
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag {
+                name,
+                attributes,
+                self_closing,
+            } => self.in_body_start_tag(name, attributes, self_closing),
+            Token::EndTag { name } => self.in_body_end_tag(name),
+            Token::Eof if !self.template_insertion_modes.is_empty() => {
+                self.handle_in_template(token)
+            }
+            Token::Eof => {
+                // 構文解析の終了
+                let unclosed = self.stack.iter().any(|node| {
+                    !matches!(
+                        element_name(node).as_str(),
+                        "dd" | "dt"
+                            | "li"
+                            | "optgroup"
+                            | "option"
+                            | "p"
+                            | "rb"
+                            | "rp"
+                            | "rt"
+                            | "rtc"
+                            | "tbody"
+                            | "td"
+                            | "tfoot"
+                            | "th"
+                            | "thead"
+                            | "tr"
+                            | "body"
+                            | "html"
+                    )
+                });
+                if unclosed {
+                    self.error(ParseErrorKind::EofWithOpenElements);
+                }
+            }
+        }
+    }
+
+    fn in_body_start_tag(
+        &mut self,
+        name: Cow<'static, str>,
+        attributes: Vec<Attribute>,
+        self_closing: bool,
+    ) {
+        match name.as_ref() {
+            "html" => {
+                // 既存の html 要素にない属性を追加する（template の中では無視する）
+                if self.has_template_on_stack() {
+                    self.error(ParseErrorKind::UnexpectedStartTag(name.into_owned()));
+                    return;
+                }
+                if let Some(html) = self.stack.first() {
+                    add_missing_attributes(html, attributes);
+                }
+            }
+            "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script" | "style"
+            | "template" | "title" => self.handle_in_head(Token::StartTag {
+                name,
+                attributes,
+                self_closing,
+            }),
+            "body" => {
+                self.error(ParseErrorKind::UnexpectedStartTag(name.into_owned()));
+                if self.stack.len() < 2
+                    || !is_element(&self.stack[1], "body")
+                    || self.has_template_on_stack()
+                {
+                    return;
+                }
+                self.frameset_ok = false;
+                add_missing_attributes(&self.stack[1], attributes);
+            }
+            "frameset" => {
+                if self.stack.len() < 2 || !is_element(&self.stack[1], "body") || !self.frameset_ok
+                {
+                    self.error(ParseErrorKind::UnexpectedStartTag(name.into_owned()));
+                    return;
+                }
+                detach(&self.stack[1]);
+                self.stack.truncate(1);
+                self.insert_html_element(&name, attributes);
+                self.insertion_mode = InsertionMode::InFrameset;
+            }
+            "address" | "article" | "aside" | "blockquote" | "center" | "details" | "dialog"
+            | "dir" | "div" | "dl" | "fieldset" | "figcaption" | "figure" | "footer" | "header"
+            | "hgroup" | "main" | "menu" | "nav" | "ol" | "p" | "search" | "section"
+            | "summary" | "ul" => {
+                self.close_p_in_button_scope();
+                self.insert_html_element(&name, attributes);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.close_p_in_button_scope();
+                if self.current_is_one_of(HEADING_ELEMENTS) {
+                    self.stack.pop();
+                }
+                self.insert_html_element(&name, attributes);
+            }
+            "pre" | "listing" => {
+                self.close_p_in_button_scope();
+                self.insert_html_element(&name, attributes);
+                self.ignore_next_line_feed = true;
+                self.frameset_ok = false;
+            }
+            "form" => {
+                let in_template = self.has_template_on_stack();
+                if self.form_element.is_some() && !in_template {
+                    self.error(ParseErrorKind::UnexpectedStartTag(name.into_owned()));
+                    return;
+                }
+                self.close_p_in_button_scope();
+                let form = self.insert_html_element(&name, attributes);
+                // template の中の form は form 要素ポインタに設定しない
+                if !in_template {
+                    self.form_element = Some(form);
+                }
+            }
+            "li" | "dd" | "dt" => {
+                self.frameset_ok = false;
+                // 閉じられていない同種のリスト項目を閉じる
+                let closes: &[&str] = if name == "li" { &["li"] } else { &["dd", "dt"] };
+                for node in self.stack.clone().iter().rev() {
+                    let node_name = element_name(node);
+                    if closes.contains(&node_name.as_str()) {
+                        self.generate_implied_end_tags(Some(&node_name));
+                        self.pop_until(&[&node_name]);
+                        break;
+                    }
+                    if is_special(&node_name)
+                        && !matches!(node_name.as_str(), "address" | "div" | "p")
+                    {
+                        break;
+                    }
+                }
+                self.close_p_in_button_scope();
+                self.insert_html_element(&name, attributes);
+            }
+            "plaintext" => {
+                self.close_p_in_button_scope();
+                self.insert_html_element(&name, attributes);
+                self.tokenizer.switch_to(TokenizerState::Plaintext);
+            }
+            "button" => {
+                if self.has_element_in_scope(&["button"], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&["button"]);
+                }
+                self.reconstruct_active_formatting_elements();
+                self.insert_html_element(&name, attributes);
+                self.frameset_ok = false;
+            }
+            "a" => {
+                if let Some(a) = self.formatting_element_after_last_marker("a") {
+                    self.adoption_agency("a");
+                    self.remove_from_active_formatting_elements(&a);
+                    self.remove_from_stack(&a);
+                }
+                self.reconstruct_active_formatting_elements();
+                let node = self.insert_html_element(&name, attributes.clone());
+                self.push_active_formatting_element(node, name, attributes);
+            }
+            "b" | "big" | "code" | "em" | "font" | "i" | "s" | "small" | "strike" | "strong"
+            | "tt" | "u" => {
+                self.reconstruct_active_formatting_elements();
+                let node = self.insert_html_element(&name, attributes.clone());
+                self.push_active_formatting_element(node, name, attributes);
+            }
+            "nobr" => {
+                self.reconstruct_active_formatting_elements();
+                if self.has_element_in_scope(&["nobr"], Scope::Default) {
+                    self.adoption_agency("nobr");
+                    self.reconstruct_active_formatting_elements();
+                }
+                let node = self.insert_html_element(&name, attributes.clone());
+                self.push_active_formatting_element(node, name, attributes);
+            }
+            "applet" | "marquee" | "object" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_html_element(&name, attributes);
+                self.active_formatting_elements
+                    .push(FormattingEntry::Marker);
+                self.frameset_ok = false;
+            }
+            "table" => {
+                if self.quirks_mode != QuirksMode::Quirks {
+                    self.close_p_in_button_scope();
+                }
+                self.insert_html_element(&name, attributes);
+                self.frameset_ok = false;
+                self.insertion_mode = InsertionMode::InTable;
+            }
+            "area" | "br" | "embed" | "img" | "keygen" | "wbr" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_void_element(&name, attributes);
+                self.frameset_ok = false;
+            }
+            "input" => {
+                let hidden = attributes
+                    .iter()
+                    .any(|attr| attr.name == "type" && attr.value.eq_ignore_ascii_case("hidden"));
+                self.reconstruct_active_formatting_elements();
+                self.insert_void_element(&name, attributes);
+                if !hidden {
+                    self.frameset_ok = false;
+                }
+            }
+            "param" | "source" | "track" => {
+                self.insert_void_element(&name, attributes);
+            }
+            "hr" => {
+                self.close_p_in_button_scope();
+                self.insert_void_element(&name, attributes);
+                self.frameset_ok = false;
+            }
+            "image" => {
+                // <image> は <img> として扱う
+                self.in_body_start_tag("img".into(), attributes, self_closing)
+            }
+            "textarea" => {
+                self.insert_html_element(&name, attributes);
+                self.tokenizer.switch_to(TokenizerState::Rcdata);
+                self.ignore_next_line_feed = true;
+                self.frameset_ok = false;
+                self.original_insertion_mode = self.insertion_mode;
+                self.insertion_mode = InsertionMode::Text;
+            }
+            "xmp" => {
+                self.close_p_in_button_scope();
+                self.reconstruct_active_formatting_elements();
+                self.frameset_ok = false;
+                self.parse_generic_text_element(&name, attributes, TokenizerState::Rawtext);
+            }
+            "iframe" => {
+                self.frameset_ok = false;
+                self.parse_generic_text_element(&name, attributes, TokenizerState::Rawtext);
+            }
+            "noembed" => {
+                self.parse_generic_text_element(&name, attributes, TokenizerState::Rawtext)
+            }
+            "select" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_html_element(&name, attributes);
+                self.frameset_ok = false;
+                self.insertion_mode = match self.insertion_mode {
+                    InsertionMode::InTable
+                    | InsertionMode::InCaption
+                    | InsertionMode::InTableBody
+                    | InsertionMode::InRow
+                    | InsertionMode::InCell => InsertionMode::InSelectInTable,
+                    _ => InsertionMode::InSelect,
+                };
+            }
+            "optgroup" | "option" => {
+                if self.current_is("option") {
+                    self.stack.pop();
+                }
+                self.reconstruct_active_formatting_elements();
+                self.insert_html_element(&name, attributes);
+            }
+            "rb" | "rtc" => {
+                if self.has_element_in_scope(&["ruby"], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                }
+                self.insert_html_element(&name, attributes);
+            }
+            "rp" | "rt" => {
+                if self.has_element_in_scope(&["ruby"], Scope::Default) {
+                    self.generate_implied_end_tags(Some("rtc"));
+                }
+                self.insert_html_element(&name, attributes);
+            }
+            "math" | "svg" => {
+                // ここから外部コンテンツ（MathML / SVG）
+                self.reconstruct_active_formatting_elements();
+                let namespace = if name == "math" {
+                    Namespace::MathMl
+                } else {
+                    Namespace::Svg
+                };
+                self.insert_foreign_element(&name, attributes, namespace);
+                if self_closing {
+                    self.stack.pop();
+                }
+            }
+            "caption" | "col" | "colgroup" | "frame" | "head" | "tbody" | "td" | "tfoot" | "th"
+            | "thead" | "tr" => {
+                // 文脈に合わない開始タグは無視する
+                self.error(ParseErrorKind::UnexpectedStartTag(name.into_owned()));
+            }
+            _ => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_html_element(&name, attributes);
+            }
+        }
+    }
+
+    fn in_body_end_tag(&mut self, name: Cow<'static, str>) {
+        match name.as_ref() {
+            "template" => self.handle_in_head(Token::EndTag { name }),
+            "body" => {
+                if self.has_element_in_scope(&["body"], Scope::Default) {
+                    self.insertion_mode = InsertionMode::AfterBody;
+                }
+            }
+            "html" => {
+                if self.has_element_in_scope(&["body"], Scope::Default) {
+                    self.insertion_mode = InsertionMode::AfterBody;
+                    self.process_token(Token::EndTag { name });
+                }
+            }
+            "address" | "article" | "aside" | "blockquote" | "button" | "center" | "details"
+            | "dialog" | "dir" | "div" | "dl" | "fieldset" | "figcaption" | "figure" | "footer"
+            | "header" | "hgroup" | "listing" | "main" | "menu" | "nav" | "ol" | "pre"
+            | "search" | "section" | "summary" | "ul" => {
+                if self.has_element_in_scope(&[&name], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until_closing(&name);
+                } else {
+                    self.error(ParseErrorKind::UnexpectedEndTag(name.into_owned()));
+                }
+            }
+            "form" if self.has_template_on_stack() => {
+                if self.has_element_in_scope(&["form"], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until_closing("form");
+                } else {
+                    self.error(ParseErrorKind::UnexpectedEndTag(name.into_owned()));
+                }
+            }
+            "form" => {
+                let Some(form) = self.form_element.take() else {
+                    return;
+                };
+                if !self.has_node_in_scope(&form) {
+                    return;
+                }
+                self.generate_implied_end_tags(None);
+                self.remove_from_stack(&form);
+            }
+            "p" => {
+                if !self.has_element_in_scope(&["p"], Scope::Button) {
+                    // 対応する開始タグがない </p> は空の <p> を作る
+                    self.error(ParseErrorKind::UnexpectedEndTag(name.into_owned()));
+                    self.insert_html_element("p", vec![]);
+                }
+                self.close_p_element();
+            }
+            "li" => {
+                if self.has_element_in_scope(&["li"], Scope::ListItem) {
+                    self.generate_implied_end_tags(Some("li"));
+                    self.pop_until_closing(&name);
+                } else {
+                    self.error(ParseErrorKind::UnexpectedEndTag(name.into_owned()));
+                }
+            }
+            "dd" | "dt" => {
+                if self.has_element_in_scope(&[&name], Scope::Default) {
+                    self.generate_implied_end_tags(Some(&name));
+                    self.pop_until_closing(&name);
+                } else {
+                    self.error(ParseErrorKind::UnexpectedEndTag(name.into_owned()));
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                if self.has_element_in_scope(HEADING_ELEMENTS, Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    if !self.current_is(&name) {
+                        self.error(ParseErrorKind::MisnestedTag(name.into_owned()));
+                    }
+                    self.pop_until(HEADING_ELEMENTS);
+                } else {
+                    self.error(ParseErrorKind::UnexpectedEndTag(name.into_owned()));
+                }
+            }
+            "a" | "b" | "big" | "code" | "em" | "font" | "i" | "nobr" | "s" | "small"
+            | "strike" | "strong" | "tt" | "u" => {
+                if !self.adoption_agency(&name) {
+                    self.in_body_any_other_end_tag(&name);
+                }
+            }
+            "applet" | "marquee" | "object" => {
+                if self.has_element_in_scope(&[&name], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until_closing(&name);
+                    self.clear_active_formatting_elements_to_last_marker();
+                } else {
+                    self.error(ParseErrorKind::UnexpectedEndTag(name.into_owned()));
+                }
+            }
+            "br" => {
+                // </br> は <br> として扱う
+                self.error(ParseErrorKind::UnexpectedEndTag(name.to_string()));
+                self.in_body_start_tag(name, vec![], false);
+            }
+            _ => self.in_body_any_other_end_tag(&name),
+        }
+    }
+
+    fn in_body_any_other_end_tag(&mut self, name: &str) {
+        for index in (0..self.stack.len()).rev() {
+            let node_name = element_name(&self.stack[index]);
+            if node_name == name {
+                self.generate_implied_end_tags(Some(name));
+                if index + 1 != self.stack.len() {
+                    self.error(ParseErrorKind::MisnestedTag(name.to_string()));
+                }
+                self.stack.truncate(index);
+                return;
+            }
+            if is_special(&node_name) {
+                self.error(ParseErrorKind::UnexpectedEndTag(name.to_string()));
+                return;
+            }
+        }
+    }
+
+    fn handle_text(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => self.insert_characters(&text),
+            Token::Eof => {
+                self.error(ParseErrorKind::EofWithOpenElements);
+                self.stack.pop();
+                self.insertion_mode = self.original_insertion_mode;
+                self.process_token(token);
+            }
+            Token::EndTag { .. } => {
+                self.stack.pop();
+                self.insertion_mode = self.original_insertion_mode;
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_in_table(&mut self, token: Token) {
+        match token {
+            Token::Text(_)
+                if self
+                    .current_is_one_of(&["table", "tbody", "template", "tfoot", "thead", "tr"]) =>
+            {
+                self.pending_table_text.clear();
+                self.original_insertion_mode = self.insertion_mode;
+                self.insertion_mode = InsertionMode::InTableText;
+                self.process_token(token);
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag {
+                name, attributes, ..
+            } => match name.as_ref() {
+                "caption" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.active_formatting_elements
+                        .push(FormattingEntry::Marker);
+                    self.insert_html_element(&name, attributes);
+                    self.insertion_mode = InsertionMode::InCaption;
+                }
+                "colgroup" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element(&name, attributes);
+                    self.insertion_mode = InsertionMode::InColumnGroup;
+                }
+                "col" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element("colgroup", vec![]);
+                    self.insertion_mode = InsertionMode::InColumnGroup;
+                    self.process_token(start_tag(name, attributes));
+                }
+                "tbody" | "tfoot" | "thead" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element(&name, attributes);
+                    self.insertion_mode = InsertionMode::InTableBody;
+                }
+                "td" | "th" | "tr" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element("tbody", vec![]);
+                    self.insertion_mode = InsertionMode::InTableBody;
+                    self.process_token(start_tag(name, attributes));
+                }
+                "table" => {
+                    if self.has_element_in_scope(&["table"], Scope::Table) {
+                        self.pop_until(&["table"]);
+                        self.reset_insertion_mode();
+                        self.process_token(start_tag(name, attributes));
+                    }
+                }
+                "style" | "script" | "template" => self.handle_in_head(start_tag(name, attributes)),
+                "input"
+                    if attributes.iter().any(|attr| {
+                        attr.name == "type" && attr.value.eq_ignore_ascii_case("hidden")
+                    }) =>
+                {
+                    self.insert_void_element(&name, attributes);
+                }
+                "form" => {
+                    if self.form_element.is_none() && !self.has_template_on_stack() {
+                        let form = self.insert_html_element(&name, attributes);
+                        self.form_element = Some(form);
+                        self.stack.pop();
+                    }
+                }
+                _ => self.in_table_anything_else(start_tag(name, attributes)),
+            },
+            Token::EndTag { ref name } => match name.as_ref() {
+                "table" => {
+                    if self.has_element_in_scope(&["table"], Scope::Table) {
+                        self.pop_until(&["table"]);
+                        self.reset_insertion_mode();
+                    }
+                }
+                "body" | "caption" | "col" | "colgroup" | "html" | "tbody" | "td" | "tfoot"
+                | "th" | "thead" | "tr" => self.ignore_token(&token),
+                "template" => self.handle_in_head(token),
+                _ => self.in_table_anything_else(token),
+            },
+            Token::Eof => self.handle_in_body(token),
+            token => self.in_table_anything_else(token),
+        }
+    }
+
+    fn in_table_anything_else(&mut self, token: Token) {
+        self.error(ParseErrorKind::FosterParenting);
+        self.foster_parenting = true;
+        self.handle_in_body(token);
+        self.foster_parenting = false;
+    }
+
+    fn handle_in_table_text(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => self.pending_table_text.push_str(&text.replace('\0', "")),
+            token => {
+                let text = std::mem::take(&mut self.pending_table_text);
+                if !is_all_whitespace(&text) {
+                    // 空白以外を含む場合はテーブルの前に移動する（エラーはテキストの位置で記録する）
+                    let token_span = std::mem::replace(&mut self.token_span, self.text_span);
+                    self.in_table_anything_else(Token::Text(text.into()));
+                    self.token_span = token_span;
+                } else if !text.is_empty() {
+                    self.insert_characters(&text);
+                }
+                self.insertion_mode = self.original_insertion_mode;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn handle_in_caption(&mut self, token: Token) {
+        match token {
+            Token::EndTag { ref name } if name == "caption" => {
+                self.close_caption();
+            }
+            Token::StartTag { ref name, .. }
+                if matches!(
+                    name.as_ref(),
+                    "caption"
+                        | "col"
+                        | "colgroup"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                if self.close_caption() {
+                    self.process_token(token);
+                }
+            }
+            Token::EndTag { ref name } if name == "table" => {
+                if self.close_caption() {
+                    self.process_token(token);
+                }
+            }
+            Token::EndTag { ref name }
+                if matches!(
+                    name.as_ref(),
+                    "body"
+                        | "col"
+                        | "colgroup"
+                        | "html"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                self.ignore_token(&token)
+            }
+            token => self.handle_in_body(token),
+        }
+    }
+
+    /// caption を閉じます。caption がスコープにない場合は`false`を返します
+    fn close_caption(&mut self) -> bool {
+        if !self.has_element_in_scope(&["caption"], Scope::Table) {
+            return false;
+        }
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["caption"]);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.insertion_mode = InsertionMode::InTable;
+        true
+    }
+
+    fn handle_in_column_group(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let rest = self.insert_leading_whitespace(&text);
+                if !rest.is_empty() {
+                    self.in_column_group_anything_else(Token::Text(rest.into()));
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag { ref name, .. } if name == "html" => self.handle_in_body(token),
+            Token::StartTag {
+                name, attributes, ..
+            } if name == "col" => {
+                self.insert_void_element(&name, attributes);
+            }
+            Token::EndTag { ref name } if name == "colgroup" => {
+                if self.current_is("colgroup") {
+                    self.stack.pop();
+                    self.insertion_mode = InsertionMode::InTable;
+                }
+            }
+            Token::EndTag { ref name } if name == "col" => self.ignore_token(&token),
+            Token::StartTag { ref name, .. } | Token::EndTag { ref name } if name == "template" => {
+                self.handle_in_head(token)
+            }
+            Token::Eof => self.handle_in_body(token),
+            token => self.in_column_group_anything_else(token),
+        }
+    }
+
+    fn in_column_group_anything_else(&mut self, token: Token) {
+        if self.current_is("colgroup") {
+            self.stack.pop();
+            self.insertion_mode = InsertionMode::InTable;
+            self.process_token(token);
+        }
+    }
+
+    fn handle_in_table_body(&mut self, token: Token) {
+        const TABLE_BODY_CONTEXT: &[&str] = &["tbody", "tfoot", "thead", "template", "html"];
+        match token {
+            Token::StartTag {
+                name, attributes, ..
+            } if name == "tr" => {
+                self.clear_stack_back_to(TABLE_BODY_CONTEXT);
+                self.insert_html_element(&name, attributes);
+                self.insertion_mode = InsertionMode::InRow;
+            }
+            Token::StartTag { ref name, .. } if matches!(name.as_ref(), "th" | "td") => {
+                self.clear_stack_back_to(TABLE_BODY_CONTEXT);
+                self.insert_html_element("tr", vec![]);
+                self.insertion_mode = InsertionMode::InRow;
+                self.process_token(token);
+            }
+            Token::EndTag { ref name } if matches!(name.as_ref(), "tbody" | "tfoot" | "thead") => {
+                if self.has_element_in_scope(&[name], Scope::Table) {
+                    self.clear_stack_back_to(TABLE_BODY_CONTEXT);
+                    self.stack.pop();
+                    self.insertion_mode = InsertionMode::InTable;
+                }
+            }
+            Token::StartTag { ref name, .. }
+                if matches!(
+                    name.as_ref(),
+                    "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead"
+                ) =>
+            {
+                self.close_table_body(token)
+            }
+            Token::EndTag { ref name } if name == "table" => self.close_table_body(token),
+            Token::EndTag { ref name }
+                if matches!(
+                    name.as_ref(),
+                    "body" | "caption" | "col" | "colgroup" | "html" | "td" | "th" | "tr"
+                ) =>
+            {
+                self.ignore_token(&token)
+            }
+            token => self.handle_in_table(token),
+        }
+    }
+
+    /// 開いている tbody/thead/tfoot を閉じてからトークンを再処理します
+    fn close_table_body(&mut self, token: Token) {
+        if !self.has_element_in_scope(&["tbody", "thead", "tfoot"], Scope::Table) {
+            return;
+        }
+        self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+        self.stack.pop();
+        self.insertion_mode = InsertionMode::InTable;
+        self.process_token(token);
+    }
+
+    fn handle_in_row(&mut self, token: Token) {
+        const TABLE_ROW_CONTEXT: &[&str] = &["tr", "template", "html"];
+        match token {
+            Token::StartTag {
+                name, attributes, ..
+            } if matches!(name.as_ref(), "th" | "td") => {
+                self.clear_stack_back_to(TABLE_ROW_CONTEXT);
+                self.insert_html_element(&name, attributes);
+                self.insertion_mode = InsertionMode::InCell;
+                self.active_formatting_elements
+                    .push(FormattingEntry::Marker);
+            }
+            Token::EndTag { ref name } if name == "tr" => {
+                self.close_row();
+            }
+            Token::StartTag { ref name, .. }
+                if matches!(
+                    name.as_ref(),
+                    "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead" | "tr"
+                ) =>
+            {
+                if self.close_row() {
+                    self.process_token(token);
+                }
+            }
+            Token::EndTag { ref name } if name == "table" => {
+                if self.close_row() {
+                    self.process_token(token);
+                }
+            }
+            Token::EndTag { ref name } if matches!(name.as_ref(), "tbody" | "tfoot" | "thead") => {
+                if self.has_element_in_scope(&[name], Scope::Table) && self.close_row() {
+                    self.process_token(token);
+                }
+            }
+            Token::EndTag { ref name }
+                if matches!(
+                    name.as_ref(),
+                    "body" | "caption" | "col" | "colgroup" | "html" | "td" | "th"
+                ) =>
+            {
+                self.ignore_token(&token)
+            }
+            token => self.handle_in_table(token),
+        }
+    }
+
+    /// tr を閉じます。tr がスコープにない場合は`false`を返します
+    fn close_row(&mut self) -> bool {
+        if !self.has_element_in_scope(&["tr"], Scope::Table) {
+            return false;
+        }
+        self.clear_stack_back_to(&["tr", "template", "html"]);
+        self.stack.pop();
+        self.insertion_mode = InsertionMode::InTableBody;
+        true
+    }
+
+    fn handle_in_cell(&mut self, token: Token) {
+        match token {
+            Token::EndTag { ref name } if matches!(name.as_ref(), "td" | "th") => {
+                if self.has_element_in_scope(&[name], Scope::Table) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[name]);
+                    self.clear_active_formatting_elements_to_last_marker();
+                    self.insertion_mode = InsertionMode::InRow;
+                }
+            }
+            Token::StartTag { ref name, .. }
+                if matches!(
+                    name.as_ref(),
+                    "caption"
+                        | "col"
+                        | "colgroup"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                if self.has_element_in_scope(&["td", "th"], Scope::Table) {
+                    self.close_cell();
+                    self.process_token(token);
+                }
+            }
+            Token::EndTag { ref name }
+                if matches!(
+                    name.as_ref(),
+                    "body" | "caption" | "col" | "colgroup" | "html"
+                ) =>
+            {
+                self.ignore_token(&token)
+            }
+            Token::EndTag { ref name }
+                if matches!(name.as_ref(), "table" | "tbody" | "tfoot" | "thead" | "tr") =>
+            {
+                if self.has_element_in_scope(&[name], Scope::Table) {
+                    self.close_cell();
+                    self.process_token(token);
+                }
+            }
+            token => self.handle_in_body(token),
+        }
+    }
+
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["td", "th"]);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.insertion_mode = InsertionMode::InRow;
+    }
+
+    fn handle_in_select(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let text = text.replace('\0', "");
+                if !text.is_empty() {
+                    self.insert_characters(&text);
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag {
+                name, attributes, ..
+            } => match name.as_ref() {
+                "html" => self.handle_in_body(start_tag(name, attributes)),
+                "option" => {
+                    if self.current_is("option") {
+                        self.stack.pop();
+                    }
+                    self.insert_html_element(&name, attributes);
+                }
+                "optgroup" => {
+                    if self.current_is("option") {
+                        self.stack.pop();
+                    }
+                    if self.current_is("optgroup") {
+                        self.stack.pop();
+                    }
+                    self.insert_html_element(&name, attributes);
+                }
+                "hr" => {
+                    if self.current_is("option") {
+                        self.stack.pop();
+                    }
+                    if self.current_is("optgroup") {
+                        self.stack.pop();
+                    }
+                    self.insert_void_element(&name, attributes);
+                }
+                "select" => self.close_select(),
+                "input" | "keygen" | "textarea"
+                    if self.has_element_in_scope(&["select"], Scope::Select) =>
+                {
+                    self.close_select();
+                    self.process_token(start_tag(name, attributes));
+                }
+                "script" | "template" => self.handle_in_head(start_tag(name, attributes)),
+                _ => self.error(ParseErrorKind::UnexpectedStartTag(name.to_string())),
+            },
+            Token::EndTag { ref name } => match name.as_ref() {
+                "optgroup" => {
+                    let len = self.stack.len();
+                    if self.current_is("option")
+                        && len >= 2
+                        && is_element(&self.stack[len - 2], "optgroup")
+                    {
+                        self.stack.pop();
+                    }
+                    if self.current_is("optgroup") {
+                        self.stack.pop();
+                    }
+                }
+                "option" if self.current_is("option") => {
+                    self.stack.pop();
+                }
+                "select" => self.close_select(),
+                "template" => self.handle_in_head(token),
+                _ => self.ignore_token(&token),
+            },
+            Token::Eof => self.handle_in_body(token),
+        }
+    }
+
+    /// select がスコープにあれば閉じて、挿入モードをリセットします
+    fn close_select(&mut self) {
+        if self.has_element_in_scope(&["select"], Scope::Select) {
+            self.pop_until(&["select"]);
+            self.reset_insertion_mode();
+        }
+    }
+
+    fn handle_in_select_in_table(&mut self, token: Token) {
+        const TABLE_ELEMENTS: &[&str] = &[
+            "caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th",
+        ];
+        match token {
+            Token::StartTag { ref name, .. } if TABLE_ELEMENTS.contains(&name.as_ref()) => {
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+                self.process_token(token);
+            }
+            Token::EndTag { ref name } if TABLE_ELEMENTS.contains(&name.as_ref()) => {
+                if self.has_element_in_scope(&[name], Scope::Table) {
+                    self.pop_until(&["select"]);
+                    self.reset_insertion_mode();
+                    self.process_token(token);
+                }
+            }
+            token => self.handle_in_select(token),
+        }
+    }
+
+    fn handle_in_template(&mut self, token: Token) {
+        match token {
+            Token::Text(_) | Token::Comment(_) | Token::Doctype { .. } => {
+                self.handle_in_body(token)
+            }
+            Token::StartTag { ref name, .. }
+                if matches!(
+                    name.as_ref(),
+                    "base"
+                        | "basefont"
+                        | "bgsound"
+                        | "link"
+                        | "meta"
+                        | "noframes"
+                        | "script"
+                        | "style"
+                        | "template"
+                        | "title"
+                ) =>
+            {
+                self.handle_in_head(token)
+            }
+            Token::EndTag { ref name } if name == "template" => self.handle_in_head(token),
+            Token::StartTag { ref name, .. } => {
+                // 最初の開始タグで、テンプレートの内容を読む挿入モードが決まる
+                let mode = match name.as_ref() {
+                    "caption" | "colgroup" | "tbody" | "tfoot" | "thead" => InsertionMode::InTable,
+                    "col" => InsertionMode::InColumnGroup,
+                    "tr" => InsertionMode::InTableBody,
+                    "td" | "th" => InsertionMode::InRow,
+                    _ => InsertionMode::InBody,
+                };
+                self.template_insertion_modes.pop();
+                self.template_insertion_modes.push(mode);
+                self.insertion_mode = mode;
+                self.process_token(token);
+            }
+            Token::EndTag { .. } => self.ignore_token(&token),
+            Token::Eof => {
+                if !self.has_template_on_stack() {
+                    // 構文解析の終了
+                    return;
+                }
+                // 入れ子の template ごとに EOF を処理し直すと再帰が深くなるので、ここでまとめて閉じる。
+                // template の中の挿入モードでの EOF は、どれも template の規則に戻ってくる
+                while self.has_template_on_stack() {
+                    self.error(ParseErrorKind::EofWithOpenElements);
+                    self.pop_until(&["template"]);
+                    self.clear_active_formatting_elements_to_last_marker();
+                    self.template_insertion_modes.pop();
+                    self.reset_insertion_mode();
+                }
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn handle_after_body(&mut self, token: Token) {
+        match token {
+            Token::Text(ref text) if is_all_whitespace(text) => self.handle_in_body(token),
+            Token::Comment(data) => {
+                // html 要素の最後の子として追加する
+                if let Some(html) = self.stack.first().cloned() {
+                    self.append_comment_to(&html, data);
+                }
+            }
+            Token::Doctype { .. } => self.error(ParseErrorKind::UnexpectedDoctype),
+            Token::StartTag { ref name, .. } if name == "html" => self.handle_in_body(token),
+            Token::EndTag { ref name } if name == "html" => {
+                self.insertion_mode = InsertionMode::AfterAfterBody;
+            }
+            Token::Eof => {}
+            token => {
+                self.insertion_mode = InsertionMode::InBody;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn handle_in_frameset(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let whitespace: String = text.chars().filter(|c| is_whitespace(*c)).collect();
+                if !whitespace.is_empty() {
+                    self.insert_characters(&whitespace);
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::StartTag {
+                name, attributes, ..
+            } => match name.as_ref() {
+                "html" => self.handle_in_body(start_tag(name, attributes)),
+                "frameset" => {
+                    self.insert_html_element(&name, attributes);
+                }
+                "frame" => {
+                    self.insert_void_element(&name, attributes);
+                }
+                "noframes" => self.handle_in_head(start_tag(name, attributes)),
+                _ => self.error(ParseErrorKind::UnexpectedStartTag(name.to_string())),
+            },
+            Token::EndTag { ref name } if name == "frameset" && self.stack.len() > 1 => {
+                self.stack.pop();
+                if !self.current_is("frameset") {
+                    self.insertion_mode = InsertionMode::AfterFrameset;
+                }
+            }
+            _ => self.ignore_token(&token),
+        }
+    }
+
+    fn handle_after_frameset(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                let whitespace: String = text.chars().filter(|c| is_whitespace(*c)).collect();
+                if !whitespace.is_empty() {
+                    self.insert_characters(&whitespace);
+                }
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::StartTag { ref name, .. } if name == "html" => self.handle_in_body(token),
+            Token::EndTag { ref name } if name == "html" => {
+                self.insertion_mode = InsertionMode::AfterAfterFrameset;
+            }
+            Token::StartTag { ref name, .. } if name == "noframes" => self.handle_in_head(token),
+            _ => self.ignore_token(&token),
+        }
+    }
+
+    fn handle_after_after_body(&mut self, token: Token) {
+        match token {
+            Token::Comment(data) => self.append_comment_to(&Rc::clone(&self.document), data),
+            Token::Doctype { .. } => self.handle_in_body(token),
+            Token::Text(ref text) if is_all_whitespace(text) => self.handle_in_body(token),
+            Token::StartTag { ref name, .. } if name == "html" => self.handle_in_body(token),
+            Token::Eof => {}
+            token => {
+                self.insertion_mode = InsertionMode::InBody;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn handle_after_after_frameset(&mut self, token: Token) {
+        match token {
+            Token::Comment(data) => self.append_comment_to(&Rc::clone(&self.document), data),
+            Token::Doctype { .. } => self.handle_in_body(token),
+            Token::Text(text) => {
+                let whitespace: String = text.chars().filter(|c| is_whitespace(*c)).collect();
+                if !whitespace.is_empty() {
+                    self.handle_in_body(Token::Text(whitespace.into()));
+                }
+            }
+            Token::StartTag { ref name, .. } if name == "html" => self.handle_in_body(token),
+            Token::StartTag { ref name, .. } if name == "noframes" => self.handle_in_head(token),
+            _ => self.ignore_token(&token),
+        }
+    }
+
+    /// 外部コンテンツ（`<svg>`・`<math>`の中）のトークンを処理します
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+    fn process_foreign_content(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => {
+                if text.contains('\0') {
+                    self.error(ParseErrorKind::UnexpectedNullCharacter);
+                }
+                if text.chars().any(|c| c != '\0' && !is_whitespace(c)) {
+                    self.frameset_ok = false;
+                }
+                self.insert_characters(&text.replace('\0', "\u{FFFD}"));
+            }
+            Token::Comment(data) => self.insert_comment(data),
+            Token::Doctype { .. } => self.ignore_token(&token),
+            Token::StartTag {
+                ref name,
+                ref attributes,
+                ..
+            } if foreign::is_breakout_start_tag(name, attributes) => {
+                self.break_out_of_foreign_content(token)
+            }
+            Token::EndTag { ref name } if name == "br" || name == "p" => {
+                self.break_out_of_foreign_content(token)
+            }
+            Token::StartTag {
+                name,
+                attributes,
+                self_closing,
+            } => {
+                let namespace = self
+                    .adjusted_current_node()
+                    .and_then(|node| node.borrow().namespace())
+                    .unwrap_or(Namespace::Html);
+                self.insert_foreign_element(&name, attributes, namespace);
+                if self_closing {
+                    self.stack.pop();
+                }
+            }
+            Token::EndTag { name } => self.foreign_content_end_tag(name),
+            Token::Eof => self.process_token(token),
+        }
+    }
+
+    /// HTMLの要素が現れたので、外部コンテンツの要素を閉じてから処理し直します
+    fn break_out_of_foreign_content(&mut self, token: Token) {
+        let kind = match &token {
+            Token::EndTag { name } => ParseErrorKind::UnexpectedEndTag(name.to_string()),
+            Token::StartTag { name, .. } => ParseErrorKind::UnexpectedStartTag(name.to_string()),
+            _ => unreachable!(),
+        };
+        self.error(kind);
+        while let Some(node) = self.stack.last() {
+            if is_html_element(node)
+                || is_mathml_text_integration_point(node)
+                || is_html_integration_point(node)
+            {
+                break;
+            }
+            self.stack.pop();
+        }
+        self.process_token(token);
+    }
+
+    /// 外部コンテンツの終了タグ（要素名は大文字小文字を区別せずに比較する）
+    fn foreign_content_end_tag(&mut self, name: Cow<'static, str>) {
+        let matches_name = |node: &NodeRef| {
+            node.borrow()
+                .tag_name()
+                .is_some_and(|tag_name| tag_name.eq_ignore_ascii_case(&name))
+        };
+        let Some(mut index) = self.stack.len().checked_sub(1) else {
+            return;
+        };
+        if !matches_name(&self.stack[index]) {
+            self.error(ParseErrorKind::MisnestedTag(name.to_string()));
+        }
+        while index > 0 {
+            if matches_name(&self.stack[index]) {
+                self.stack.truncate(index);
+                return;
+            }
+            index -= 1;
+            if is_html_element(&self.stack[index]) {
+                // HTMLの要素まで戻ったら、挿入モードの規則で処理する
+                self.process_token(Token::EndTag { name });
+                return;
+            }
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    // ノードの挿入
+    // ---------------------------------------------------------------------
+
+    /// 現在のノード（スタックの最後の要素）を返します
+    fn current_node(&self) -> NodeRef {
+        self.stack
+            .last()
+            .cloned()
+            .unwrap_or_else(|| Rc::clone(&self.document))
+    }
+
+    /// ノードを挿入すべき位置を返します（appropriate place for inserting a node）
+    ///
+    /// 木の深さが`MAX_TREE_DEPTH`を超える位置には挿入せず、上限の深さの祖先の末尾に移します。
+    fn appropriate_insertion_place(&self) -> InsertionPoint {
+        self.limit_depth(self.unbounded_insertion_place())
+    }
+
+    fn unbounded_insertion_place(&self) -> InsertionPoint {
+        let target = self.current_node();
+        if self.foster_parenting
+            && matches!(
+                element_name(&target).as_str(),
+                "table" | "tbody" | "tfoot" | "thead" | "tr"
+            )
+        {
+            // テーブルの直前（foster parenting）。テーブルより内側の template があればその中
+            let last_template = self.stack.iter().rposition(|n| is_element(n, "template"));
+            let last_table = self.stack.iter().rposition(|n| is_element(n, "table"));
+            if let Some(template) =
+                last_template.filter(|&template| last_table.is_none_or(|table| template > table))
+            {
+                return InsertionPoint {
+                    parent: insertion_parent(&self.stack[template]),
+                    before: None,
+                };
+            }
+            if let Some(index) = last_table {
+                let table = &self.stack[index];
+                if let Some(parent) = table.borrow().parent() {
+                    return InsertionPoint {
+                        parent,
+                        before: Some(Rc::clone(table)),
+                    };
+                }
+                if index > 0 {
+                    return InsertionPoint {
+                        parent: insertion_parent(&self.stack[index - 1]),
+                        before: None,
+                    };
+                }
+            }
+            if let Some(html) = self.stack.first() {
+                return InsertionPoint {
+                    parent: Rc::clone(html),
+                    before: None,
+                };
+            }
+        }
+        InsertionPoint {
+            parent: insertion_parent(&target),
+            before: None,
+        }
+    }
 
-#[allow(dead_code)]
-#[derive(Debug)]
-pub enum NodeType {
-    Document,
-    Element {
-        tag_name: String,
-        attributes: Vec<Attribute>,
-    },
-    Text(String),
-    Comment(String),
-    Doctype {
-        name: Option<String>,
-        public_id: Option<String>,
-        system_id: Option<String>,
-    },
-}
+    /// 挿入位置の深さが上限を超える場合は、上限の深さの祖先を挿入先にします
+    ///
+    /// `<template>`の内容の深さは、スタック上の`<template>`要素から数えます。
+    fn limit_depth(&self, point: InsertionPoint) -> InsertionPoint {
+        // 挿入先から根までの祖先（挿入先の深さは`chain.len() - 1`）
+        let mut chain = vec![];
+        // 外側の <template> ほどスタックの下にあるので、前回見つけた位置より下だけを探す
+        let mut stack = &self.stack[..];
+        let mut node = Some(Rc::clone(&point.parent));
+        while let Some(current) = node {
+            node = current.borrow().parent();
+            if node.is_none() && matches!(current.borrow().node_type, NodeType::DocumentFragment) {
+                let host = stack.iter().rposition(|element| {
+                    element
+                        .borrow()
+                        .template_content()
+                        .is_some_and(|content| Rc::ptr_eq(&content, &current))
+                });
+                if let Some(index) = host {
+                    node = Some(Rc::clone(&stack[index]));
+                    stack = &stack[..index];
+                }
+            }
+            chain.push(current);
+        }
+        if chain.len() <= MAX_TREE_DEPTH {
+            return point;
+        }
+        InsertionPoint {
+            parent: insertion_parent(&chain[chain.len() - MAX_TREE_DEPTH]),
+            before: None,
+        }
+    }
 
-#[allow(dead_code)]
-#[derive(Debug)]
-pub struct Node {
-    pub node_type: NodeType,
-    pub children: Vec<Rc<RefCell<Node>>>,
-    pub parent: Option<Rc<RefCell<Node>>>,
-}
+    /// 要素を作成して適切な位置に挿入し、スタックに積みます
+    fn insert_html_element(&mut self, name: &str, attributes: Vec<Attribute>) -> NodeRef {
+        self.insert_element(new_element(name, attributes), name)
+    }
 
-pub type NodeRef = Rc<RefCell<Node>>;
+    /// 外部コンテンツの要素を作成して挿入し、スタックに積みます
+    ///
+    /// SVGの要素名と属性名の大文字小文字や、`xlink:href`などの属性の名前空間を補正します。
+    fn insert_foreign_element(
+        &mut self,
+        name: &str,
+        mut attributes: Vec<Attribute>,
+        namespace: Namespace,
+    ) -> NodeRef {
+        let tag_name = match namespace {
+            Namespace::Svg => {
+                foreign::adjust_svg_attributes(&mut attributes);
+                foreign::adjust_svg_tag_name(name)
+            }
+            Namespace::MathMl => {
+                foreign::adjust_mathml_attributes(&mut attributes);
+                name.to_string()
+            }
+            _ => name.to_string(),
+        };
+        foreign::adjust_foreign_attributes(&mut attributes);
+        self.insert_element(new_element_in(namespace, &tag_name, attributes), name)
+    }
 
-pub struct Parser<'a> {
-    tokenizer: crate::engine::html::tokenizer::Tokenizer<'a>,
-    stack: Vec<NodeRef>,
-}
+    /// 作成した要素に処理中のタグ（`name`）の範囲を設定し、適切な位置に挿入してスタックに積みます
+    fn insert_element(&mut self, element: NodeRef, name: &str) -> NodeRef {
+        element.borrow_mut().span = self.take_element_span(name);
+        let point = self.appropriate_insertion_place();
+        insert_at(&point, Rc::clone(&element));
+        self.stack.push(Rc::clone(&element));
+        element
+    }
 
-impl<'a> Parser<'a> {
-    pub fn new(input: &'a str) -> Self {
-        let document = Rc::new(RefCell::new(Node {
-            node_type: NodeType::Document,
-            children: vec![],
-            parent: None,
-        }));
+    /// 空要素を挿入します（スタックには残しません）
+    fn insert_void_element(&mut self, name: &str, attributes: Vec<Attribute>) {
+        self.insert_html_element(name, attributes);
+        self.stack.pop();
+    }
 
-        Self {
-            tokenizer: Tokenizer::new(input),
-            stack: vec![document],
+    /// 文字列を適切な位置に挿入します。直前のノードがテキストなら連結します
+    fn insert_characters(&mut self, text: &str) {
+        let point = self.appropriate_insertion_place();
+        if matches!(point.parent.borrow().node_type, NodeType::Document) {
+            return;
+        }
+        let previous = match &point.before {
+            Some(before) => before.borrow().previous_sibling(),
+            None => point.parent.borrow().last_child(),
+        };
+        if let Some(previous) = previous {
+            let mut previous = previous.borrow_mut();
+            if let NodeType::Text(ref mut data) = previous.node_type {
+                data.push_str(text);
+                if let (Some(span), Some(text_span)) = (&mut previous.span, self.text_span) {
+                    span.end = span.end.max(text_span.end);
+                }
+                return;
+            }
         }
+        let node = new_node(NodeType::Text(text.to_string()));
+        node.borrow_mut().span = self.text_span;
+        insert_at(&point, node);
     }
 
-    pub fn parse(&mut self) -> NodeRef {
-        while let Some(token) = self.tokenizer.next_token() {
-            //println!("---");
-            //println!("Processing token: {token:?}");
-            match token {
-                Token::StartTag { .. } => self.handle_start_tag(token),
-                Token::EndTag { .. } => self.handle_end_tag(token),
-                Token::Doctype { .. } => self.handle_doctype(token),
-                Token::Comment(_) => self.handle_comment(token),
-                Token::Text(_) => self.handle_text(token),
+    /// 先頭の空白を挿入し、残りの文字列を返します
+    fn insert_leading_whitespace(&mut self, text: &str) -> String {
+        let (whitespace, rest) = split_leading_whitespace(text);
+        if !whitespace.is_empty() {
+            self.insert_characters(whitespace);
+        }
+        rest.to_string()
+    }
+
+    fn insert_comment(&mut self, data: Cow<'_, str>) {
+        let point = self.appropriate_insertion_place();
+        insert_at(&point, self.new_comment(data));
+    }
+
+    fn append_comment_to(&mut self, parent: &NodeRef, data: Cow<'_, str>) {
+        append_child(parent, self.new_comment(data));
+    }
+
+    fn new_comment(&self, data: Cow<'_, str>) -> NodeRef {
+        let comment = new_node(NodeType::Comment(data.into_owned()));
+        comment.borrow_mut().span = self.token_span;
+        comment
+    }
+
+    /// RCDATA / RAWTEXT 要素（title, style など）を挿入し、Text モードに移ります
+    ///
+    /// # 引数
+    /// * `state` - 要素の内容を読むときの字句解析の状態
+    fn parse_generic_text_element(
+        &mut self,
+        name: &str,
+        attributes: Vec<Attribute>,
+        state: TokenizerState,
+    ) {
+        self.insert_html_element(name, attributes);
+        self.tokenizer.switch_to(state);
+        self.original_insertion_mode = self.insertion_mode;
+        self.insertion_mode = InsertionMode::Text;
+    }
+
+    // ---------------------------------------------------------------------
+    // 開いている要素のスタック
+    // ---------------------------------------------------------------------
+
+    fn current_is(&self, name: &str) -> bool {
+        self.stack.last().is_some_and(|n| is_element(n, name))
+    }
+
+    fn current_is_one_of(&self, names: &[&str]) -> bool {
+        self.stack
+            .last()
+            .is_some_and(|n| names.contains(&element_name(n).as_str()))
+    }
+
+    /// いずれかの要素が指定したスコープにあるかどうか
+    fn has_element_in_scope(&self, names: &[&str], scope: Scope) -> bool {
+        for node in self.stack.iter().rev() {
+            let name = element_name(node);
+            if names.contains(&name.as_str()) {
+                return true;
+            }
+            if is_scope_boundary(&name, scope) {
+                return false;
             }
         }
+        false
+    }
 
-        Rc::clone(&self.stack[0])
+    /// 指定したノードがスコープにあるかどうか
+    fn has_node_in_scope(&self, target: &NodeRef) -> bool {
+        for node in self.stack.iter().rev() {
+            if Rc::ptr_eq(node, target) {
+                return true;
+            }
+            if is_scope_boundary(&element_name(node), Scope::Default) {
+                return false;
+            }
+        }
+        false
     }
 
-    fn handle_start_tag(&mut self, token: Token) {
-        if let Token::StartTag {
-            name,
-            attributes,
-            self_closing,
-        } = token
-        {
-            let mut parent = Rc::clone(self.stack.last().unwrap());
-            if self.check_start_tag_with_invalid_nesting(&name, &parent) {
-                if let NodeType::Element { tag_name, .. } = &parent.borrow().node_type {
-                    //println!("Auto-closing tag: <{}> to allow <{}> inside it.", tag_name, name);
-                    self.handle_end_tag(Token::EndTag {name: tag_name.clone()});
-                }
-                parent = Rc::clone(self.stack.last().unwrap());
+    /// いずれかの要素が取り出されるまでスタックから要素を取り出します
+    /// 終了タグ`name`の要素まで取り除きます。途中に別の要素が開いていればエラーを記録します
+    fn pop_until_closing(&mut self, name: &str) {
+        if !self.current_is(name) {
+            self.error(ParseErrorKind::MisnestedTag(name.to_string()));
+        }
+        self.pop_until(&[name]);
+    }
+
+    fn pop_until(&mut self, names: &[&str]) {
+        while let Some(node) = self.stack.pop() {
+            if names.contains(&element_name(&node).as_str()) {
+                break;
             }
-            let new_node = Rc::new(RefCell::new(Node {
-                node_type: NodeType::Element {
-                    tag_name: name,
-                    attributes,
-                },
-                children: vec![],
-                parent: Some(Rc::clone(&parent)),
-            }));
+        }
+    }
+
+    /// 現在のノードがいずれかの要素になるまでスタックから要素を取り出します
+    fn clear_stack_back_to(&mut self, names: &[&str]) {
+        while !self.stack.is_empty() && !self.current_is_one_of(names) {
+            self.stack.pop();
+        }
+    }
 
-            parent.borrow_mut().children.push(Rc::clone(&new_node));
+    fn remove_from_stack(&mut self, node: &NodeRef) {
+        if let Some(index) = self.stack.iter().rposition(|n| Rc::ptr_eq(n, node)) {
+            self.stack.remove(index);
+        }
+    }
 
-            // Self-closing タグは stack に push しない
-            if !self_closing {
-                self.stack.push(new_node);
+    /// 暗黙の終了タグを生成します（`except`の要素は閉じない）
+    fn generate_implied_end_tags(&mut self, except: Option<&str>) {
+        while let Some(node) = self.stack.last() {
+            let name = element_name(node);
+            if Some(name.as_str()) == except || !IMPLIED_END_TAG_ELEMENTS.contains(&name.as_str()) {
+                break;
             }
+            self.stack.pop();
         }
     }
 
-    fn handle_end_tag(&mut self, token: Token) {
-        if let Token::EndTag { name } = token {
-            while let Some(top) = self.stack.pop() {
-                if let NodeType::Element { tag_name, .. } = &top.borrow().node_type {
-                    if tag_name == &name {
-                        break;
+    /// 表の要素も含めて、暗黙の終了タグをすべて生成します
+    fn generate_all_implied_end_tags_thoroughly(&mut self) {
+        while self.stack.last().is_some_and(|node| {
+            IMPLIED_END_TAG_ELEMENTS_THOROUGHLY.contains(&element_name(node).as_str())
+        }) {
+            self.stack.pop();
+        }
+    }
+
+    fn has_template_on_stack(&self) -> bool {
+        self.stack.iter().any(|node| is_element(node, "template"))
+    }
+
+    fn close_p_in_button_scope(&mut self) {
+        if self.has_element_in_scope(&["p"], Scope::Button) {
+            self.close_p_element();
+        }
+    }
+
+    fn close_p_element(&mut self) {
+        self.generate_implied_end_tags(Some("p"));
+        self.pop_until(&["p"]);
+    }
+
+    /// 挿入モードを適切にリセットします（reset the insertion mode appropriately）
+    fn reset_insertion_mode(&mut self) {
+        for (index, node) in self.stack.iter().enumerate().rev() {
+            let last = index == 0;
+            // 断片の解析では、html 要素の代わりに文脈要素で判定する
+            let node = match &self.context_element {
+                Some(context) if last => context,
+                _ => node,
+            };
+            let mode = match element_name(node).as_str() {
+                "select" => {
+                    let in_table = !last
+                        && self.stack[..index]
+                            .iter()
+                            .rev()
+                            .map(element_name)
+                            .take_while(|name| name != "template")
+                            .any(|name| name == "table");
+                    if in_table {
+                        InsertionMode::InSelectInTable
+                    } else {
+                        InsertionMode::InSelect
+                    }
+                }
+                "template" => match self.template_insertion_modes.last() {
+                    Some(&mode) => mode,
+                    None => continue,
+                },
+                "td" | "th" if !last => InsertionMode::InCell,
+                "tr" => InsertionMode::InRow,
+                "tbody" | "thead" | "tfoot" => InsertionMode::InTableBody,
+                "caption" => InsertionMode::InCaption,
+                "colgroup" => InsertionMode::InColumnGroup,
+                "table" => InsertionMode::InTable,
+                "head" if !last => InsertionMode::InHead,
+                "body" => InsertionMode::InBody,
+                "frameset" => InsertionMode::InFrameset,
+                "html" => {
+                    if self.head_element.is_none() {
+                        InsertionMode::BeforeHead
+                    } else {
+                        InsertionMode::AfterHead
                     }
                 }
+                _ if last => InsertionMode::InBody,
+                _ => continue,
+            };
+            self.insertion_mode = mode;
+            return;
+        }
+        self.insertion_mode = InsertionMode::InBody;
+    }
+
+    // ---------------------------------------------------------------------
+    // 活性書式要素リスト
+    // ---------------------------------------------------------------------
+
+    fn push_active_formatting_element(
+        &mut self,
+        node: NodeRef,
+        name: Cow<'static, str>,
+        attributes: Vec<Attribute>,
+    ) {
+        // 同じ要素が最後のマーカー以降に3つあれば、最も古いものを取り除く（Noah's Ark clause）
+        let mut same = vec![];
+        for (index, entry) in self.active_formatting_elements.iter().enumerate().rev() {
+            match entry {
+                FormattingEntry::Marker => break,
+                FormattingEntry::Element {
+                    name: n,
+                    attributes: a,
+                    ..
+                } if *n == name && same_attributes(a, &attributes) => same.push(index),
+                _ => {}
             }
         }
+        if same.len() >= 3 {
+            self.active_formatting_elements.remove(same[same.len() - 1]);
+        }
+        self.active_formatting_elements
+            .push(FormattingEntry::Element {
+                node,
+                name,
+                attributes: attributes.into_iter().map(Attribute::into_owned).collect(),
+            });
     }
 
-    fn handle_text(&mut self, token: Token) {
-        if let Token::Text(data) = token {
-            let parent = Rc::clone(self.stack.last().unwrap());
-            // 親ノードが pre, textarea, script, style でない場合、空白改行を無視する
-            if let Some(parent_node) = &parent.borrow().parent {
-                let parent_node_borrow = parent_node.borrow();
-                if let NodeType::Element { tag_name, .. } = &parent_node_borrow.node_type {
-                    if !matches!(tag_name.as_str(), "pre" | "textarea" | "script" | "style")
-                        && data.trim().is_empty()
-                    {
-                        return;
-                    }
-                } else if data.trim().is_empty() {
-                    return;
-                }
-            } else if data.trim().is_empty() {
-                return;
+    /// 開いている書式要素を作り直します（reconstruct the active formatting elements）
+    fn reconstruct_active_formatting_elements(&mut self) {
+        let is_open = |parser: &Self, entry: &FormattingEntry| match entry {
+            FormattingEntry::Marker => true,
+            FormattingEntry::Element { node, .. } => {
+                parser.stack.iter().any(|n| Rc::ptr_eq(n, node))
             }
-            let text_node = Rc::new(RefCell::new(Node {
-                node_type: NodeType::Text(data),
-                children: vec![],
-                parent: Some(Rc::clone(&parent)),
-            }));
-            parent.borrow_mut().children.push(text_node);
+        };
+        let Some(last) = self.active_formatting_elements.last() else {
+            return;
+        };
+        if is_open(self, last) {
+            return;
+        }
+        // 作り直した要素には処理中のタグの範囲を付けない
+        let element_span = self.element_span.take();
+        let len = self.active_formatting_elements.len();
+        let start = self.active_formatting_elements[..len - 1]
+            .iter()
+            .rposition(|entry| is_open(self, entry))
+            .map_or(0, |index| index + 1);
+        for index in start..len {
+            let FormattingEntry::Element {
+                name, attributes, ..
+            } = self.active_formatting_elements[index].clone()
+            else {
+                continue;
+            };
+            let node = self.insert_html_element(&name, attributes.clone());
+            self.active_formatting_elements[index] = FormattingEntry::Element {
+                node,
+                name,
+                attributes,
+            };
         }
+        self.element_span = element_span;
     }
 
-    fn handle_comment(&mut self, token: Token) {
-        if let Token::Comment(data) = token {
-            let parent = Rc::clone(self.stack.last().unwrap());
-            let comment_node = Rc::new(RefCell::new(Node {
-                node_type: NodeType::Comment(data),
-                children: vec![],
-                parent: Some(Rc::clone(&parent)),
-            }));
-            parent.borrow_mut().children.push(comment_node);
+    fn clear_active_formatting_elements_to_last_marker(&mut self) {
+        while let Some(entry) = self.active_formatting_elements.pop() {
+            if matches!(entry, FormattingEntry::Marker) {
+                break;
+            }
         }
     }
 
-    fn handle_doctype(&mut self, token: Token) {
-        if let Token::Doctype {
-            name,
-            public_id,
-            system_id,
-            ..
-        } = token
-        {
-            let parent = Rc::clone(self.stack.last().unwrap());
-            let doctype_node = Rc::new(RefCell::new(Node {
-                node_type: NodeType::Doctype {
-                    name,
-                    public_id,
-                    system_id,
-                },
-                children: vec![],
-                parent: Some(Rc::clone(&parent)),
-            }));
-            parent.borrow_mut().children.push(doctype_node);
+    /// 最後のマーカー以降にある、指定したタグ名の書式要素を返します
+    fn formatting_element_after_last_marker(&self, name: &str) -> Option<NodeRef> {
+        for entry in self.active_formatting_elements.iter().rev() {
+            match entry {
+                FormattingEntry::Marker => return None,
+                FormattingEntry::Element { node, name: n, .. } if n == name => {
+                    return Some(Rc::clone(node))
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn formatting_index_of(&self, node: &NodeRef) -> Option<usize> {
+        self.active_formatting_elements
+            .iter()
+            .position(|entry| matches!(entry, FormattingEntry::Element { node: n, .. } if Rc::ptr_eq(n, node)))
+    }
+
+    fn remove_from_active_formatting_elements(&mut self, node: &NodeRef) {
+        if let Some(index) = self.formatting_index_of(node) {
+            self.active_formatting_elements.remove(index);
         }
     }
 
-    fn check_start_tag_with_invalid_nesting(&self, name: &String, parent: &NodeRef) -> bool {
-        if let NodeType::Element { tag_name, .. } = &parent.borrow().node_type {
-            // <p> の中に <p> が来た場合、前の <p> を閉じる
-            if tag_name == "p" && name == "p" {
+    /// 養子縁組アルゴリズム（adoption agency algorithm）
+    ///
+    /// 書式要素の終了タグで、入れ子が崩れた書式要素を組み直します。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    ///
+    /// # 戻り値
+    /// * 書式要素が見つからず、「その他の終了タグ」として処理すべき場合は`false`を返します
+    fn adoption_agency(&mut self, subject: &str) -> bool {
+        if self.current_is(subject) {
+            let current = self.current_node();
+            if self.formatting_index_of(&current).is_none() {
+                self.stack.pop();
                 return true;
             }
-            // <li> の中に <li> が来た場合、前の <li> を閉じる
-            if tag_name == "li" && name == "li" {
+        }
+
+        for iteration in 0..8 {
+            let Some(formatting_element) = self.formatting_element_after_last_marker(subject)
+            else {
+                return false;
+            };
+            let Some(formatting_stack_index) = self
+                .stack
+                .iter()
+                .position(|n| Rc::ptr_eq(n, &formatting_element))
+            else {
+                self.error(ParseErrorKind::UnexpectedEndTag(subject.to_string()));
+                self.remove_from_active_formatting_elements(&formatting_element);
                 return true;
-            }
-            // <a> の中に <a> が来た場合、前の <a> を閉じる
-            if tag_name == "a" && name == "a" {
+            };
+            if !self.has_node_in_scope(&formatting_element) {
+                self.error(ParseErrorKind::UnexpectedEndTag(subject.to_string()));
                 return true;
             }
-            // <dt> の中に <dt> または <dd> が来た場合、前の <dt> を閉じる
-            if tag_name == "dt" && (name == "dt" || name == "dd") {
-                return true;
+            if iteration == 0 && !Rc::ptr_eq(&formatting_element, &self.current_node()) {
+                self.error(ParseErrorKind::MisnestedTag(subject.to_string()));
             }
-            // <dd> の中に <dt> または <dd> が来た場合、前の <dd> を閉じる
-            if tag_name == "dd" && (name == "dt" || name == "dd") {
+
+            let furthest_block = self.stack[formatting_stack_index + 1..]
+                .iter()
+                .find(|n| is_special(&element_name(n)))
+                .cloned();
+            let Some(furthest_block) = furthest_block else {
+                self.stack.truncate(formatting_stack_index);
+                self.remove_from_active_formatting_elements(&formatting_element);
                 return true;
+            };
+
+            let common_ancestor = Rc::clone(&self.stack[formatting_stack_index - 1]);
+            let mut bookmark = self
+                .formatting_index_of(&formatting_element)
+                .unwrap_or(self.active_formatting_elements.len());
+
+            let mut node_index = self
+                .stack
+                .iter()
+                .position(|n| Rc::ptr_eq(n, &furthest_block))
+                .unwrap_or(self.stack.len() - 1);
+            let mut last_node = Rc::clone(&furthest_block);
+            let mut inner_loop_counter = 0;
+            loop {
+                inner_loop_counter += 1;
+                node_index -= 1;
+                let node = Rc::clone(&self.stack[node_index]);
+                if Rc::ptr_eq(&node, &formatting_element) {
+                    break;
+                }
+                let mut formatting_index = self.formatting_index_of(&node);
+                if inner_loop_counter > 3 {
+                    if let Some(index) = formatting_index.take() {
+                        self.active_formatting_elements.remove(index);
+                        if index < bookmark {
+                            bookmark -= 1;
+                        }
+                    }
+                }
+                let Some(formatting_index) = formatting_index else {
+                    self.stack.remove(node_index);
+                    continue;
+                };
+
+                // node を作り直し、リストとスタックの両方で置き換える
+                let FormattingEntry::Element {
+                    name, attributes, ..
+                } = self.active_formatting_elements[formatting_index].clone()
+                else {
+                    break;
+                };
+                let new_element = new_element(&name, attributes.clone());
+                self.active_formatting_elements[formatting_index] = FormattingEntry::Element {
+                    node: Rc::clone(&new_element),
+                    name,
+                    attributes,
+                };
+                self.stack[node_index] = Rc::clone(&new_element);
+
+                if Rc::ptr_eq(&last_node, &furthest_block) {
+                    bookmark = formatting_index + 1;
+                }
+                append_child(&new_element, Rc::clone(&last_node));
+                last_node = new_element;
             }
-            // <option> の中に <option> が来た場合、前の <option> を閉じる
-            if tag_name == "option" && name == "option" {
+
+            // last_node を共通の祖先（テーブル関連なら foster parenting）に挿入する
+            let saved = std::mem::replace(&mut self.stack, vec![Rc::clone(&common_ancestor)]);
+            let point = self.appropriate_insertion_place();
+            self.stack = saved;
+            insert_at(&point, last_node);
+
+            // 書式要素を作り直し、furthest block の子をすべて移す
+            let Some(FormattingEntry::Element {
+                name, attributes, ..
+            }) = self
+                .formatting_index_of(&formatting_element)
+                .map(|index| self.active_formatting_elements[index].clone())
+            else {
                 return true;
+            };
+            let new_element = new_element(&name, attributes.clone());
+            let children = furthest_block.borrow().children.clone();
+            for child in children {
+                append_child(&new_element, child);
             }
-            // <p> の中にブロック要素が来た場合、前の <p> を閉じる
-            if matches!(
-                tag_name.as_str(),
-                "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6"
-            ) && Self::is_block_level_element(name)
-            {
-                return true;
+            append_child(&furthest_block, Rc::clone(&new_element));
+
+            if let Some(index) = self.formatting_index_of(&formatting_element) {
+                self.active_formatting_elements.remove(index);
+                if index < bookmark {
+                    bookmark -= 1;
+                }
             }
-        } else if let NodeType::Document = &parent.borrow().node_type {
-            if !(name == "html") {
-                todo!("Document の中に DOCTYPE宣言 以外のが来た場合の処理");
+            bookmark = bookmark.min(self.active_formatting_elements.len());
+            self.active_formatting_elements.insert(
+                bookmark,
+                FormattingEntry::Element {
+                    node: Rc::clone(&new_element),
+                    name,
+                    attributes,
+                },
+            );
+
+            self.remove_from_stack(&formatting_element);
+            let furthest_index = self
+                .stack
+                .iter()
+                .position(|n| Rc::ptr_eq(n, &furthest_block))
+                .map_or(self.stack.len(), |index| index + 1);
+            self.stack.insert(furthest_index, new_element);
+        }
+        true
+    }
+}
+
+// -------------------------------------------------------------------------
+// DOM 操作の補助関数
+// -------------------------------------------------------------------------
+
+fn new_node(node_type: NodeType) -> NodeRef {
+    Rc::new(RefCell::new(Node {
+        document_state: matches!(node_type, NodeType::Document).then(Box::default),
+        node_type,
+        children: vec![],
+        parent: None,
+        previous_sibling: None,
+        next_sibling: None,
+        event_listeners: vec![],
+        span: None,
+    }))
+}
+
+fn new_element(name: &str, attributes: Vec<Attribute>) -> NodeRef {
+    new_element_in(Namespace::Html, name, attributes)
+}
+
+fn new_element_in(namespace: Namespace, name: &str, attributes: Vec<Attribute>) -> NodeRef {
+    let template_content = (namespace == Namespace::Html && name == "template")
+        .then(|| new_node(NodeType::DocumentFragment));
+    new_node(NodeType::Element {
+        tag_name: name.to_string(),
+        attributes: attributes.into_iter().map(Attribute::into_owned).collect(),
+        namespace,
+        template_content,
+    })
+}
+
+/// ノードを親から切り離します
+///
+/// 前後の兄弟ノードのリンクもつなぎ直します。
+pub(crate) fn detach(node: &NodeRef) {
+    if let Some(document) = owner_document(node) {
+        update_index(&document, node, false);
+    }
+    let (parent, previous, next) = {
+        let mut node = node.borrow_mut();
+        (
+            node.parent.take(),
+            node.previous_sibling.take(),
+            node.next_sibling.take(),
+        )
+    };
+    if let Some(previous) = previous.as_ref().and_then(Weak::upgrade) {
+        previous.borrow_mut().next_sibling = next.clone();
+    }
+    if let Some(next) = next.as_ref().and_then(Weak::upgrade) {
+        next.borrow_mut().previous_sibling = previous;
+    }
+    if let Some(parent) = parent.as_ref().and_then(Weak::upgrade) {
+        parent
+            .borrow_mut()
+            .children
+            .retain(|child| !Rc::ptr_eq(child, node));
+    }
+}
+
+/// ノードを親の末尾に追加します（すでに別の親がある場合は移動します）
+pub(crate) fn append_child(parent: &NodeRef, child: NodeRef) {
+    detach(&child);
+    let last = parent.borrow().last_child();
+    {
+        let mut child = child.borrow_mut();
+        child.parent = Some(Rc::downgrade(parent));
+        child.previous_sibling = last.as_ref().map(Rc::downgrade);
+    }
+    if let Some(last) = last {
+        last.borrow_mut().next_sibling = Some(Rc::downgrade(&child));
+    }
+    parent.borrow_mut().children.push(Rc::clone(&child));
+    if let Some(document) = owner_document(parent) {
+        update_index(&document, &child, true);
+    }
+}
+
+/// ノードを`reference`の直前に挿入します
+pub(crate) fn insert_before(parent: &NodeRef, child: NodeRef, reference: &NodeRef) {
+    let Some(index) = parent
+        .borrow()
+        .children
+        .iter()
+        .position(|c| Rc::ptr_eq(c, reference))
+    else {
+        append_child(parent, child);
+        return;
+    };
+    detach(&child);
+    // 切り離したノードが同じ親の前にあった場合は位置がずれる
+    let index = parent
+        .borrow()
+        .children
+        .iter()
+        .position(|c| Rc::ptr_eq(c, reference))
+        .unwrap_or(index);
+    let previous = reference.borrow().previous_sibling();
+    {
+        let mut child = child.borrow_mut();
+        child.parent = Some(Rc::downgrade(parent));
+        child.previous_sibling = previous.as_ref().map(Rc::downgrade);
+        child.next_sibling = Some(Rc::downgrade(reference));
+    }
+    if let Some(previous) = previous {
+        previous.borrow_mut().next_sibling = Some(Rc::downgrade(&child));
+    }
+    reference.borrow_mut().previous_sibling = Some(Rc::downgrade(&child));
+    parent
+        .borrow_mut()
+        .children
+        .insert(index, Rc::clone(&child));
+    if let Some(document) = owner_document(parent) {
+        update_index(&document, &child, true);
+    }
+}
+
+/// ノードがつながっている文書ノードを返します（ノード自身が文書ノードの場合はそれ自身）
+pub(crate) fn owner_document(node: &NodeRef) -> Option<NodeRef> {
+    let mut root = Rc::clone(node);
+    loop {
+        let parent = root.borrow().parent();
+        match parent {
+            Some(parent) => root = parent,
+            None => break,
+        }
+    }
+    let is_document = matches!(root.borrow().node_type, NodeType::Document);
+    is_document.then_some(root)
+}
+
+/// `subtree`以下の要素を文書の索引に加える、または索引から取り除きます
+///
+/// `<template>`の内容は文書につながっていないので対象にしません。
+fn update_index(document: &NodeRef, subtree: &NodeRef, insert: bool) {
+    let mut document = document.borrow_mut();
+    let Some(DocumentState { index, .. }) = document.document_state_mut() else {
+        return;
+    };
+    let mut stack = vec![Rc::clone(subtree)];
+    while let Some(node) = stack.pop() {
+        if insert {
+            index.insert(&node);
+        } else {
+            index.remove(&node);
+        }
+        stack.extend(node.borrow().children.iter().cloned());
+    }
+}
+
+/// ノードの子を挿入する先を返します（`<template>`の場合は内容の DocumentFragment）
+fn insertion_parent(node: &NodeRef) -> NodeRef {
+    node.borrow()
+        .template_content()
+        .unwrap_or_else(|| Rc::clone(node))
+}
+
+fn insert_at(point: &InsertionPoint, node: NodeRef) {
+    match &point.before {
+        Some(before) => insert_before(&point.parent, node, before),
+        None => append_child(&point.parent, node),
+    }
+}
+
+/// 要素のタグ名を返します（要素でない場合は空文字列）
+///
+/// HTML以外の要素は`svg title`のように名前空間の接頭辞を付け、同じ名前のHTML要素と区別します。
+fn element_name(node: &NodeRef) -> String {
+    match &node.borrow().node_type {
+        NodeType::Element {
+            tag_name,
+            namespace: Namespace::Html,
+            ..
+        } => tag_name.clone(),
+        NodeType::Element {
+            tag_name,
+            namespace,
+            ..
+        } => format!("{} {tag_name}", namespace.prefix()),
+        _ => String::new(),
+    }
+}
+
+/// `name`という名前のHTML要素かどうか（外部コンテンツは`svg title`のように指定する）
+fn is_element(node: &NodeRef, name: &str) -> bool {
+    element_name(node) == name
+}
+
+fn is_html_element(node: &NodeRef) -> bool {
+    node.borrow().namespace() == Some(Namespace::Html)
+}
+
+/// MathML text integration point（中身をHTMLとして扱うMathMLの要素）かどうか
+fn is_mathml_text_integration_point(node: &NodeRef) -> bool {
+    matches!(
+        element_name(node).as_str(),
+        "math mi" | "math mo" | "math mn" | "math ms" | "math mtext"
+    )
+}
+
+/// HTML integration point（中身をHTMLとして扱う外部コンテンツの要素）かどうか
+fn is_html_integration_point(node: &NodeRef) -> bool {
+    match element_name(node).as_str() {
+        "math annotation-xml" => node.borrow().attribute("encoding").is_some_and(|encoding| {
+            encoding.eq_ignore_ascii_case("text/html")
+                || encoding.eq_ignore_ascii_case("application/xhtml+xml")
+        }),
+        "svg foreignObject" | "svg desc" | "svg title" => true,
+        _ => false,
+    }
+}
+
+fn is_special(name: &str) -> bool {
+    SPECIAL_ELEMENTS.contains(&name)
+}
+
+fn is_scope_boundary(name: &str, scope: Scope) -> bool {
+    const DEFAULT_SCOPE: &[&str] = &[
+        "applet",
+        "caption",
+        "html",
+        "table",
+        "td",
+        "th",
+        "marquee",
+        "object",
+        "template",
+        "math mi",
+        "math mo",
+        "math mn",
+        "math ms",
+        "math mtext",
+        "math annotation-xml",
+        "svg foreignObject",
+        "svg desc",
+        "svg title",
+    ];
+    match scope {
+        Scope::Default => DEFAULT_SCOPE.contains(&name),
+        Scope::ListItem => DEFAULT_SCOPE.contains(&name) || matches!(name, "ol" | "ul"),
+        Scope::Button => DEFAULT_SCOPE.contains(&name) || name == "button",
+        Scope::Table => matches!(name, "html" | "table" | "template"),
+        Scope::Select => !matches!(name, "optgroup" | "option"),
+    }
+}
+
+/// 要素にない属性を追加します（`<html>`や`<body>`が重複した場合）
+fn add_missing_attributes(node: &NodeRef, new_attributes: Vec<Attribute>) {
+    // 追加した id や class を索引に反映する
+    let document = owner_document(node);
+    if let Some(document) = &document {
+        if let Some(state) = document.borrow_mut().document_state_mut() {
+            state.index.remove(node);
+        }
+    }
+    if let NodeType::Element {
+        ref mut attributes, ..
+    } = node.borrow_mut().node_type
+    {
+        for attr in new_attributes {
+            if !attributes.iter().any(|a| a.name == attr.name) {
+                attributes.push(attr.into_owned());
             }
         }
-        false
     }
+    if let Some(document) = &document {
+        if let Some(state) = document.borrow_mut().document_state_mut() {
+            state.index.insert(node);
+        }
+    }
+}
 
-    /// is_block_level_element - タグ名が典型的なブロック要素かどうか判定する
-    ///
-    /// 注意:
-    /// - HTML5 の「デフォルトでブロック扱いされる要素」を代表例で列挙していますが、
-    ///   仕様の解釈やブラウザ依存・CSSでのdisplay変更には触れていません。
-    /// - 必要なら要素リストに追加・削除してください。
-    fn is_block_level_element(tag_name: &str) -> bool {
-        let tag = tag_name.trim().to_ascii_lowercase();
-        matches!(
-            tag.as_str(),
-            // 主要なセクショナル要素
-            "html" | "body" | "main" | "header" | "footer" | "section" | "nav" | "article" | "aside" |
-            // 見出し
-            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" |
-            // テキスト／段落系
-            "p" | "pre" | "blockquote" | "address" | "hr" |
-            // グループ／レイアウト
-            "div" | "fieldset" | "legend" | "details" | "summary" | "figure" | "figcaption" |
-            // リスト／表組み
-            "ul" | "ol" | "li" | "dl" | "dt" | "dd" |
-            "table" | "thead" | "tbody" | "tfoot" | "tr" | "td" | "th" |
-            // フォーム系（多くはブロック表示される）
-            "form" | "textarea" | "output" | "meter" | "progress" |
-            // メディア・埋め込み
-            "canvas" | "video" | "audio" | "svg" | "object" | "embed" | "iframe"
+fn same_attributes(a: &[Attribute], b: &[Attribute]) -> bool {
+    a.len() == b.len() && a.iter().all(|attr| b.contains(attr))
+}
+
+fn start_tag<'a>(name: Cow<'static, str>, attributes: Vec<Attribute<'a>>) -> Token<'a> {
+    Token::StartTag {
+        name,
+        attributes,
+        self_closing: false,
+    }
+}
+
+/// HTML の空白文字（ASCII whitespace）かどうか
+fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')
+}
+
+fn is_all_whitespace(text: &str) -> bool {
+    text.chars().all(is_whitespace)
+}
+
+fn split_leading_whitespace(text: &str) -> (&str, &str) {
+    let index = text.find(|c| !is_whitespace(c)).unwrap_or(text.len());
+    text.split_at(index)
+}
+
+fn trim_leading_whitespace(text: &str) -> &str {
+    split_leading_whitespace(text).1
+}
+
+/// DOCTYPE から文書のモードを判定します
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+fn quirks_mode_for_doctype(
+    name: Option<&str>,
+    public_id: Option<&str>,
+    system_id: Option<&str>,
+    force_quirks: bool,
+) -> QuirksMode {
+    const QUIRKS_PUBLIC_ID_PREFIXES: &[&str] = &[
+        "+//silmaril//dtd html pro v0r11 19970101//",
+        "-//as//dtd html 3.0 aswedit + extensions//",
+        "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+        "-//ietf//dtd html 2.0 level 1//",
+        "-//ietf//dtd html 2.0 level 2//",
+        "-//ietf//dtd html 2.0 strict level 1//",
+        "-//ietf//dtd html 2.0 strict level 2//",
+        "-//ietf//dtd html 2.0 strict//",
+        "-//ietf//dtd html 2.0//",
+        "-//ietf//dtd html 2.1e//",
+        "-//ietf//dtd html 3.0//",
+        "-//ietf//dtd html 3.2 final//",
+        "-//ietf//dtd html 3.2//",
+        "-//ietf//dtd html 3//",
+        "-//ietf//dtd html level 0//",
+        "-//ietf//dtd html level 1//",
+        "-//ietf//dtd html level 2//",
+        "-//ietf//dtd html level 3//",
+        "-//ietf//dtd html strict level 0//",
+        "-//ietf//dtd html strict level 1//",
+        "-//ietf//dtd html strict level 2//",
+        "-//ietf//dtd html strict level 3//",
+        "-//ietf//dtd html strict//",
+        "-//ietf//dtd html//",
+        "-//metrius//dtd metrius presentational//",
+        "-//microsoft//dtd internet explorer 2.0 html strict//",
+        "-//microsoft//dtd internet explorer 2.0 html//",
+        "-//microsoft//dtd internet explorer 2.0 tables//",
+        "-//microsoft//dtd internet explorer 3.0 html strict//",
+        "-//microsoft//dtd internet explorer 3.0 html//",
+        "-//microsoft//dtd internet explorer 3.0 tables//",
+        "-//netscape comm. corp.//dtd html//",
+        "-//netscape comm. corp.//dtd strict html//",
+        "-//o'reilly and associates//dtd html 2.0//",
+        "-//o'reilly and associates//dtd html extended 1.0//",
+        "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+        "-//sq//dtd html 2.0 hotmetal + extensions//",
+        "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+        "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+        "-//spyglass//dtd html 2.0 extended//",
+        "-//sun microsystems corp.//dtd hotjava html//",
+        "-//sun microsystems corp.//dtd hotjava strict html//",
+        "-//w3c//dtd html 3 1995-03-24//",
+        "-//w3c//dtd html 3.2 draft//",
+        "-//w3c//dtd html 3.2 final//",
+        "-//w3c//dtd html 3.2//",
+        "-//w3c//dtd html 3.2s draft//",
+        "-//w3c//dtd html 4.0 frameset//",
+        "-//w3c//dtd html 4.0 transitional//",
+        "-//w3c//dtd html experimental 19960712//",
+        "-//w3c//dtd html experimental 970421//",
+        "-//w3c//dtd w3 html//",
+        "-//w3o//dtd w3 html 3.0//",
+        "-//webtechs//dtd mozilla html 2.0//",
+        "-//webtechs//dtd mozilla html//",
+    ];
+    const HTML4_PUBLIC_ID_PREFIXES: &[&str] = &[
+        "-//w3c//dtd html 4.01 frameset//",
+        "-//w3c//dtd html 4.01 transitional//",
+    ];
+    const LIMITED_QUIRKS_PUBLIC_ID_PREFIXES: &[&str] = &[
+        "-//w3c//dtd xhtml 1.0 frameset//",
+        "-//w3c//dtd xhtml 1.0 transitional//",
+    ];
+
+    let public_id = public_id.map(|id| id.to_ascii_lowercase());
+    let public_id = public_id.as_deref().unwrap_or("");
+    let system_id_lower = system_id.map(|id| id.to_ascii_lowercase());
+    let starts_with_any =
+        |prefixes: &[&str]| prefixes.iter().any(|prefix| public_id.starts_with(prefix));
+
+    if force_quirks
+        || name != Some("html")
+        || matches!(
+            public_id,
+            "-//w3o//dtd w3 html strict 3.0//en//" | "-/w3c/dtd html 4.0 transitional/en" | "html"
         )
+        || system_id_lower.as_deref()
+            == Some("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || starts_with_any(QUIRKS_PUBLIC_ID_PREFIXES)
+        || (system_id.is_none() && starts_with_any(HTML4_PUBLIC_ID_PREFIXES))
+    {
+        QuirksMode::Quirks
+    } else if starts_with_any(LIMITED_QUIRKS_PUBLIC_ID_PREFIXES)
+        || (system_id.is_some() && starts_with_any(HTML4_PUBLIC_ID_PREFIXES))
+    {
+        QuirksMode::LimitedQuirks
+    } else {
+        QuirksMode::NoQuirks
     }
 }
 
@@ -249,7 +3352,11 @@ impl std::fmt::Display for Node {
 }
 
 impl Node {
-    fn fmt_dom_tree(&self, f: &mut std::fmt::Formatter, ancestors_last: &[bool]) -> std::fmt::Result {
+    fn fmt_dom_tree(
+        &self,
+        f: &mut std::fmt::Formatter,
+        ancestors_last: &[bool],
+    ) -> std::fmt::Result {
         let n = self;
 
         // ├── か └── を決める（自身の最後かどうかは ancestors_last の最後で判断）
@@ -272,10 +3379,14 @@ impl Node {
         match &n.node_type {
             NodeType::Document => {
                 writeln!(f, "{prefix}{connector}Document")?;
-            },
+            }
+            NodeType::DocumentFragment => {
+                writeln!(f, "{prefix}{connector}DocumentFragment")?;
+            }
             NodeType::Element {
                 tag_name,
                 attributes,
+                ..
             } => {
                 let attrs_str = if attributes.is_empty() {
                     "".to_string()
@@ -297,7 +3408,7 @@ impl Node {
             }
             NodeType::Comment(data) => {
                 writeln!(f, "{prefix}{connector}Comment: {data:?}")?;
-            },
+            }
             NodeType::Doctype {
                 name,
                 public_id,
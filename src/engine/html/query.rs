@@ -0,0 +1,228 @@
+//! DOM の検索
+//!
+//! `getElementById`や`querySelector`のように、ノードの子孫から要素を探すAPIと、
+//! 子孫・祖先をたどるイテレーター、属性やテキストの取得をまとめます。
+//! 文書ノードからの ID とクラス名の検索は、ツリー構築時に作られる索引を使います。
+//! https://dom.spec.whatwg.org/#interface-parentnode
+
+use std::rc::Rc;
+
+use crate::engine::css::selector::{SelectorError, SelectorList};
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::parser::{Node, NodeRef, NodeType};
+use crate::engine::html::tokenizer::Attribute;
+
+/// 子孫を文書順（深さ優先の前順）にたどるイテレーター
+///
+/// `<template>`の内容は子孫に含みません。
+pub struct Descendants {
+    stack: Vec<NodeRef>,
+}
+
+impl Iterator for Descendants {
+    type Item = NodeRef;
+
+    fn next(&mut self) -> Option<NodeRef> {
+        let node = self.stack.pop()?;
+        self.stack
+            .extend(node.borrow().children.iter().rev().cloned());
+        Some(node)
+    }
+}
+
+/// 親から文書ノードまで祖先をたどるイテレーター
+pub struct Ancestors {
+    next: Option<NodeRef>,
+}
+
+impl Iterator for Ancestors {
+    type Item = NodeRef;
+
+    fn next(&mut self) -> Option<NodeRef> {
+        let node = self.next.take()?;
+        self.next = node.borrow().parent();
+        Some(node)
+    }
+}
+
+impl Node {
+    /// 子孫を文書順に返します（自身は含みません）
+    pub fn descendants(&self) -> Descendants {
+        Descendants {
+            stack: self.children.iter().rev().cloned().collect(),
+        }
+    }
+
+    /// 祖先を近い順に返します（自身は含みません）
+    pub fn ancestors(&self) -> Ancestors {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// 子孫の要素を文書順に返します
+    pub fn descendant_elements(&self) -> impl Iterator<Item = NodeRef> {
+        self.descendants()
+            .filter(|node| matches!(node.borrow().node_type, NodeType::Element { .. }))
+    }
+
+    /// テキストの内容を返します（`textContent`）
+    ///
+    /// 要素と DocumentFragment は子孫のテキストをつなげたもの、テキストとコメントはその内容です。
+    /// 文書ノードと DOCTYPE は空文字列を返します。
+    pub fn text_content(&self) -> String {
+        match &self.node_type {
+            NodeType::Text(data) | NodeType::Comment(data) => data.clone(),
+            NodeType::Document | NodeType::Doctype { .. } => String::new(),
+            NodeType::Element { .. } | NodeType::DocumentFragment => self
+                .descendants()
+                .filter_map(|node| match &node.borrow().node_type {
+                    NodeType::Text(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// 要素の属性を返します（要素以外は空）
+    pub fn attributes(&self) -> &[Attribute<'static>] {
+        match &self.node_type {
+            NodeType::Element { attributes, .. } => attributes,
+            _ => &[],
+        }
+    }
+
+    /// 要素が属性`name`を持つかを返します
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attribute(name).is_some()
+    }
+
+    /// 要素の`id`属性を返します（空の場合は`None`）
+    pub fn id(&self) -> Option<&str> {
+        self.attribute("id").filter(|id| !id.is_empty())
+    }
+
+    /// 要素の`class`属性のクラス名を返します
+    pub fn class_names(&self) -> Vec<&str> {
+        self.attribute("class")
+            .map(|class| class.split_ascii_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    /// 要素がクラス名`class`を持つかを返します
+    pub fn has_class(&self, class: &str) -> bool {
+        self.class_names().contains(&class)
+    }
+
+    /// `id`属性が`id`の最初の子孫要素を返します（`getElementById`）
+    pub fn get_element_by_id(&self, id: &str) -> Option<NodeRef> {
+        if id.is_empty() {
+            return None;
+        }
+        if let Some(index) = self.document_index() {
+            return index
+                .elements_with_id(id)
+                .into_iter()
+                .min_by_key(tree_order_key);
+        }
+        self.descendant_elements()
+            .find(|node| node.borrow().id() == Some(id))
+    }
+
+    /// タグ名が`name`の子孫要素を文書順に返します（`getElementsByTagName`）
+    ///
+    /// `*`はすべての要素に一致します。HTMLの要素は大文字小文字を区別せずに比べます。
+    pub fn get_elements_by_tag_name(&self, name: &str) -> Vec<NodeRef> {
+        self.descendant_elements()
+            .filter(|node| {
+                let node = node.borrow();
+                let (Some(tag_name), Some(namespace)) = (node.tag_name(), node.namespace()) else {
+                    return false;
+                };
+                name == "*"
+                    || tag_name == name
+                    || (namespace == Namespace::Html && tag_name.eq_ignore_ascii_case(name))
+            })
+            .collect()
+    }
+
+    /// 空白区切りのクラス名をすべて持つ子孫要素を文書順に返します（`getElementsByClassName`）
+    pub fn get_elements_by_class_name(&self, class_names: &str) -> Vec<NodeRef> {
+        let classes: Vec<&str> = class_names.split_ascii_whitespace().collect();
+        if classes.is_empty() {
+            return vec![];
+        }
+        let has_all = |node: &NodeRef| {
+            let node = node.borrow();
+            let names = node.class_names();
+            classes.iter().all(|class| names.contains(class))
+        };
+        if let Some(index) = self.document_index() {
+            // 最も少ない候補から絞り込む
+            let Some(mut elements) = classes
+                .iter()
+                .map(|class| index.elements_with_class(class))
+                .min_by_key(Vec::len)
+            else {
+                return vec![];
+            };
+            elements.retain(has_all);
+            elements.sort_by_cached_key(tree_order_key);
+            return elements;
+        }
+        self.descendant_elements().filter(has_all).collect()
+    }
+
+    /// セレクターに一致する最初の子孫要素を返します（`querySelector`）
+    ///
+    /// # 引数
+    /// * `selectors` - カンマ区切りのセレクター（`nav > a.active`など）
+    ///
+    /// # 戻り値
+    /// * セレクターの構文が正しくない場合は`SelectorError`
+    pub fn query_selector(&self, selectors: &str) -> Result<Option<NodeRef>, SelectorError> {
+        let list = SelectorList::parse(selectors)?;
+        Ok(self
+            .descendant_elements()
+            .find(|node| list.matches(&node.borrow())))
+    }
+
+    /// セレクターに一致するすべての子孫要素を文書順に返します（`querySelectorAll`）
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<NodeRef>, SelectorError> {
+        let list = SelectorList::parse(selectors)?;
+        Ok(self
+            .descendant_elements()
+            .filter(|node| list.matches(&node.borrow()))
+            .collect())
+    }
+
+    /// 要素自身がセレクターに一致するかを返します（`matches`）
+    pub fn matches_selector(&self, selectors: &str) -> Result<bool, SelectorError> {
+        Ok(SelectorList::parse(selectors)?.matches(self))
+    }
+}
+
+/// 文書順で比べるためのキー（根から各祖先の兄弟中の位置を並べたもの）
+fn tree_order_key(node: &NodeRef) -> Vec<usize> {
+    let mut key = vec![];
+    let mut current = Rc::clone(node);
+    loop {
+        let (position, parent) = {
+            let node = current.borrow();
+            let mut position = 0;
+            let mut sibling = node.previous_sibling();
+            while let Some(previous) = sibling {
+                position += 1;
+                sibling = previous.borrow().previous_sibling();
+            }
+            (position, node.parent())
+        };
+        key.push(position);
+        match parent {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    key.reverse();
+    key
+}
@@ -0,0 +1,340 @@
+//! HTML のサニタイズ
+//!
+//! 信頼できない HTML の断片をパーサーで DOM にし、許可リストにない要素・属性・URL スキームを
+//! 取り除いてから書き出し直します。文字列の置換ではなく、ブラウザと同じ規則で解析した木を
+//! 検査するので、解析のされ方の違いを突いた入力も木の上では無害な形になります。
+//!
+//! 書き出した HTML を解析し直すと別の木になる入力（mutation XSS）に備えて、結果が変わらなく
+//! なるまでサニタイズを繰り返します。
+//! https://wicg.github.io/sanitizer-api/
+
+use std::collections::{HashMap, HashSet};
+
+use url::Url;
+
+use crate::engine::html::mutation;
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::parser::{self, Node, NodeRef, NodeType, Parser};
+
+/// ポリシーに関わらず中身ごと取り除く要素
+///
+/// 中身がテキストとして解析される要素は、書き出すと中身がエスケープされないか、
+/// スクリプトの有効・無効で解析のされ方が変わるため、安全に残せません。
+const ALWAYS_REMOVED_ELEMENTS: &[&str] = &[
+    "script",
+    "style",
+    "template",
+    "noscript",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+];
+
+/// 結果が安定するまでサニタイズを繰り返す回数の上限
+const MAX_PASSES: usize = 4;
+
+/// サニタイズの許可リスト
+///
+/// 要素名・属性名は小文字で指定します。SVG や MathML の要素は常に中身ごと取り除き、
+/// `on`で始まるイベントハンドラー属性はどの要素でも常に取り除きます。
+#[derive(Debug, Clone)]
+pub struct SanitizerPolicy {
+    /// 残す要素
+    pub allowed_elements: HashSet<String>,
+    /// すべての要素で残す属性
+    pub generic_attributes: HashSet<String>,
+    /// 要素ごとに残す属性
+    pub element_attributes: HashMap<String, HashSet<String>>,
+    /// 値を URL として検査する属性
+    pub url_attributes: HashSet<String>,
+    /// URL 属性に許すスキーム（相対 URL は常に許す）
+    pub allowed_url_schemes: HashSet<String>,
+    /// 中身ごと取り除く要素（それ以外の許可されていない要素は、子を残して取り除く）
+    pub removed_elements: HashSet<String>,
+    /// コメントを残すか（書き出すと途中で閉じうるコメントは、この設定に関わらず取り除く）
+    pub allow_comments: bool,
+    /// `href`を持つ`<a>`に設定する`rel`属性の値（`None`の場合は入力のまま）
+    pub link_rel: Option<String>,
+}
+
+impl Default for SanitizerPolicy {
+    /// 文章の書式（見出し・リスト・表・リンク・画像など）だけを残すポリシー
+    fn default() -> Self {
+        let element_attributes = [
+            ("a", &["href", "hreflang"][..]),
+            ("bdo", &["dir"]),
+            ("blockquote", &["cite"]),
+            ("col", &["span"]),
+            ("colgroup", &["span"]),
+            ("del", &["cite", "datetime"]),
+            ("img", &["src", "alt", "width", "height"]),
+            ("ins", &["cite", "datetime"]),
+            ("ol", &["start", "reversed"]),
+            ("q", &["cite"]),
+            ("td", &["colspan", "rowspan", "headers"]),
+            ("th", &["colspan", "rowspan", "headers", "scope"]),
+            ("time", &["datetime"]),
+        ]
+        .into_iter()
+        .map(|(element, attributes)| (element.to_string(), set(attributes)))
+        .collect();
+
+        Self {
+            allowed_elements: set(&[
+                "a",
+                "abbr",
+                "b",
+                "bdi",
+                "bdo",
+                "blockquote",
+                "br",
+                "caption",
+                "cite",
+                "code",
+                "col",
+                "colgroup",
+                "dd",
+                "del",
+                "details",
+                "dfn",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "figcaption",
+                "figure",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "ins",
+                "kbd",
+                "li",
+                "mark",
+                "ol",
+                "p",
+                "pre",
+                "q",
+                "rp",
+                "rt",
+                "ruby",
+                "s",
+                "samp",
+                "small",
+                "span",
+                "strong",
+                "sub",
+                "summary",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "tfoot",
+                "th",
+                "thead",
+                "time",
+                "tr",
+                "u",
+                "ul",
+                "var",
+                "wbr",
+            ]),
+            generic_attributes: set(&["dir", "lang", "title"]),
+            element_attributes,
+            url_attributes: set(&[
+                "action",
+                "background",
+                "cite",
+                "formaction",
+                "href",
+                "longdesc",
+                "poster",
+                "src",
+            ]),
+            allowed_url_schemes: set(&["http", "https", "mailto"]),
+            removed_elements: set(&[
+                "applet", "embed", "frame", "frameset", "object", "select", "textarea", "title",
+            ]),
+            allow_comments: false,
+            link_rel: Some("noopener noreferrer".to_string()),
+        }
+    }
+}
+
+impl SanitizerPolicy {
+    /// 要素をすべて取り除き、テキストだけを残すポリシー
+    pub fn text_only() -> Self {
+        Self {
+            allowed_elements: HashSet::new(),
+            generic_attributes: HashSet::new(),
+            element_attributes: HashMap::new(),
+            link_rel: None,
+            ..Self::default()
+        }
+    }
+
+    /// HTML の断片をサニタイズし、安全な HTML にして返します
+    ///
+    /// 断片は`<body>`の中身として解析します。
+    pub fn sanitize(&self, html: &str) -> String {
+        let mut output = self.sanitize_once(html);
+        for _ in 0..MAX_PASSES {
+            let again = self.sanitize_once(&output);
+            if again == output {
+                return output;
+            }
+            output = again;
+        }
+        // 解析し直すたびに木が変わる入力は、テキストだけを残す
+        let container = parse_fragment(&output);
+        let text = container.borrow().text_content();
+        let escaped = Node::new_document_fragment();
+        parser::append_child(&escaped, Node::new_text(&text));
+        let escaped = escaped.borrow().inner_html();
+        escaped
+    }
+
+    /// `root`の子孫をその場でサニタイズします（`root`自身は検査しません）
+    pub fn sanitize_tree(&self, root: &NodeRef) {
+        let mut pending: Vec<NodeRef> = root.borrow().children.iter().rev().cloned().collect();
+        while let Some(node) = pending.pop() {
+            let action = self.action_for(&node.borrow());
+            match action {
+                Action::Keep => {
+                    self.clean_attributes(&node);
+                    pending.extend(node.borrow().children.iter().rev().cloned());
+                }
+                Action::Unwrap => {
+                    let children = node.borrow().children.clone();
+                    let parent = node.borrow().parent();
+                    if let Some(parent) = parent {
+                        for child in &children {
+                            let _ = mutation::insert_before(&parent, child, Some(&node));
+                        }
+                    }
+                    mutation::remove(&node);
+                    pending.extend(children.into_iter().rev());
+                }
+                Action::Remove => mutation::remove(&node),
+            }
+        }
+    }
+
+    fn sanitize_once(&self, html: &str) -> String {
+        let container = parse_fragment(html);
+        self.sanitize_tree(&container);
+        let html = container.borrow().inner_html();
+        html
+    }
+
+    fn action_for(&self, node: &Node) -> Action {
+        match &node.node_type {
+            NodeType::Text(_) => Action::Keep,
+            NodeType::Comment(data) if self.allow_comments && is_safe_comment(data) => Action::Keep,
+            NodeType::Element {
+                tag_name,
+                namespace: Namespace::Html,
+                ..
+            } => {
+                let name = tag_name.as_str();
+                if ALWAYS_REMOVED_ELEMENTS.contains(&name) || self.removed_elements.contains(name) {
+                    Action::Remove
+                } else if self.allowed_elements.contains(name) {
+                    Action::Keep
+                } else {
+                    Action::Unwrap
+                }
+            }
+            _ => Action::Remove,
+        }
+    }
+
+    /// 許可されていない属性と、許可されていないスキームの URL を取り除きます
+    fn clean_attributes(&self, element: &NodeRef) {
+        let Some(tag_name) = element.borrow().tag_name().map(str::to_string) else {
+            return;
+        };
+        let disallowed: Vec<String> = element
+            .borrow()
+            .attributes()
+            .iter()
+            .filter(|attr| !self.is_allowed_attribute(&tag_name, &attr.name, &attr.value))
+            .map(|attr| attr.name.to_string())
+            .collect();
+        for name in disallowed {
+            mutation::remove_attribute(element, &name);
+        }
+        if let Some(rel) = &self.link_rel {
+            if tag_name == "a" && element.borrow().has_attribute("href") {
+                let _ = mutation::set_attribute(element, "rel", rel);
+            }
+        }
+    }
+
+    fn is_allowed_attribute(&self, tag_name: &str, name: &str, value: &str) -> bool {
+        if name
+            .get(..2)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("on"))
+        {
+            return false;
+        }
+        let allowed = self.generic_attributes.contains(name)
+            || self
+                .element_attributes
+                .get(tag_name)
+                .is_some_and(|attributes| attributes.contains(name));
+        allowed && (!self.url_attributes.contains(name) || self.is_allowed_url(value))
+    }
+
+    /// URL のスキームが許可されているか（相対 URL は許可する）
+    ///
+    /// スキームの前後の空白や制御文字、途中のタブ・改行は URL の解析で取り除かれるので、
+    /// `java\tscript:`のような値も`javascript`スキームとして判定されます。
+    fn is_allowed_url(&self, value: &str) -> bool {
+        match Url::parse(value) {
+            Ok(url) => self.allowed_url_schemes.contains(url.scheme()),
+            Err(url::ParseError::RelativeUrlWithoutBase) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+/// ノードの扱い
+enum Action {
+    /// 残す（要素は属性を検査し、子を続けて検査する）
+    Keep,
+    /// 子を親に移してから取り除く
+    Unwrap,
+    /// 中身ごと取り除く
+    Remove,
+}
+
+/// 断片を`<body>`の中身として解析し、DocumentFragment にまとめます
+fn parse_fragment(html: &str) -> NodeRef {
+    let container = Node::new_document_fragment();
+    let nodes = Parser::new_fragment(html, &container).parse_fragment();
+    for node in nodes {
+        parser::append_child(&container, node);
+    }
+    container
+}
+
+/// 書き出した`<!--data-->`が、最後の`-->`で閉じるコメントになるかどうか
+///
+/// ブラウザは`<!-->`、`<!--->`、`--->`、`--!>`でもコメントを閉じるため、中身をそのまま
+/// 書き出すと、その後ろがコメントの外でタグとして解析されることがあります。コメントは`>`でしか
+/// 閉じないので`>`を含むものは残さず、仕様上不正な`--`、`<!-`、末尾の`-`を含むものも残しません。
+fn is_safe_comment(data: &str) -> bool {
+    !(data.contains('>') || data.contains("--") || data.contains("<!-") || data.ends_with('-'))
+}
+
+fn set(names: &[&str]) -> HashSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
@@ -0,0 +1,122 @@
+//! DOM の HTML への書き出し
+//!
+//! HTML fragment serialization algorithm に従って、ノードをHTMLの文字列にします。
+//! ページの保存や、解析結果の確認に使います。
+//! https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::parser::{is_void_element, Node, NodeType};
+
+/// 中のテキストをエスケープせずに書き出す要素
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+    "style",
+    "script",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+];
+
+impl Node {
+    /// 子孫をHTMLとして書き出します（`innerHTML`）
+    ///
+    /// `<template>`要素の場合は内容（DocumentFragment）を書き出します。
+    pub fn inner_html(&self) -> String {
+        let mut out = String::new();
+        serialize_children(self, &mut out);
+        out
+    }
+
+    /// ノード自身を含めてHTMLとして書き出します（`outerHTML`）
+    pub fn outer_html(&self) -> String {
+        let raw_text = self
+            .parent()
+            .is_some_and(|parent| is_raw_text_element(&parent.borrow()));
+        let mut out = String::new();
+        serialize_node(self, raw_text, &mut out);
+        out
+    }
+}
+
+fn is_raw_text_element(node: &Node) -> bool {
+    node.namespace() == Some(Namespace::Html)
+        && node
+            .tag_name()
+            .is_some_and(|name| RAW_TEXT_ELEMENTS.contains(&name))
+}
+
+fn serialize_children(node: &Node, out: &mut String) {
+    if let Some(content) = node.template_content() {
+        serialize_children(&content.borrow(), out);
+        return;
+    }
+    let raw_text = is_raw_text_element(node);
+    for child in &node.children {
+        serialize_node(&child.borrow(), raw_text, out);
+    }
+}
+
+/// ノードを書き出します
+///
+/// # 引数
+/// * `raw_text` - 親が`<script>`や`<style>`など、テキストをエスケープしない要素か
+fn serialize_node(node: &Node, raw_text: bool, out: &mut String) {
+    match &node.node_type {
+        NodeType::Document | NodeType::DocumentFragment => serialize_children(node, out),
+        NodeType::Element {
+            tag_name,
+            attributes,
+            namespace,
+            ..
+        } => {
+            // 属性名は`xlink:href`のような修飾名のまま書き出す
+            out.push('<');
+            out.push_str(tag_name);
+            for attribute in attributes {
+                out.push(' ');
+                out.push_str(&attribute.name);
+                out.push_str("=\"");
+                escape(&attribute.value, true, out);
+                out.push('"');
+            }
+            out.push('>');
+            if *namespace == Namespace::Html && is_void_element(tag_name) {
+                return;
+            }
+            serialize_children(node, out);
+            out.push_str("</");
+            out.push_str(tag_name);
+            out.push('>');
+        }
+        NodeType::Text(text) if raw_text => out.push_str(text),
+        NodeType::Text(text) => escape(text, false, out),
+        NodeType::Comment(data) => {
+            out.push_str("<!--");
+            out.push_str(data);
+            out.push_str("-->");
+        }
+        NodeType::Doctype { name, .. } => {
+            out.push_str("<!DOCTYPE ");
+            out.push_str(name.as_deref().unwrap_or_default());
+            out.push('>');
+        }
+    }
+}
+
+/// 文字列をエスケープして書き出します（escaping a string）
+///
+/// # 引数
+/// * `attribute_mode` - 属性値として書き出すか（`"`もエスケープする）
+fn escape(text: &str, attribute_mode: bool, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '\u{A0}' => out.push_str("&nbsp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attribute_mode => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! ソース上の位置
+//!
+//! トークンやノードがHTMLソースのどこから来たかを表します。ソース表示やバリデーターで、
+//! エラーや要素の位置を示すために使います。
+
+use std::cell::Cell;
+
+/// ソース上の1点
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct SourcePosition {
+    /// 入力先頭からのバイトオフセット
+    pub offset: usize,
+    /// 行番号（1始まり）
+    pub line: usize,
+    /// 列番号（1始まり、文字単位）
+    pub column: usize,
+}
+
+/// ソース上の範囲（`start`を含み`end`を含まない）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
+impl Span {
+    /// 範囲に対応するソースの部分文字列を返します
+    ///
+    /// # 引数
+    /// * `source` - 字句解析に渡した入力
+    ///
+    /// # 戻り値
+    /// * 範囲が`source`の外にある場合は`None`を返します
+    pub fn slice<'a>(&self, source: &'a str) -> Option<&'a str> {
+        source.get(self.start.offset..self.end.offset)
+    }
+}
+
+/// バイトオフセットを行と列に変換するための索引
+#[derive(Debug, Clone)]
+pub(crate) struct LineIndex {
+    /// 各行の先頭のバイトオフセット
+    line_starts: Vec<usize>,
+    /// 最後に求めた位置
+    ///
+    /// 字句解析器は位置をほぼ先頭から順に求めるので、同じ行の後ろの位置は行頭からではなく
+    /// ここから列を数えます。改行のない長い入力でも、列を数える量が入力の長さに比例します。
+    last: Cell<SourcePosition>,
+}
+
+impl LineIndex {
+    pub(crate) fn new() -> Self {
+        LineIndex {
+            line_starts: vec![0],
+            last: Cell::default(),
+        }
+    }
+
+    /// 入力の`offset`の位置に追加された`text`の行を登録します
+    pub(crate) fn push(&mut self, text: &str, offset: usize) {
+        self.line_starts
+            .extend(text.match_indices('\n').map(|(i, _)| offset + i + 1));
+    }
+
+    /// `offset`の位置を返します（`offset`は`input`の文字境界であること）
+    pub(crate) fn position(&self, input: &str, offset: usize) -> SourcePosition {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let last = self.last.get();
+        let (from, column) = if last.line == line && last.offset <= offset {
+            (last.offset, last.column)
+        } else {
+            (self.line_starts[line - 1], 1)
+        };
+        let position = SourcePosition {
+            offset,
+            line,
+            column: column + input[from..offset].chars().count(),
+        };
+        self.last.set(position);
+        position
+    }
+
+    pub(crate) fn span(&self, input: &str, start: usize, end: usize) -> Span {
+        Span {
+            start: self.position(input, start),
+            end: self.position(input, end),
+        }
+    }
+}
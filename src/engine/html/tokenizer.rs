@@ -1,34 +1,97 @@
-#[derive(Debug, PartialEq)]
-pub struct Attribute {
-    pub name: String,
-    pub value: String,
+use crate::engine::html::entities::{self, LONGEST_NAME_LEN};
+use crate::engine::html::error::{ParseError, ParseErrorKind};
+use crate::engine::html::names;
+use crate::engine::html::namespace::Namespace;
+use crate::engine::html::span::{LineIndex, SourcePosition, Span};
+use memchr::{memchr, memchr2, memchr3};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+/// 属性
+///
+/// 字句解析器が出力する属性の値は、文字参照などの変換が不要であれば入力を借用します。
+/// DOM の要素は`Attribute<'static>`として値を所有します。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute<'a> {
+    /// 小文字の属性名（よく使われる名前は静的な文字列を借用する）
+    pub name: Cow<'static, str>,
+    pub value: Cow<'a, str>,
+    /// 名前空間（外部コンテンツの`xlink:href`などのみ）
+    pub namespace: Option<Namespace>,
 }
 
+impl Attribute<'_> {
+    /// 値を所有する属性に変換します
+    pub fn into_owned(self) -> Attribute<'static> {
+        Attribute {
+            name: self.name,
+            value: Cow::Owned(self.value.into_owned()),
+            namespace: self.namespace,
+        }
+    }
+}
+
+/// トークン
+///
+/// テキストやコメントなどの内容は、入力と同じであれば入力を借用します（`Tokenizer::new`の場合）。
 #[allow(dead_code)]
-#[derive(Debug, PartialEq)]
-pub enum Token {
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
     Doctype {
-        name: Option<String>,
-        public_id: Option<String>,
-        system_id: Option<String>,
+        name: Option<Cow<'a, str>>,
+        public_id: Option<Cow<'a, str>>,
+        system_id: Option<Cow<'a, str>>,
         force_quirks: bool,
     },
     StartTag {
-        name: String,
-        attributes: Vec<Attribute>,
+        name: Cow<'static, str>,
+        attributes: Vec<Attribute<'a>>,
         self_closing: bool,
     },
     EndTag {
-        name: String,
+        name: Cow<'static, str>,
     },
-    Comment(String),
-    Text(String),
+    Comment(Cow<'a, str>),
+    Text(Cow<'a, str>),
+    /// 入力の終わり（ツリー構築で使用する。`Tokenizer::next_token`は代わりに`None`を返す）
+    Eof,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenizerState {
     Data,
+    /// `<title>`、`<textarea>`の内容（文字参照のみ解釈する）
+    Rcdata,
+    RcdataLessThanSign,
+    RcdataEndTagOpen,
+    RcdataEndTagName,
+    /// `<style>`、`<xmp>`、`<iframe>`などの内容（何も解釈しない）
+    Rawtext,
+    RawtextLessThanSign,
+    RawtextEndTagOpen,
+    RawtextEndTagName,
+    /// `<script>`の内容
+    ScriptData,
+    ScriptDataLessThanSign,
+    ScriptDataEndTagOpen,
+    ScriptDataEndTagName,
+    ScriptDataEscapeStart,
+    ScriptDataEscapeStartDash,
+    ScriptDataEscaped,
+    ScriptDataEscapedDash,
+    ScriptDataEscapedDashDash,
+    ScriptDataEscapedLessThanSign,
+    ScriptDataEscapedEndTagOpen,
+    ScriptDataEscapedEndTagName,
+    ScriptDataDoubleEscapeStart,
+    ScriptDataDoubleEscaped,
+    ScriptDataDoubleEscapedDash,
+    ScriptDataDoubleEscapedDashDash,
+    ScriptDataDoubleEscapedLessThanSign,
+    ScriptDataDoubleEscapeEnd,
+    /// `<plaintext>`以降のすべて
+    Plaintext,
     TagOpen,
     EndTagOpen,
     TagName,
@@ -40,30 +103,203 @@ pub enum TokenizerState {
     AttributeValueSingleQuoted,
     AttributeValueUnquoted,
     SelfClosingStartTag,
+    /// `<!--` の後
+    CommentStart,
+    /// `<!---` の後
     CommentStartDash,
     Comment,
+    /// コメント中の `<`、`<!`、`<!-`、`<!--` の後（入れ子のコメントの検出）
+    CommentLessThanSign,
+    CommentLessThanSignBang,
+    CommentLessThanSignBangDash,
+    CommentLessThanSignBangDashDash,
     CommentEndDash,
     CommentEnd,
+    /// `--!` の後
+    CommentEndBang,
     BogusComment,
     Doctype,
+    BeforeDoctypeName,
     DoctypeName,
+    AfterDoctypeName,
+    /// `PUBLIC` キーワードの後
     BeforeDoctypePublicId,
     DoctypePublicIdWithSingleQuote,
     DoctypePublicIdWithDoubleQuote,
     AfterDoctypePublicId,
-    DoctypeSystemId,
+    /// `SYSTEM` キーワードの後
+    BeforeDoctypeSystemId,
+    DoctypeSystemIdWithSingleQuote,
+    DoctypeSystemIdWithDoubleQuote,
+    AfterDoctypeSystemId,
     BogusDoctype,
+    /// `<![CDATA[`〜`]]>`の内容（外部コンテンツの中のみ）
+    CdataSection,
+}
+
+/// 組み立て中のトークンの文字列
+///
+/// 入力をそのまま写している間は入力上の範囲だけを持ち、文字参照の展開や NULL の置き換えなどで
+/// 入力と異なる内容になった時点で文字列にコピーします。
+#[derive(Debug, Default)]
+enum TextBuffer {
+    #[default]
+    Empty,
+    /// 入力の`start..end`と同じ内容
+    Source {
+        start: usize,
+        end: usize,
+    },
+    Owned(String),
 }
 
+impl TextBuffer {
+    /// 入力の`start..end`を追加します（直前に追加した範囲に続いていればコピーしない）
+    fn push_source(&mut self, input: &str, start: usize, end: usize) {
+        match self {
+            TextBuffer::Empty => *self = TextBuffer::Source { start, end },
+            TextBuffer::Source { end: last, .. } if *last == start => *last = end,
+            _ => self.to_mut(input).push_str(&input[start..end]),
+        }
+    }
+
+    /// 入力と異なる文字列を追加します
+    fn push_str(&mut self, input: &str, s: &str) {
+        self.to_mut(input).push_str(s);
+    }
+
+    fn push(&mut self, input: &str, c: char) {
+        self.to_mut(input).push(c);
+    }
+
+    fn to_mut(&mut self, input: &str) -> &mut String {
+        match self {
+            TextBuffer::Empty => *self = TextBuffer::Owned(String::new()),
+            TextBuffer::Source { start, end } => {
+                *self = TextBuffer::Owned(input[*start..*end].to_string())
+            }
+            TextBuffer::Owned(_) => {}
+        }
+        match self {
+            TextBuffer::Owned(s) => s,
+            _ => unreachable!(),
+        }
+    }
+
+    /// 文字列にします（入力を借用している場合、入力と同じ内容なら借用する）
+    fn resolve<'a>(self, input: &Cow<'a, str>) -> Cow<'a, str> {
+        match (self, input) {
+            (TextBuffer::Empty, _) => Cow::Borrowed(""),
+            (TextBuffer::Source { start, end }, Cow::Borrowed(input)) => {
+                Cow::Borrowed(&input[start..end])
+            }
+            (TextBuffer::Source { start, end }, Cow::Owned(input)) => {
+                Cow::Owned(input[start..end].to_string())
+            }
+            (TextBuffer::Owned(s), _) => Cow::Owned(s),
+        }
+    }
+}
+
+/// 組み立て中の属性
+#[derive(Debug)]
+struct PendingAttribute {
+    name: Cow<'static, str>,
+    value: TextBuffer,
+}
+
+/// 組み立て中のトークン（出力するときに`Token`にする）
+#[derive(Debug)]
+enum PendingToken {
+    Doctype {
+        name: Option<TextBuffer>,
+        public_id: Option<TextBuffer>,
+        system_id: Option<TextBuffer>,
+        force_quirks: bool,
+    },
+    StartTag {
+        name: Cow<'static, str>,
+        attributes: Vec<PendingAttribute>,
+        self_closing: bool,
+    },
+    EndTag {
+        name: Cow<'static, str>,
+    },
+    Comment(TextBuffer),
+    Text(TextBuffer),
+}
+
+impl PendingToken {
+    fn resolve<'a>(self, input: &Cow<'a, str>) -> Token<'a> {
+        let resolve = |text: Option<TextBuffer>| text.map(|text| text.resolve(input));
+        match self {
+            PendingToken::Doctype {
+                name,
+                public_id,
+                system_id,
+                force_quirks,
+            } => Token::Doctype {
+                name: resolve(name),
+                public_id: resolve(public_id),
+                system_id: resolve(system_id),
+                force_quirks,
+            },
+            PendingToken::StartTag {
+                name,
+                attributes,
+                self_closing,
+            } => Token::StartTag {
+                name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|attr| Attribute {
+                        name: attr.name,
+                        value: attr.value.resolve(input),
+                        namespace: None,
+                    })
+                    .collect(),
+                self_closing,
+            },
+            PendingToken::EndTag { name } => Token::EndTag { name },
+            PendingToken::Comment(text) => Token::Comment(text.resolve(input)),
+            PendingToken::Text(text) => Token::Text(text.resolve(input)),
+        }
+    }
+}
+
+/// HTMLの字句解析器
+///
+/// 入力は`feed`で少しずつ渡せます。タグや文字参照、マルチバイト文字の途中で入力が途切れた
+/// 場合は、続きが届くまでその手前で止まります。`finish`を呼ぶと残りを入力の終わりとして処理します。
+///
+/// `new`で作った場合は入力をコピーせずに借用し、出力するトークンの内容も変換が不要な部分は
+/// 入力を借用します。`feed`で受け取った入力は字句解析器が保持し、トークンの内容はコピーします。
 #[allow(dead_code)]
 pub struct Tokenizer<'a> {
-    input: &'a str,
+    /// これまでに受け取った入力（スパンの位置はこの先頭からのオフセット）
+    input: Cow<'a, str>,
+    /// UTF-8として不完全なまま届いた末尾のバイト列
+    incomplete_utf8: Vec<u8>,
+    /// 入力がすべて届いたか
+    finished: bool,
     pos: usize,
-    token: Option<Token>,
+    /// 出力待ちのトークンと、そのソース上の範囲
+    tokens: VecDeque<(Token<'a>, Span)>,
     state: TokenizerState,
-    current_token: Option<Token>,
-    current_attribute: Option<Attribute>,
-    buffer: String,
+    current_token: Option<PendingToken>,
+    current_attribute: Option<PendingAttribute>,
+    /// 終了タグ名やエスケープの判定に使う一時バッファ（temporary buffer）
+    temporary_buffer: String,
+    /// 最後に出力した開始タグの名前（appropriate end tag の判定に使う）
+    last_start_tag_name: String,
+    /// `<![CDATA[`をCDATAセクションとして読むか（ツリー構築が外部コンテンツの中で有効にする）
+    cdata_allowed: bool,
+    /// 次に出力するトークンの開始位置（直前のトークンの終了位置）
+    span_start: usize,
+    /// 現在処理している文字の開始位置（エラーの位置に使う）
+    char_start: usize,
+    line_index: LineIndex,
+    errors: Vec<ParseError>,
 }
 
 #[allow(dead_code)]
@@ -72,50 +308,264 @@ impl TokenizerState {
         matches!(
             self,
             TokenizerState::Doctype
+                | TokenizerState::BeforeDoctypeName
                 | TokenizerState::DoctypeName
+                | TokenizerState::AfterDoctypeName
                 | TokenizerState::BeforeDoctypePublicId
                 | TokenizerState::DoctypePublicIdWithSingleQuote
                 | TokenizerState::DoctypePublicIdWithDoubleQuote
                 | TokenizerState::AfterDoctypePublicId
-                | TokenizerState::DoctypeSystemId
+                | TokenizerState::BeforeDoctypeSystemId
+                | TokenizerState::DoctypeSystemIdWithSingleQuote
+                | TokenizerState::DoctypeSystemIdWithDoubleQuote
+                | TokenizerState::AfterDoctypeSystemId
                 | TokenizerState::BogusDoctype
         )
     }
 
+    /// `state_script_data`で処理する script data の状態かどうか
+    fn is_script_data(&self) -> bool {
+        matches!(
+            self,
+            TokenizerState::ScriptData
+                | TokenizerState::ScriptDataLessThanSign
+                | TokenizerState::ScriptDataEscapeStart
+                | TokenizerState::ScriptDataEscapeStartDash
+                | TokenizerState::ScriptDataEscaped
+                | TokenizerState::ScriptDataEscapedDash
+                | TokenizerState::ScriptDataEscapedDashDash
+                | TokenizerState::ScriptDataEscapedLessThanSign
+                | TokenizerState::ScriptDataDoubleEscapeStart
+                | TokenizerState::ScriptDataDoubleEscaped
+                | TokenizerState::ScriptDataDoubleEscapedDash
+                | TokenizerState::ScriptDataDoubleEscapedDashDash
+                | TokenizerState::ScriptDataDoubleEscapedLessThanSign
+                | TokenizerState::ScriptDataDoubleEscapeEnd
+        )
+    }
+
+    /// 文字をそのままテキストとして読む状態かどうか
+    fn is_text(&self) -> bool {
+        matches!(
+            self,
+            TokenizerState::Data
+                | TokenizerState::Rcdata
+                | TokenizerState::Rawtext
+                | TokenizerState::ScriptData
+                | TokenizerState::ScriptDataEscaped
+                | TokenizerState::ScriptDataDoubleEscaped
+                | TokenizerState::Plaintext
+                | TokenizerState::CdataSection
+        )
+    }
+
+    fn is_doctype_public_id(&self) -> bool {
+        matches!(
+            self,
+            TokenizerState::DoctypePublicIdWithSingleQuote
+                | TokenizerState::DoctypePublicIdWithDoubleQuote
+        )
+    }
+
     fn is_comment(&self) -> bool {
         matches!(
             self,
-            TokenizerState::Comment
+            TokenizerState::CommentStart
                 | TokenizerState::CommentStartDash
+                | TokenizerState::Comment
+                | TokenizerState::CommentLessThanSign
+                | TokenizerState::CommentLessThanSignBang
+                | TokenizerState::CommentLessThanSignBangDash
+                | TokenizerState::CommentLessThanSignBangDashDash
                 | TokenizerState::CommentEndDash
                 | TokenizerState::CommentEnd
+                | TokenizerState::CommentEndBang
                 | TokenizerState::BogusComment
         )
     }
 }
 
 impl<'a> Tokenizer<'a> {
+    /// 入力全体を字句解析する字句解析器を作ります（入力は借用し、コピーしません）
     pub fn new(input: &'a str) -> Self {
+        let mut tokenizer = Tokenizer::new_streaming();
+        tokenizer.line_index.push(input, 0);
+        tokenizer.input = Cow::Borrowed(input);
+        tokenizer.finished = true;
+        tokenizer
+    }
+
+    /// 入力を`feed`で少しずつ受け取る字句解析器を作ります
+    pub fn new_streaming() -> Self {
         Tokenizer {
-            input,
+            input: Cow::Owned(String::new()),
+            incomplete_utf8: Vec::new(),
+            finished: false,
             pos: 0,
-            token: None,
+            tokens: VecDeque::new(),
             state: TokenizerState::Data,
             current_token: None,
             current_attribute: None,
-            buffer: String::new(),
+            temporary_buffer: String::new(),
+            last_start_tag_name: String::new(),
+            cdata_allowed: false,
+            span_start: 0,
+            char_start: 0,
+            line_index: LineIndex::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// 入力の続きを渡します
+    ///
+    /// 不正なUTF-8のバイト列は U+FFFD に置き換えます。文字の途中で途切れたバイト列は
+    /// 次の`feed`（または`finish`）まで保留します。
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let mut bytes = std::mem::take(&mut self.incomplete_utf8);
+        bytes.extend_from_slice(chunk);
+        let mut rest = bytes.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.push_input(text);
+                    return;
+                }
+                Err(error) => {
+                    let (valid, invalid) = rest.split_at(error.valid_up_to());
+                    self.push_input(std::str::from_utf8(valid).unwrap_or_default());
+                    match error.error_len() {
+                        Some(len) => {
+                            self.push_input("\u{FFFD}");
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.incomplete_utf8 = invalid.to_vec();
+                            return;
+                        }
+                    }
+                }
+            }
         }
     }
 
-    pub fn next_token(&mut self) -> Option<Token> {
+    /// 入力の終わりを知らせます。以降の`next_token`は残りのトークンを出力し終えると`None`を返します
+    pub fn finish(&mut self) {
+        if !self.incomplete_utf8.is_empty() {
+            self.incomplete_utf8.clear();
+            self.push_input("\u{FFFD}");
+        }
+        self.finished = true;
+    }
+
+    fn push_input(&mut self, text: &str) {
+        self.line_index.push(text, self.input.len());
+        self.input.to_mut().push_str(text);
+    }
+
+    /// 字句解析の状態を切り替えます
+    ///
+    /// ツリー構築が`<script>`や`<title>`などを挿入した直後に、その内容を
+    /// RCDATA / RAWTEXT / script data / PLAINTEXT として読むために呼び出します。
+    pub fn switch_to(&mut self, state: TokenizerState) {
+        self.state = state;
+    }
+
+    /// 直前の開始タグ名を設定します
+    ///
+    /// RCDATA などの途中から字句解析を始める場合に、どの終了タグで内容が終わるかを指定します。
+    pub fn set_last_start_tag(&mut self, name: &str) {
+        self.last_start_tag_name = name.to_ascii_lowercase();
+    }
+
+    /// `<![CDATA[`をCDATAセクションとして読むかどうかを設定します
+    ///
+    /// 調整済みの現在のノードがHTML以外の要素のときに、ツリー構築が有効にします。
+    /// 無効な場合、CDATAセクションはコメントとして扱います。
+    pub fn set_cdata_allowed(&mut self, allowed: bool) {
+        self.cdata_allowed = allowed;
+    }
+
+    /// 次のトークンを返します
+    ///
+    /// `finish`の前に`None`を返した場合は、続きの入力が必要なことを表します。
+    pub fn next_token(&mut self) -> Option<Token<'a>> {
+        self.next_token_with_span().map(|(token, _)| token)
+    }
+
+    /// 次のトークンを、そのソース上の範囲とともに返します
+    ///
+    /// 範囲はタグなら`<`から`>`まで、テキストなら文字参照を含む元の文字列全体です。
+    /// 入力の途中では、テキストは届いた分だけで区切って出力します。
+    pub fn next_token_with_span(&mut self) -> Option<(Token<'a>, Span)> {
+        if let Some(token) = self.tokens.pop_front() {
+            return Some(token);
+        }
+
         while self.pos < self.input.len() {
-            let c = self.input[self.pos..].chars().next().unwrap();
+            if self.consume_run() {
+                continue;
+            }
+            // ASCII はバイトのまま文字にし、それ以外だけ UTF-8 として読む
+            let c = match self.input.as_bytes()[self.pos] {
+                b if b.is_ascii() => b as char,
+                _ => self.input[self.pos..].chars().next().unwrap(),
+            };
+            self.char_start = self.pos;
             self.pos += c.len_utf8();
-
-            //print!("State: {:?}, Char: '{}'\n", self.state, c);
+            if self.needs_more_input(c) {
+                // 続きの入力が届くまで待つ
+                self.pos = self.char_start;
+                break;
+            }
 
             match self.state {
                 TokenizerState::Data => self.state_data(c),
+                TokenizerState::Rcdata => self.state_rcdata(c),
+                TokenizerState::Rawtext => self.state_rawtext(c),
+                TokenizerState::Plaintext => self.push_text_char(c),
+                TokenizerState::RcdataLessThanSign => self.state_text_less_than_sign(
+                    c,
+                    TokenizerState::RcdataEndTagOpen,
+                    TokenizerState::Rcdata,
+                ),
+                TokenizerState::RawtextLessThanSign => self.state_text_less_than_sign(
+                    c,
+                    TokenizerState::RawtextEndTagOpen,
+                    TokenizerState::Rawtext,
+                ),
+                TokenizerState::RcdataEndTagOpen => self.state_text_end_tag_open(
+                    c,
+                    TokenizerState::RcdataEndTagName,
+                    TokenizerState::Rcdata,
+                ),
+                TokenizerState::RawtextEndTagOpen => self.state_text_end_tag_open(
+                    c,
+                    TokenizerState::RawtextEndTagName,
+                    TokenizerState::Rawtext,
+                ),
+                TokenizerState::ScriptDataEndTagOpen => self.state_text_end_tag_open(
+                    c,
+                    TokenizerState::ScriptDataEndTagName,
+                    TokenizerState::ScriptData,
+                ),
+                TokenizerState::ScriptDataEscapedEndTagOpen => self.state_text_end_tag_open(
+                    c,
+                    TokenizerState::ScriptDataEscapedEndTagName,
+                    TokenizerState::ScriptDataEscaped,
+                ),
+                TokenizerState::RcdataEndTagName => {
+                    self.state_text_end_tag_name(c, TokenizerState::Rcdata)
+                }
+                TokenizerState::RawtextEndTagName => {
+                    self.state_text_end_tag_name(c, TokenizerState::Rawtext)
+                }
+                TokenizerState::ScriptDataEndTagName => {
+                    self.state_text_end_tag_name(c, TokenizerState::ScriptData)
+                }
+                TokenizerState::ScriptDataEscapedEndTagName => {
+                    self.state_text_end_tag_name(c, TokenizerState::ScriptDataEscaped)
+                }
+                _ if self.state.is_script_data() => self.state_script_data(c),
                 _ if self.state.is_doctype() => self.state_doctype(c),
                 TokenizerState::TagOpen => self.state_tag_open(c),
                 TokenizerState::TagName => self.state_tag_name(c),
@@ -130,6 +580,7 @@ impl<'a> Tokenizer<'a> {
                 TokenizerState::AttributeValueUnquoted => self.state_attribute_value_unquoted(c),
                 TokenizerState::SelfClosingStartTag => self.state_self_closing_start_tag(c),
                 TokenizerState::EndTagOpen => self.state_end_tag_open(c),
+                TokenizerState::CdataSection => self.state_cdata_section(c),
                 _ if self.state.is_comment() => self.state_comment(c),
                 _ => {
                     // 未実装の状態は無視してData状態に戻る
@@ -137,300 +588,1055 @@ impl<'a> Tokenizer<'a> {
                 }
             }
 
-            if let Some(token) = self.token.take() {
+            if let Some(token) = self.tokens.pop_front() {
                 return Some(token);
             }
         }
 
+        if !self.finished {
+            // 入力の途中では、届いた分のテキストだけを出力する
+            if self.state.is_text() && matches!(self.current_token, Some(PendingToken::Text(_))) {
+                self.commit_token();
+            }
+            return self.tokens.pop_front();
+        }
+
+        self.char_start = self.input.len();
+
+        // 閉じられていない DOCTYPE は互換モードにする
+        if self.state.is_doctype() {
+            if self.state != TokenizerState::BogusDoctype {
+                self.error(ParseErrorKind::EofInDoctype);
+                self.set_force_quirks();
+            }
+            self.state = TokenizerState::Data;
+            self.commit_token();
+            return self.tokens.pop_front();
+        }
+
         // BogusComment処理
         if self.state.is_comment() {
+            if self.state != TokenizerState::BogusComment {
+                self.error(ParseErrorKind::EofInComment);
+            }
             self.state = TokenizerState::BogusComment;
             self.commit_token();
-            return self.token.take();
+            return self.tokens.pop_front();
+        }
+
+        match self.state {
+            // 閉じられていないタグは捨てる
+            TokenizerState::TagName
+            | TokenizerState::BeforeAttributeName
+            | TokenizerState::AttributeName
+            | TokenizerState::AfterAttributeName
+            | TokenizerState::BeforeAttributeValue
+            | TokenizerState::AttributeValueDoubleQuoted
+            | TokenizerState::AttributeValueSingleQuoted
+            | TokenizerState::AttributeValueUnquoted
+            | TokenizerState::SelfClosingStartTag => {
+                self.error(ParseErrorKind::EofInTag);
+                self.current_token = None;
+                self.current_attribute = None;
+                self.state = TokenizerState::Data;
+            }
+            TokenizerState::TagOpen | TokenizerState::EndTagOpen => {
+                self.error(ParseErrorKind::EofBeforeTagName);
+            }
+            TokenizerState::ScriptDataEscaped
+            | TokenizerState::ScriptDataEscapedDash
+            | TokenizerState::ScriptDataEscapedDashDash
+            | TokenizerState::ScriptDataDoubleEscaped
+            | TokenizerState::ScriptDataDoubleEscapedDash
+            | TokenizerState::ScriptDataDoubleEscapedDashDash => {
+                self.error(ParseErrorKind::EofInScriptHtmlCommentLikeText);
+                self.state = TokenizerState::Data;
+            }
+            TokenizerState::CdataSection => {
+                self.error(ParseErrorKind::EofInCdata);
+                self.state = TokenizerState::Data;
+            }
+            _ => {}
+        }
+
+        // タグになりきらなかった `<` や `</name` はテキストとして扱う
+        let pending = match self.state {
+            TokenizerState::TagOpen
+            | TokenizerState::RcdataLessThanSign
+            | TokenizerState::RawtextLessThanSign
+            | TokenizerState::ScriptDataLessThanSign
+            | TokenizerState::ScriptDataEscapedLessThanSign => Some("<".to_string()),
+            TokenizerState::EndTagOpen
+            | TokenizerState::RcdataEndTagOpen
+            | TokenizerState::RawtextEndTagOpen
+            | TokenizerState::ScriptDataEndTagOpen
+            | TokenizerState::ScriptDataEscapedEndTagOpen => Some("</".to_string()),
+            TokenizerState::RcdataEndTagName
+            | TokenizerState::RawtextEndTagName
+            | TokenizerState::ScriptDataEndTagName
+            | TokenizerState::ScriptDataEscapedEndTagName => {
+                Some(format!("</{}", self.temporary_buffer))
+            }
+            _ => None,
+        };
+        if let Some(pending) = pending {
+            self.push_text(&pending);
+            self.temporary_buffer.clear();
+            self.state = TokenizerState::Data;
+        }
+
+        // 入力末尾に残ったテキスト
+        if let Some(PendingToken::Text(_)) = self.current_token {
+            self.commit_token();
+            return self.tokens.pop_front();
         }
 
         None
     }
 
+    /// 次に読む位置を返します（入力をすべて読んだ後は入力の末尾）
+    pub fn position(&self) -> SourcePosition {
+        self.line_index.position(&self.input, self.pos)
+    }
+
+    /// `c`を処理するのに、まだ届いていない入力の先読みが必要かどうか
+    fn needs_more_input(&self, c: char) -> bool {
+        if self.finished {
+            return false;
+        }
+        let rest = &self.input[self.pos..];
+        match (self.state, c) {
+            // `<!--` / `<!DOCTYPE` / `<![CDATA[` の判定
+            (TokenizerState::TagOpen, '!') => {
+                rest.len() < "doctype".len() && !rest.starts_with("--")
+            }
+            // `PUBLIC` / `SYSTEM` の判定
+            (TokenizerState::AfterDoctypeName, c) => {
+                !is_whitespace(c) && c != '>' && c.len_utf8() + rest.len() < "public".len()
+            }
+            // `]]>` の判定
+            (TokenizerState::CdataSection, ']') => rest.len() < 2 && "]>".starts_with(rest),
+            (
+                TokenizerState::Data
+                | TokenizerState::Rcdata
+                | TokenizerState::BeforeAttributeValue
+                | TokenizerState::AttributeValueDoubleQuoted
+                | TokenizerState::AttributeValueSingleQuoted
+                | TokenizerState::AttributeValueUnquoted,
+                '&',
+            ) => is_incomplete_character_reference(rest),
+            _ => false,
+        }
+    }
+
+    /// これまでに検出したパースエラー
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// これまでに検出したパースエラーを取り出します
+    pub fn take_errors(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn error(&mut self, kind: ParseErrorKind) {
+        let position = self.line_index.position(&self.input, self.char_start);
+        self.errors.push(ParseError { kind, position });
+    }
+
     fn commit_token(&mut self) {
-        self.token = self.current_token.take();
-        self.buffer.clear();
+        self.commit_token_at(self.pos);
+    }
+
+    /// 現在のトークンを、`end`までを範囲として出力します
+    ///
+    /// 次のトークンの範囲は`end`から始まります。
+    fn commit_token_at(&mut self, end: usize) {
+        if let Some(token) = self.current_token.take() {
+            if let PendingToken::StartTag { ref name, .. } = token {
+                self.last_start_tag_name.clear();
+                self.last_start_tag_name.push_str(name);
+            }
+            let token = token.resolve(&self.input);
+            let span = self.line_index.span(&self.input, self.span_start, end);
+            self.tokens.push_back((token, span));
+        }
+        self.span_start = end;
+    }
+
+    /// 捨てたトークンの後から次のトークンの範囲を始めます
+    fn discard_token(&mut self) {
+        self.current_token = None;
+        self.span_start = self.pos;
+    }
+
+    /// 現在の文字を読まなかったことにして、指定した状態で読み直します（reconsume）
+    fn reconsume(&mut self, c: char, state: TokenizerState) {
+        self.pos -= c.len_utf8();
+        self.state = state;
+    }
+
+    /// 状態を変えない文字の並びを、1文字ずつ処理せずにまとめて読みます
+    ///
+    /// テキストやコメント、属性値の中で、次に特別な扱いが必要な文字（`<`や`&`、引用符など）
+    /// までを探し、その手前までを現在のトークンに追加します（入力上の範囲として追加し、コピーしない）。
+    ///
+    /// # 戻り値
+    /// * 1文字以上読んだ場合は`true`
+    fn consume_run(&mut self) -> bool {
+        use TokenizerState as S;
+        let rest = &self.input.as_bytes()[self.pos..];
+        let len = match self.state {
+            S::Data | S::Rcdata => memchr3(b'<', b'&', b'\0', rest),
+            S::Rawtext | S::ScriptData => memchr2(b'<', b'\0', rest),
+            S::ScriptDataEscaped | S::ScriptDataDoubleEscaped => memchr3(b'-', b'<', b'\0', rest),
+            S::Plaintext => memchr(b'\0', rest),
+            S::CdataSection => memchr(b']', rest),
+            S::Comment => memchr3(b'-', b'<', b'\0', rest),
+            S::AttributeValueDoubleQuoted => memchr2(b'"', b'&', rest),
+            S::AttributeValueSingleQuoted => memchr2(b'\'', b'&', rest),
+            S::AttributeValueUnquoted => rest
+                .iter()
+                .position(|&b| is_whitespace(b as char) || b == b'>' || b == b'&'),
+            _ => return false,
+        }
+        .unwrap_or(rest.len());
+        if len == 0 {
+            return false;
+        }
+
+        let (start, end) = (self.pos, self.pos + len);
+        match self.state {
+            S::Comment => self.push_comment_source(start, end),
+            S::AttributeValueDoubleQuoted
+            | S::AttributeValueSingleQuoted
+            | S::AttributeValueUnquoted => {
+                if let Some(ref mut attr) = self.current_attribute {
+                    attr.value.push_source(&self.input, start, end);
+                }
+            }
+            _ => self.push_text_source(start, end),
+        }
+        self.pos = end;
+        true
+    }
+
+    /// `char_start`から始まるタグ名・属性名をまとめて読み、ASCII の小文字にして返します
+    ///
+    /// 空白・`/`・`>`（属性名では`=`も）の手前までが名前です。NULL と、属性名の`"`・`'`・`<`は
+    /// エラーを報告する必要があるため、その手前で止めて各状態で1文字ずつ処理します。
+    /// 最初の文字で止まった場合は空の名前を返し、その文字を読み直します。
+    fn consume_name(&mut self, attribute: bool) -> Cow<'static, str> {
+        let start = self.char_start;
+        let len = self.input.as_bytes()[start..]
+            .iter()
+            .position(|&b| {
+                is_whitespace(b as char)
+                    || matches!(b, b'/' | b'>' | b'\0')
+                    || (attribute && matches!(b, b'=' | b'"' | b'\'' | b'<'))
+            })
+            .unwrap_or(self.input.len() - start);
+        self.pos = start + len;
+        names::intern_lowercase(&self.input[start..self.pos])
+    }
+
+    fn state_rcdata(&mut self, c: char) {
+        match c {
+            '&' => match self.consume_character_reference(false) {
+                Some(decoded) => self.push_text(&decoded),
+                None => self.push_text_source(self.char_start, self.pos),
+            },
+            '<' => self.state = TokenizerState::RcdataLessThanSign,
+            _ => self.push_text_char(c),
+        }
+    }
+
+    fn state_rawtext(&mut self, c: char) {
+        match c {
+            '<' => self.state = TokenizerState::RawtextLessThanSign,
+            _ => self.push_text_char(c),
+        }
+    }
+
+    /// RCDATA / RAWTEXT の `<` の後
+    fn state_text_less_than_sign(
+        &mut self,
+        c: char,
+        end_tag_open: TokenizerState,
+        text_state: TokenizerState,
+    ) {
+        if c == '/' {
+            self.temporary_buffer.clear();
+            self.state = end_tag_open;
+        } else {
+            self.push_text_source(self.char_start - 1, self.char_start);
+            self.reconsume(c, text_state);
+        }
+    }
+
+    /// RCDATA / RAWTEXT / script data の `</` の後
+    fn state_text_end_tag_open(
+        &mut self,
+        c: char,
+        end_tag_name: TokenizerState,
+        text_state: TokenizerState,
+    ) {
+        if c.is_ascii_alphabetic() {
+            self.temporary_buffer.clear();
+            self.reconsume(c, end_tag_name);
+        } else {
+            self.push_text_source(self.char_start - 2, self.char_start);
+            self.reconsume(c, text_state);
+        }
+    }
+
+    /// RCDATA / RAWTEXT / script data の終了タグ名
+    ///
+    /// 直前の開始タグと同じ名前の終了タグ（appropriate end tag）だけを終了タグとして扱い、
+    /// それ以外は読んだ文字をそのままテキストに戻します。
+    fn state_text_end_tag_name(&mut self, c: char, text_state: TokenizerState) {
+        if c.is_ascii_alphabetic() {
+            self.temporary_buffer.push(c);
+            return;
+        }
+        if self
+            .temporary_buffer
+            .eq_ignore_ascii_case(&self.last_start_tag_name)
+        {
+            let next_state = match c {
+                c if is_whitespace(c) => Some(TokenizerState::BeforeAttributeName),
+                '/' => Some(TokenizerState::SelfClosingStartTag),
+                '>' => Some(TokenizerState::Data),
+                _ => None,
+            };
+            if let Some(next_state) = next_state {
+                // ここまでのテキストを `</` の手前までとして出力してから終了タグを始める
+                self.commit_token_at(self.char_start - self.temporary_buffer.len() - 2);
+                self.current_token = Some(PendingToken::EndTag {
+                    name: names::intern_lowercase(&self.temporary_buffer),
+                });
+                self.temporary_buffer.clear();
+                if c == '>' {
+                    self.commit_token();
+                }
+                self.state = next_state;
+                return;
+            }
+        }
+        // `</`と一時バッファの内容（ASCII の英字のみ）を入力のままテキストに戻す
+        let start = self.char_start - self.temporary_buffer.len() - 2;
+        self.push_text_source(start, self.char_start);
+        self.reconsume(c, text_state);
+    }
+
+    /// script data（エスケープ、二重エスケープを含む）
+    ///
+    /// `<!--` の中では `<script>` が入れ子になり、その間は `</script>` で終了しません。
+    /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-state
+    fn state_script_data(&mut self, c: char) {
+        use TokenizerState as S;
+        match (self.state, c) {
+            (S::ScriptData, '<') => self.state = S::ScriptDataLessThanSign,
+            (S::ScriptDataLessThanSign, '/') => {
+                self.temporary_buffer.clear();
+                self.state = S::ScriptDataEndTagOpen;
+            }
+            (S::ScriptDataLessThanSign, '!') => {
+                self.push_text_source(self.char_start - 1, self.pos);
+                self.state = S::ScriptDataEscapeStart;
+            }
+            (S::ScriptDataLessThanSign, _) => {
+                self.push_text_source(self.char_start - 1, self.char_start);
+                self.reconsume(c, S::ScriptData);
+            }
+            (S::ScriptDataEscapeStart, '-') => {
+                self.push_current_char();
+                self.state = S::ScriptDataEscapeStartDash;
+            }
+            (S::ScriptDataEscapeStartDash, '-') => {
+                self.push_current_char();
+                self.state = S::ScriptDataEscapedDashDash;
+            }
+            (S::ScriptDataEscapeStart | S::ScriptDataEscapeStartDash, _) => {
+                self.reconsume(c, S::ScriptData)
+            }
+            (S::ScriptDataEscaped, '-') => {
+                self.push_current_char();
+                self.state = S::ScriptDataEscapedDash;
+            }
+            (S::ScriptDataEscapedDash, '-') | (S::ScriptDataEscapedDashDash, '-') => {
+                self.push_current_char();
+                self.state = S::ScriptDataEscapedDashDash;
+            }
+            (
+                S::ScriptDataEscaped | S::ScriptDataEscapedDash | S::ScriptDataEscapedDashDash,
+                '<',
+            ) => self.state = S::ScriptDataEscapedLessThanSign,
+            (S::ScriptDataEscapedDashDash, '>') => {
+                self.push_current_char();
+                self.state = S::ScriptData;
+            }
+            (S::ScriptDataEscapedDash | S::ScriptDataEscapedDashDash, _) => {
+                self.push_text_char(c);
+                self.state = S::ScriptDataEscaped;
+            }
+            (S::ScriptDataEscapedLessThanSign, '/') => {
+                self.temporary_buffer.clear();
+                self.state = S::ScriptDataEscapedEndTagOpen;
+            }
+            (S::ScriptDataEscapedLessThanSign, c) if c.is_ascii_alphabetic() => {
+                self.temporary_buffer.clear();
+                self.push_text_source(self.char_start - 1, self.char_start);
+                self.reconsume(c, S::ScriptDataDoubleEscapeStart);
+            }
+            (S::ScriptDataEscapedLessThanSign, _) => {
+                self.push_text_source(self.char_start - 1, self.char_start);
+                self.reconsume(c, S::ScriptDataEscaped);
+            }
+            (S::ScriptDataDoubleEscapeStart | S::ScriptDataDoubleEscapeEnd, c)
+                if c.is_ascii_alphabetic() =>
+            {
+                self.temporary_buffer.push(c.to_ascii_lowercase());
+                self.push_current_char();
+            }
+            (S::ScriptDataDoubleEscapeStart, '\t' | '\n' | '\x0C' | '\r' | ' ' | '/' | '>') => {
+                self.state = if self.temporary_buffer == "script" {
+                    S::ScriptDataDoubleEscaped
+                } else {
+                    S::ScriptDataEscaped
+                };
+                self.push_current_char();
+            }
+            (S::ScriptDataDoubleEscapeStart, _) => self.reconsume(c, S::ScriptDataEscaped),
+            (S::ScriptDataDoubleEscaped, '-') => {
+                self.push_current_char();
+                self.state = S::ScriptDataDoubleEscapedDash;
+            }
+            (S::ScriptDataDoubleEscapedDash | S::ScriptDataDoubleEscapedDashDash, '-') => {
+                self.push_current_char();
+                self.state = S::ScriptDataDoubleEscapedDashDash;
+            }
+            (
+                S::ScriptDataDoubleEscaped
+                | S::ScriptDataDoubleEscapedDash
+                | S::ScriptDataDoubleEscapedDashDash,
+                '<',
+            ) => {
+                self.push_current_char();
+                self.state = S::ScriptDataDoubleEscapedLessThanSign;
+            }
+            (S::ScriptDataDoubleEscapedDashDash, '>') => {
+                self.push_current_char();
+                self.state = S::ScriptData;
+            }
+            (S::ScriptDataDoubleEscapedDash | S::ScriptDataDoubleEscapedDashDash, _) => {
+                self.push_text_char(c);
+                self.state = S::ScriptDataDoubleEscaped;
+            }
+            (S::ScriptDataDoubleEscapedLessThanSign, '/') => {
+                self.temporary_buffer.clear();
+                self.push_current_char();
+                self.state = S::ScriptDataDoubleEscapeEnd;
+            }
+            (S::ScriptDataDoubleEscapedLessThanSign, _) => {
+                self.reconsume(c, S::ScriptDataDoubleEscaped)
+            }
+            (S::ScriptDataDoubleEscapeEnd, '\t' | '\n' | '\x0C' | '\r' | ' ' | '/' | '>') => {
+                self.state = if self.temporary_buffer == "script" {
+                    S::ScriptDataEscaped
+                } else {
+                    S::ScriptDataDoubleEscaped
+                };
+                self.push_current_char();
+            }
+            (S::ScriptDataDoubleEscapeEnd, _) => self.reconsume(c, S::ScriptDataDoubleEscaped),
+            // ScriptData / ScriptDataEscaped / ScriptDataDoubleEscaped の通常の文字
+            _ => self.push_text_char(c),
+        }
     }
 
     fn state_data(&mut self, c: char) {
         match c {
             '<' => {
-                self.commit_token();
+                self.commit_token_at(self.char_start);
                 self.state = TokenizerState::TagOpen
             }
-            '&' => {
-                todo!(); // エスケープ処理 (未実装)
+            '\0' => {
+                // Data 状態の NULL はそのまま出力する
+                self.error(ParseErrorKind::UnexpectedNullCharacter);
+                self.push_current_char();
             }
-            _ => {
-                self.buffer.push(c);
-                if let Some(Token::Text(ref mut text)) = self.current_token {
-                    text.push(c);
-                } else {
-                    self.current_token = Some(Token::Text(c.to_string()));
-                }
+            '&' => match self.consume_character_reference(false) {
+                Some(decoded) => self.push_text(&decoded),
+                None => self.push_text_source(self.char_start, self.pos),
+            },
+            _ => self.push_current_char(),
+        }
+    }
+
+    /// 現在の文字`c`を現在のテキストトークンに追加します（NULLは U+FFFD に置き換える）
+    fn push_text_char(&mut self, c: char) {
+        if c == '\0' {
+            self.error(ParseErrorKind::UnexpectedNullCharacter);
+            self.push_text("\u{FFFD}");
+        } else {
+            self.push_current_char();
+        }
+    }
+
+    /// 現在の文字を入力のまま現在のテキストトークンに追加します
+    fn push_current_char(&mut self) {
+        self.push_text_source(self.char_start, self.pos);
+    }
+
+    /// RCDATA などの中の U+0000 を U+FFFD に置き換えます
+    fn replace_null(&mut self, c: char) -> char {
+        if c == '\0' {
+            self.error(ParseErrorKind::UnexpectedNullCharacter);
+            char::REPLACEMENT_CHARACTER
+        } else {
+            c
+        }
+    }
+
+    /// 入力の`start..end`を現在のテキストトークンに追加します
+    fn push_text_source(&mut self, start: usize, end: usize) {
+        if !matches!(self.current_token, Some(PendingToken::Text(_))) {
+            self.current_token = Some(PendingToken::Text(TextBuffer::Empty));
+        }
+        if let Some(PendingToken::Text(ref mut text)) = self.current_token {
+            text.push_source(&self.input, start, end);
+        }
+    }
+
+    /// 入力と異なる文字列（文字参照を展開したものなど）を現在のテキストトークンに追加します
+    fn push_text(&mut self, s: &str) {
+        if !matches!(self.current_token, Some(PendingToken::Text(_))) {
+            self.current_token = Some(PendingToken::Text(TextBuffer::Empty));
+        }
+        if let Some(PendingToken::Text(ref mut text)) = self.current_token {
+            text.push_str(&self.input, s);
+        }
+    }
+
+    /// 入力の`start..end`を現在のコメントトークンに追加します
+    fn push_comment_source(&mut self, start: usize, end: usize) {
+        if !matches!(self.current_token, Some(PendingToken::Comment(_))) {
+            self.current_token = Some(PendingToken::Comment(TextBuffer::Empty));
+        }
+        if let Some(PendingToken::Comment(ref mut comment)) = self.current_token {
+            comment.push_source(&self.input, start, end);
+        }
+    }
+
+    /// 現在の文字`c`を現在のコメントトークンに追加します（NULLは U+FFFD に置き換える）
+    fn push_comment_char(&mut self, c: char) {
+        if c == '\0' {
+            self.error(ParseErrorKind::UnexpectedNullCharacter);
+            if let Some(PendingToken::Comment(ref mut comment)) = self.current_token {
+                comment.push(&self.input, char::REPLACEMENT_CHARACTER);
             }
+        } else {
+            self.push_comment_source(self.char_start, self.pos);
         }
     }
 
+    /// 文字参照を解釈します（`&` を読んだ直後に呼び出す）
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+    ///
+    /// # 引数
+    /// * `in_attribute` - 属性値の中かどうか（`;` のないレガシーな参照の扱いが変わる）
+    ///
+    /// # 戻り値
+    /// * `&` とそれに続く文字参照を置き換える文字列を返します
+    /// * 文字参照として展開しない場合は`None`を返します。`char_start`から読んだ位置までの
+    ///   入力（`&`のみ、または属性値中の`&copy=`の`&copy`）をそのまま使います
+    fn consume_character_reference(&mut self, in_attribute: bool) -> Option<Cow<'static, str>> {
+        match self.input.as_bytes().get(self.pos) {
+            Some(b'#') => self.consume_numeric_character_reference(),
+            Some(b) if b.is_ascii_alphanumeric() => {
+                self.consume_named_character_reference(in_attribute)
+            }
+            _ => None,
+        }
+    }
+
+    fn consume_named_character_reference(
+        &mut self,
+        in_attribute: bool,
+    ) -> Option<Cow<'static, str>> {
+        let rest = &self.input[self.pos..];
+        let bytes = rest.as_bytes();
+
+        // 名前になりうる範囲（英数字の並びと、続く `;`）から最長一致を探す
+        let mut candidate_len = bytes
+            .iter()
+            .take(LONGEST_NAME_LEN)
+            .take_while(|b| b.is_ascii_alphanumeric())
+            .count();
+        if candidate_len < LONGEST_NAME_LEN && bytes.get(candidate_len) == Some(&b';') {
+            candidate_len += 1;
+        }
+        let Some((len, value)) = (1..=candidate_len)
+            .rev()
+            .find_map(|len| entities::lookup(&rest[..len]).map(|value| (len, value)))
+        else {
+            // 一致しない場合（ambiguous ampersand）は `&` 以降をそのままテキストとして扱う
+            let alphanumeric = bytes
+                .iter()
+                .take_while(|b| b.is_ascii_alphanumeric())
+                .count();
+            if bytes.get(alphanumeric) == Some(&b';') {
+                self.error(ParseErrorKind::UnknownNamedCharacterReference);
+            }
+            return None;
+        };
+
+        let name = &rest[..len];
+        let next = bytes.get(len).copied();
+        self.pos += len;
+        // 属性値中の `;` のない参照は、後ろに `=` や英数字が続く場合は展開しない
+        // （`?a=1&copy=2` のようなURLを壊さないため）
+        if in_attribute
+            && !name.ends_with(';')
+            && next.is_some_and(|b| b == b'=' || b.is_ascii_alphanumeric())
+        {
+            return None;
+        }
+        if !name.ends_with(';') {
+            self.error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+        }
+        Some(value.into())
+    }
+
+    fn consume_numeric_character_reference(&mut self) -> Option<Cow<'static, str>> {
+        // `#` の後ろ
+        let rest = &self.input[self.pos + 1..];
+        let (radix, digits_start) = match rest.as_bytes().first() {
+            Some(b'x' | b'X') => (16, 1),
+            _ => (10, 0),
+        };
+        let digits = rest[digits_start..]
+            .chars()
+            .take_while(|c| c.is_digit(radix))
+            .count();
+        if digits == 0 {
+            // 数字がない場合は `&#` などをそのままテキストとして扱う
+            self.error(ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference);
+            return None;
+        }
+
+        let mut code: u32 = 0;
+        for c in rest[digits_start..digits_start + digits].chars() {
+            // 範囲外になった時点で以降の桁は結果に影響しない
+            code = (code * radix + c.to_digit(radix).unwrap_or(0)).min(0x110000);
+        }
+
+        let mut consumed = 1 + digits_start + digits;
+        if rest.as_bytes().get(digits_start + digits) == Some(&b';') {
+            consumed += 1;
+        } else {
+            self.error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+        }
+        if let Some(error) = numeric_character_reference_error(code) {
+            self.error(error);
+        }
+        self.pos += consumed;
+        Some(numeric_character_reference(code).to_string().into())
+    }
+
     fn state_tag_open(&mut self, c: char) {
         match c {
             '/' => self.state = TokenizerState::EndTagOpen,
             '!' => {
-                if self.input[self.pos..].starts_with('-') {
-                    self.pos += 1;
-                    self.state = TokenizerState::CommentStartDash;
-                } else if self.input[self.pos..].to_lowercase().starts_with("doctype") {
+                if self.input[self.pos..].starts_with("--") {
+                    self.pos += 2;
+                    self.state = TokenizerState::CommentStart;
+                    self.current_token = Some(PendingToken::Comment(TextBuffer::Empty));
+                } else if self
+                    .input
+                    .get(self.pos..self.pos + 7)
+                    .is_some_and(|s| s.eq_ignore_ascii_case("doctype"))
+                {
                     self.pos += 7;
                     self.state = TokenizerState::Doctype;
-                    self.current_token = Some(Token::Doctype {
+                    self.current_token = Some(PendingToken::Doctype {
                         name: None,
                         public_id: None,
                         system_id: None,
                         force_quirks: false,
                     });
+                } else if self.input[self.pos..].starts_with("[CDATA[") && self.cdata_allowed {
+                    self.pos += 7;
+                    self.state = TokenizerState::CdataSection;
                 } else {
+                    // HTMLの中の `<![CDATA[...]]>` は `[CDATA[...]]` というコメントに、
+                    // `<!-x>` は `-x` というコメントになる
+                    if self.input[self.pos..].starts_with("[CDATA[") {
+                        self.error(ParseErrorKind::CdataInHtmlContent);
+                    } else {
+                        self.error(ParseErrorKind::IncorrectlyOpenedComment);
+                    }
                     self.state = TokenizerState::BogusComment;
+                    self.current_token = Some(PendingToken::Comment(TextBuffer::Empty));
                 }
             }
+            '?' => {
+                // `<?xml ...?>` などは `?` から始まるコメントとして扱う
+                self.error(ParseErrorKind::UnexpectedQuestionMarkInsteadOfTagName);
+                self.current_token = Some(PendingToken::Comment(TextBuffer::Empty));
+                self.reconsume(c, TokenizerState::BogusComment);
+            }
             c if c.is_ascii_alphabetic() => {
                 // cがアルファベットの場合
                 self.state = TokenizerState::TagName;
-                self.buffer.push(c);
-                self.current_token = Some(Token::StartTag {
-                    name: c.to_string(),
+                self.current_token = Some(PendingToken::StartTag {
+                    name: self.consume_name(false),
                     attributes: Vec::new(),
                     self_closing: false,
                 });
             }
             _ => {
                 // テキストノードとして処理
-                self.buffer.push('<');
-                self.buffer.push(c);
-                if let Some(Token::Text(ref mut text)) = self.current_token {
-                    text.push('<');
-                    text.push(c);
-                } else {
-                    self.current_token = Some(Token::Text(format!("<{c}")));
-                }
-                self.state = TokenizerState::Data;
+                self.error(ParseErrorKind::InvalidFirstCharacterOfTagName);
+                self.push_text_source(self.char_start - 1, self.char_start);
+                self.reconsume(c, TokenizerState::Data);
             }
         }
     }
 
+    /// CDATAセクションの中身はすべてテキストとして扱います（NULLもそのまま）
+    fn state_cdata_section(&mut self, c: char) {
+        if c == ']' && self.input[self.pos..].starts_with("]>") {
+            // 途中で区切られた場合と範囲が変わらないよう、`]]>`の手前でテキストを区切る
+            self.commit_token_at(self.char_start);
+            self.pos += 2;
+            self.state = TokenizerState::Data;
+        } else {
+            self.push_current_char();
+        }
+    }
+
     fn state_tag_name(&mut self, c: char) {
         match c {
-            c if c.is_whitespace() => self.state = TokenizerState::BeforeAttributeName,
+            c if is_whitespace(c) => self.state = TokenizerState::BeforeAttributeName,
             '/' => self.state = TokenizerState::SelfClosingStartTag,
             '>' => {
                 self.commit_token();
                 self.state = TokenizerState::Data;
             }
-            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':' => {
-                self.buffer.push(c);
-                if let Some(Token::StartTag { ref mut name, .. }) = self.current_token {
-                    name.push(c);
-                } else if let Some(Token::EndTag { ref mut name }) = self.current_token {
-                    name.push(c);
-                }
-            }
             _ => {
-                // 不正な文字
-                // タグの終了として処理
-                self.commit_token();
-                self.state = TokenizerState::Data;
+                // NULL の後、または入力の途中で区切られた名前の続き
+                let c = self.replace_null(c.to_ascii_lowercase());
+                if let Some(
+                    PendingToken::StartTag { ref mut name, .. }
+                    | PendingToken::EndTag { ref mut name },
+                ) = self.current_token
+                {
+                    name.to_mut().push(c);
+                    names::intern_in_place(name);
+                }
             }
         }
     }
 
+    /// DOCTYPE の各状態
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-state
     fn state_doctype(&mut self, c: char) {
-        match c {
-            c if c.is_whitespace() => match self.state {
-                TokenizerState::Doctype => self.state = TokenizerState::DoctypeName,
-                TokenizerState::DoctypeName => {
-                    if self.input[self.pos..].to_lowercase().starts_with("public") {
-                        self.pos += 6;
-                        self.state = TokenizerState::BeforeDoctypePublicId
-                    } else if self.input[self.pos..].to_lowercase().starts_with("system") {
-                        self.pos += 6;
-                        self.state = TokenizerState::BeforeDoctypePublicId
-                    }
-                }
-                TokenizerState::AfterDoctypePublicId => {
-                    self.state = TokenizerState::DoctypeSystemId
-                }
-                _ => {}
-            },
-            '>' => {
-                if self.state == TokenizerState::BogusDoctype {
-                    if let Some(Token::Doctype {
-                        ref mut force_quirks,
-                        ..
-                    }) = self.current_token
-                    {
-                        *force_quirks = true;
-                    }
+        use TokenizerState as S;
+        let whitespace = is_whitespace(c);
+        match (self.state, c) {
+            (S::Doctype, _) if whitespace => self.state = S::BeforeDoctypeName,
+            (S::Doctype, '>') => self.reconsume(c, S::BeforeDoctypeName),
+            (S::Doctype, _) => {
+                self.error(ParseErrorKind::MissingWhitespaceBeforeDoctypeName);
+                self.reconsume(c, S::BeforeDoctypeName);
+            }
+            (S::BeforeDoctypeName | S::AfterDoctypeName | S::AfterDoctypeSystemId, _)
+                if whitespace => {}
+            (S::BeforeDoctypeName, '>') => {
+                self.error(ParseErrorKind::MissingDoctypeName);
+                self.set_force_quirks();
+                self.emit_doctype();
+            }
+            (S::BeforeDoctypeName, _) => {
+                self.push_doctype_name(c);
+                self.state = S::DoctypeName;
+            }
+            (S::DoctypeName, _) if whitespace => self.state = S::AfterDoctypeName,
+            (S::DoctypeName, '>') => self.emit_doctype(),
+            (S::DoctypeName, _) => self.push_doctype_name(c),
+            (S::AfterDoctypeName, '>') => self.emit_doctype(),
+            (S::AfterDoctypeName, _) => {
+                let start = self.pos - c.len_utf8();
+                let keyword = self.input.get(start..start + 6);
+                if keyword.is_some_and(|k| k.eq_ignore_ascii_case("public")) {
+                    self.pos = start + 6;
+                    self.state = S::BeforeDoctypePublicId;
+                } else if keyword.is_some_and(|k| k.eq_ignore_ascii_case("system")) {
+                    self.pos = start + 6;
+                    self.state = S::BeforeDoctypeSystemId;
+                } else {
+                    self.error(ParseErrorKind::InvalidCharacterSequenceAfterDoctypeName);
+                    self.set_force_quirks();
+                    self.state = S::BogusDoctype;
                 }
-                self.commit_token();
-                self.state = TokenizerState::Data;
             }
-            _ => {
-                self.buffer.push(c);
-                match self.state {
-                    TokenizerState::Doctype => {
-                        self.state = TokenizerState::BogusDoctype;
-                    }
-                    TokenizerState::DoctypeName => {
-                        if let Some(Token::Doctype { ref mut name, .. }) = self.current_token {
-                            if name.is_none() {
-                                *name = Some(c.to_string());
-                            } else if let Some(ref mut n) = name {
-                                n.push(c);
-                            }
-                        }
-                    }
-                    TokenizerState::BeforeDoctypePublicId => {
-                        match c {
-                            '"' => self.state = TokenizerState::DoctypePublicIdWithDoubleQuote,
-                            '\'' => self.state = TokenizerState::DoctypePublicIdWithSingleQuote,
-                            _ if c.is_whitespace() => {} // 無視
-                            _ => {
-                                // 不正な文字
-                                self.state = TokenizerState::BogusDoctype;
-                            }
-                        }
-                        if let Some(Token::Doctype {
-                            ref mut public_id, ..
-                        }) = self.current_token
-                        {
-                            *public_id = Some(c.to_string());
-                        }
-                    }
-                    TokenizerState::DoctypePublicIdWithSingleQuote
-                    | TokenizerState::DoctypePublicIdWithDoubleQuote => {
-                        if let Some(Token::Doctype {
-                            ref mut public_id, ..
-                        }) = self.current_token
-                        {
-                            if let Some(ref mut pid) = public_id {
-                                pid.push(c);
-                            }
-                        }
-                        if (self.state == TokenizerState::DoctypePublicIdWithSingleQuote
-                            && c == '\'')
-                            || (self.state == TokenizerState::DoctypePublicIdWithDoubleQuote
-                                && c == '"')
-                        {
-                            self.state = TokenizerState::AfterDoctypePublicId;
-                        }
-                    }
-                    TokenizerState::DoctypeSystemId => {
-                        if let Some(Token::Doctype {
-                            ref mut system_id, ..
-                        }) = self.current_token
-                        {
-                            if system_id.is_none() {
-                                *system_id = Some(c.to_string());
-                            } else if let Some(ref mut sid) = system_id {
-                                sid.push(c);
-                            }
-                        }
-                    }
-                    _ => {}
+            // 公開識別子とシステム識別子の間の空白
+            (S::BeforeDoctypePublicId | S::BeforeDoctypeSystemId | S::AfterDoctypePublicId, _)
+                if whitespace => {}
+            (S::BeforeDoctypePublicId, '"' | '\'') => {
+                self.set_doctype_id(false, TextBuffer::Empty);
+                self.state = if c == '"' {
+                    S::DoctypePublicIdWithDoubleQuote
+                } else {
+                    S::DoctypePublicIdWithSingleQuote
+                };
+            }
+            (S::BeforeDoctypeSystemId | S::AfterDoctypePublicId, '"' | '\'') => {
+                self.set_doctype_id(true, TextBuffer::Empty);
+                self.state = if c == '"' {
+                    S::DoctypeSystemIdWithDoubleQuote
+                } else {
+                    S::DoctypeSystemIdWithSingleQuote
+                };
+            }
+            (S::AfterDoctypePublicId | S::AfterDoctypeSystemId, '>') => self.emit_doctype(),
+            (S::AfterDoctypeSystemId, _) => {
+                self.error(ParseErrorKind::UnexpectedCharacterAfterDoctypeSystemIdentifier);
+                self.state = S::BogusDoctype;
+            }
+            (S::DoctypePublicIdWithDoubleQuote, '"')
+            | (S::DoctypePublicIdWithSingleQuote, '\'') => self.state = S::AfterDoctypePublicId,
+            (S::DoctypeSystemIdWithDoubleQuote, '"')
+            | (S::DoctypeSystemIdWithSingleQuote, '\'') => self.state = S::AfterDoctypeSystemId,
+            (S::BeforeDoctypePublicId | S::BeforeDoctypeSystemId, '>') => {
+                // 識別子がない
+                self.error(if self.state == S::BeforeDoctypePublicId {
+                    ParseErrorKind::MissingDoctypePublicIdentifier
+                } else {
+                    ParseErrorKind::MissingDoctypeSystemIdentifier
+                });
+                self.set_force_quirks();
+                self.emit_doctype();
+            }
+            (
+                S::DoctypePublicIdWithDoubleQuote
+                | S::DoctypePublicIdWithSingleQuote
+                | S::DoctypeSystemIdWithDoubleQuote
+                | S::DoctypeSystemIdWithSingleQuote,
+                '>',
+            ) => {
+                // 識別子が閉じられていない
+                self.error(if self.state.is_doctype_public_id() {
+                    ParseErrorKind::AbruptDoctypePublicIdentifier
+                } else {
+                    ParseErrorKind::AbruptDoctypeSystemIdentifier
+                });
+                self.set_force_quirks();
+                self.emit_doctype();
+            }
+            (S::DoctypePublicIdWithDoubleQuote | S::DoctypePublicIdWithSingleQuote, _) => {
+                self.push_doctype_id(false, c)
+            }
+            (S::DoctypeSystemIdWithDoubleQuote | S::DoctypeSystemIdWithSingleQuote, _) => {
+                self.push_doctype_id(true, c)
+            }
+            (S::BeforeDoctypePublicId | S::BeforeDoctypeSystemId | S::AfterDoctypePublicId, _) => {
+                self.error(if self.state == S::BeforeDoctypePublicId {
+                    ParseErrorKind::MissingQuoteBeforeDoctypePublicIdentifier
+                } else {
+                    ParseErrorKind::MissingQuoteBeforeDoctypeSystemIdentifier
+                });
+                self.set_force_quirks();
+                self.state = S::BogusDoctype;
+            }
+            (S::BogusDoctype, '>') => self.emit_doctype(),
+            _ => {}
+        }
+    }
+
+    fn emit_doctype(&mut self) {
+        self.commit_token();
+        self.state = TokenizerState::Data;
+    }
+
+    /// 現在の文字`c`を DOCTYPE の名前に追加します（小文字にし、NULLは U+FFFD に置き換える）
+    fn push_doctype_name(&mut self, c: char) {
+        let decoded = self.replace_null(c.to_ascii_lowercase());
+        if let Some(PendingToken::Doctype { ref mut name, .. }) = self.current_token {
+            let name = name.get_or_insert_with(TextBuffer::default);
+            if decoded == c {
+                name.push_source(&self.input, self.char_start, self.pos);
+            } else {
+                name.push(&self.input, decoded);
+            }
+        }
+    }
+
+    /// 現在の文字`c`を公開識別子（`system`が`false`）またはシステム識別子に追加します
+    fn push_doctype_id(&mut self, system: bool, c: char) {
+        let decoded = self.replace_null(c);
+        if let Some(PendingToken::Doctype {
+            ref mut public_id,
+            ref mut system_id,
+            ..
+        }) = self.current_token
+        {
+            let id = if system { system_id } else { public_id };
+            if let Some(id) = id {
+                if decoded == c {
+                    id.push_source(&self.input, self.char_start, self.pos);
+                } else {
+                    id.push(&self.input, decoded);
                 }
             }
         }
     }
 
+    /// 公開識別子（`system`が`false`）またはシステム識別子を設定します
+    fn set_doctype_id(&mut self, system: bool, value: TextBuffer) {
+        if let Some(PendingToken::Doctype {
+            ref mut public_id,
+            ref mut system_id,
+            ..
+        }) = self.current_token
+        {
+            if system {
+                *system_id = Some(value);
+            } else {
+                *public_id = Some(value);
+            }
+        }
+    }
+
+    fn set_force_quirks(&mut self) {
+        if let Some(PendingToken::Doctype {
+            ref mut force_quirks,
+            ..
+        }) = self.current_token
+        {
+            *force_quirks = true;
+        }
+    }
+
     fn state_before_attribute_name(&mut self, c: char) {
         match c {
-            c if c.is_whitespace() => {} // 無視
+            c if is_whitespace(c) => {} // 無視
             '/' => self.state = TokenizerState::SelfClosingStartTag,
             '>' => {
                 self.commit_token();
                 self.state = TokenizerState::Data;
             }
-            c if c.is_ascii_alphanumeric() => {
+            '=' => {
+                // `=` から始まる名前として扱う
+                self.error(ParseErrorKind::UnexpectedEqualsSignBeforeAttributeName);
                 self.state = TokenizerState::AttributeName;
-                self.buffer.push(c);
-                self.current_attribute = Some(Attribute {
-                    name: c.to_string(),
-                    value: String::new(),
+                self.current_attribute = Some(PendingAttribute {
+                    name: "=".into(),
+                    value: TextBuffer::Empty,
                 });
             }
-            _ => { // 不正な文字
-                 // 壊れたトークンは無視
-            }
+            _ => self.start_attribute(),
         }
     }
 
+    /// 現在の文字から始まる属性を始めます
+    ///
+    /// 名前をまとめて読めなかった文字（`"`や NULL など）は属性名の状態で読み直します。
+    fn start_attribute(&mut self) {
+        self.state = TokenizerState::AttributeName;
+        self.current_attribute = Some(PendingAttribute {
+            name: self.consume_name(true),
+            value: TextBuffer::Empty,
+        });
+    }
+
     fn state_attribute_name(&mut self, c: char) {
         match c {
-            c if c.is_whitespace() => self.state = TokenizerState::AfterAttributeName,
+            c if is_whitespace(c) => self.state = TokenizerState::AfterAttributeName,
             '=' => self.state = TokenizerState::BeforeAttributeValue,
             '/' => {
-                if let Some(attr) = self.current_attribute.take() {
-                    if let Some(Token::StartTag {
-                        ref mut attributes, ..
-                    }) = self.current_token
-                    {
-                        attributes.push(attr);
-                    }
-                }
+                self.finish_attribute();
                 self.state = TokenizerState::SelfClosingStartTag;
             }
             '>' => {
-                if let Some(attr) = self.current_attribute.take() {
-                    if let Some(Token::StartTag {
-                        ref mut attributes, ..
-                    }) = self.current_token
-                    {
-                        attributes.push(attr);
-                    }
-                }
+                self.finish_attribute();
                 self.commit_token();
                 self.state = TokenizerState::Data;
             }
-            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':' => {
-                self.buffer.push(c);
-                if let Some(ref mut attr) = self.current_attribute {
-                    attr.name.push(c);
+            '"' | '\'' | '<' => {
+                // エラーとして報告するが、名前の一部として扱う
+                self.error(ParseErrorKind::UnexpectedCharacterInAttributeName);
+                self.push_attribute_name(c);
+            }
+            _ => {
+                // NULL の後、または入力の途中で区切られた名前の続き
+                let c = self.replace_null(c.to_ascii_lowercase());
+                self.push_attribute_name(c);
+            }
+        }
+    }
+
+    fn push_attribute_name(&mut self, c: char) {
+        if let Some(ref mut attr) = self.current_attribute {
+            attr.name.to_mut().push(c);
+            names::intern_in_place(&mut attr.name);
+        }
+    }
+
+    /// 読み終えた属性を現在のタグに追加します
+    ///
+    /// 同じ名前の属性が既にある場合は最初のものを残します。終了タグの属性は捨てます。
+    fn finish_attribute(&mut self) {
+        let Some(attr) = self.current_attribute.take() else {
+            return;
+        };
+        let error = match self.current_token {
+            Some(PendingToken::StartTag {
+                ref mut attributes, ..
+            }) => {
+                if attributes.iter().any(|a| a.name == attr.name) {
+                    Some(ParseErrorKind::DuplicateAttribute)
+                } else {
+                    attributes.push(attr);
+                    None
                 }
             }
-            _ => { // 不正な文字
-                 // 壊れたトークンは無視
+            // 同じ終了タグについては1度だけ報告する
+            Some(PendingToken::EndTag { .. })
+                if !self.errors.last().is_some_and(|e| {
+                    e.kind == ParseErrorKind::EndTagWithAttributes
+                        && e.position.offset >= self.span_start
+                }) =>
+            {
+                Some(ParseErrorKind::EndTagWithAttributes)
             }
+            _ => None,
+        };
+        if let Some(error) = error {
+            self.error(error);
         }
     }
 
     fn state_before_attribute_value(&mut self, c: char) {
         match c {
-            c if c.is_whitespace() => {} // 無視
+            c if is_whitespace(c) => {} // 無視
             '"' => self.state = TokenizerState::AttributeValueDoubleQuoted,
             '\'' => self.state = TokenizerState::AttributeValueSingleQuoted,
             '>' => {
                 // 属性値がない場合は空文字列として扱う
-                if let Some(attr) = self.current_attribute.take() {
-                    if let Some(Token::StartTag {
-                        ref mut attributes, ..
-                    }) = self.current_token
-                    {
-                        attributes.push(attr);
-                    }
-                }
+                self.error(ParseErrorKind::MissingAttributeValue);
+                self.finish_attribute();
                 self.commit_token();
                 self.state = TokenizerState::Data;
             }
             _ => {
                 // 属性値が引用符で囲まれていない場合
                 self.state = TokenizerState::AttributeValueUnquoted;
-                if let Some(ref mut attr) = self.current_attribute {
-                    attr.value.push(c);
-                }
+                self.state_attribute_value_unquoted(c);
             }
         }
     }
@@ -438,85 +1644,72 @@ impl<'a> Tokenizer<'a> {
     fn state_attribute_value_quoted(&mut self, c: char) {
         match c {
             '"' if self.state == TokenizerState::AttributeValueDoubleQuoted => {
-                if let Some(attr) = self.current_attribute.take() {
-                    if let Some(Token::StartTag {
-                        ref mut attributes, ..
-                    }) = self.current_token
-                    {
-                        attributes.push(attr);
-                    }
-                }
+                self.finish_attribute();
                 self.state = TokenizerState::AfterAttributeName;
             }
             '\'' if self.state == TokenizerState::AttributeValueSingleQuoted => {
-                if let Some(attr) = self.current_attribute.take() {
-                    if let Some(Token::StartTag {
-                        ref mut attributes, ..
-                    }) = self.current_token
-                    {
-                        attributes.push(attr);
-                    }
-                }
+                self.finish_attribute();
                 self.state = TokenizerState::AfterAttributeName;
             }
+            '&' => self.push_attribute_character_reference(),
             _ => {
                 if let Some(ref mut attr) = self.current_attribute {
-                    attr.value.push(c);
+                    attr.value
+                        .push_source(&self.input, self.char_start, self.pos);
                 }
             }
         }
     }
 
+    /// 属性値の中の文字参照を読み、現在の属性の値に追加します
+    fn push_attribute_character_reference(&mut self) {
+        let decoded = self.consume_character_reference(true);
+        if let Some(ref mut attr) = self.current_attribute {
+            match decoded {
+                Some(decoded) => attr.value.push_str(&self.input, &decoded),
+                None => attr
+                    .value
+                    .push_source(&self.input, self.char_start, self.pos),
+            }
+        }
+    }
+
     fn state_after_attribute_name(&mut self, c: char) {
         match c {
-            c if c.is_whitespace() => {} // 無視
-            '/' => self.state = TokenizerState::SelfClosingStartTag,
+            c if is_whitespace(c) => {} // 無視
+            '=' => self.state = TokenizerState::BeforeAttributeValue,
+            '/' => {
+                self.finish_attribute();
+                self.state = TokenizerState::SelfClosingStartTag;
+            }
             '>' => {
+                self.finish_attribute();
                 self.commit_token();
                 self.state = TokenizerState::Data;
             }
-            c if c.is_ascii_alphanumeric() => {
-                self.state = TokenizerState::AttributeName;
-                self.buffer.push(c);
-                self.current_attribute = Some(Attribute {
-                    name: c.to_string(),
-                    value: String::new(),
-                });
-            }
-            _ => { // 不正な文字
-                 // 壊れたトークンは無視
+            _ => {
+                self.finish_attribute();
+                self.start_attribute();
             }
         }
     }
 
     fn state_attribute_value_unquoted(&mut self, c: char) {
         match c {
-            c if c.is_whitespace() => {
-                if let Some(attr) = self.current_attribute.take() {
-                    if let Some(Token::StartTag {
-                        ref mut attributes, ..
-                    }) = self.current_token
-                    {
-                        attributes.push(attr);
-                    }
-                }
+            c if is_whitespace(c) => {
+                self.finish_attribute();
                 self.state = TokenizerState::BeforeAttributeName;
             }
             '>' => {
-                if let Some(attr) = self.current_attribute.take() {
-                    if let Some(Token::StartTag {
-                        ref mut attributes, ..
-                    }) = self.current_token
-                    {
-                        attributes.push(attr);
-                    }
-                }
+                self.finish_attribute();
                 self.commit_token();
                 self.state = TokenizerState::Data;
             }
+            '&' => self.push_attribute_character_reference(),
             _ => {
                 if let Some(ref mut attr) = self.current_attribute {
-                    attr.value.push(c);
+                    attr.value
+                        .push_source(&self.input, self.char_start, self.pos);
                 }
             }
         }
@@ -525,19 +1718,23 @@ impl<'a> Tokenizer<'a> {
     fn state_self_closing_start_tag(&mut self, c: char) {
         match c {
             '>' => {
-                if let Some(Token::StartTag {
-                    ref mut self_closing,
-                    ..
-                }) = self.current_token
-                {
-                    *self_closing = true;
+                match self.current_token {
+                    Some(PendingToken::StartTag {
+                        ref mut self_closing,
+                        ..
+                    }) => *self_closing = true,
+                    Some(PendingToken::EndTag { .. }) => {
+                        self.error(ParseErrorKind::EndTagWithTrailingSolidus)
+                    }
+                    _ => {}
                 }
                 self.commit_token();
                 self.state = TokenizerState::Data;
             }
             _ => {
-                // 壊れたトークンは無視
-                self.state = TokenizerState::Data;
+                // `/` の後に `>` 以外が続く場合は `/` を無視する
+                self.error(ParseErrorKind::UnexpectedSolidusInTag);
+                self.reconsume(c, TokenizerState::BeforeAttributeName);
             }
         }
     }
@@ -546,73 +1743,187 @@ impl<'a> Tokenizer<'a> {
         match c {
             c if c.is_ascii_alphabetic() => {
                 self.state = TokenizerState::TagName;
-                self.buffer.push(c);
-                self.current_token = Some(Token::EndTag {
-                    name: c.to_string(),
+                self.current_token = Some(PendingToken::EndTag {
+                    name: self.consume_name(false),
                 });
             }
             '>' => {
-                // 壊れたトークンは無視
+                // `</>` は無視
+                self.error(ParseErrorKind::MissingEndTagName);
+                self.discard_token();
                 self.state = TokenizerState::Data;
             }
             _ => {
-                // 壊れたトークンは無視
-                self.state = TokenizerState::Data;
+                // `</` に続く不正な文字はコメントとして扱う
+                self.error(ParseErrorKind::InvalidFirstCharacterOfTagName);
+                self.current_token = Some(PendingToken::Comment(TextBuffer::Empty));
+                self.reconsume(c, TokenizerState::BogusComment);
             }
         }
     }
 
+    /// コメントの各状態
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-start-state
+    ///
+    /// 読み飛ばした `-` や `--!` は、コメントの一部と分かった時点で入力上の範囲として追加します。
     fn state_comment(&mut self, c: char) {
-        match self.state {
-            TokenizerState::CommentStartDash => {
-                if c == '-' {
-                    self.state = TokenizerState::Comment;
-                    self.current_token = Some(Token::Comment(String::new()));
-                } else {
-                    self.state = TokenizerState::BogusComment;
-                }
+        use TokenizerState as S;
+        match (self.state, c) {
+            (S::CommentStart, '-') => self.state = S::CommentStartDash,
+            (S::CommentStart | S::CommentStartDash, '>') => {
+                // `<!-->` / `<!--->` は空のコメント
+                self.error(ParseErrorKind::AbruptClosingOfEmptyComment);
+                self.emit_comment();
             }
-            TokenizerState::Comment => {
-                if c == '-' {
-                    self.state = TokenizerState::CommentEndDash;
-                } else if let Some(Token::Comment(ref mut comment)) = self.current_token {
-                    comment.push(c);
-                }
+            (S::CommentStart, _) => self.reconsume(c, S::Comment),
+            (S::CommentStartDash, '-') => self.state = S::CommentEnd,
+            (S::CommentStartDash, _) => {
+                self.push_comment_source(self.char_start - 1, self.char_start);
+                self.reconsume(c, S::Comment);
             }
-            TokenizerState::CommentEndDash => {
-                if c == '-' {
-                    // コメント終了
-                    self.state = TokenizerState::CommentEnd;
-                } else {
-                    self.state = TokenizerState::Comment;
-                    self.buffer.push('-');
-                    self.buffer.push(c);
-                    if let Some(Token::Comment(ref mut comment)) = self.current_token {
-                        comment.push('-');
-                        comment.push(c);
-                    } else {
-                        self.current_token = Some(Token::Comment(format!("-{c}")));
-                    }
-                }
+            (S::Comment, '<') => {
+                self.push_comment_char(c);
+                self.state = S::CommentLessThanSign;
             }
-            TokenizerState::CommentEnd => {
-                if c == '>' {
-                    // コメント終了
-                    self.commit_token();
-                    self.state = TokenizerState::Data;
-                } else {
-                    self.state = TokenizerState::Comment;
-                    self.buffer.push_str("--");
-                    self.buffer.push(c);
-                    if let Some(Token::Comment(ref mut comment)) = self.current_token {
-                        comment.push_str("--");
-                        comment.push(c);
-                    } else {
-                        self.current_token = Some(Token::Comment(format!("--{c}")));
-                    }
+            (S::Comment, '-') => self.state = S::CommentEndDash,
+            (S::Comment, _) => self.push_comment_char(c),
+            (S::CommentLessThanSign, '!') => {
+                self.push_comment_char(c);
+                self.state = S::CommentLessThanSignBang;
+            }
+            (S::CommentLessThanSign, '<') => self.push_comment_char(c),
+            (S::CommentLessThanSignBang, '-') => self.state = S::CommentLessThanSignBangDash,
+            (S::CommentLessThanSignBangDash, '-') => {
+                self.state = S::CommentLessThanSignBangDashDash
+            }
+            (S::CommentLessThanSignBangDash, _) => self.reconsume(c, S::CommentEndDash),
+            (S::CommentLessThanSignBangDashDash, _) => {
+                // コメントの中の `<!--`
+                if c != '>' {
+                    self.error(ParseErrorKind::NestedComment);
                 }
+                self.reconsume(c, S::CommentEnd);
+            }
+            (S::CommentLessThanSign | S::CommentLessThanSignBang, _) => {
+                self.reconsume(c, S::Comment)
+            }
+            (S::CommentEndDash, '-') => self.state = S::CommentEnd,
+            (S::CommentEndDash, _) => {
+                self.push_comment_source(self.char_start - 1, self.char_start);
+                self.reconsume(c, S::Comment);
+            }
+            (S::CommentEnd, '>') => self.emit_comment(),
+            (S::CommentEnd, '!') => self.state = S::CommentEndBang,
+            // `--` の後の `-` は1つ目の `-` をコメントに追加する
+            (S::CommentEnd, '-') => {
+                self.push_comment_source(self.char_start - 2, self.char_start - 1)
+            }
+            (S::CommentEnd, _) => {
+                self.push_comment_source(self.char_start - 2, self.char_start);
+                self.reconsume(c, S::Comment);
+            }
+            (S::CommentEndBang, '-') => {
+                self.push_comment_source(self.char_start - 3, self.char_start);
+                self.state = S::CommentEndDash;
+            }
+            (S::CommentEndBang, '>') => {
+                // `--!>` でもコメントは終わる
+                self.error(ParseErrorKind::IncorrectlyClosedComment);
+                self.emit_comment();
+            }
+            (S::CommentEndBang, _) => {
+                self.push_comment_source(self.char_start - 3, self.char_start);
+                self.reconsume(c, S::Comment);
             }
+            (S::BogusComment, '>') => self.emit_comment(),
+            (S::BogusComment, _) => self.push_comment_char(c),
             _ => {}
         }
     }
+
+    fn emit_comment(&mut self) {
+        self.commit_token();
+        self.state = TokenizerState::Data;
+    }
+}
+
+/// HTML の空白文字（ASCII whitespace）かどうか
+fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')
+}
+
+/// `&`の後の`rest`が、文字参照の終わりまで届いていないかどうか
+fn is_incomplete_character_reference(rest: &str) -> bool {
+    let bytes = rest.as_bytes();
+    let (start, is_reference_char): (usize, fn(&u8) -> bool) = match bytes {
+        [] | [b'#'] => return true,
+        [b'#', b'x' | b'X', ..] => (2, u8::is_ascii_hexdigit),
+        [b'#', ..] => (1, u8::is_ascii_digit),
+        _ => (0, u8::is_ascii_alphanumeric),
+    };
+    let len = bytes[start..]
+        .iter()
+        .take_while(|b| is_reference_char(b))
+        .count();
+    // 名前は最長の名前の長さまで見れば足りる
+    start + len == bytes.len() && (start > 0 || len < LONGEST_NAME_LEN)
+}
+
+/// 数値文字参照のコードポイントに対するパースエラーを返します
+fn numeric_character_reference_error(code: u32) -> Option<ParseErrorKind> {
+    match code {
+        0 => Some(ParseErrorKind::NullCharacterReference),
+        0x110000.. => Some(ParseErrorKind::CharacterReferenceOutsideUnicodeRange),
+        0xD800..=0xDFFF => Some(ParseErrorKind::SurrogateCharacterReference),
+        0xFDD0..=0xFDEF => Some(ParseErrorKind::NoncharacterCharacterReference),
+        c if c & 0xFFFE == 0xFFFE => Some(ParseErrorKind::NoncharacterCharacterReference),
+        0x0D | 0x7F..=0x9F => Some(ParseErrorKind::ControlCharacterReference),
+        c if c < 0x20 && !matches!(c, 0x09 | 0x0A | 0x0C) => {
+            Some(ParseErrorKind::ControlCharacterReference)
+        }
+        _ => None,
+    }
+}
+
+/// 数値文字参照のコードポイントを、仕様の置き換え規則に従って文字に変換します
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+fn numeric_character_reference(code: u32) -> char {
+    // 0x80〜0x9F は Windows-1252 として解釈する
+    let windows_1252 = match code {
+        0x80 => 0x20AC,
+        0x82 => 0x201A,
+        0x83 => 0x0192,
+        0x84 => 0x201E,
+        0x85 => 0x2026,
+        0x86 => 0x2020,
+        0x87 => 0x2021,
+        0x88 => 0x02C6,
+        0x89 => 0x2030,
+        0x8A => 0x0160,
+        0x8B => 0x2039,
+        0x8C => 0x0152,
+        0x8E => 0x017D,
+        0x91 => 0x2018,
+        0x92 => 0x2019,
+        0x93 => 0x201C,
+        0x94 => 0x201D,
+        0x95 => 0x2022,
+        0x96 => 0x2013,
+        0x97 => 0x2014,
+        0x98 => 0x02DC,
+        0x99 => 0x2122,
+        0x9A => 0x0161,
+        0x9B => 0x203A,
+        0x9C => 0x0153,
+        0x9E => 0x017E,
+        0x9F => 0x0178,
+        _ => code,
+    };
+    // NULL、範囲外、サロゲートは U+FFFD に置き換える（char::from_u32 は後者2つで None を返す）
+    match windows_1252 {
+        0 => char::REPLACEMENT_CHARACTER,
+        c => char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER),
+    }
 }
@@ -1,6 +1,7 @@
 // engine::renderer - HTML/CSSレイアウト結果から描画命令を生成する論理描画層
 
-use crate::engine::html::parser::{NodeRef, NodeType};
+use crate::engine::css::selector::SelectorList;
+use crate::engine::html::parser::{Node, NodeRef, NodeType};
 
 /// 描画命令を表す列挙型
 #[derive(Debug, Clone)]
@@ -59,10 +60,20 @@ impl Color {
     }
 }
 
+const FONT_SIZE: f32 = 16.0;
+const LINE_HEIGHT: f32 = 20.0;
+
+/// 簡易的なテキスト幅計算（実際にはフォントメトリクスが必要）
+fn text_width(text: &str) -> f32 {
+    text.len() as f32 * 8.0
+}
+
 /// レンダラー構造体
 pub struct Renderer {
     viewport_width: f32,
     viewport_height: f32,
+    /// 中身ごと描画しない要素のセレクター（広告ブロックの要素隠しルールなど）
+    hidden_elements: Vec<SelectorList>,
 }
 
 impl Renderer {
@@ -71,6 +82,7 @@ impl Renderer {
         Self {
             viewport_width,
             viewport_height,
+            hidden_elements: Vec::new(),
         }
     }
 
@@ -80,68 +92,108 @@ impl Renderer {
         self.viewport_height = height;
     }
 
+    /// 一致する要素を中身ごと描画しないセレクターを設定します（`display: none`と同じ扱い）
+    ///
+    /// `ContentBlocker::cosmetic_selectors`の結果をそのまま渡せます。解析できないセレクターは、
+    /// スタイルシートの規則と同じく、そのセレクターだけを無視します。
+    pub fn set_hidden_elements(&mut self, selectors: &[String]) {
+        self.hidden_elements = selectors
+            .iter()
+            .filter_map(|selector| match SelectorList::parse(selector) {
+                Ok(list) => Some(list),
+                Err(e) => {
+                    log::warn!("ignoring hidden element selector {selector:?}: {e}");
+                    None
+                }
+            })
+            .collect();
+    }
+
     /// DOM Treeから描画命令を生成
     pub fn generate_draw_commands(&self, dom_root: &NodeRef) -> Vec<DrawCommand> {
         let mut commands = Vec::new();
-        let mut current_x = 10.0;
-        let mut current_y = 10.0;
 
-        self.traverse_and_generate(dom_root, &mut commands, &mut current_x, &mut current_y);
+        self.layout_text(dom_root, &mut |_, x, y, text| {
+            commands.push(DrawCommand::DrawText {
+                x,
+                y,
+                text: text.to_string(),
+                font_size: FONT_SIZE,
+                color: Color::BLACK,
+            });
+        });
 
         commands
     }
 
-    /// DOMツリーを走査して描画命令を生成（再帰的）
-    fn traverse_and_generate(
+    /// ビューポート上の位置にあるテキストを含む要素を返す（イベントの対象を決めるのに使う）
+    pub fn hit_test(&self, dom_root: &NodeRef, x: f32, y: f32) -> Option<NodeRef> {
+        let mut hit = None;
+
+        self.layout_text(dom_root, &mut |node, text_x, text_y, text| {
+            let width = text_width(text);
+            if (text_x..text_x + width).contains(&x) && (text_y..text_y + LINE_HEIGHT).contains(&y)
+            {
+                hit = node.borrow().parent();
+            }
+        });
+
+        hit
+    }
+
+    /// テキストを配置し、テキストノードごとに位置を`visit`に渡す
+    fn layout_text(&self, dom_root: &NodeRef, visit: &mut dyn FnMut(&NodeRef, f32, f32, &str)) {
+        let mut current_x = 10.0;
+        let mut current_y = 10.0;
+
+        self.traverse_and_layout(dom_root, visit, &mut current_x, &mut current_y);
+    }
+
+    /// DOMツリーを走査してテキストを配置（再帰的）
+    fn traverse_and_layout(
         &self,
         node: &NodeRef,
-        commands: &mut Vec<DrawCommand>,
+        visit: &mut dyn FnMut(&NodeRef, f32, f32, &str),
         current_x: &mut f32,
         current_y: &mut f32,
     ) {
         let node_borrow = node.borrow();
 
         match &node_borrow.node_type {
-            NodeType::Document => {
+            NodeType::Document | NodeType::DocumentFragment => {
                 // ドキュメントノードは子要素を処理
                 for child in &node_borrow.children {
-                    self.traverse_and_generate(child, commands, current_x, current_y);
+                    self.traverse_and_layout(child, visit, current_x, current_y);
                 }
             }
+            NodeType::Element { .. } if self.is_hidden(&node_borrow) => {
+                // 隠す要素は子孫も含めて配置しない
+            }
             NodeType::Element { tag_name, .. } => {
                 // 要素ノードの処理
-                let line_height = 20.0;
-
                 // ブロック要素の場合は改行
                 if self.is_block_element(tag_name) {
                     *current_x = 10.0;
-                    *current_y += line_height;
+                    *current_y += LINE_HEIGHT;
                 }
 
                 // 子要素を処理
                 for child in &node_borrow.children {
-                    self.traverse_and_generate(child, commands, current_x, current_y);
+                    self.traverse_and_layout(child, visit, current_x, current_y);
                 }
 
                 // ブロック要素の後は改行
                 if self.is_block_element(tag_name) {
                     *current_x = 10.0;
-                    *current_y += line_height / 2.0;
+                    *current_y += LINE_HEIGHT / 2.0;
                 }
             }
             NodeType::Text(text) => {
                 // テキストノードの処理
                 if !text.trim().is_empty() {
-                    commands.push(DrawCommand::DrawText {
-                        x: *current_x,
-                        y: *current_y,
-                        text: text.clone(),
-                        font_size: 16.0,
-                        color: Color::BLACK,
-                    });
-
-                    // 簡易的なテキスト幅計算（実際にはフォントメトリクスが必要）
-                    *current_x += text.len() as f32 * 8.0;
+                    visit(node, *current_x, *current_y, text);
+
+                    *current_x += text_width(text);
                 }
             }
             NodeType::Comment(_) => {
@@ -153,6 +205,10 @@ impl Renderer {
         }
     }
 
+    fn is_hidden(&self, node: &Node) -> bool {
+        self.hidden_elements.iter().any(|list| list.matches(node))
+    }
+
     /// ブロック要素かどうかを判定
     fn is_block_element(&self, tag_name: &str) -> bool {
         matches!(
@@ -41,8 +41,9 @@ async fn main() -> Result<()> {
     log::info!("Generated {} draw commands", draw_commands.len());
 
     // ウィンドウとイベントループを作成
-    let event_loop = EventLoop::<orinium_browser::platform::ui::State>::with_user_event().build()?;
-    let mut app = App::new();
+    let event_loop =
+        EventLoop::<orinium_browser::platform::ui::State>::with_user_event().build()?;
+    let mut app = App::with_document(dom_tree);
 
     event_loop.run_app(&mut app)?;
 
@@ -4,6 +4,8 @@ use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 use url::Url;
 
+use super::cors::Origin;
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct CachedResponse {
@@ -13,9 +15,33 @@ pub struct CachedResponse {
     pub expires_at: Option<SystemTime>,
 }
 
+/// キャッシュのエントリを区別するキー
+///
+/// 同じURLでも、リクエスト元のオリジンや Cookie を送ったかどうかでレスポンスの内容や
+/// CORS ヘッダーが変わるため、これらが異なるリクエストの間ではエントリを共有しません。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// リクエストのURL
+    pub url: String,
+    /// リクエスト元のオリジン（シリアライズしたもの。オリジンのないリクエストでは`None`）
+    pub origin: Option<String>,
+    /// クレデンシャル（Cookie）を送信したかどうか
+    pub credentials: bool,
+}
+
+impl CacheKey {
+    pub fn new(url: &Url, origin: Option<&Origin>, credentials: bool) -> Self {
+        Self {
+            url: url.as_str().to_string(),
+            origin: origin.map(Origin::serialize),
+            credentials,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Cache {
-    store: Arc<RwLock<HashMap<String, CachedResponse>>>,
+    store: Arc<RwLock<HashMap<CacheKey, CachedResponse>>>,
 }
 
 #[allow(dead_code)]
@@ -32,9 +58,8 @@ impl Cache {
         }
     }
 
-    pub async fn get(&self, url: &Url) -> Option<CachedResponse> {
+    pub async fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
         let store = self.store.read().await;
-        let key = url.as_str();
         if let Some(entry) = store.get(key) {
             if let Some(exp) = entry.expires_at {
                 if SystemTime::now() > exp {
@@ -46,9 +71,14 @@ impl Cache {
         None
     }
 
-    pub async fn set(&self, url: &Url, body: Vec<u8>, headers: Vec<(String, String)>) {
+    /// 有効期限を無視してキャッシュを取得します（オフライン時用）
+    pub async fn get_stale(&self, key: &CacheKey) -> Option<CachedResponse> {
+        let store = self.store.read().await;
+        store.get(key).cloned()
+    }
+
+    pub async fn set(&self, key: CacheKey, body: Vec<u8>, headers: Vec<(String, String)>) {
         let mut store = self.store.write().await;
-        let key = url.as_str().to_string();
         let mut expires = None;
         if let Some((_, cc)) = headers
             .iter()
@@ -1,3 +1,5 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::time::Duration;
 
 /// ネットワーク層全体の設定
@@ -31,6 +33,119 @@ pub struct NetworkConfig {
 
     /// WebSocketを有効化するか
     pub enable_websocket: bool,
+
+    /// リクエスト/レスポンスをネットワークログに記録するか
+    pub enable_network_log: bool,
+
+    /// 一時的な通信エラー時の再試行ポリシー
+    pub retry_policy: RetryPolicy,
+
+    /// オフラインモード（キャッシュからのみ応答し、ネットワークには接続しない）
+    pub offline: bool,
+}
+
+/// 再試行の対象とする失敗の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryCondition {
+    /// 接続の確立に失敗した（接続拒否、到達不能など）
+    ConnectFailed,
+    /// 接続の確立がタイムアウトした
+    ConnectTimeout,
+    /// 送受信中に接続がリセット・切断された（閉じられた keep-alive 接続の再利用など）
+    ConnectionReset,
+}
+
+/// 指数バックオフによる再試行ポリシー
+///
+/// 接続の確立に失敗した場合はリクエストがまだ送信されていないため、どのメソッドでも再試行します。
+/// 送信後に接続が切断された場合は、`idempotent_methods`に含まれるメソッドのみ再試行します。
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最大試行回数（最初の試行を含む。1の場合は再試行しない）
+    pub max_attempts: u32,
+    /// 最初の再試行までの待ち時間
+    pub initial_backoff: Duration,
+    /// 待ち時間の上限
+    pub max_backoff: Duration,
+    /// 再試行ごとに待ち時間に掛ける倍率
+    pub backoff_multiplier: f64,
+    /// 待ち時間をランダムに揺らすか（計算した待ち時間の 1/2 〜 1 倍になる）
+    pub jitter: bool,
+    /// 再試行の対象とする失敗の種類
+    pub retry_on: Vec<RetryCondition>,
+    /// 送信後の失敗でも再試行してよいメソッド
+    pub idempotent_methods: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            backoff_multiplier: 2.0,
+            jitter: true,
+            retry_on: vec![
+                RetryCondition::ConnectFailed,
+                RetryCondition::ConnectTimeout,
+                RetryCondition::ConnectionReset,
+            ],
+            idempotent_methods: ["GET", "HEAD", "OPTIONS", "PUT", "DELETE", "TRACE"]
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 再試行を行わないポリシーを返します
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// 指定したメソッドが冪等として扱われるかどうかを返します
+    pub fn is_idempotent(&self, method: &str) -> bool {
+        self.idempotent_methods
+            .iter()
+            .any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    /// 指定した失敗をこのメソッドで再試行してよいかどうかを返します
+    pub fn allows(&self, condition: RetryCondition, method: &str) -> bool {
+        self.retry_on.contains(&condition)
+            && (condition != RetryCondition::ConnectionReset || self.is_idempotent(method))
+    }
+
+    /// `retry`回目（1始まり）の再試行の前に待つ時間を返します
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .max(1.0)
+            .powi(retry.saturating_sub(1) as i32);
+        let delay =
+            (self.initial_backoff.as_secs_f64() * factor).min(self.max_backoff.as_secs_f64());
+        let delay = if self.jitter {
+            delay * (0.5 + 0.5 * random_unit())
+        } else {
+            delay
+        };
+        Duration::from_secs_f64(delay)
+    }
+}
+
+/// `[0, 1)` の疑似乱数を返します（ジッター用。暗号用途には使わないこと）
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
 }
 
 #[allow(dead_code)]
@@ -72,6 +187,9 @@ impl Default for NetworkConfig {
             max_connections: 100,
             follow_redirects: true,
             enable_websocket: true,
+            enable_network_log: false,
+            retry_policy: RetryPolicy::default(),
+            offline: false,
         }
     }
 }
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 use crate::platform::network::tcp::TcpConnection;
@@ -19,6 +20,23 @@ pub enum Connection {
     Tls(TlsConnection),
 }
 
+impl Connection {
+    /// 接続確立時に計測したタイミングを返します
+    ///
+    /// # 戻り値
+    /// * `(名前解決時間, TCP接続時間, TLSハンドシェイク時間)` のタプル
+    ///   （平文接続の場合、TLSハンドシェイク時間は`None`）
+    pub fn connect_timings(&self) -> (Duration, Duration, Option<Duration>) {
+        match self {
+            Connection::Tcp(c) => (c.dns_time, c.connect_time, None),
+            Connection::Tls(c) => {
+                let tcp = c.stream.get_ref().0;
+                (tcp.dns_time, tcp.connect_time, Some(c.handshake_time))
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ConnectionPool {
     pool: Arc<RwLock<HashMap<HostKey, Vec<Connection>>>>,
@@ -0,0 +1,484 @@
+//! フィルタリストに基づくコンテンツブロッカー
+//!
+//! ネットワークルールは「トークン」単位で索引化され、リクエストURLに含まれるトークンに
+//! 対応するルールだけを照合するため、ルール数が多くても高速に判定できます。
+
+use std::collections::{HashMap, HashSet};
+
+use url::Url;
+
+use crate::platform::network::filter_list::{
+    CosmeticFilter, Filter, FilterList, NetworkFilter, PatternPart, StartAnchor,
+};
+use crate::platform::network::middleware::{Middleware, RequestAction};
+use crate::platform::network::network_core::{Request, ResourceType};
+
+/// ほとんどのURLに含まれるため、索引のキーとしては役に立たないトークン
+const COMMON_TOKENS: &[&str] = &["http", "https", "www", "com", "js", "html"];
+
+/// ブロック判定の結果
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockDecision {
+    /// 一致するルールがない
+    Allow,
+    /// 例外ルールに一致したため許可
+    Exception(String),
+    /// ブロックルールに一致
+    Block(String),
+}
+
+impl BlockDecision {
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, BlockDecision::Block(_))
+    }
+}
+
+/// 判定対象のリクエストから事前に計算した情報
+struct RequestContext<'a> {
+    /// 小文字化したURL
+    url_lower: String,
+    /// 元のURL（match-case 用）
+    url: &'a str,
+    /// URL内のホスト名の範囲（バイト位置）
+    host_range: (usize, usize),
+    /// URLに含まれるトークン
+    tokens: Vec<String>,
+    resource_type: ResourceType,
+    /// リクエスト元のホスト（トップレベルのドキュメントの場合は自身のホスト）
+    source_host: String,
+    third_party: bool,
+}
+
+impl<'a> RequestContext<'a> {
+    fn new(url: &'a Url, resource_type: ResourceType, initiator: Option<&Url>) -> Self {
+        let url_str = url.as_str();
+        let url_lower = url_str.to_ascii_lowercase();
+        let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+        let host_start = url_lower
+            .find("://")
+            .map(|p| p + 3)
+            .and_then(|p| url_lower[p..].find(&host).map(|h| p + h))
+            .unwrap_or(0);
+        let host_range = (host_start, host_start + host.len());
+
+        let source_host = initiator
+            .and_then(|u| u.host_str())
+            .map(|h| h.to_ascii_lowercase())
+            .unwrap_or_else(|| host.clone());
+        let third_party = base_domain(&source_host) != base_domain(&host);
+
+        Self {
+            tokens: tokenize(&url_lower),
+            url_lower,
+            url: url_str,
+            host_range,
+            resource_type,
+            source_host,
+            third_party,
+        }
+    }
+}
+
+/// トークンで索引化したネットワークルールの集合
+#[derive(Debug, Default)]
+struct FilterIndex {
+    filters: Vec<NetworkFilter>,
+    by_token: HashMap<String, Vec<usize>>,
+    /// 索引に使えるトークンを持たないルール（常に照合する）
+    untokenized: Vec<usize>,
+}
+
+impl FilterIndex {
+    fn insert(&mut self, filter: NetworkFilter) {
+        let index = self.filters.len();
+        match select_token(&filter) {
+            Some(token) => self.by_token.entry(token).or_default().push(index),
+            None => self.untokenized.push(index),
+        }
+        self.filters.push(filter);
+    }
+
+    fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// リクエストに一致する最初のルールを返します
+    fn find_match(&self, ctx: &RequestContext) -> Option<&NetworkFilter> {
+        let mut seen = HashSet::new();
+        let candidates = ctx
+            .tokens
+            .iter()
+            .filter_map(|t| self.by_token.get(t))
+            .flatten()
+            .chain(self.untokenized.iter());
+        for &index in candidates {
+            if !seen.insert(index) {
+                continue;
+            }
+            let filter = &self.filters[index];
+            if filter_matches(filter, ctx) {
+                return Some(filter);
+            }
+        }
+        None
+    }
+}
+
+/// Adblock Plus / EasyList 形式のルールでリクエストをブロックするコンテンツブロッカー
+///
+/// `Middleware`を実装しているため、`NetworkCore::with_middlewares`に登録して使用します。
+/// 要素隠しルールは`cosmetic_selectors`で取り出し、`Renderer::set_hidden_elements`に渡すと
+/// 一致する要素が描画されなくなります。
+#[derive(Debug, Default)]
+pub struct ContentBlocker {
+    blocking: FilterIndex,
+    exceptions: FilterIndex,
+    cosmetic: Vec<CosmeticFilter>,
+    cosmetic_exceptions: Vec<CosmeticFilter>,
+}
+
+impl ContentBlocker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// フィルタリストの本文からコンテンツブロッカーを作成します
+    pub fn from_filter_list(text: &str) -> Self {
+        let mut blocker = Self::new();
+        blocker.add_filter_list(text);
+        blocker
+    }
+
+    /// フィルタリストを追加で読み込みます
+    ///
+    /// # 引数
+    /// * `text` - フィルタリストの本文（EasyList など）
+    ///
+    /// # 戻り値
+    /// * 読み込んだルールの数を返します（対応していないルールは数えません）
+    pub fn add_filter_list(&mut self, text: &str) -> usize {
+        let list = FilterList::parse(text);
+        if list.skipped > 0 {
+            log::debug!(
+                "content blocker: skipped {} unsupported rules",
+                list.skipped
+            );
+        }
+        let count = list.filters.len();
+        for filter in list.filters {
+            match filter {
+                Filter::Network(f) if f.exception => self.exceptions.insert(f),
+                Filter::Network(f) => self.blocking.insert(f),
+                Filter::Cosmetic(f) if f.exception => self.cosmetic_exceptions.push(f),
+                Filter::Cosmetic(f) => self.cosmetic.push(f),
+            }
+        }
+        count
+    }
+
+    /// 読み込まれているネットワークルールの数を返します
+    pub fn network_rule_count(&self) -> usize {
+        self.blocking.len() + self.exceptions.len()
+    }
+
+    /// 読み込まれている要素隠しルールの数を返します
+    pub fn cosmetic_rule_count(&self) -> usize {
+        self.cosmetic.len() + self.cosmetic_exceptions.len()
+    }
+
+    /// リクエストをブロックすべきか判定します
+    ///
+    /// # 引数
+    /// * `url` - リクエスト先URL
+    /// * `resource_type` - 要求されているリソースの種類
+    /// * `initiator` - リクエストを発行したドキュメントのURL
+    pub fn check(
+        &self,
+        url: &Url,
+        resource_type: ResourceType,
+        initiator: Option<&Url>,
+    ) -> BlockDecision {
+        // `@@...$document` に一致するページ上ではブロックしない
+        if let Some(page) = initiator {
+            let page_ctx = RequestContext::new(page, ResourceType::Document, None);
+            if let Some(f) = self.exceptions.find_match(&page_ctx) {
+                if f.resource_types
+                    .as_ref()
+                    .is_some_and(|t| t.contains(&ResourceType::Document))
+                {
+                    return BlockDecision::Exception(f.raw.clone());
+                }
+            }
+        }
+
+        let ctx = RequestContext::new(url, resource_type, initiator);
+        let Some(block) = self.blocking.find_match(&ctx) else {
+            return BlockDecision::Allow;
+        };
+        match self.exceptions.find_match(&ctx) {
+            Some(exception) => BlockDecision::Exception(exception.raw.clone()),
+            None => BlockDecision::Block(block.raw.clone()),
+        }
+    }
+
+    /// 指定したホストのページで隠すべき要素のCSSセレクタを返します
+    pub fn cosmetic_selectors(&self, host: &str) -> Vec<String> {
+        let host = host.to_ascii_lowercase();
+        let applies = |f: &CosmeticFilter| {
+            (f.include_domains.is_empty()
+                || f.include_domains.iter().any(|d| domain_matches(&host, d)))
+                && !f.exclude_domains.iter().any(|d| domain_matches(&host, d))
+        };
+        let excepted = self
+            .cosmetic_exceptions
+            .iter()
+            .filter(|f| applies(f))
+            .map(|f| f.selector.as_str())
+            .collect::<HashSet<_>>();
+
+        let mut seen = HashSet::new();
+        self.cosmetic
+            .iter()
+            .filter(|f| applies(f) && !excepted.contains(f.selector.as_str()))
+            .filter(|f| seen.insert(f.selector.as_str()))
+            .map(|f| f.selector.clone())
+            .collect()
+    }
+
+    /// 要素隠しルールをスタイルシートとして返します
+    ///
+    /// 隠す要素がない場合は空文字列を返します。
+    pub fn element_hiding_css(&self, host: &str) -> String {
+        self.cosmetic_selectors(host)
+            .iter()
+            .map(|selector| format!("{selector} {{ display: none !important; }}\n"))
+            .collect()
+    }
+}
+
+impl Middleware for ContentBlocker {
+    fn name(&self) -> &str {
+        "content-blocker"
+    }
+
+    fn on_request(&self, request: &mut Request) -> RequestAction {
+        match self.check(
+            &request.url,
+            request.resource_type,
+            request.initiator.as_ref(),
+        ) {
+            BlockDecision::Block(rule) => RequestAction::Block(rule),
+            _ => RequestAction::Continue,
+        }
+    }
+}
+
+/// ルールがリクエストに一致するか判定します
+fn filter_matches(filter: &NetworkFilter, ctx: &RequestContext) -> bool {
+    // リソース種別（種別指定のないルールはドキュメント以外に適用）
+    let type_ok = match &filter.resource_types {
+        Some(types) => types.contains(&ctx.resource_type),
+        None => ctx.resource_type != ResourceType::Document,
+    };
+    if !type_ok || filter.excluded_resource_types.contains(&ctx.resource_type) {
+        return false;
+    }
+    if filter.third_party.is_some_and(|tp| tp != ctx.third_party) {
+        return false;
+    }
+    if !filter.include_domains.is_empty()
+        && !filter
+            .include_domains
+            .iter()
+            .any(|d| domain_matches(&ctx.source_host, d))
+    {
+        return false;
+    }
+    if filter
+        .exclude_domains
+        .iter()
+        .any(|d| domain_matches(&ctx.source_host, d))
+    {
+        return false;
+    }
+
+    let url = if filter.match_case {
+        ctx.url.as_bytes()
+    } else {
+        ctx.url_lower.as_bytes()
+    };
+    match filter.start_anchor {
+        StartAnchor::Start => match_parts(&filter.parts, url, 0, false, filter.end_anchor),
+        StartAnchor::Domain => {
+            // ホスト名の先頭、または各ラベルの先頭から照合する
+            let (start, end) = ctx.host_range;
+            std::iter::once(start)
+                .chain((start..end).filter(|&i| url[i] == b'.').map(|i| i + 1))
+                .any(|pos| match_parts(&filter.parts, url, pos, false, filter.end_anchor))
+        }
+        // アンカーがない場合は、先頭に `*` があるものとして照合する
+        StartAnchor::None => match_parts(&filter.parts, url, 0, true, filter.end_anchor),
+    }
+}
+
+/// パターンを1文字ずつに分けた要素
+#[derive(Clone, Copy)]
+enum PatternAtom {
+    Byte(u8),
+    Separator,
+    Wildcard,
+}
+
+/// パターン中の位置（要素の番号と、リテラルの中のバイト位置）
+type PatternPos = (usize, usize);
+
+/// `pos`の要素と、その次の位置を返します（パターンの終わりでは`None`）
+fn pattern_atom(
+    parts: &[PatternPart],
+    (mut i, mut j): PatternPos,
+) -> Option<(PatternAtom, PatternPos)> {
+    loop {
+        match parts.get(i)? {
+            PatternPart::Literal(literal) => match literal.as_bytes().get(j) {
+                Some(&b) => return Some((PatternAtom::Byte(b), (i, j + 1))),
+                None => (i, j) = (i + 1, 0),
+            },
+            PatternPart::Separator => return Some((PatternAtom::Separator, (i + 1, 0))),
+            PatternPart::Wildcard => return Some((PatternAtom::Wildcard, (i + 1, 0))),
+        }
+    }
+}
+
+/// パターン要素を `url[pos..]` に対して照合します
+///
+/// `*` を含むパターンの照合は、最後に読んだ `*` の位置だけを覚えておき、以降が一致しなければ
+/// その `*` が読む範囲を1文字ずつ延ばして読み直します（ワイルドカードの一般的な照合方法）。
+/// 手前の `*` まで戻る必要はないので、信頼できないフィルターでも照合は
+/// パターンの長さと URL の長さの積で終わります。
+///
+/// # 引数
+/// * `floating` - 先頭に `*` があるものとして照合するか（アンカーのないパターン）
+fn match_parts(
+    parts: &[PatternPart],
+    url: &[u8],
+    mut pos: usize,
+    floating: bool,
+    end_anchor: bool,
+) -> bool {
+    let mut pattern = (0, 0);
+    // 最後の `*` の直後のパターン位置と、その `*` が読み終えた URL の位置
+    let mut backtrack = floating.then_some((pattern, pos));
+    loop {
+        // 一致した場合は、次のパターン位置と URL の位置
+        let step = match pattern_atom(parts, pattern) {
+            None if !end_anchor || pos == url.len() => return true,
+            None => None,
+            Some((PatternAtom::Wildcard, next)) => {
+                backtrack = Some((next, pos));
+                Some((next, pos))
+            }
+            Some((PatternAtom::Byte(b), next)) => {
+                (url.get(pos) == Some(&b)).then_some((next, pos + 1))
+            }
+            Some((PatternAtom::Separator, next)) => match url.get(pos) {
+                // `^` はURL末尾にも一致する
+                None => Some((next, pos)),
+                Some(&c) => is_separator(c).then_some((next, pos + 1)),
+            },
+        };
+        match (step, backtrack) {
+            (Some((next, next_pos)), _) => (pattern, pos) = (next, next_pos),
+            // 最後の `*` に1文字多く読ませて、その直後から読み直す
+            (None, Some((next, star_end))) if star_end < url.len() => {
+                backtrack = Some((next, star_end + 1));
+                (pattern, pos) = (next, star_end + 1);
+            }
+            (None, _) => return false,
+        }
+    }
+}
+
+fn is_separator(c: u8) -> bool {
+    !(c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'%'))
+}
+
+/// 英数字の連続をトークンとして取り出します
+fn tokenize(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|t| t.len() >= 2)
+        .map(str::to_string)
+        .collect()
+}
+
+/// ルールの索引に使うトークンを選びます
+///
+/// ワイルドカードやパターンの端に接していて、URL中では途中で切れている可能性があるトークンは使えません。
+/// 使えるトークンのうち最も長いものを選びます。
+fn select_token(filter: &NetworkFilter) -> Option<String> {
+    let mut best: Option<String> = None;
+    let last = filter.parts.len().saturating_sub(1);
+    for (i, part) in filter.parts.iter().enumerate() {
+        let PatternPart::Literal(literal) = part else {
+            continue;
+        };
+        let literal = literal.to_ascii_lowercase();
+        // リテラルの左右が単語境界として確定しているか
+        let left_bounded = if i == 0 {
+            filter.start_anchor != StartAnchor::None
+        } else {
+            filter.parts[i - 1] == PatternPart::Separator
+        };
+        let right_bounded = if i == last {
+            filter.end_anchor
+        } else {
+            filter.parts[i + 1] == PatternPart::Separator
+        };
+
+        let bytes = literal.as_bytes();
+        let mut start = 0;
+        while start < bytes.len() {
+            if !bytes[start].is_ascii_alphanumeric() {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < bytes.len() && bytes[end].is_ascii_alphanumeric() {
+                end += 1;
+            }
+            let bounded = (start > 0 || left_bounded) && (end < bytes.len() || right_bounded);
+            let token = &literal[start..end];
+            if bounded
+                && token.len() >= 2
+                && !COMMON_TOKENS.contains(&token)
+                && best.as_ref().is_none_or(|b| token.len() > b.len())
+            {
+                best = Some(token.to_string());
+            }
+            start = end;
+        }
+    }
+    best
+}
+
+/// `host` が `domain` 自身またはそのサブドメインかどうか
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+}
+
+/// サードパーティ判定に用いるベースドメイン
+///
+/// Public Suffix List を持たないため、末尾2ラベルで近似します（IPアドレスはそのまま）。
+fn base_domain(host: &str) -> &str {
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return host;
+    }
+    let mut dots = host.rmatch_indices('.');
+    dots.next();
+    match dots.next() {
+        Some((pos, _)) => &host[pos + 1..],
+        None => host,
+    }
+}
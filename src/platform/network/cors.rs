@@ -0,0 +1,440 @@
+//! オリジンと CORS (Cross-Origin Resource Sharing) プロトコルの実装
+//!
+//! Fetch Standard の CORS 関連の処理（リクエストモード、プリフライト、
+//! `Access-Control-*` ヘッダーの検査、クレデンシャルの扱い）を提供します。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::platform::network::network_core::{Request, Response};
+
+/// プリフライト結果をキャッシュする既定の秒数（`Access-Control-Max-Age` がない場合）
+const DEFAULT_PREFLIGHT_MAX_AGE: u64 = 5;
+
+/// プリフライト結果をキャッシュする上限の秒数
+const MAX_PREFLIGHT_MAX_AGE: u64 = 7200;
+
+/// リソースの出どころを表すオリジン
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// `data:` URL やサンドボックス化された文書など、どのオリジンとも一致しないオリジン
+    Opaque,
+    /// スキーム・ホスト・ポートの組
+    Tuple {
+        scheme: String,
+        host: String,
+        port: u16,
+    },
+}
+
+impl Origin {
+    /// URLからオリジンを求めます
+    ///
+    /// http / https 以外のスキームや、ホストを持たないURLはオペークなオリジンになります。
+    pub fn from_url(url: &Url) -> Self {
+        match (url.scheme(), url.host_str(), url.port_or_known_default()) {
+            (scheme @ ("http" | "https" | "ws" | "wss"), Some(host), Some(port)) => Origin::Tuple {
+                scheme: scheme.to_string(),
+                host: host.to_ascii_lowercase(),
+                port,
+            },
+            _ => Origin::Opaque,
+        }
+    }
+
+    /// 同一オリジンかどうかを判定します（オペークなオリジンは自身とも一致しません）
+    pub fn same_origin(&self, other: &Origin) -> bool {
+        match (self, other) {
+            (Origin::Tuple { .. }, Origin::Tuple { .. }) => self == other,
+            _ => false,
+        }
+    }
+
+    /// `Origin` ヘッダーなどに使う文字列表現（オペークなオリジンは `"null"`）
+    pub fn serialize(&self) -> String {
+        match self {
+            Origin::Opaque => "null".to_string(),
+            Origin::Tuple { scheme, host, port } => {
+                let default_port = match scheme.as_str() {
+                    "http" | "ws" => 80,
+                    "https" | "wss" => 443,
+                    _ => 0,
+                };
+                if *port == default_port {
+                    format!("{scheme}://{host}")
+                } else {
+                    format!("{scheme}://{host}:{port}")
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.serialize())
+    }
+}
+
+/// リクエストモード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestMode {
+    /// トップレベルの遷移（CORS の検査を行わない）
+    Navigate,
+    /// 同一オリジンのみ許可
+    SameOrigin,
+    /// クロスオリジンを許可するが、レスポンスはオペークになる
+    NoCors,
+    /// CORS プロトコルに従ってクロスオリジンを許可
+    Cors,
+}
+
+/// クレデンシャル（Cookie など）の送信モード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialsMode {
+    /// 送信しない
+    Omit,
+    /// 同一オリジンの場合のみ送信する
+    SameOrigin,
+    /// 常に送信する
+    Include,
+}
+
+/// CORS の検査に失敗したことを表すエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorsError {
+    /// `same-origin` モードでクロスオリジンのリクエストを行った
+    SameOriginViolation { url: Url },
+    /// `no-cors` モードで許可されていないメソッドを使った
+    MethodNotSafelisted { method: String },
+    /// プリフライトのレスポンスが成功ステータスではなかった
+    PreflightStatus { status: u16 },
+    /// `Access-Control-Allow-Origin` ヘッダーがない
+    MissingAllowOrigin,
+    /// `Access-Control-Allow-Origin` がリクエスト元のオリジンと一致しない
+    AllowOriginMismatch { expected: String, actual: String },
+    /// クレデンシャル付きのリクエストにワイルドカードが返された
+    WildcardWithCredentials,
+    /// クレデンシャル付きのリクエストに `Access-Control-Allow-Credentials: true` がない
+    MissingAllowCredentials,
+    /// プリフライトでメソッドが許可されなかった
+    MethodNotAllowed { method: String },
+    /// プリフライトでヘッダーが許可されなかった
+    HeaderNotAllowed { header: String },
+}
+
+impl fmt::Display for CorsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorsError::SameOriginViolation { url } => {
+                write!(
+                    f,
+                    "Cross-origin request to {url} is not allowed in same-origin mode"
+                )
+            }
+            CorsError::MethodNotSafelisted { method } => {
+                write!(f, "Method {method} is not allowed in no-cors mode")
+            }
+            CorsError::PreflightStatus { status } => {
+                write!(f, "CORS preflight failed with status {status}")
+            }
+            CorsError::MissingAllowOrigin => {
+                write!(f, "Missing Access-Control-Allow-Origin header")
+            }
+            CorsError::AllowOriginMismatch { expected, actual } => write!(
+                f,
+                "Access-Control-Allow-Origin {actual:?} does not match origin {expected:?}"
+            ),
+            CorsError::WildcardWithCredentials => write!(
+                f,
+                "Access-Control-Allow-Origin must not be '*' for credentialed requests"
+            ),
+            CorsError::MissingAllowCredentials => write!(
+                f,
+                "Access-Control-Allow-Credentials must be 'true' for credentialed requests"
+            ),
+            CorsError::MethodNotAllowed { method } => {
+                write!(f, "Method {method} is not allowed by CORS preflight")
+            }
+            CorsError::HeaderNotAllowed { header } => {
+                write!(f, "Header {header} is not allowed by CORS preflight")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CorsError {}
+
+/// プリフライトで得られた許可内容
+#[derive(Debug, Clone)]
+struct PreflightEntry {
+    /// 許可されたメソッド（大文字）
+    methods: Vec<String>,
+    /// 許可されたヘッダー名（小文字）
+    headers: Vec<String>,
+    /// メソッド/ヘッダーにワイルドカードが指定されたか
+    any_method: bool,
+    any_header: bool,
+    expires_at: SystemTime,
+}
+
+/// プリフライトキャッシュのキー（オリジン、URL、クレデンシャルの有無）
+type PreflightKey = (String, String, bool);
+
+/// プリフライトの結果を保持するキャッシュ
+#[derive(Debug, Clone, Default)]
+pub struct PreflightCache {
+    store: Arc<RwLock<HashMap<PreflightKey, PreflightEntry>>>,
+}
+
+impl PreflightCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// キャッシュがリクエストのメソッドとヘッダーをすべて許可しているか判定します
+    pub async fn allows(&self, origin: &Origin, request: &Request, credentials: bool) -> bool {
+        let store = self.store.read().await;
+        let key = (origin.serialize(), request.url.to_string(), credentials);
+        let Some(entry) = store.get(&key) else {
+            return false;
+        };
+        if SystemTime::now() > entry.expires_at {
+            return false;
+        }
+        check_preflight_permissions(entry, request, credentials).is_ok()
+    }
+
+    async fn insert(&self, key: PreflightKey, entry: PreflightEntry) {
+        self.store.write().await.insert(key, entry);
+    }
+
+    pub async fn clear(&self) {
+        self.store.write().await.clear();
+    }
+}
+
+/// CORS セーフリストに含まれるメソッドかどうか
+pub fn is_safelisted_method(method: &str) -> bool {
+    matches!(
+        method.to_ascii_uppercase().as_str(),
+        "GET" | "HEAD" | "POST"
+    )
+}
+
+/// CORS セーフリストに含まれるリクエストヘッダーかどうか
+pub fn is_safelisted_request_header(name: &str, value: &str) -> bool {
+    match name.to_ascii_lowercase().as_str() {
+        "accept" | "accept-language" | "content-language" => value.len() <= 128,
+        "content-type" => {
+            let essence = value
+                .split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase();
+            matches!(
+                essence.as_str(),
+                "application/x-www-form-urlencoded" | "multipart/form-data" | "text/plain"
+            )
+        }
+        _ => false,
+    }
+}
+
+/// CORS レスポンスで公開される既定のレスポンスヘッダーかどうか
+fn is_safelisted_response_header(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "cache-control"
+            | "content-language"
+            | "content-length"
+            | "content-type"
+            | "expires"
+            | "last-modified"
+            | "pragma"
+    )
+}
+
+/// リクエストにプリフライトが必要かどうか
+pub fn needs_preflight(request: &Request) -> bool {
+    !is_safelisted_method(&request.method) || !unsafe_request_headers(request).is_empty()
+}
+
+/// セーフリストに含まれないリクエストヘッダー名（小文字、ソート済み）を返します
+fn unsafe_request_headers(request: &Request) -> Vec<String> {
+    let mut names = request
+        .headers
+        .iter()
+        .filter(|(k, v)| !is_safelisted_request_header(k, v))
+        .map(|(k, _)| k.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// プリフライトの `OPTIONS` リクエストを組み立てます
+pub fn preflight_request(request: &Request, origin: &Origin) -> Request {
+    let mut preflight = Request::new("OPTIONS", request.url.clone());
+    preflight.resource_type = request.resource_type;
+    preflight.initiator = request.initiator.clone();
+    preflight.set_header("Origin", &origin.serialize());
+    preflight.set_header("Access-Control-Request-Method", &request.method);
+    let headers = unsafe_request_headers(request);
+    if !headers.is_empty() {
+        preflight.set_header("Access-Control-Request-Headers", &headers.join(","));
+    }
+    preflight
+}
+
+/// CORS チェック（`Access-Control-Allow-Origin` / `-Credentials` の検査）を行います
+pub fn check_cors_response(
+    response: &Response,
+    origin: &Origin,
+    credentials: bool,
+) -> Result<(), CorsError> {
+    let allow_origin = header(response, "access-control-allow-origin")
+        .ok_or(CorsError::MissingAllowOrigin)?
+        .trim();
+
+    if allow_origin == "*" {
+        return if credentials {
+            Err(CorsError::WildcardWithCredentials)
+        } else {
+            Ok(())
+        };
+    }
+
+    let expected = origin.serialize();
+    if allow_origin != expected {
+        return Err(CorsError::AllowOriginMismatch {
+            expected,
+            actual: allow_origin.to_string(),
+        });
+    }
+
+    if credentials
+        && header(response, "access-control-allow-credentials").map(str::trim) != Some("true")
+    {
+        return Err(CorsError::MissingAllowCredentials);
+    }
+    Ok(())
+}
+
+/// プリフライトのレスポンスを検査し、結果をキャッシュに保存します
+pub async fn handle_preflight_response(
+    cache: &PreflightCache,
+    request: &Request,
+    response: &Response,
+    origin: &Origin,
+    credentials: bool,
+) -> Result<(), CorsError> {
+    if !(200..300).contains(&response.status_code) {
+        return Err(CorsError::PreflightStatus {
+            status: response.status_code,
+        });
+    }
+    check_cors_response(response, origin, credentials)?;
+
+    let list = |name: &str| -> Vec<String> {
+        header(response, name)
+            .map(|v| {
+                v.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let methods = list("access-control-allow-methods");
+    let headers = list("access-control-allow-headers");
+    let max_age = header(response, "access-control-max-age")
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_PREFLIGHT_MAX_AGE)
+        .min(MAX_PREFLIGHT_MAX_AGE);
+
+    let entry = PreflightEntry {
+        any_method: !credentials && methods.iter().any(|m| m == "*"),
+        any_header: !credentials && headers.iter().any(|h| h == "*"),
+        methods: methods.iter().map(|m| m.to_ascii_uppercase()).collect(),
+        headers: headers.iter().map(|h| h.to_ascii_lowercase()).collect(),
+        expires_at: SystemTime::now() + Duration::from_secs(max_age),
+    };
+    check_preflight_permissions(&entry, request, credentials)?;
+
+    if max_age > 0 {
+        let key = (origin.serialize(), request.url.to_string(), credentials);
+        cache.insert(key, entry).await;
+    }
+    Ok(())
+}
+
+/// プリフライトで許可された範囲にリクエストが収まっているか判定します
+fn check_preflight_permissions(
+    entry: &PreflightEntry,
+    request: &Request,
+    credentials: bool,
+) -> Result<(), CorsError> {
+    let method = request.method.to_ascii_uppercase();
+    if !is_safelisted_method(&method) && !entry.any_method && !entry.methods.contains(&method) {
+        return Err(CorsError::MethodNotAllowed { method });
+    }
+    for name in unsafe_request_headers(request) {
+        // Authorization はワイルドカードでは許可されない
+        let wildcard_ok = entry.any_header && !credentials && name != "authorization";
+        if !wildcard_ok && !entry.headers.contains(&name) {
+            return Err(CorsError::HeaderNotAllowed { header: name });
+        }
+    }
+    Ok(())
+}
+
+/// CORS レスポンスのヘッダーを、スクリプトに公開してよいものだけに絞り込みます
+pub fn filter_cors_response(response: &mut Response, credentials: bool) {
+    let exposed = header(response, "access-control-expose-headers")
+        .map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_ascii_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let expose_all = !credentials && exposed.iter().any(|h| h == "*");
+
+    response.headers.retain(|(k, _)| {
+        let name = k.to_ascii_lowercase();
+        if name == "set-cookie" || name == "set-cookie2" {
+            return false;
+        }
+        k == "Status-Line"
+            || expose_all
+            || is_safelisted_response_header(&name)
+            || exposed.contains(&name)
+    });
+}
+
+/// `no-cors` モードのクロスオリジンリクエストに対するオペークなレスポンスを作成します
+///
+/// ステータスは0、ヘッダーとボディは空になります。
+pub fn opaque_response(response: &Response) -> Response {
+    Response {
+        http_version: response.http_version.clone(),
+        status_code: 0,
+        reason_phrase: String::new(),
+        headers: vec![],
+        body: vec![],
+    }
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
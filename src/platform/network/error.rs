@@ -0,0 +1,243 @@
+//! ネットワーク層のエラー型
+//!
+//! ブラウザがエラーページを出し分けられるよう、失敗の種類ごとに必要な情報（ホスト、ポート、
+//! 証明書の問題の種類など）を保持します。下位のエラーは`source()`でたどれます。
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use url::Url;
+
+use crate::platform::network::config::RetryCondition;
+use crate::platform::network::cors::CorsError;
+use crate::platform::network::middleware::RequestBlocked;
+
+/// ネットワーク層の公開APIが返す結果型
+pub type NetworkResult<T> = std::result::Result<T, NetworkError>;
+
+/// タイムアウトが発生した段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// 名前解決
+    Dns,
+    /// TCP接続
+    Connect,
+    /// TLSハンドシェイク
+    TlsHandshake,
+}
+
+/// サーバー証明書の問題の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateProblem {
+    /// 有効期限切れ
+    Expired,
+    /// まだ有効期間に入っていない
+    NotValidYet,
+    /// 失効している
+    Revoked,
+    /// 信頼されていない発行者（自己署名を含む）
+    UnknownIssuer,
+    /// 署名が不正
+    BadSignature,
+    /// 接続先のホスト名に対して発行されていない
+    NotValidForName,
+    /// 証明書の形式が不正
+    BadEncoding,
+    /// その他の問題
+    Other,
+}
+
+/// TLSエラーの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsErrorKind {
+    /// サーバー証明書の検証に失敗した
+    Certificate(CertificateProblem),
+    /// ホスト名がTLSのサーバー名として不正
+    InvalidDnsName,
+    /// ルート証明書を読み込めなかった
+    RootStore,
+    /// ハンドシェイク中のその他のエラー（プロトコル違反、アラートの受信など）
+    Handshake,
+}
+
+/// ネットワーク層のエラー
+#[derive(Debug)]
+pub enum NetworkError {
+    /// URLが不正、またはホストを含まない
+    InvalidUrl {
+        url: String,
+        source: Option<url::ParseError>,
+    },
+    /// 名前解決に失敗した
+    Dns { host: String, source: io::Error },
+    /// 接続を拒否された
+    ConnectionRefused {
+        host: String,
+        port: u16,
+        source: io::Error,
+    },
+    /// その他の理由で接続を確立できなかった（到達不能など）
+    Connect {
+        host: String,
+        port: u16,
+        source: io::Error,
+    },
+    /// タイムアウトした
+    Timeout {
+        host: String,
+        port: u16,
+        phase: TimeoutPhase,
+    },
+    /// TLSの確立に失敗した
+    Tls {
+        host: String,
+        kind: TlsErrorKind,
+        source: Option<Box<dyn Error + Send + Sync>>,
+    },
+    /// 送受信中に接続がリセット・切断された
+    ConnectionReset { source: io::Error },
+    /// その他の入出力エラー
+    Io { source: io::Error },
+    /// サーバーの応答がHTTPとして解釈できない
+    Protocol { message: String },
+    /// オフラインモードで、要求されたリソースがキャッシュにない
+    Offline { url: Url },
+    /// ミドルウェアによってブロックされた
+    Blocked(Box<RequestBlocked>),
+    /// CORS の検査に失敗した
+    Cors(CorsError),
+}
+
+impl NetworkError {
+    /// 送受信中の`io::Error`を、切断とそれ以外に分類して変換します
+    pub(crate) fn from_exchange(source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => NetworkError::ConnectionReset { source },
+            _ => NetworkError::Io { source },
+        }
+    }
+
+    /// TLSハンドシェイク中の`io::Error`を変換します
+    pub(crate) fn from_tls_handshake(host: &str, source: io::Error) -> Self {
+        let kind = match source
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<rustls::Error>())
+        {
+            Some(rustls::Error::InvalidCertificate(e)) => {
+                TlsErrorKind::Certificate(certificate_problem(e))
+            }
+            Some(_) => TlsErrorKind::Handshake,
+            // TLS層より下での失敗
+            None => return Self::from_exchange(source),
+        };
+        NetworkError::Tls {
+            host: host.to_string(),
+            kind,
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// 再試行の対象となる失敗の種類を返します（`None`の場合は再試行しない）
+    pub(crate) fn retry_condition(&self) -> Option<RetryCondition> {
+        match self {
+            NetworkError::ConnectionRefused { .. } | NetworkError::Connect { .. } => {
+                Some(RetryCondition::ConnectFailed)
+            }
+            NetworkError::Timeout { .. } => Some(RetryCondition::ConnectTimeout),
+            NetworkError::ConnectionReset { .. } => Some(RetryCondition::ConnectionReset),
+            _ => None,
+        }
+    }
+}
+
+fn certificate_problem(error: &rustls::CertificateError) -> CertificateProblem {
+    use rustls::CertificateError as E;
+    match error {
+        E::Expired => CertificateProblem::Expired,
+        E::NotValidYet => CertificateProblem::NotValidYet,
+        E::Revoked => CertificateProblem::Revoked,
+        E::UnknownIssuer => CertificateProblem::UnknownIssuer,
+        E::BadSignature => CertificateProblem::BadSignature,
+        E::NotValidForName => CertificateProblem::NotValidForName,
+        E::BadEncoding => CertificateProblem::BadEncoding,
+        _ => CertificateProblem::Other,
+    }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::InvalidUrl { url, .. } => write!(f, "Invalid URL: {url}"),
+            NetworkError::Dns { host, .. } => write!(f, "Could not resolve host {host}"),
+            NetworkError::ConnectionRefused { host, port, .. } => {
+                write!(f, "Connection to {host}:{port} was refused")
+            }
+            NetworkError::Connect { host, port, .. } => {
+                write!(f, "Could not connect to {host}:{port}")
+            }
+            NetworkError::Timeout { host, port, phase } => {
+                let phase = match phase {
+                    TimeoutPhase::Dns => "name resolution",
+                    TimeoutPhase::Connect => "connecting",
+                    TimeoutPhase::TlsHandshake => "TLS handshake",
+                };
+                write!(f, "Timed out during {phase} with {host}:{port}")
+            }
+            NetworkError::Tls { host, kind, .. } => match kind {
+                TlsErrorKind::Certificate(problem) => {
+                    write!(f, "Invalid certificate for {host}: {problem:?}")
+                }
+                TlsErrorKind::InvalidDnsName => write!(f, "Invalid DNS name: {host}"),
+                TlsErrorKind::RootStore => write!(f, "Could not load root certificates"),
+                TlsErrorKind::Handshake => write!(f, "TLS handshake with {host} failed"),
+            },
+            NetworkError::ConnectionReset { .. } => write!(f, "Connection was closed"),
+            NetworkError::Io { .. } => write!(f, "I/O error"),
+            NetworkError::Protocol { message } => write!(f, "Protocol error: {message}"),
+            NetworkError::Offline { url } => {
+                write!(f, "offline: {url} is not available in the cache")
+            }
+            NetworkError::Blocked(blocked) => blocked.fmt(f),
+            NetworkError::Cors(cors) => cors.fmt(f),
+        }
+    }
+}
+
+impl Error for NetworkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NetworkError::InvalidUrl { source, .. } => {
+                source.as_ref().map(|e| e as &(dyn Error + 'static))
+            }
+            NetworkError::Dns { source, .. }
+            | NetworkError::ConnectionRefused { source, .. }
+            | NetworkError::Connect { source, .. }
+            | NetworkError::ConnectionReset { source }
+            | NetworkError::Io { source } => Some(source),
+            NetworkError::Tls { source, .. } => {
+                source.as_deref().map(|e| e as &(dyn Error + 'static))
+            }
+            NetworkError::Timeout { .. }
+            | NetworkError::Protocol { .. }
+            | NetworkError::Offline { .. } => None,
+            // ラップしたエラー自身を表示しているため、原因としては返さない
+            NetworkError::Blocked(_) | NetworkError::Cors(_) => None,
+        }
+    }
+}
+
+impl From<RequestBlocked> for NetworkError {
+    fn from(blocked: RequestBlocked) -> Self {
+        NetworkError::Blocked(Box::new(blocked))
+    }
+}
+
+impl From<CorsError> for NetworkError {
+    fn from(error: CorsError) -> Self {
+        NetworkError::Cors(error)
+    }
+}
@@ -0,0 +1,304 @@
+//! Adblock Plus / EasyList 形式のフィルタリストの解析
+
+use crate::platform::network::network_core::ResourceType;
+
+/// URLパターンを構成する要素
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternPart {
+    /// そのまま一致する文字列
+    Literal(String),
+    /// `*` : 任意の文字列
+    Wildcard,
+    /// `^` : 区切り文字（英数字と `_-.%` 以外の文字、またはURL末尾）
+    Separator,
+}
+
+/// パターン先頭のアンカー
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartAnchor {
+    /// アンカーなし（URLの任意の位置から一致）
+    None,
+    /// `|` : URLの先頭から一致
+    Start,
+    /// `||` : ホスト名のラベルの先頭から一致
+    Domain,
+}
+
+/// ネットワークフィルタ（URLに対するブロック/例外ルール）
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkFilter {
+    /// 元のルール文字列
+    pub raw: String,
+    /// `@@` で始まる例外ルールかどうか
+    pub exception: bool,
+    pub start_anchor: StartAnchor,
+    /// `|` で終わり、URL末尾に一致させるかどうか
+    pub end_anchor: bool,
+    pub parts: Vec<PatternPart>,
+    /// 対象のリソース種別（`None`の場合はドキュメント以外のすべて）
+    pub resource_types: Option<Vec<ResourceType>>,
+    /// 除外するリソース種別（`~script` など）
+    pub excluded_resource_types: Vec<ResourceType>,
+    /// `third-party` (`Some(true)`) / `~third-party` (`Some(false)`)
+    pub third_party: Option<bool>,
+    /// `domain=` で指定された、ルールを適用するドメイン
+    pub include_domains: Vec<String>,
+    /// `domain=~...` で指定された、ルールを適用しないドメイン
+    pub exclude_domains: Vec<String>,
+    /// 大文字小文字を区別するかどうか
+    pub match_case: bool,
+}
+
+/// 要素隠しフィルタ（`##selector`）
+#[derive(Debug, Clone, PartialEq)]
+pub struct CosmeticFilter {
+    /// 元のルール文字列
+    pub raw: String,
+    /// `#@#` で始まる例外ルールかどうか
+    pub exception: bool,
+    /// 隠す要素のCSSセレクタ
+    pub selector: String,
+    /// 適用するドメイン（空の場合はすべてのドメイン）
+    pub include_domains: Vec<String>,
+    /// 適用しないドメイン
+    pub exclude_domains: Vec<String>,
+}
+
+/// 1行のフィルタを解析した結果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Network(NetworkFilter),
+    Cosmetic(CosmeticFilter),
+}
+
+/// フィルタリスト全体を解析した結果
+#[derive(Debug, Default)]
+pub struct FilterList {
+    pub filters: Vec<Filter>,
+    /// 対応していない、または不正なため読み飛ばしたルールの数
+    pub skipped: usize,
+}
+
+impl FilterList {
+    /// フィルタリストの本文を解析します
+    ///
+    /// コメント行（`!`）、ヘッダー（`[Adblock Plus 2.0]`）、空行は無視されます。
+    /// 正規表現ルールや未知のオプションを含むルールなど、解釈できないルールは`skipped`に数えられます。
+    pub fn parse(text: &str) -> Self {
+        let mut list = FilterList::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+                continue;
+            }
+            match parse_filter(line) {
+                Some(filter) => list.filters.push(filter),
+                None => list.skipped += 1,
+            }
+        }
+        list
+    }
+}
+
+/// 1行のルールを解析します
+///
+/// # 戻り値
+/// * 解釈できたルールを返します
+/// * 対応していないルールの場合は`None`を返します
+pub fn parse_filter(line: &str) -> Option<Filter> {
+    if let Some(filter) = parse_cosmetic_filter(line) {
+        return filter.map(Filter::Cosmetic);
+    }
+    parse_network_filter(line).map(Filter::Network)
+}
+
+/// 要素隠しルールを解析します
+///
+/// 要素隠しルールでない場合は`None`、要素隠しルールだが対応していない場合は`Some(None)`を返します。
+fn parse_cosmetic_filter(line: &str) -> Option<Option<CosmeticFilter>> {
+    let (pos, exception, sep_len) = if let Some(pos) = line.find("#@#") {
+        (pos, true, 3)
+    } else if let Some(pos) = line.find("##") {
+        (pos, false, 2)
+    } else {
+        // 拡張構文（#?#, #$# など）は対応しない
+        if line.contains("#?#") || line.contains("#$#") || line.contains("#%#") {
+            return Some(None);
+        }
+        return None;
+    };
+
+    let domains = &line[..pos];
+    let selector = line[pos + sep_len..].trim();
+    if selector.is_empty() || domains.contains('/') || domains.contains('$') {
+        return Some(None);
+    }
+
+    let (include_domains, exclude_domains) = parse_domain_list(domains, ',');
+    Some(Some(CosmeticFilter {
+        raw: line.to_string(),
+        exception,
+        selector: selector.to_string(),
+        include_domains,
+        exclude_domains,
+    }))
+}
+
+/// ネットワークルールを解析します
+fn parse_network_filter(line: &str) -> Option<NetworkFilter> {
+    let mut filter = NetworkFilter {
+        raw: line.to_string(),
+        exception: false,
+        start_anchor: StartAnchor::None,
+        end_anchor: false,
+        parts: vec![],
+        resource_types: None,
+        excluded_resource_types: vec![],
+        third_party: None,
+        include_domains: vec![],
+        exclude_domains: vec![],
+        match_case: false,
+    };
+
+    let mut rule = line;
+    if let Some(rest) = rule.strip_prefix("@@") {
+        filter.exception = true;
+        rule = rest;
+    }
+
+    // 正規表現ルールは対応しない
+    if rule.starts_with('/') && rule.len() > 1 && rule.ends_with('/') {
+        return None;
+    }
+
+    // オプション部分（最後の `$` 以降）
+    let mut pattern = rule;
+    if let Some(pos) = rule.rfind('$') {
+        let options = &rule[pos + 1..];
+        // `$` がオプション区切りでなくURLの一部である可能性もあるが、EasyList に倣いオプションとして扱う
+        if !options.is_empty() && !options.contains('/') {
+            parse_options(options, &mut filter)?;
+            pattern = &rule[..pos];
+        }
+    }
+
+    if let Some(rest) = pattern.strip_prefix("||") {
+        filter.start_anchor = StartAnchor::Domain;
+        pattern = rest;
+    } else if let Some(rest) = pattern.strip_prefix('|') {
+        filter.start_anchor = StartAnchor::Start;
+        pattern = rest;
+    }
+    if let Some(rest) = pattern.strip_suffix('|') {
+        filter.end_anchor = true;
+        pattern = rest;
+    }
+
+    filter.parts = parse_pattern(pattern, filter.match_case);
+    Some(filter)
+}
+
+/// パターン文字列を要素に分解します
+fn parse_pattern(pattern: &str, match_case: bool) -> Vec<PatternPart> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    for c in pattern.chars() {
+        match c {
+            '*' | '^' => {
+                if !literal.is_empty() {
+                    parts.push(PatternPart::Literal(std::mem::take(&mut literal)));
+                }
+                let part = if c == '*' {
+                    PatternPart::Wildcard
+                } else {
+                    PatternPart::Separator
+                };
+                // 連続するワイルドカードは1つにまとめる
+                if !(part == PatternPart::Wildcard && parts.last() == Some(&PatternPart::Wildcard))
+                {
+                    parts.push(part);
+                }
+            }
+            _ if match_case => literal.push(c),
+            _ => literal.push(c.to_ascii_lowercase()),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(PatternPart::Literal(literal));
+    }
+    parts
+}
+
+/// `$` 以降のオプションを解析します
+///
+/// 未知のオプションを含むルールは誤ブロックを避けるため丸ごと無視します（`None`を返す）。
+fn parse_options(options: &str, filter: &mut NetworkFilter) -> Option<()> {
+    let mut resource_types = Vec::new();
+    for option in options.split(',') {
+        let option = option.trim();
+        let (negated, name) = match option.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, option),
+        };
+
+        if let Some(domains) = name.strip_prefix("domain=") {
+            let (include, exclude) = parse_domain_list(domains, '|');
+            filter.include_domains = include;
+            filter.exclude_domains = exclude;
+            continue;
+        }
+
+        match name {
+            "third-party" | "3p" => filter.third_party = Some(!negated),
+            "first-party" | "1p" => filter.third_party = Some(negated),
+            "match-case" => filter.match_case = true,
+            _ => {
+                let resource_type = parse_resource_type(name)?;
+                if negated {
+                    filter.excluded_resource_types.push(resource_type);
+                } else {
+                    resource_types.push(resource_type);
+                }
+            }
+        }
+    }
+    if !resource_types.is_empty() {
+        filter.resource_types = Some(resource_types);
+    }
+    Some(())
+}
+
+fn parse_resource_type(name: &str) -> Option<ResourceType> {
+    Some(match name {
+        "document" | "doc" => ResourceType::Document,
+        "subdocument" | "frame" => ResourceType::Subdocument,
+        "stylesheet" | "css" => ResourceType::Stylesheet,
+        "script" => ResourceType::Script,
+        "image" => ResourceType::Image,
+        "font" => ResourceType::Font,
+        "media" => ResourceType::Media,
+        "object" => ResourceType::Object,
+        "xmlhttprequest" | "xhr" => ResourceType::XmlHttpRequest,
+        "ping" => ResourceType::Ping,
+        "websocket" => ResourceType::WebSocket,
+        "other" => ResourceType::Other,
+        _ => return None,
+    })
+}
+
+/// `a.com|~b.a.com` のようなドメインリストを、適用/除外ドメインに分けます
+fn parse_domain_list(domains: &str, separator: char) -> (Vec<String>, Vec<String>) {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    for domain in domains.split(separator) {
+        let domain = domain.trim().to_ascii_lowercase();
+        if domain.is_empty() {
+            continue;
+        }
+        match domain.strip_prefix('~') {
+            Some(rest) => exclude.push(rest.to_string()),
+            None => include.push(domain),
+        }
+    }
+    (include, exclude)
+}
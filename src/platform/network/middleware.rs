@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::platform::network::network_core::{Request, Response};
+
+/// ミドルウェアがリクエストに対して下す判断
+#[derive(Debug)]
+pub enum RequestAction {
+    /// 次のミドルウェア（最後ならネットワーク）へ処理を渡す
+    Continue,
+    /// ネットワークに送信せず、このレスポンスを返す
+    Respond(Response),
+    /// リクエストをブロックする（理由を添える）
+    Block(String),
+}
+
+/// リクエストの送信前後に処理を差し込むためのトレイト
+///
+/// `NetworkCore::with_middlewares`に登録した順に`on_request`が呼ばれ、
+/// `on_response`はその逆順に呼ばれます。
+pub trait Middleware: Send + Sync {
+    /// ログやデバッグ表示に使う名前
+    fn name(&self) -> &str;
+
+    /// 送信前のリクエストを検査・書き換えます
+    ///
+    /// # 引数
+    /// * `request` - 送信予定のリクエスト（ヘッダーやURLを書き換えてよい）
+    ///
+    /// # 戻り値
+    /// * 処理を続けるか、合成したレスポンスを返すか、ブロックするかを返します
+    fn on_request(&self, _request: &mut Request) -> RequestAction {
+        RequestAction::Continue
+    }
+
+    /// 受信したレスポンスを後処理します
+    ///
+    /// # 引数
+    /// * `request` - 実際に送信された（書き換え後の）リクエスト
+    /// * `response` - 受信した、または途中のミドルウェアが合成したレスポンス
+    fn on_response(&self, _request: &Request, _response: &mut Response) {}
+}
+
+/// ミドルウェアによってリクエストがブロックされたことを表すエラー
+#[derive(Debug, Clone)]
+pub struct RequestBlocked {
+    /// ブロックされたURL
+    pub url: Url,
+    /// ブロックしたミドルウェアの名前
+    pub middleware: String,
+    /// ブロックの理由
+    pub reason: String,
+}
+
+impl fmt::Display for RequestBlocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Request to {} was blocked by {}: {}",
+            self.url, self.middleware, self.reason
+        )
+    }
+}
+
+impl std::error::Error for RequestBlocked {}
+
+/// 登録順に並んだミドルウェアの列
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+/// `on_request`を適用した結果
+pub(crate) enum ChainOutcome {
+    /// 全ミドルウェアを通過した。ネットワークへ送信する
+    Send,
+    /// `index`番目のミドルウェアがレスポンスを合成した
+    Respond { index: usize, response: Response },
+    /// リクエストがブロックされた
+    Blocked(RequestBlocked),
+}
+
+impl MiddlewareChain {
+    pub fn new(middlewares: Vec<Arc<dyn Middleware>>) -> Self {
+        Self { middlewares }
+    }
+
+    pub fn len(&self) -> usize {
+        self.middlewares.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.middlewares.is_empty()
+    }
+
+    /// 登録順に`on_request`を適用します
+    pub(crate) fn process_request(&self, request: &mut Request) -> ChainOutcome {
+        for (index, middleware) in self.middlewares.iter().enumerate() {
+            match middleware.on_request(request) {
+                RequestAction::Continue => {}
+                RequestAction::Respond(response) => {
+                    return ChainOutcome::Respond { index, response };
+                }
+                RequestAction::Block(reason) => {
+                    return ChainOutcome::Blocked(RequestBlocked {
+                        url: request.url.clone(),
+                        middleware: middleware.name().to_string(),
+                        reason,
+                    });
+                }
+            }
+        }
+        ChainOutcome::Send
+    }
+
+    /// 先頭から`upto`個（リクエストを見たミドルウェア）に逆順で`on_response`を適用します
+    pub(crate) fn process_response(&self, upto: usize, request: &Request, response: &mut Response) {
+        for middleware in self.middlewares[..upto].iter().rev() {
+            middleware.on_response(request, response);
+        }
+    }
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.middlewares.iter().map(|m| m.name()))
+            .finish()
+    }
+}
@@ -1,16 +1,27 @@
 pub mod cache;
 pub mod config;
 pub mod connection_pool;
+pub mod content_blocker;
 pub mod cookie_store;
+pub mod cors;
+pub mod error;
+pub mod filter_list;
+pub mod middleware;
 pub mod network_core;
+pub mod network_log;
 pub mod tcp;
 pub mod tls;
 
 // 外部公開用
-pub use cache::Cache;
-pub use config::NetworkConfig;
+pub use cache::{Cache, CacheKey};
+pub use config::{NetworkConfig, RetryCondition, RetryPolicy};
 pub use connection_pool::{Connection, ConnectionPool, HostKey};
+pub use content_blocker::{BlockDecision, ContentBlocker};
 pub use cookie_store::CookieStore;
-pub use network_core::{NetworkCore, Response};
+pub use cors::{CorsError, CredentialsMode, Origin, RequestMode};
+pub use error::{CertificateProblem, NetworkError, NetworkResult, TimeoutPhase, TlsErrorKind};
+pub use middleware::{Middleware, MiddlewareChain, RequestAction, RequestBlocked};
+pub use network_core::{NetworkCore, Request, ResourceType, Response};
+pub use network_log::{NetworkLog, NetworkLogEntry, RequestTimings};
 pub use tcp::TcpConnection;
 pub use tls::TlsConnection;
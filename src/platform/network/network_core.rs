@@ -1,15 +1,20 @@
-use anyhow::Result;
 use std::sync::Arc;
-use std::time::SystemTime;
-use tokio::io::{self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{
+    self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
 use tokio::sync::RwLock;
 use url::Url;
 
 use crate::platform::network::{
-    cache::Cache,
+    cache::{Cache, CacheKey},
     config::NetworkConfig,
     connection_pool::{Connection, ConnectionPool, HostKey},
     cookie_store::CookieStore,
+    cors::{self, CorsError, CredentialsMode, Origin, PreflightCache, RequestMode},
+    error::{NetworkError, NetworkResult},
+    middleware::{ChainOutcome, Middleware, MiddlewareChain},
+    network_log::{NetworkLog, NetworkLogEntry, RequestTimings},
     tcp::TcpConnection,
     tls::TlsConnection,
 };
@@ -32,6 +37,101 @@ pub struct Response {
     pub body: Vec<u8>,
 }
 
+/// 送信前のHTTPリクエストを表す構造体
+///
+/// ミドルウェアはこの構造体を書き換えることで、送信内容を変更できます。
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// HTTPメソッド (例: "GET", "POST")
+    pub method: String,
+    /// 接続先URL
+    pub url: Url,
+    /// 追加のHTTPヘッダー（Host, User-Agentなどは送信時に付与されます）
+    pub headers: Vec<(String, String)>,
+    /// リクエストボディ
+    pub body: Option<Vec<u8>>,
+    /// 要求されているリソースの種類
+    pub resource_type: ResourceType,
+    /// リクエストを発行したドキュメントのURL（トップレベルの遷移では`None`）
+    pub initiator: Option<Url>,
+    /// リクエストを発行したドキュメントのオリジン（`None`の場合は CORS の検査を行わない）
+    pub origin: Option<Origin>,
+    /// リクエストモード
+    pub mode: RequestMode,
+    /// クレデンシャル（Cookie）の送信モード
+    pub credentials: CredentialsMode,
+}
+
+/// リクエストされるリソースの種類
+///
+/// コンテンツブロッカーのフィルタ判定などに使用されます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceType {
+    /// トップレベルのドキュメント
+    Document,
+    /// iframe などのサブドキュメント
+    Subdocument,
+    Stylesheet,
+    Script,
+    Image,
+    Font,
+    /// 音声・動画
+    Media,
+    /// object / embed
+    Object,
+    /// XMLHttpRequest / fetch
+    XmlHttpRequest,
+    Ping,
+    WebSocket,
+    Other,
+}
+
+impl Request {
+    pub fn new(method: &str, url: Url) -> Self {
+        Self {
+            method: method.to_string(),
+            url,
+            headers: vec![],
+            body: None,
+            resource_type: ResourceType::Document,
+            initiator: None,
+            origin: None,
+            mode: RequestMode::Navigate,
+            credentials: CredentialsMode::Include,
+        }
+    }
+
+    /// 指定した名前のヘッダーを取得します（大文字小文字を区別しない）
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// ヘッダーを設定します。同名のヘッダーがあれば置き換えます
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+}
+
+/// 1回の送信試行の失敗
+struct AttemptFailure {
+    error: NetworkError,
+    /// プールから再利用した接続での失敗かどうか
+    reused_connection: bool,
+}
+
+impl From<NetworkError> for AttemptFailure {
+    fn from(error: NetworkError) -> Self {
+        Self {
+            error,
+            reused_connection: false,
+        }
+    }
+}
+
 /// キャッシュに保存されるレスポンスエントリを表す構造体
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -57,6 +157,12 @@ pub struct NetworkCore {
     pub cookie_store: CookieStore,
     /// レスポンスキャッシュ
     pub cache: Cache,
+    /// リクエスト/レスポンスの記録（`enable_network_log`が有効な場合のみ記録）
+    pub network_log: NetworkLog,
+    /// CORS プリフライトの結果のキャッシュ
+    pub preflight_cache: PreflightCache,
+    /// リクエストの送信前後に適用されるミドルウェア
+    middlewares: MiddlewareChain,
 }
 
 #[allow(dead_code)]
@@ -67,43 +173,342 @@ impl NetworkCore {
     ///
     /// # 戻り値
     /// * 成功した場合は`NetworkCore`のインスタンスを返します
-    /// * 初期化に失敗した場合は`anyhow::Error`を返します
-    pub fn new() -> Result<Self> {
+    /// * 初期化に失敗した場合は`NetworkError`を返します
+    pub fn new() -> NetworkResult<Self> {
+        Self::with_middlewares(vec![])
+    }
+
+    /// ミドルウェアを登録したNetworkCoreインスタンスを作成します
+    ///
+    /// ミドルウェアは登録順にリクエストを処理し、逆順にレスポンスを処理します。
+    ///
+    /// # 引数
+    /// * `middlewares` - 適用するミドルウェアのリスト
+    ///
+    /// # 戻り値
+    /// * 成功した場合は`NetworkCore`のインスタンスを返します
+    /// * 初期化に失敗した場合は`NetworkError`を返します
+    pub fn with_middlewares(middlewares: Vec<Arc<dyn Middleware>>) -> NetworkResult<Self> {
         let config = NetworkConfig::default();
         Ok(Self {
             config: Arc::new(RwLock::new(config)),
             connection_pool: ConnectionPool::new(),
             cookie_store: CookieStore::new(),
             cache: Cache::new(),
+            network_log: NetworkLog::new(),
+            preflight_cache: PreflightCache::new(),
+            middlewares: MiddlewareChain::new(middlewares),
         })
     }
 
+    /// ミドルウェアを適用してリクエストを送信します
+    ///
+    /// # 引数
+    /// * `request` - 送信するリクエスト
+    /// * `use_cache` - キャッシュを使用するかどうか
+    ///
+    /// # 戻り値
+    /// * 成功した場合は`Response`を返します（ミドルウェアが合成したものを含む）
+    /// * ブロックされた場合は`NetworkError::Blocked`を返します
+    async fn dispatch(&self, mut request: Request, use_cache: bool) -> NetworkResult<Response> {
+        match self.middlewares.process_request(&mut request) {
+            ChainOutcome::Blocked(blocked) => {
+                log::info!("{blocked}");
+                Err(blocked.into())
+            }
+            ChainOutcome::Respond {
+                index,
+                mut response,
+            } => {
+                self.middlewares
+                    .process_response(index, &request, &mut response);
+                Ok(response)
+            }
+            ChainOutcome::Send => {
+                let mut response = self.fetch_with_cors(&request, use_cache).await?;
+                self.middlewares
+                    .process_response(self.middlewares.len(), &request, &mut response);
+                Ok(response)
+            }
+        }
+    }
+
+    /// リクエストモードとオリジンに従って CORS の検査を行い、リクエストを送信します
+    ///
+    /// # 引数
+    /// * `request` - 送信するリクエスト
+    /// * `use_cache` - キャッシュを使用するかどうか
+    ///
+    /// # 戻り値
+    /// * 成功した場合は`Response`を返します（`no-cors`のクロスオリジンではオペークなレスポンス）
+    /// * CORS の検査に失敗した場合は`NetworkError::Cors`を返します
+    async fn fetch_with_cors(&self, request: &Request, use_cache: bool) -> NetworkResult<Response> {
+        let origin = match &request.origin {
+            Some(origin) if request.mode != RequestMode::Navigate => origin,
+            _ => {
+                let credentials = request.credentials != CredentialsMode::Omit;
+                return self
+                    .send_request(
+                        &request.method,
+                        &request.url,
+                        request.headers.clone(),
+                        request.body.clone(),
+                        use_cache.then(|| CacheKey::new(&request.url, None, credentials)),
+                        credentials,
+                    )
+                    .await;
+            }
+        };
+
+        let same_origin = origin.same_origin(&Origin::from_url(&request.url));
+        let credentials = match request.credentials {
+            CredentialsMode::Omit => false,
+            CredentialsMode::SameOrigin => same_origin,
+            CredentialsMode::Include => true,
+        };
+        // オリジンやクレデンシャルの異なるリクエストとはキャッシュを共有しない
+        let cache_key = use_cache.then(|| CacheKey::new(&request.url, Some(origin), credentials));
+
+        let mut headers = request.headers.clone();
+        if same_origin {
+            if !matches!(request.method.to_ascii_uppercase().as_str(), "GET" | "HEAD") {
+                headers.push(("Origin".to_string(), origin.serialize()));
+            }
+            return self
+                .send_request(
+                    &request.method,
+                    &request.url,
+                    headers,
+                    request.body.clone(),
+                    cache_key,
+                    credentials,
+                )
+                .await;
+        }
+
+        match request.mode {
+            RequestMode::SameOrigin => Err(CorsError::SameOriginViolation {
+                url: request.url.clone(),
+            }
+            .into()),
+            RequestMode::NoCors => {
+                if !cors::is_safelisted_method(&request.method) {
+                    return Err(CorsError::MethodNotSafelisted {
+                        method: request.method.clone(),
+                    }
+                    .into());
+                }
+                // セーフリスト外のヘッダーは送信しない
+                headers.retain(|(k, v)| cors::is_safelisted_request_header(k, v));
+                let response = self
+                    .send_request(
+                        &request.method,
+                        &request.url,
+                        headers,
+                        request.body.clone(),
+                        cache_key,
+                        credentials,
+                    )
+                    .await?;
+                Ok(cors::opaque_response(&response))
+            }
+            RequestMode::Cors | RequestMode::Navigate => {
+                if cors::needs_preflight(request)
+                    && !self
+                        .preflight_cache
+                        .allows(origin, request, credentials)
+                        .await
+                {
+                    // プリフライトはクレデンシャルなしで送信する
+                    let preflight = cors::preflight_request(request, origin);
+                    let preflight_response = self
+                        .send_request(
+                            &preflight.method,
+                            &preflight.url,
+                            preflight.headers.clone(),
+                            None,
+                            None,
+                            false,
+                        )
+                        .await?;
+                    cors::handle_preflight_response(
+                        &self.preflight_cache,
+                        request,
+                        &preflight_response,
+                        origin,
+                        credentials,
+                    )
+                    .await?;
+                }
+
+                headers.push(("Origin".to_string(), origin.serialize()));
+                let mut response = self
+                    .send_request(
+                        &request.method,
+                        &request.url,
+                        headers,
+                        request.body.clone(),
+                        cache_key,
+                        credentials,
+                    )
+                    .await?;
+                cors::check_cors_response(&response, origin, credentials)?;
+                cors::filter_cors_response(&mut response, credentials);
+                Ok(response)
+            }
+        }
+    }
+
     /// 汎用 HTTP リクエスト関数
     ///
     /// 指定されたURLにHTTPリクエストを送信し、レスポンスを取得します。
     /// この関数はGET、POST、その他のHTTPメソッドでの通信を処理します。
+    /// 一時的な通信エラーは`NetworkConfig::retry_policy`に従って再試行します。
+    /// オフラインモードではキャッシュ（期限切れのものを含む）からのみ応答します。
     ///
     /// # 引数
     /// * `method` - HTTPメソッド（例: "GET", "POST"）
     /// * `url` - 接続先URL
     /// * `extra_headers` - 追加のHTTPヘッダー
     /// * `body` - リクエストボディ（省略可能）
-    /// * `use_cache` - キャッシュを使用するかどうか
+    /// * `cache_key` - キャッシュを使用する場合のキー（`None`ならキャッシュを使用しない）
+    /// * `with_credentials` - Cookieを送信・保存するかどうか
     ///
     /// # 戻り値
     /// * 成功した場合は`Response`を返します
-    /// * 接続エラーなどの場合は`anyhow::Error`を返します
+    /// * オフラインでキャッシュがない場合は`NetworkError::Offline`を返します
+    /// * 接続エラーなどの場合は`NetworkError`を返します
     async fn send_request(
         &self,
         method: &str,
         url: &Url,
         extra_headers: Vec<(String, String)>,
         body: Option<Vec<u8>>,
-        use_cache: bool,
-    ) -> Result<Response> {
+        cache_key: Option<CacheKey>,
+        with_credentials: bool,
+    ) -> NetworkResult<Response> {
+        let started_at = SystemTime::now();
+        let (log_enabled, offline, retry_policy) = {
+            let cfg = self.config.read().await;
+            (
+                cfg.enable_network_log,
+                cfg.offline,
+                cfg.retry_policy.clone(),
+            )
+        };
+
+        // GET キャッシュチェック（オフライン時は期限切れのエントリも使う）
+        if let Some(key) = &cache_key {
+            let cached = if offline {
+                self.cache.get_stale(key).await
+            } else {
+                self.cache.get(key).await
+            };
+            if let Some(cached) = cached {
+                let response = Response {
+                    http_version: "HTTP/1.1".to_string(),
+                    status_code: 200,
+                    reason_phrase: "OK (cache)".to_string(),
+                    headers: cached.headers,
+                    body: cached.body,
+                };
+                if log_enabled {
+                    self.network_log
+                        .record(NetworkLogEntry {
+                            started_at,
+                            method: method.to_string(),
+                            url: url.clone(),
+                            request_headers: vec![],
+                            request_body_size: 0,
+                            http_version: response.http_version.clone(),
+                            status_code: response.status_code,
+                            reason_phrase: response.reason_phrase.clone(),
+                            response_headers: response.headers.clone(),
+                            response_body_size: response.body.len(),
+                            timings: RequestTimings::default(),
+                            cache_hit: true,
+                            connection_reused: false,
+                        })
+                        .await;
+                }
+                return Ok(response);
+            }
+        }
+
+        if offline {
+            return Err(NetworkError::Offline { url: url.clone() });
+        }
+
+        let mut attempt = 1;
+        loop {
+            let failure = match self
+                .send_request_once(
+                    method,
+                    url,
+                    &extra_headers,
+                    body.as_deref(),
+                    cache_key.as_ref(),
+                    with_credentials,
+                )
+                .await
+            {
+                Ok(response) => return Ok(response),
+                Err(failure) => failure,
+            };
+
+            let retryable = failure
+                .error
+                .retry_condition()
+                .is_some_and(|c| retry_policy.allows(c, method));
+            if !retryable {
+                return Err(failure.error);
+            }
+            // 切断済みの keep-alive 接続だった場合は、試行回数に数えずすぐに新しい接続でやり直す
+            // （失敗した接続は破棄されるため、プール内の接続数を超えて繰り返すことはない）
+            if failure.reused_connection
+                && matches!(failure.error, NetworkError::ConnectionReset { .. })
+            {
+                log::debug!(
+                    "Retrying {method} {url} on a fresh connection: {}",
+                    failure.error
+                );
+                continue;
+            }
+            if attempt >= retry_policy.max_attempts {
+                return Err(failure.error);
+            }
+            let delay = retry_policy.backoff(attempt);
+            log::warn!(
+                "Retrying {method} {url} in {delay:?} (attempt {}/{}): {}",
+                attempt + 1,
+                retry_policy.max_attempts,
+                failure.error
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// HTTPリクエストを1回だけ送信します
+    ///
+    /// # 戻り値
+    /// * 成功した場合は`Response`を返します
+    /// * 失敗した場合は、再試行できるかどうかの判断材料を含む`AttemptFailure`を返します
+    async fn send_request_once(
+        &self,
+        method: &str,
+        url: &Url,
+        extra_headers: &[(String, String)],
+        body: Option<&[u8]>,
+        cache_key: Option<&CacheKey>,
+        with_credentials: bool,
+    ) -> Result<Response, AttemptFailure> {
         let host = url
             .host_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid host"))?
+            .ok_or_else(|| NetworkError::InvalidUrl {
+                url: url.to_string(),
+                source: None,
+            })?
             .to_string();
         let port = url.port_or_known_default().unwrap_or(80);
         let key = HostKey {
@@ -112,88 +517,70 @@ impl NetworkCore {
             port,
         };
 
-        // GET キャッシュチェック
-        if use_cache {
-            if let Some(cached) = self.cache.get(url).await {
-                return Ok(Response {
-                    http_version: "HTTP/1.1".to_string(),
-                    status_code: 200,
-                    reason_phrase: "OK (cache)".to_string(),
-                    headers: cached.headers,
-                    body: cached.body,
-                });
-            }
-        }
+        let started_at = SystemTime::now();
+        let log_enabled = self.config.read().await.enable_network_log;
 
         // Connection取得
-        let mut conn = match self.connection_pool.get_connection(&key).await {
-            Some(c) => c,
+        let (mut conn, connection_reused) = match self.connection_pool.get_connection(&key).await {
+            Some(c) => (c, true),
             None => {
-                let cfg = self.config.read().await;
-                if url.scheme() == "https" {
-                    Connection::Tls(TlsConnection::connect(&host, port, cfg.connect_timeout).await?)
+                let timeout = self.config.read().await.connect_timeout;
+                let conn = if url.scheme() == "https" {
+                    TlsConnection::connect(&host, port, timeout)
+                        .await
+                        .map(Connection::Tls)
                 } else {
-                    Connection::Tcp(TcpConnection::connect(&host, port, cfg.connect_timeout).await?)
-                }
+                    TcpConnection::connect(&host, port, timeout)
+                        .await
+                        .map(Connection::Tcp)
+                };
+                (conn?, false)
             }
         };
 
         // Cookie
-        let cookie_header = self.cookie_store.get_cookie_header(url).await;
+        let use_cookies = with_credentials && self.config.read().await.enable_cookies;
+        let cookie_header = if use_cookies {
+            self.cookie_store.get_cookie_header(url).await
+        } else {
+            None
+        };
 
         // ヘッダ作成
-        let mut request = format!(
-            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: keep-alive\r\nUser-Agent: {}\r\n",
-            method,
-            url.path(),
-            host,
-            self.config.read().await.user_agent
-        );
-
-        for (k, v) in extra_headers {
-            request.push_str(&format!("{k}: {v}\r\n"));
-        }
+        let mut request_headers = vec![
+            ("Host".to_string(), host.clone()),
+            ("Connection".to_string(), "keep-alive".to_string()),
+            (
+                "User-Agent".to_string(),
+                self.config.read().await.user_agent.clone(),
+            ),
+        ];
+        request_headers.extend(extra_headers.iter().cloned());
 
         if let Some(cookie) = cookie_header {
-            request.push_str(&format!("Cookie: {cookie}\r\n"));
+            request_headers.push(("Cookie".to_string(), cookie));
         }
 
-        if let Some(ref b) = body {
-            request.push_str(&format!("Content-Length: {}\r\n", b.len()));
+        if let Some(b) = body {
+            request_headers.push(("Content-Length".to_string(), b.len().to_string()));
+        }
+
+        let mut request = format!("{} {} HTTP/1.1\r\n", method, url.path());
+        for (k, v) in &request_headers {
+            request.push_str(&format!("{k}: {v}\r\n"));
         }
         request.push_str("\r\n"); // ヘッダ終端
 
         // 送信 & レスポンス受信
-        let (headers, body) = match &mut conn {
-            Connection::Tcp(c) => {
-                c.stream.write_all(request.as_bytes()).await?;
-                if let Some(body_bytes) = body.clone() {
-                    c.stream.write_all(&body_bytes).await?;
-                }
-                let (body_start, headers) = Self::read_headers(&mut c.stream).await?;
-                let content_length = headers
-                    .iter()
-                    .find(|(k, _)| k.to_lowercase() == "content-length")
-                    .and_then(|(_, v)| v.parse::<usize>().ok())
-                    .unwrap_or(0);
-                let body = Self::read_body(&mut c.stream, body_start, content_length).await?;
-                (headers, body)
-            }
-            Connection::Tls(c) => {
-                c.stream.write_all(request.as_bytes()).await?;
-                if let Some(body_bytes) = body.clone() {
-                    c.stream.write_all(&body_bytes).await?;
-                }
-                let (body_start, headers) = Self::read_headers(&mut c.stream).await?;
-                let content_length = headers
-                    .iter()
-                    .find(|(k, _)| k.to_lowercase() == "content-length")
-                    .and_then(|(_, v)| v.parse::<usize>().ok())
-                    .unwrap_or(0);
-                let body = Self::read_body(&mut c.stream, body_start, content_length).await?;
-                (headers, body)
-            }
+        let exchanged = match &mut conn {
+            Connection::Tcp(c) => Self::exchange(&mut c.stream, &request, body).await,
+            Connection::Tls(c) => Self::exchange(&mut c.stream, &request, body).await,
         };
+        let (headers, response_body, send, wait, receive) =
+            exchanged.map_err(|e| AttemptFailure {
+                error: NetworkError::from_exchange(e),
+                reused_connection: connection_reused,
+            })?;
 
         // ステータス行パース
         let status_line = headers
@@ -210,22 +597,66 @@ impl NetworkCore {
             .split_whitespace()
             .nth(1)
             .and_then(|s| s.parse::<u16>().ok())
-            .unwrap_or(200);
+            .ok_or_else(|| NetworkError::Protocol {
+                message: format!("invalid status line: {status_line:?}"),
+            })?;
         let reason_phrase = status_line.splitn(3, ' ').nth(2).unwrap_or("").to_string();
 
         // Cookie 保存
-        let set_cookie_headers = headers
-            .iter()
-            .filter(|(k, _)| k.to_lowercase() == "set-cookie")
-            .map(|(_, v)| v.clone())
-            .collect::<Vec<_>>();
-        self.cookie_store
-            .set_cookies(url, &set_cookie_headers)
-            .await;
+        if use_cookies {
+            let set_cookie_headers = headers
+                .iter()
+                .filter(|(k, _)| k.to_lowercase() == "set-cookie")
+                .map(|(_, v)| v.clone())
+                .collect::<Vec<_>>();
+            self.cookie_store
+                .set_cookies(url, &set_cookie_headers)
+                .await;
+        }
 
         // Cache 保存（GETのみ）
-        if use_cache {
-            self.cache.set(url, body.clone(), headers.clone()).await;
+        if let Some(key) = cache_key {
+            self.cache
+                .set(key.clone(), response_body.clone(), headers.clone())
+                .await;
+        }
+
+        // ネットワークログ記録
+        if log_enabled {
+            let (dns, connect, tls) = if connection_reused {
+                (None, None, None)
+            } else {
+                let (dns, connect, tls) = conn.connect_timings();
+                (Some(dns), Some(connect), tls)
+            };
+            self.network_log
+                .record(NetworkLogEntry {
+                    started_at,
+                    method: method.to_string(),
+                    url: url.clone(),
+                    request_headers,
+                    request_body_size: body.map_or(0, |b| b.len()),
+                    http_version: http_version.clone(),
+                    status_code,
+                    reason_phrase: reason_phrase.clone(),
+                    response_headers: headers
+                        .iter()
+                        .filter(|(k, _)| k != "Status-Line")
+                        .cloned()
+                        .collect(),
+                    response_body_size: response_body.len(),
+                    timings: RequestTimings {
+                        dns,
+                        connect,
+                        tls,
+                        send,
+                        wait,
+                        receive,
+                    },
+                    cache_hit: false,
+                    connection_reused,
+                })
+                .await;
         }
 
         // Connection プールに戻す
@@ -236,10 +667,66 @@ impl NetworkCore {
             status_code,
             reason_phrase,
             headers,
-            body,
+            body: response_body,
         })
     }
 
+    /// リクエストを送信し、レスポンスのヘッダーとボディを受信します
+    ///
+    /// # 引数
+    /// * `stream` - 送受信に使うストリーム
+    /// * `request` - リクエスト行とヘッダー
+    /// * `body` - リクエストボディ（省略可能）
+    ///
+    /// # 戻り値
+    /// * `(ヘッダー, ボディ, 送信時間, 応答待ち時間, 受信時間)` を返します
+    /// * 送受信エラーの場合は`io::Error`を返します
+    async fn exchange<S>(
+        stream: &mut S,
+        request: &str,
+        body: Option<&[u8]>,
+    ) -> io::Result<(Vec<(String, String)>, Vec<u8>, Duration, Duration, Duration)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let send_start = Instant::now();
+        stream.write_all(request.as_bytes()).await?;
+        if let Some(body_bytes) = body {
+            stream.write_all(body_bytes).await?;
+        }
+        let send = send_start.elapsed();
+
+        let wait_start = Instant::now();
+        let (body_start, headers) = Self::read_headers(stream).await?;
+        let wait = wait_start.elapsed();
+
+        let receive_start = Instant::now();
+        let content_length = headers
+            .iter()
+            .find(|(k, _)| k.to_lowercase() == "content-length")
+            .and_then(|(_, v)| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        let body = Self::read_body(stream, body_start, content_length).await?;
+        let receive = receive_start.elapsed();
+
+        Ok((headers, body, send, wait, receive))
+    }
+
+    /// 任意のリクエストを送信します
+    ///
+    /// ミドルウェアを適用した上で送信します。GETリクエストのみキャッシュを使用します。
+    ///
+    /// # 引数
+    /// * `request` - 送信するリクエスト
+    ///
+    /// # 戻り値
+    /// * 成功した場合は`Response`を返します
+    /// * 接続エラーやブロックされた場合は`NetworkError`を返します
+    pub async fn send(&self, request: Request) -> NetworkResult<Response> {
+        let use_cache = request.method.eq_ignore_ascii_case("GET");
+        self.dispatch(request, use_cache).await
+    }
+
     /// GET要求を送信し、結果を取得します（キャッシュを使用）
     ///
     /// 指定されたURLにGETリクエストを送信し、レスポンスを返します。
@@ -250,10 +737,38 @@ impl NetworkCore {
     ///
     /// # 戻り値
     /// * 成功した場合は`Response`を返します
-    /// * URL解析エラーや接続エラーなどの場合は`anyhow::Error`を返します
-    pub async fn fetch(&self, url: &str) -> Result<Response> {
-        let url = Url::parse(url)?;
-        self.send_request("GET", &url, vec![], None, true).await
+    /// * URL解析エラーや接続エラーなどの場合は`NetworkError`を返します
+    pub async fn fetch(&self, url: &str) -> NetworkResult<Response> {
+        let url = parse_url(url)?;
+        self.dispatch(Request::new("GET", url), true).await
+    }
+
+    /// ドキュメントからサブリソースを取得します
+    ///
+    /// リクエスト元のドキュメントのオリジンとリクエストモードに従って、
+    /// 同一オリジンポリシーと CORS の検査を行います。
+    /// クレデンシャルは同一オリジンの場合のみ送信されます。
+    ///
+    /// # 引数
+    /// * `url` - 取得するURL（文字列）
+    /// * `origin` - リクエスト元のドキュメントのオリジン
+    /// * `mode` - リクエストモード
+    ///
+    /// # 戻り値
+    /// * 成功した場合は`Response`を返します
+    /// * URL解析エラー、接続エラー、CORS の検査に失敗した場合は`NetworkError`を返します
+    pub async fn fetch_from(
+        &self,
+        url: &str,
+        origin: &Origin,
+        mode: RequestMode,
+    ) -> NetworkResult<Response> {
+        let mut request = Request::new("GET", parse_url(url)?);
+        request.origin = Some(origin.clone());
+        request.mode = mode;
+        request.credentials = CredentialsMode::SameOrigin;
+        request.resource_type = ResourceType::Other;
+        self.send(request).await
     }
 
     /// POSTリクエストを送信します（キャッシュなし）
@@ -268,12 +783,17 @@ impl NetworkCore {
     ///
     /// # 戻り値
     /// * 成功した場合は`Response`を返します
-    /// * URL解析エラーや接続エラーなどの場合は`anyhow::Error`を返します
-    pub async fn post(&self, url: &str, body: Vec<u8>, content_type: &str) -> Result<Response> {
-        let url = Url::parse(url)?;
-        let headers = vec![("Content-Type".to_string(), content_type.to_string())];
-        self.send_request("POST", &url, headers, Some(body), false)
-            .await
+    /// * URL解析エラーや接続エラーなどの場合は`NetworkError`を返します
+    pub async fn post(
+        &self,
+        url: &str,
+        body: Vec<u8>,
+        content_type: &str,
+    ) -> NetworkResult<Response> {
+        let mut request = Request::new("POST", parse_url(url)?);
+        request.set_header("Content-Type", content_type);
+        request.body = Some(body);
+        self.dispatch(request, false).await
     }
 
     /// HTTPヘッダーを読み取ります
@@ -298,6 +818,13 @@ impl NetworkCore {
         loop {
             line.clear();
             let n = reader.read_line(&mut line).await?;
+            if n == 0 && headers.is_empty() {
+                // ステータス行を受け取る前に切断された（閉じられた keep-alive 接続など）
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed before response",
+                ));
+            }
             if n == 0 || line == "\r\n" {
                 break;
             }
@@ -324,12 +851,12 @@ impl NetworkCore {
     ///
     /// # 戻り値
     /// * 成功した場合は`Vec<u8>`としてボディデータを返します
-    /// * 読み取りエラーの場合は`anyhow::Error`を返します
+    /// * 読み取りエラーの場合は`io::Error`を返します
     async fn read_body<R>(
         stream: &mut R,
         body_start: Vec<u8>,
         content_length: usize,
-    ) -> anyhow::Result<Vec<u8>>
+    ) -> io::Result<Vec<u8>>
     where
         R: AsyncRead + Unpin,
     {
@@ -346,3 +873,11 @@ impl NetworkCore {
         Ok(body)
     }
 }
+
+/// URL文字列を解析します
+fn parse_url(url: &str) -> NetworkResult<Url> {
+    Url::parse(url).map_err(|e| NetworkError::InvalidUrl {
+        url: url.to_string(),
+        source: Some(e),
+    })
+}
@@ -0,0 +1,261 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use url::Url;
+
+/// 1リクエスト分の各フェーズの所要時間
+///
+/// 接続を再利用した場合やキャッシュから応答した場合、
+/// 該当しないフェーズは`None`になります。
+#[derive(Debug, Clone, Default)]
+pub struct RequestTimings {
+    /// 名前解決
+    pub dns: Option<Duration>,
+    /// TCP接続（TLSハンドシェイクは含まない）
+    pub connect: Option<Duration>,
+    /// TLSハンドシェイク
+    pub tls: Option<Duration>,
+    /// リクエストの送信
+    pub send: Duration,
+    /// 送信完了からレスポンスヘッダー受信まで（first byte）
+    pub wait: Duration,
+    /// レスポンスボディのダウンロード
+    pub receive: Duration,
+}
+
+impl RequestTimings {
+    /// 全フェーズの合計時間を返します
+    pub fn total(&self) -> Duration {
+        self.dns.unwrap_or_default()
+            + self.connect.unwrap_or_default()
+            + self.tls.unwrap_or_default()
+            + self.send
+            + self.wait
+            + self.receive
+    }
+}
+
+/// ネットワークログに記録される1件のリクエスト/レスポンス
+#[derive(Debug, Clone)]
+pub struct NetworkLogEntry {
+    /// リクエスト開始時刻
+    pub started_at: SystemTime,
+    /// HTTPメソッド
+    pub method: String,
+    /// リクエストURL
+    pub url: Url,
+    /// 送信したリクエストヘッダー
+    pub request_headers: Vec<(String, String)>,
+    /// リクエストボディのサイズ（バイト）
+    pub request_body_size: usize,
+    /// レスポンスのHTTPバージョン
+    pub http_version: String,
+    /// HTTPステータスコード
+    pub status_code: u16,
+    /// ステータスコードの説明文
+    pub reason_phrase: String,
+    /// 受信したレスポンスヘッダー
+    pub response_headers: Vec<(String, String)>,
+    /// レスポンスボディのサイズ（バイト）
+    pub response_body_size: usize,
+    /// 各フェーズの所要時間
+    pub timings: RequestTimings,
+    /// キャッシュから応答したかどうか
+    pub cache_hit: bool,
+    /// コネクションプールの接続を再利用したかどうか
+    pub connection_reused: bool,
+}
+
+impl NetworkLogEntry {
+    /// 指定した名前のレスポンスヘッダーを取得します（大文字小文字を区別しない）
+    pub fn response_header(&self, name: &str) -> Option<&str> {
+        self.response_headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// HARの`entries`要素に変換します
+    fn to_har_entry(&self) -> Value {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let opt_ms = |d: Option<Duration>| d.map(ms).unwrap_or(-1.0);
+        let headers_json = |headers: &[(String, String)]| {
+            headers
+                .iter()
+                .map(|(k, v)| json!({ "name": k, "value": v }))
+                .collect::<Vec<_>>()
+        };
+        let query_string = self
+            .url
+            .query_pairs()
+            .map(|(k, v)| json!({ "name": k, "value": v }))
+            .collect::<Vec<_>>();
+        let mime_type = self.response_header("content-type").unwrap_or("");
+        let redirect_url = self.response_header("location").unwrap_or("");
+
+        // HAR の connect は TLS ハンドシェイクの時間を含む
+        let connect = match (self.timings.connect, self.timings.tls) {
+            (Some(c), Some(t)) => Some(c + t),
+            (c, _) => c,
+        };
+
+        json!({
+            "startedDateTime": format_iso8601(self.started_at),
+            "time": ms(self.timings.total()),
+            "request": {
+                "method": self.method,
+                "url": self.url.as_str(),
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": headers_json(&self.request_headers),
+                "queryString": query_string,
+                "headersSize": headers_size(&self.request_headers),
+                "bodySize": self.request_body_size,
+            },
+            "response": {
+                "status": self.status_code,
+                "statusText": self.reason_phrase,
+                "httpVersion": self.http_version,
+                "cookies": [],
+                "headers": headers_json(&self.response_headers),
+                "content": {
+                    "size": self.response_body_size,
+                    "mimeType": mime_type,
+                },
+                "redirectURL": redirect_url,
+                "headersSize": if self.cache_hit { -1 } else { headers_size(&self.response_headers) as i64 },
+                "bodySize": if self.cache_hit { 0 } else { self.response_body_size },
+            },
+            "cache": {},
+            "timings": {
+                "blocked": -1,
+                "dns": opt_ms(self.timings.dns),
+                "connect": opt_ms(connect),
+                "ssl": opt_ms(self.timings.tls),
+                "send": ms(self.timings.send),
+                "wait": ms(self.timings.wait),
+                "receive": ms(self.timings.receive),
+            },
+            "_fromCache": self.cache_hit,
+            "_connectionReused": self.connection_reused,
+        })
+    }
+}
+
+/// リクエスト/レスポンスの記録を保持するネットワークログ
+///
+/// `NetworkConfig::enable_network_log`が有効な場合に`NetworkCore`から記録されます。
+/// 記録内容はHAR 1.2形式のJSONとして書き出すことができます。
+#[derive(Debug, Clone, Default)]
+pub struct NetworkLog {
+    entries: Arc<RwLock<Vec<NetworkLogEntry>>>,
+}
+
+impl NetworkLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// エントリを追加します
+    pub async fn record(&self, entry: NetworkLogEntry) {
+        self.entries.write().await.push(entry);
+    }
+
+    /// 記録されたすべてのエントリを記録順に返します
+    pub async fn entries(&self) -> Vec<NetworkLogEntry> {
+        self.entries.read().await.clone()
+    }
+
+    /// 条件に一致するエントリのみを返します
+    ///
+    /// # 引数
+    /// * `predicate` - エントリを残す場合に`true`を返す関数
+    pub async fn filter<F>(&self, predicate: F) -> Vec<NetworkLogEntry>
+    where
+        F: Fn(&NetworkLogEntry) -> bool,
+    {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .filter(|e| predicate(e))
+            .cloned()
+            .collect()
+    }
+
+    /// 指定したホストへのリクエストのみを返します
+    pub async fn entries_for_host(&self, host: &str) -> Vec<NetworkLogEntry> {
+        self.filter(|e| e.url.host_str() == Some(host)).await
+    }
+
+    /// 記録されたエントリ数を返します
+    pub async fn len(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.entries.read().await.is_empty()
+    }
+
+    /// 記録をすべて削除します
+    pub async fn clear(&self) {
+        self.entries.write().await.clear();
+    }
+
+    /// 記録内容をHAR 1.2形式のJSON文字列として書き出します
+    pub async fn to_har(&self) -> String {
+        let entries = self
+            .entries
+            .read()
+            .await
+            .iter()
+            .map(NetworkLogEntry::to_har_entry)
+            .collect::<Vec<_>>();
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": "Orinium Browser",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "pages": [],
+                "entries": entries,
+            }
+        });
+        serde_json::to_string_pretty(&har).unwrap_or_default()
+    }
+}
+
+/// ヘッダーを "Name: value\r\n" 形式で送った場合のバイト数（終端の空行を含む）
+fn headers_size(headers: &[(String, String)]) -> usize {
+    headers
+        .iter()
+        .map(|(k, v)| k.len() + v.len() + 4)
+        .sum::<usize>()
+        + 2
+}
+
+/// `SystemTime`をISO 8601形式（UTC, ミリ秒精度）の文字列に変換します
+fn format_iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let millis = since_epoch.subsec_millis();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (hour, minute, second) = (rem / 3600, (rem % 3600) / 60, rem % 60);
+
+    // 1970-01-01 からの日数を年月日に変換 (Howard Hinnant の civil_from_days)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}.{millis:03}Z")
+}
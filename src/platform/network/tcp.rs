@@ -1,6 +1,9 @@
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::net::TcpStream;
+use tokio::net::{lookup_host, TcpStream};
+
+use crate::platform::network::error::{NetworkError, NetworkResult, TimeoutPhase};
 
 /// TCP接続を管理する構造体
 ///
@@ -9,6 +12,10 @@ use tokio::net::TcpStream;
 pub struct TcpConnection {
     /// 内部のTCPストリーム
     pub stream: TcpStream,
+    /// 名前解決にかかった時間
+    pub dns_time: Duration,
+    /// TCPハンドシェイクにかかった時間
+    pub connect_time: Duration,
 }
 
 impl TcpConnection {
@@ -21,11 +28,55 @@ impl TcpConnection {
     ///
     /// # 戻り値
     /// * 成功した場合は`TcpConnection`のインスタンスを返します
-    /// * タイムアウトまたは接続エラーの場合は`anyhow::Error`を返します
-    pub async fn connect(host: &str, port: u16, timeout: Duration) -> anyhow::Result<Self> {
-        let addr = format!("{host}:{port}");
-        let stream = tokio::time::timeout(timeout, TcpStream::connect(addr)).await??;
-        Ok(Self { stream })
+    /// * 名前解決の失敗、接続拒否、タイムアウトなどの場合は`NetworkError`を返します
+    pub async fn connect(host: &str, port: u16, timeout: Duration) -> NetworkResult<Self> {
+        let timed_out = |phase| NetworkError::Timeout {
+            host: host.to_string(),
+            port,
+            phase,
+        };
+
+        // 名前解決と接続を分けて計測する（ネットワークログのタイミング用）
+        let dns_start = Instant::now();
+        let addrs: Vec<SocketAddr> = tokio::time::timeout(timeout, lookup_host((host, port)))
+            .await
+            .map_err(|_| timed_out(TimeoutPhase::Dns))?
+            .map_err(|source| NetworkError::Dns {
+                host: host.to_string(),
+                source,
+            })?
+            .collect();
+        if addrs.is_empty() {
+            return Err(NetworkError::Dns {
+                host: host.to_string(),
+                source: std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses found"),
+            });
+        }
+        let dns_time = dns_start.elapsed();
+
+        let connect_start = Instant::now();
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(&addrs[..]))
+            .await
+            .map_err(|_| timed_out(TimeoutPhase::Connect))?
+            .map_err(|source| match source.kind() {
+                std::io::ErrorKind::ConnectionRefused => NetworkError::ConnectionRefused {
+                    host: host.to_string(),
+                    port,
+                    source,
+                },
+                _ => NetworkError::Connect {
+                    host: host.to_string(),
+                    port,
+                    source,
+                },
+            })?;
+        let connect_time = connect_start.elapsed();
+
+        Ok(Self {
+            stream,
+            dns_time,
+            connect_time,
+        })
     }
 }
 
@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use rustls::pki_types::ServerName;
 use rustls::ClientConfig;
@@ -8,6 +8,7 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_rustls::client::TlsStream;
 use tokio_rustls::TlsConnector;
 
+use crate::platform::network::error::{NetworkError, NetworkResult, TimeoutPhase, TlsErrorKind};
 use crate::platform::network::tcp::TcpConnection;
 
 /// TLS接続を管理するための構造体
@@ -15,6 +16,8 @@ use crate::platform::network::tcp::TcpConnection;
 pub struct TlsConnection {
     /// 内部のTLS暗号化されたストリーム
     pub stream: TlsStream<TcpConnection>,
+    /// TLSハンドシェイクにかかった時間
+    pub handshake_time: Duration,
 }
 
 impl TlsConnection {
@@ -27,19 +30,25 @@ impl TlsConnection {
     ///
     /// # 戻り値
     /// * 成功した場合は`TlsConnection`のインスタンスを返します
-    /// * 証明書検証失敗、タイムアウト、または接続エラーの場合は`anyhow::Error`を返します
+    /// * 証明書検証失敗、タイムアウト、または接続エラーの場合は`NetworkError`を返します
     ///
     /// # エラー
     /// * ホスト名が無効な場合
     /// * 証明書検証に失敗した場合
     /// * 接続がタイムアウトした場合
     /// * 下層のTCP接続に失敗した場合
-    pub async fn connect(host: &str, port: u16, timeout: Duration) -> anyhow::Result<Self> {
+    pub async fn connect(host: &str, port: u16, timeout: Duration) -> NetworkResult<Self> {
         let tcp_conn = TcpConnection::connect(host, port, timeout).await?;
 
+        let root_store_error =
+            |source: Box<dyn std::error::Error + Send + Sync>| NetworkError::Tls {
+                host: host.to_string(),
+                kind: TlsErrorKind::RootStore,
+                source: Some(source),
+            };
         let mut roots = rustls::RootCertStore::empty();
-        for cert in load_native_certs()? {
-            roots.add(cert)?;
+        for cert in load_native_certs().map_err(|e| root_store_error(Box::new(e)))? {
+            roots.add(cert).map_err(|e| root_store_error(Box::new(e)))?;
         }
 
         let config = ClientConfig::builder()
@@ -48,13 +57,28 @@ impl TlsConnection {
 
         let connector = TlsConnector::from(Arc::new(config));
 
-        let server_name = ServerName::try_from(host.to_string())
-            .map_err(|_| anyhow::anyhow!("Invalid DNS name: {}", host))?;
+        let server_name =
+            ServerName::try_from(host.to_string()).map_err(|e| NetworkError::Tls {
+                host: host.to_string(),
+                kind: TlsErrorKind::InvalidDnsName,
+                source: Some(Box::new(e)),
+            })?;
 
-        let stream =
-            tokio::time::timeout(timeout, connector.connect(server_name, tcp_conn)).await??;
+        let handshake_start = Instant::now();
+        let stream = tokio::time::timeout(timeout, connector.connect(server_name, tcp_conn))
+            .await
+            .map_err(|_| NetworkError::Timeout {
+                host: host.to_string(),
+                port,
+                phase: TimeoutPhase::TlsHandshake,
+            })?
+            .map_err(|e| NetworkError::from_tls_handshake(host, e))?;
+        let handshake_time = handshake_start.elapsed();
 
-        Ok(Self { stream })
+        Ok(Self {
+            stream,
+            handshake_time,
+        })
     }
 }
 
@@ -1,5 +1,12 @@
+use std::rc::Rc;
 use std::sync::Arc;
 
+use crate::engine::html::event::{
+    self, DefaultAction, Event, KeyboardEventData, Modifiers, MouseButton as DomMouseButton,
+    MouseEventData,
+};
+use crate::engine::html::parser::{Node, NodeRef};
+use crate::engine::renderer::Renderer;
 use crate::platform::renderer::gpu::GpuRenderer;
 
 #[allow(unused_imports)]
@@ -7,7 +14,7 @@ use winit::{
     application::ApplicationHandler,
     event::*,
     event_loop::{ActiveEventLoop, EventLoop},
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{Key, KeyCode, PhysicalKey},
     window::Window,
 };
 
@@ -18,12 +25,23 @@ pub struct State {
 
 pub struct App {
     state: Option<State>,
+    /// 表示中の文書（入力をイベントとして届ける先）
+    document: Option<NodeRef>,
+    renderer: Renderer,
+    /// ビューポート上のカーソル位置
+    cursor: (f64, f64),
+    modifiers: Modifiers,
+    /// ボタンを押した要素（離したときに同じ要素なら click を送る）
+    pressed: Option<NodeRef>,
 }
 
 impl State {
     pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
         let gpu_renderer = GpuRenderer::new(window.clone()).await?;
-        Ok(Self { window, gpu_renderer })
+        Ok(Self {
+            window,
+            gpu_renderer,
+        })
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -51,7 +69,90 @@ impl Default for App {
 
 impl App {
     pub fn new() -> Self {
-        Self { state: None }
+        Self {
+            state: None,
+            document: None,
+            renderer: Renderer::new(800.0, 600.0),
+            cursor: (0.0, 0.0),
+            modifiers: Modifiers::default(),
+            pressed: None,
+        }
+    }
+
+    /// 文書を表示するアプリを作成します。マウスとキーボードの入力は文書のイベントとして届けます
+    pub fn with_document(document: NodeRef) -> Self {
+        Self {
+            document: Some(document),
+            ..Self::new()
+        }
+    }
+
+    /// カーソルの下の要素にマウスイベントを送ります
+    ///
+    /// # 戻り値
+    /// * イベントを送った要素
+    fn dispatch_mouse_event(&self, event_type: &str, button: DomMouseButton) -> Option<NodeRef> {
+        let document = self.document.as_ref()?;
+        let (x, y) = self.cursor;
+        let target = self.renderer.hit_test(document, x as f32, y as f32)?;
+        let mut event = Event::mouse(
+            event_type,
+            MouseEventData {
+                client_x: x,
+                client_y: y,
+                button,
+                modifiers: self.modifiers,
+            },
+        );
+        if let Some(action) = event::dispatch_event(&target, &mut event) {
+            run_default_action(&document.borrow(), action);
+        }
+        Some(target)
+    }
+
+    /// body 要素にキーボードイベントを送ります
+    ///
+    /// # 戻り値
+    /// * 既定の動作が取り消されたか（文書がない場合は`false`）
+    fn dispatch_keyboard_event(&self, event_type: &str, data: KeyboardEventData) -> bool {
+        let Some(document) = &self.document else {
+            return false;
+        };
+        let body = document.borrow().get_elements_by_tag_name("body").pop();
+        let Some(target) = body else {
+            return false;
+        };
+        let mut event = Event::keyboard(event_type, data);
+        event::dispatch_event(&target, &mut event);
+        event.default_prevented()
+    }
+}
+
+/// 既定の動作を行います（読み込み処理につながるまではログに出すだけ）
+///
+/// リンク先と送信先は文書の基底 URL で解決します。解決できない場合は元の値を出します。
+fn run_default_action(document: &Node, action: DefaultAction) {
+    let resolve = |href: &str| {
+        document
+            .resolve_url(href)
+            .map_or_else(|| href.to_string(), String::from)
+    };
+    match action {
+        DefaultAction::FollowLink { href, .. } => log::info!("Follow link: {}", resolve(&href)),
+        DefaultAction::SubmitForm { form, .. } => log::info!(
+            "Submit form: {}",
+            resolve(form.borrow().attribute("action").unwrap_or_default())
+        ),
+    }
+}
+
+fn dom_mouse_button(button: MouseButton) -> DomMouseButton {
+    match button {
+        MouseButton::Middle => DomMouseButton::Auxiliary,
+        MouseButton::Right => DomMouseButton::Secondary,
+        MouseButton::Back => DomMouseButton::Back,
+        MouseButton::Forward => DomMouseButton::Forward,
+        _ => DomMouseButton::Primary,
     }
 }
 
@@ -83,20 +184,77 @@ impl ApplicationHandler<State> for App {
 
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            WindowEvent::Resized(size) => {
+                state.resize(size.width, size.height);
+                self.renderer.resize(size.width as f32, size.height as f32);
+            }
             WindowEvent::RedrawRequested => {
                 state.render().ok();
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let modifiers = modifiers.state();
+                self.modifiers = Modifiers {
+                    shift: modifiers.shift_key(),
+                    ctrl: modifiers.control_key(),
+                    alt: modifiers.alt_key(),
+                    meta: modifiers.super_key(),
+                };
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x, position.y);
+                self.dispatch_mouse_event("mousemove", DomMouseButton::Primary);
+            }
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => {
+                let button = dom_mouse_button(button);
+                if button_state.is_pressed() {
+                    self.pressed = self.dispatch_mouse_event("mousedown", button);
+                } else {
+                    let released = self.dispatch_mouse_event("mouseup", button);
+                    let pressed = self.pressed.take();
+                    if let (Some(pressed), Some(released)) = (pressed, released) {
+                        if button == DomMouseButton::Primary && Rc::ptr_eq(&pressed, &released) {
+                            self.dispatch_mouse_event("click", button);
+                        }
+                    }
+                }
+            }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
-                        physical_key: PhysicalKey::Code(code),
+                        physical_key,
+                        logical_key,
                         state: key_state,
+                        repeat,
                         ..
                     },
                 ..
             } => {
-                if let (KeyCode::Escape, true) = (code, key_state.is_pressed()) {
+                let data = KeyboardEventData {
+                    key: match &logical_key {
+                        Key::Character(text) => text.to_string(),
+                        Key::Named(named) => format!("{named:?}"),
+                        _ => "Unidentified".to_string(),
+                    },
+                    code: match physical_key {
+                        PhysicalKey::Code(code) => format!("{code:?}"),
+                        PhysicalKey::Unidentified(_) => String::new(),
+                    },
+                    repeat,
+                    modifiers: self.modifiers,
+                };
+                let event_type = if key_state.is_pressed() {
+                    "keydown"
+                } else {
+                    "keyup"
+                };
+                let canceled = self.dispatch_keyboard_event(event_type, data);
+                if let (PhysicalKey::Code(KeyCode::Escape), true, false) =
+                    (physical_key, key_state.is_pressed(), canceled)
+                {
                     event_loop.exit()
                 }
             }
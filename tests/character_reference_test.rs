@@ -0,0 +1,107 @@
+use std::borrow::Cow;
+
+use orinium_browser::engine::html::entities;
+use orinium_browser::engine::html::tokenizer::{Attribute, Token, Tokenizer};
+
+fn text_of(input: &str) -> String {
+    let mut tokenizer = Tokenizer::new(input);
+    let mut text = String::new();
+    while let Some(token) = tokenizer.next_token() {
+        if let Token::Text(t) = token {
+            text.push_str(&t);
+        }
+    }
+    text
+}
+
+fn attribute_of(input: &str) -> String {
+    let mut tokenizer = Tokenizer::new(input);
+    match tokenizer.next_token() {
+        Some(Token::StartTag { attributes, .. }) => attributes
+            .into_iter()
+            .next()
+            .map(|Attribute { value, .. }| value.into_owned())
+            .unwrap_or_default(),
+        other => panic!("unexpected token: {other:?}"),
+    }
+}
+
+#[test]
+fn test_entity_table() {
+    assert_eq!(entities::NAMED_CHARACTER_REFERENCES.len(), 2231);
+    assert_eq!(entities::lookup("amp;"), Some("&"));
+    assert_eq!(entities::lookup("amp"), Some("&"));
+    assert_eq!(entities::lookup("nbsp;"), Some("\u{a0}"));
+    // 2つのコードポイントに展開されるもの
+    assert_eq!(entities::lookup("NotEqualTilde;"), Some("\u{2242}\u{338}"));
+    assert_eq!(entities::lookup("nbsp"), Some("\u{a0}"));
+    assert_eq!(entities::lookup("notanentity;"), None);
+}
+
+#[test]
+fn test_named_references_in_text() {
+    assert_eq!(text_of("a &amp; b"), "a & b");
+    assert_eq!(text_of("&lt;p&gt;"), "<p>");
+    assert_eq!(text_of("&nbsp;&copy;"), "\u{a0}\u{a9}");
+    // 最長一致: `&notin;` は `∉`、`&notit;` は `¬it;`
+    assert_eq!(text_of("&notin;"), "\u{2209}");
+    assert_eq!(text_of("&notit;"), "\u{ac}it;");
+    // `;` のないレガシーな参照
+    assert_eq!(text_of("&copy 2024"), "\u{a9} 2024");
+    // 一致しないものはそのまま
+    assert_eq!(text_of("&foo; & &;"), "&foo; & &;");
+    assert_eq!(text_of("AT&T"), "AT&T");
+}
+
+#[test]
+fn test_numeric_references() {
+    assert_eq!(text_of("&#65;&#x42;&#X43"), "ABC");
+    assert_eq!(text_of("&#x3042;&#12354;"), "\u{3042}\u{3042}");
+    assert_eq!(text_of("&#x1F600;"), "\u{1F600}");
+    // 置き換え規則
+    assert_eq!(text_of("&#0;"), "\u{FFFD}");
+    assert_eq!(text_of("&#xD800;"), "\u{FFFD}");
+    assert_eq!(text_of("&#x110000;"), "\u{FFFD}");
+    assert_eq!(text_of("&#99999999999999999999;"), "\u{FFFD}");
+    assert_eq!(text_of("&#128;&#x99;"), "\u{20AC}\u{2122}");
+    // 数字がない場合はそのまま
+    assert_eq!(text_of("&#; &#x; &#xg"), "&#; &#x; &#xg");
+}
+
+#[test]
+fn test_references_in_attributes() {
+    assert_eq!(attribute_of(r#"<a title="a&amp;b">"#), "a&b");
+    assert_eq!(attribute_of("<a title='&lt;&#x3042;'>"), "<\u{3042}");
+    assert_eq!(attribute_of("<a title=&quot;x&quot;>"), "\"x\"");
+    // `;` のない参照の後ろに `=` や英数字が続く場合は展開しない
+    assert_eq!(
+        attribute_of(r#"<a href="/?a=1&copy=2&not=3">"#),
+        "/?a=1&copy=2&not=3"
+    );
+    assert_eq!(attribute_of(r#"<a href="/?x&copyright">"#), "/?x&copyright");
+    // それ以外の場合は展開する
+    assert_eq!(attribute_of(r#"<a title="&copy 2024">"#), "\u{a9} 2024");
+    assert_eq!(attribute_of(r#"<a href="/?a&copy;=2">"#), "/?a\u{a9}=2");
+}
+
+#[test]
+fn test_undecoded_values_borrow_from_input() {
+    let mut tokenizer = Tokenizer::new("a <p class=x title='&amp;'>b &amp; c<!--d-->");
+    assert!(matches!(
+        tokenizer.next_token(),
+        Some(Token::Text(Cow::Borrowed("a ")))
+    ));
+    match tokenizer.next_token() {
+        Some(Token::StartTag { attributes, .. }) => {
+            assert!(matches!(attributes[0].value, Cow::Borrowed("x")));
+            // 文字参照を含む値は展開結果を持つ
+            assert!(matches!(attributes[1].value, Cow::Owned(ref v) if v == "&"));
+        }
+        other => panic!("unexpected token: {other:?}"),
+    }
+    assert!(matches!(tokenizer.next_token(), Some(Token::Text(Cow::Owned(ref t))) if t == "b & c"));
+    assert!(matches!(
+        tokenizer.next_token(),
+        Some(Token::Comment(Cow::Borrowed("d")))
+    ));
+}
@@ -0,0 +1,75 @@
+//! 結合テスト用の共通ヘルパー
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// ローカルにHTTPサーバーを起動し、ポート番号を返す
+///
+/// `handler` はリクエストのヘッダー部分（リクエスト行を含む）とボディを受け取り、
+/// ステータス行から始まるレスポンス全体を返す。keep-alive 接続上の複数リクエストにも応答する。
+#[allow(dead_code)]
+pub async fn spawn_http_server<F>(handler: F) -> u16
+where
+    F: Fn(&str, &[u8]) -> String + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let handler = Arc::clone(&handler);
+            tokio::spawn(async move {
+                let mut pending = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    // ヘッダー終端まで読み込む
+                    let head_end = loop {
+                        if let Some(pos) = pending.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break pos + 4;
+                        }
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => pending.extend_from_slice(&buf[..n]),
+                        }
+                    };
+                    let head = String::from_utf8_lossy(&pending[..head_end]).to_string();
+                    let content_length = head
+                        .lines()
+                        .find_map(|l| {
+                            let (k, v) = l.split_once(':')?;
+                            k.eq_ignore_ascii_case("content-length")
+                                .then(|| v.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    while pending.len() < head_end + content_length {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => pending.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let body = pending[head_end..head_end + content_length].to_vec();
+                    pending.drain(..head_end + content_length);
+
+                    let response = handler(&head, &body);
+                    if socket.write_all(response.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+/// ステータスコードとボディから簡単なレスポンスを組み立てる
+#[allow(dead_code)]
+pub fn http_response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let mut response = format!("HTTP/1.1 {status}\r\n");
+    for (k, v) in headers {
+        response.push_str(&format!("{k}: {v}\r\n"));
+    }
+    response.push_str(&format!("Content-Length: {}\r\n\r\n{body}", body.len()));
+    response
+}
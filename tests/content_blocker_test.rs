@@ -0,0 +1,300 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{http_response, spawn_http_server};
+use orinium_browser::engine::html::parser::Parser;
+use orinium_browser::engine::renderer::{DrawCommand, Renderer};
+use orinium_browser::platform::network::{
+    BlockDecision, ContentBlocker, NetworkCore, NetworkError, Request, ResourceType,
+};
+use url::Url;
+
+const FILTER_LIST: &str = r#"[Adblock Plus 2.0]
+! Title: test list
+||ads.example.com^
+/banner/*/track.gif$image
+||cdn.example.net/ads.js$script,third-party
+@@||ads.example.com/allowed/*
+||tracker.example.org^$domain=news.example.com|~blog.news.example.com
+|http://exact.example.com/file.js|
+/regex-rule-[0-9]+/
+||unknown.example.com^$some-unknown-option
+##.ad-banner
+news.example.com##div[id^="sponsor"]
+~docs.example.com##.generic-ad
+docs.example.com#@#.ad-banner
+example.com#?#.extended:has(> .ad)
+"#;
+
+fn url(s: &str) -> Url {
+    Url::parse(s).unwrap()
+}
+
+fn check(blocker: &ContentBlocker, target: &str, ty: ResourceType, page: &str) -> BlockDecision {
+    blocker.check(&url(target), ty, Some(&url(page)))
+}
+
+#[test]
+fn test_filter_list_rule_counts() {
+    let blocker = ContentBlocker::from_filter_list(FILTER_LIST);
+    // 正規表現・未知のオプション・拡張構文のルールは読み込まれない
+    assert_eq!(blocker.network_rule_count(), 6);
+    assert_eq!(blocker.cosmetic_rule_count(), 4);
+}
+
+#[test]
+fn test_domain_anchor_and_exceptions() {
+    let blocker = ContentBlocker::from_filter_list(FILTER_LIST);
+    let page = "https://site.test/";
+
+    assert!(check(
+        &blocker,
+        "https://ads.example.com/x.js",
+        ResourceType::Script,
+        page
+    )
+    .is_blocked());
+    assert!(check(
+        &blocker,
+        "https://sub.ads.example.com/x.js",
+        ResourceType::Script,
+        page
+    )
+    .is_blocked());
+    // ラベルの途中からは一致しない
+    assert_eq!(
+        check(
+            &blocker,
+            "https://badads.example.com/x.js",
+            ResourceType::Script,
+            page
+        ),
+        BlockDecision::Allow
+    );
+    assert_eq!(
+        check(
+            &blocker,
+            "https://ads.example.com/allowed/x.js",
+            ResourceType::Script,
+            page
+        ),
+        BlockDecision::Exception("@@||ads.example.com/allowed/*".to_string())
+    );
+}
+
+#[test]
+fn test_resource_type_and_third_party_options() {
+    let blocker = ContentBlocker::from_filter_list(FILTER_LIST);
+    let page = "https://site.test/";
+
+    let gif = "https://img.test/banner/123/track.gif";
+    assert!(check(&blocker, gif, ResourceType::Image, page).is_blocked());
+    assert!(!check(&blocker, gif, ResourceType::Script, page).is_blocked());
+
+    let script = "https://cdn.example.net/ads.js";
+    assert!(check(&blocker, script, ResourceType::Script, page).is_blocked());
+    // ファーストパーティでは適用されない
+    assert!(!check(
+        &blocker,
+        script,
+        ResourceType::Script,
+        "https://www.example.net/"
+    )
+    .is_blocked());
+}
+
+#[test]
+fn test_domain_option_and_end_anchor() {
+    let blocker = ContentBlocker::from_filter_list(FILTER_LIST);
+    let tracker = "https://tracker.example.org/t.js";
+
+    assert!(check(
+        &blocker,
+        tracker,
+        ResourceType::Script,
+        "https://news.example.com/"
+    )
+    .is_blocked());
+    assert!(check(
+        &blocker,
+        tracker,
+        ResourceType::Script,
+        "https://a.news.example.com/"
+    )
+    .is_blocked());
+    assert!(!check(
+        &blocker,
+        tracker,
+        ResourceType::Script,
+        "https://blog.news.example.com/"
+    )
+    .is_blocked());
+    assert!(!check(
+        &blocker,
+        tracker,
+        ResourceType::Script,
+        "https://other.test/"
+    )
+    .is_blocked());
+
+    let page = "https://site.test/";
+    assert!(check(
+        &blocker,
+        "http://exact.example.com/file.js",
+        ResourceType::Script,
+        page
+    )
+    .is_blocked());
+    assert!(!check(
+        &blocker,
+        "http://exact.example.com/file.js?v=1",
+        ResourceType::Script,
+        page
+    )
+    .is_blocked());
+}
+
+#[test]
+fn test_document_requests_are_not_blocked_by_default() {
+    let blocker = ContentBlocker::from_filter_list(FILTER_LIST);
+    let decision = blocker.check(
+        &url("https://ads.example.com/"),
+        ResourceType::Document,
+        None,
+    );
+    assert_eq!(decision, BlockDecision::Allow);
+}
+
+#[test]
+fn test_document_exception_disables_blocking_on_page() {
+    let blocker =
+        ContentBlocker::from_filter_list("||ads.example.com^\n@@||trusted.test^$document");
+    let target = "https://ads.example.com/x.js";
+    assert!(check(&blocker, target, ResourceType::Script, "https://site.test/").is_blocked());
+    assert!(!check(
+        &blocker,
+        target,
+        ResourceType::Script,
+        "https://trusted.test/page"
+    )
+    .is_blocked());
+}
+
+#[test]
+fn test_cosmetic_selectors() {
+    let blocker = ContentBlocker::from_filter_list(FILTER_LIST);
+
+    assert_eq!(
+        blocker.cosmetic_selectors("news.example.com"),
+        vec![
+            ".ad-banner".to_string(),
+            "div[id^=\"sponsor\"]".to_string(),
+            ".generic-ad".to_string()
+        ]
+    );
+    // docs.example.com では .ad-banner が例外、.generic-ad は除外ドメイン
+    assert!(blocker.cosmetic_selectors("docs.example.com").is_empty());
+    assert_eq!(
+        blocker.element_hiding_css("other.test"),
+        ".ad-banner { display: none !important; }\n.generic-ad { display: none !important; }\n"
+    );
+}
+
+#[test]
+fn test_cosmetic_selectors_hide_elements_when_rendering() {
+    let blocker = ContentBlocker::from_filter_list(FILTER_LIST);
+    let document = Parser::new(
+        "<p>news</p><div class=ad-banner><p>ad</p></div><div id=sponsor-1>sponsor</div>",
+    )
+    .parse();
+    let drawn_text = |renderer: &Renderer| {
+        renderer
+            .generate_draw_commands(&document)
+            .into_iter()
+            .filter_map(|command| match command {
+                DrawCommand::DrawText { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut renderer = Renderer::new(800.0, 600.0);
+    assert_eq!(drawn_text(&renderer), ["news", "ad", "sponsor"]);
+    let mut selectors = blocker.cosmetic_selectors("news.example.com");
+    // 解析できないセレクターはそれだけを無視する
+    selectors.push("a::before".to_string());
+    renderer.set_hidden_elements(&selectors);
+    assert_eq!(drawn_text(&renderer), ["news"]);
+}
+
+#[test]
+fn test_many_rules_are_indexed() {
+    let mut list = String::new();
+    for i in 0..5000 {
+        list.push_str(&format!("||ads{i}.example.com^\n"));
+    }
+    let blocker = ContentBlocker::from_filter_list(&list);
+    let page = "https://site.test/";
+    assert!(check(
+        &blocker,
+        "https://ads4999.example.com/a.js",
+        ResourceType::Script,
+        page
+    )
+    .is_blocked());
+    assert!(!check(
+        &blocker,
+        "https://content.example.com/a.js",
+        ResourceType::Script,
+        page
+    )
+    .is_blocked());
+}
+
+#[test]
+fn test_wildcards() {
+    let blocker = ContentBlocker::from_filter_list(
+        "/a*b^c*d|\n|https://w.example.com/*.js^\n*a*a*a*a*a*a*a*a*a*a*a*a*b\n",
+    );
+    let page = "https://site.test/";
+    let blocked = |target: &str| check(&blocker, target, ResourceType::Script, page).is_blocked();
+
+    // `*` は任意の長さに一致し、`^` は区切り文字か URL 末尾に一致する
+    assert!(blocked("https://x.test/a-b/c--d"));
+    assert!(blocked("https://x.test/ab/cx/a1b?cd"));
+    assert!(!blocked("https://x.test/a-b/c--dx"));
+    assert!(!blocked("https://x.test/a-bc/d"));
+    assert!(blocked("https://w.example.com/lib/app.js"));
+    assert!(blocked("https://w.example.com/app.js?v=1"));
+    assert!(!blocked("https://w.example.com/app.jsx"));
+
+    // 照合はワイルドカードの数に対して指数的に遅くならない
+    let long = format!("https://x.test/{}", "a".repeat(20_000));
+    let start = std::time::Instant::now();
+    assert!(!blocked(&long));
+    assert!(blocked(&format!("{long}b")));
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_content_blocker_as_middleware() {
+    let port = spawn_http_server(|_, _| http_response("200 OK", &[], "ok")).await;
+    let blocker = ContentBlocker::from_filter_list("/ads/*$script");
+    let net = NetworkCore::with_middlewares(vec![Arc::new(blocker)]).unwrap();
+
+    let mut request = Request::new("GET", url(&format!("http://127.0.0.1:{port}/ads/a.js")));
+    request.resource_type = ResourceType::Script;
+    request.initiator = Some(url("https://site.test/"));
+    let err = net.send(request).await.unwrap_err();
+    assert!(matches!(
+        err,
+        NetworkError::Blocked(ref blocked) if blocked.reason == "/ads/*$script"
+    ));
+
+    // トップレベルのドキュメントはブロックされない
+    assert!(net
+        .fetch(&format!("http://127.0.0.1:{port}/ads/index.html"))
+        .await
+        .is_ok());
+}
@@ -0,0 +1,297 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use common::{http_response, spawn_http_server};
+use orinium_browser::platform::network::{
+    CorsError, CredentialsMode, NetworkCore, NetworkError, Origin, Request, RequestMode,
+};
+use url::Url;
+
+fn foreign_origin() -> Origin {
+    Origin::from_url(&Url::parse("https://app.example.test/index.html").unwrap())
+}
+
+/// パスに応じて CORS ヘッダーを返すサーバー。OPTIONS リクエストの回数を数える
+async fn spawn_cors_server(preflights: Arc<AtomicUsize>) -> u16 {
+    spawn_http_server(move |head, _| {
+        let request_line = head.lines().next().unwrap_or("");
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+        if request_line.starts_with("OPTIONS") {
+            preflights.fetch_add(1, Ordering::SeqCst);
+            return match path {
+                "/api" => http_response(
+                    "204 No Content",
+                    &[
+                        ("Access-Control-Allow-Origin", "https://app.example.test"),
+                        ("Access-Control-Allow-Methods", "PUT, DELETE"),
+                        ("Access-Control-Allow-Headers", "X-Custom"),
+                        ("Access-Control-Max-Age", "60"),
+                    ],
+                    "",
+                ),
+                _ => http_response("403 Forbidden", &[], ""),
+            };
+        }
+        match path {
+            "/public" => http_response(
+                "200 OK",
+                &[
+                    ("Access-Control-Allow-Origin", "*"),
+                    ("Access-Control-Expose-Headers", "X-Exposed"),
+                    ("Content-Type", "text/plain"),
+                    ("X-Exposed", "1"),
+                    ("X-Secret", "1"),
+                    ("Set-Cookie", "a=b"),
+                ],
+                "public",
+            ),
+            "/api" => http_response(
+                "200 OK",
+                &[("Access-Control-Allow-Origin", "https://app.example.test")],
+                "api",
+            ),
+            "/login" => http_response("200 OK", &[("Set-Cookie", "session=1")], "ok"),
+            "/echo-cors" => http_response("200 OK", &[("Access-Control-Allow-Origin", "*")], head),
+            // リクエストヘッダーをそのまま返す（Access-Control-Allow-Origin なし）
+            _ => http_response("200 OK", &[], head),
+        }
+    })
+    .await
+}
+
+#[test]
+fn test_origin_serialization_and_comparison() {
+    let a = Origin::from_url(&Url::parse("https://example.com/a").unwrap());
+    let b = Origin::from_url(&Url::parse("https://EXAMPLE.com:443/b?q").unwrap());
+    let c = Origin::from_url(&Url::parse("http://example.com:8080/").unwrap());
+    let opaque = Origin::from_url(&Url::parse("data:text/plain,hi").unwrap());
+
+    assert!(a.same_origin(&b));
+    assert!(!a.same_origin(&c));
+    assert!(!opaque.same_origin(&opaque));
+    assert_eq!(a.serialize(), "https://example.com");
+    assert_eq!(c.serialize(), "http://example.com:8080");
+    assert_eq!(opaque.serialize(), "null");
+}
+
+#[tokio::test]
+async fn test_same_origin_request_is_not_checked() {
+    let port = spawn_cors_server(Arc::new(AtomicUsize::new(0))).await;
+    let net = NetworkCore::new().unwrap();
+    let url = format!("http://127.0.0.1:{port}/echo");
+    let origin = Origin::from_url(&Url::parse(&url).unwrap());
+
+    let resp = net
+        .fetch_from(&url, &origin, RequestMode::SameOrigin)
+        .await
+        .unwrap();
+    assert_eq!(resp.status_code, 200);
+}
+
+#[tokio::test]
+async fn test_cross_origin_modes() {
+    let port = spawn_cors_server(Arc::new(AtomicUsize::new(0))).await;
+    let net = NetworkCore::new().unwrap();
+    let url = format!("http://127.0.0.1:{port}/echo");
+
+    let err = net
+        .fetch_from(&url, &foreign_origin(), RequestMode::SameOrigin)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        NetworkError::Cors(CorsError::SameOriginViolation { .. })
+    ));
+
+    let err = net
+        .fetch_from(&url, &foreign_origin(), RequestMode::Cors)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        NetworkError::Cors(CorsError::MissingAllowOrigin)
+    ));
+
+    // no-cors ではレスポンスの中身は見えない
+    let resp = net
+        .fetch_from(&url, &foreign_origin(), RequestMode::NoCors)
+        .await
+        .unwrap();
+    assert_eq!(resp.status_code, 0);
+    assert!(resp.body.is_empty());
+    assert!(resp.headers.is_empty());
+}
+
+#[tokio::test]
+async fn test_cors_response_headers_are_filtered() {
+    let port = spawn_cors_server(Arc::new(AtomicUsize::new(0))).await;
+    let net = NetworkCore::new().unwrap();
+
+    let resp = net
+        .fetch_from(
+            &format!("http://127.0.0.1:{port}/public"),
+            &foreign_origin(),
+            RequestMode::Cors,
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.body, b"public");
+    let names = resp
+        .headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"Content-Type"));
+    assert!(names.contains(&"X-Exposed"));
+    assert!(!names.contains(&"X-Secret"));
+    assert!(!names.contains(&"Set-Cookie"));
+}
+
+#[tokio::test]
+async fn test_preflight_and_preflight_cache() {
+    let preflights = Arc::new(AtomicUsize::new(0));
+    let port = spawn_cors_server(Arc::clone(&preflights)).await;
+    let net = NetworkCore::new().unwrap();
+
+    let make_request = |path: &str, method: &str| {
+        let mut request = Request::new(
+            method,
+            Url::parse(&format!("http://127.0.0.1:{port}{path}")).unwrap(),
+        );
+        request.origin = Some(foreign_origin());
+        request.mode = RequestMode::Cors;
+        request.credentials = CredentialsMode::Omit;
+        request.set_header("X-Custom", "1");
+        request
+    };
+
+    let resp = net.send(make_request("/api", "PUT")).await.unwrap();
+    assert_eq!(resp.body, b"api");
+    assert_eq!(preflights.load(Ordering::SeqCst), 1);
+
+    // 2回目はプリフライトキャッシュが使われる
+    net.send(make_request("/api", "DELETE")).await.unwrap();
+    assert_eq!(preflights.load(Ordering::SeqCst), 1);
+
+    // 許可されていないメソッド
+    let err = net.send(make_request("/api", "PATCH")).await.unwrap_err();
+    assert!(matches!(
+        err,
+        NetworkError::Cors(CorsError::MethodNotAllowed { ref method }) if method == "PATCH"
+    ));
+
+    let err = net.send(make_request("/denied", "PUT")).await.unwrap_err();
+    assert!(matches!(
+        err,
+        NetworkError::Cors(CorsError::PreflightStatus { status: 403 })
+    ));
+}
+
+#[tokio::test]
+async fn test_credentials_handling() {
+    let port = spawn_cors_server(Arc::new(AtomicUsize::new(0))).await;
+    let net = NetworkCore::new().unwrap();
+
+    // トップレベルの遷移で Cookie を保存する
+    net.fetch(&format!("http://127.0.0.1:{port}/login"))
+        .await
+        .unwrap();
+    let resp = net
+        .fetch(&format!("http://127.0.0.1:{port}/echo-navigate"))
+        .await
+        .unwrap();
+    assert!(String::from_utf8_lossy(&resp.body).contains("Cookie: session=1"));
+
+    // credentials: same-origin（既定）ではクロスオリジンに Cookie を送らない
+    let resp = net
+        .fetch_from(
+            &format!("http://127.0.0.1:{port}/echo-cors"),
+            &foreign_origin(),
+            RequestMode::Cors,
+        )
+        .await
+        .unwrap();
+    let echoed = String::from_utf8_lossy(&resp.body);
+    assert!(echoed.contains("Origin: https://app.example.test"));
+    assert!(!echoed.contains("Cookie:"));
+
+    // credentials: include ではワイルドカードの許可は認められない
+    let mut request = Request::new(
+        "GET",
+        Url::parse(&format!("http://127.0.0.1:{port}/public")).unwrap(),
+    );
+    request.origin = Some(foreign_origin());
+    request.mode = RequestMode::Cors;
+    request.credentials = CredentialsMode::Include;
+    let err = net.send(request).await.unwrap_err();
+    assert!(matches!(
+        err,
+        NetworkError::Cors(CorsError::WildcardWithCredentials)
+    ));
+}
+
+#[tokio::test]
+async fn test_cache_is_partitioned_by_origin_and_credentials() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&requests);
+    // リクエストの Origin をそのまま許可し、Origin と Cookie をボディとして返すキャッシュ可能なレスポンス
+    let port = spawn_http_server(move |head, _| {
+        if head.starts_with("GET /login") {
+            return http_response("200 OK", &[("Set-Cookie", "session=1")], "ok");
+        }
+        counter.fetch_add(1, Ordering::SeqCst);
+        let header = |name: &str| {
+            head.lines()
+                .find_map(|l| l.strip_prefix(name))
+                .unwrap_or("")
+                .to_string()
+        };
+        let origin = header("Origin: ");
+        let body = format!("{origin} {}", header("Cookie: "));
+        http_response(
+            "200 OK",
+            &[
+                ("Access-Control-Allow-Origin", &origin),
+                ("Access-Control-Allow-Credentials", "true"),
+                ("Cache-Control", "max-age=60"),
+            ],
+            &body,
+        )
+    })
+    .await;
+    let net = NetworkCore::new().unwrap();
+    net.fetch(&format!("http://127.0.0.1:{port}/login"))
+        .await
+        .unwrap();
+
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/data")).unwrap();
+    let origins = [
+        foreign_origin(),
+        Origin::from_url(&Url::parse("https://other.example.test/").unwrap()),
+    ];
+    let modes = [CredentialsMode::Omit, CredentialsMode::Include];
+    for round in 0..2 {
+        for origin in &origins {
+            for mode in modes {
+                let mut request = Request::new("GET", url.clone());
+                request.origin = Some(origin.clone());
+                request.mode = RequestMode::Cors;
+                request.credentials = mode;
+                let resp = net.send(request).await.unwrap();
+
+                // 他のオリジンやクレデンシャルモードで取得したレスポンスは使われない
+                let cookie = if mode == CredentialsMode::Include {
+                    "session=1"
+                } else {
+                    ""
+                };
+                let expected = format!("{} {cookie}", origin.serialize());
+                assert_eq!(String::from_utf8_lossy(&resp.body), expected, "{round}");
+            }
+        }
+    }
+    // 2 周目はすべてキャッシュから応答する
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+}
@@ -0,0 +1,162 @@
+use orinium_browser::engine::html::document::SubresourceKind;
+use orinium_browser::engine::html::mutation;
+use orinium_browser::engine::html::parser::{NodeRef, Parser};
+use url::Url;
+
+fn parse(html: &str, url: &str) -> NodeRef {
+    let document = Parser::new(html).parse();
+    document.borrow_mut().set_url(Url::parse(url).unwrap());
+    document
+}
+
+fn resolve(document: &NodeRef, href: &str) -> Option<String> {
+    document.borrow().resolve_url(href).map(String::from)
+}
+
+#[test]
+fn test_resolve_against_document_url() {
+    let document = parse("<p>x</p>", "https://example.com/dir/page.html?q=1");
+    assert_eq!(
+        document.borrow().url().map(Url::as_str),
+        Some("https://example.com/dir/page.html?q=1")
+    );
+    assert_eq!(
+        resolve(&document, "next.html").as_deref(),
+        Some("https://example.com/dir/next.html")
+    );
+    assert_eq!(
+        resolve(&document, "/root").as_deref(),
+        Some("https://example.com/root")
+    );
+    assert_eq!(
+        resolve(&document, "//cdn.example.net/a.js").as_deref(),
+        Some("https://cdn.example.net/a.js")
+    );
+    // 前後の空白は取り除かれる
+    assert_eq!(
+        resolve(&document, "  #top\n").as_deref(),
+        Some("https://example.com/dir/page.html?q=1#top")
+    );
+    assert!(resolve(&document, "http://[::1").is_none());
+
+    // URL のない文書では絶対 URL だけを解決できる
+    let document = Parser::new("").parse();
+    assert!(document.borrow().url().is_none());
+    assert!(resolve(&document, "a.html").is_none());
+    assert_eq!(
+        resolve(&document, "https://example.com/").as_deref(),
+        Some("https://example.com/")
+    );
+}
+
+#[test]
+fn test_base_element() {
+    let document = parse(
+        "<head><base target=_blank><base href='/static/'><base href='/ignored/'></head>",
+        "https://example.com/dir/page.html",
+    );
+    // href を持つ最初の base だけが使われる
+    assert_eq!(
+        document.borrow().base_url().map(String::from).as_deref(),
+        Some("https://example.com/static/")
+    );
+    assert_eq!(
+        resolve(&document, "img/a.png").as_deref(),
+        Some("https://example.com/static/img/a.png")
+    );
+
+    // base を取り除くと文書の URL に戻る
+    let base = document
+        .borrow()
+        .query_selector("base[href]")
+        .unwrap()
+        .unwrap();
+    mutation::remove(&base);
+    assert_eq!(
+        resolve(&document, "img/a.png").as_deref(),
+        Some("https://example.com/ignored/img/a.png")
+    );
+    let base = document
+        .borrow()
+        .query_selector("base[href]")
+        .unwrap()
+        .unwrap();
+    mutation::set_attribute(&base, "href", "http://[::1").unwrap();
+    assert_eq!(
+        resolve(&document, "img/a.png").as_deref(),
+        Some("https://example.com/dir/img/a.png")
+    );
+
+    // 複製した文書は URL を引き継ぐ
+    let clone = document.borrow().clone_node(false);
+    assert_eq!(clone.borrow().url(), document.borrow().url());
+}
+
+#[test]
+fn test_subresources() {
+    let document = parse(
+        r#"<!DOCTYPE html>
+        <head>
+          <base href="https://static.example.com/v1/">
+          <link rel="stylesheet" href="main.css">
+          <link rel="alternate stylesheet" href="alt.css">
+          <link rel="stylesheet" href="off.css" disabled>
+          <link rel="Shortcut Icon" href="/favicon.ico">
+          <link rel="preload" as="Font" href="font.woff2">
+          <link rel="preload" href="no-destination.js">
+          <link rel="modulepreload" href="module.js">
+          <link rel="canonical" href="https://example.com/">
+          <script src="app.js"></script>
+          <script type="module" src="module-app.js"></script>
+          <script type="text/template" src="template.html"></script>
+          <script>inline()</script>
+        </head>
+        <body>
+          <img src="a.png">
+          <img srcset=" b.png 1x, c.png 2x">
+          <img src="">
+          <input type="image" src="submit.png">
+          <input src="not-image.png">
+          <iframe src="frame.html"></iframe>
+          <iframe srcdoc="<p>x</p>" src="ignored.html"></iframe>
+          <template><img src="in-template.png"></template>
+          <svg><image href="svg.png"/></svg>
+        </body>"#,
+        "https://example.com/page.html",
+    );
+    let resources: Vec<(SubresourceKind, String)> = document
+        .borrow()
+        .subresources()
+        .into_iter()
+        .map(|resource| (resource.kind, resource.url.into()))
+        .collect();
+    let preload = |destination: &str| SubresourceKind::Preload {
+        destination: destination.to_string(),
+    };
+    let base = "https://static.example.com/v1/";
+    assert_eq!(
+        resources,
+        [
+            (SubresourceKind::Stylesheet, format!("{base}main.css")),
+            (
+                SubresourceKind::Icon,
+                "https://static.example.com/favicon.ico".to_string()
+            ),
+            (preload("font"), format!("{base}font.woff2")),
+            (preload("script"), format!("{base}module.js")),
+            (SubresourceKind::Script, format!("{base}app.js")),
+            (SubresourceKind::Script, format!("{base}module-app.js")),
+            (SubresourceKind::Image, format!("{base}a.png")),
+            (SubresourceKind::Image, format!("{base}b.png")),
+            (SubresourceKind::Image, format!("{base}submit.png")),
+            (SubresourceKind::Iframe, format!("{base}frame.html")),
+        ]
+    );
+
+    // 要素からは crossorigin などの属性を読める
+    let resource = document.borrow().subresources().remove(0);
+    assert_eq!(
+        resource.element.borrow().attribute("href"),
+        Some("main.css")
+    );
+}
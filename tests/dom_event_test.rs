@@ -0,0 +1,240 @@
+use orinium_browser::engine::html::event::{
+    self, DefaultAction, Event, EventPhase, ListenerOptions, MouseEventData,
+};
+use orinium_browser::engine::html::mutation;
+use orinium_browser::engine::html::parser::{NodeRef, Parser};
+use orinium_browser::engine::renderer::Renderer;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn by_id(document: &NodeRef, id: &str) -> NodeRef {
+    document.borrow().get_element_by_id(id).unwrap()
+}
+
+/// 呼ばれた順に「ノードの id:フェーズ」を記録するリスナーを登録する
+fn record(node: &NodeRef, event_type: &str, capture: bool, log: &Rc<RefCell<Vec<String>>>) {
+    let log = Rc::clone(log);
+    event::add_event_listener(
+        node,
+        event_type,
+        ListenerOptions {
+            capture,
+            ..Default::default()
+        },
+        move |event| {
+            let current = event.current_target().unwrap().borrow();
+            let name = current
+                .id()
+                .or(current.tag_name())
+                .unwrap_or("#document")
+                .to_string();
+            log.borrow_mut().push(format!("{name}:{:?}", event.phase()));
+        },
+    );
+}
+
+#[test]
+fn test_capture_target_bubble_order() {
+    let document = Parser::new("<div id=outer><p id=inner><b id=leaf>x</b></p></div>").parse();
+    let outer = by_id(&document, "outer");
+    let inner = by_id(&document, "inner");
+    let leaf = by_id(&document, "leaf");
+    let log = Rc::new(RefCell::new(vec![]));
+    for node in [&document, &outer, &inner, &leaf] {
+        record(node, "click", false, &log);
+        record(node, "click", true, &log);
+    }
+
+    let mut event = Event::mouse("click", MouseEventData::default());
+    event::dispatch_event(&leaf, &mut event);
+    assert_eq!(
+        *log.borrow(),
+        [
+            "#document:Capturing",
+            "outer:Capturing",
+            "inner:Capturing",
+            "leaf:AtTarget",
+            "leaf:AtTarget",
+            "inner:Bubbling",
+            "outer:Bubbling",
+            "#document:Bubbling",
+        ]
+    );
+    assert!(Rc::ptr_eq(event.target().unwrap(), &leaf));
+    assert!(event.current_target().is_none());
+    assert_eq!(event.phase(), EventPhase::None);
+
+    // バブリングしないイベントもキャプチャは行う
+    log.borrow_mut().clear();
+    event::dispatch_event(&leaf, &mut Event::new("click", false, false));
+    assert_eq!(
+        *log.borrow(),
+        [
+            "#document:Capturing",
+            "outer:Capturing",
+            "inner:Capturing",
+            "leaf:AtTarget",
+            "leaf:AtTarget",
+        ]
+    );
+}
+
+#[test]
+fn test_stop_propagation() {
+    let document = Parser::new("<div id=outer><p id=inner>x</p></div>").parse();
+    let outer = by_id(&document, "outer");
+    let inner = by_id(&document, "inner");
+    let log = Rc::new(RefCell::new(vec![]));
+
+    event::add_event_listener(&inner, "ping", ListenerOptions::default(), |event| {
+        event.stop_propagation()
+    });
+    record(&inner, "ping", false, &log);
+    record(&outer, "ping", false, &log);
+    event::dispatch_event(&inner, &mut Event::new("ping", true, false));
+    // 同じノードの残りのリスナーは呼ばれる
+    assert_eq!(*log.borrow(), ["inner:AtTarget"]);
+
+    log.borrow_mut().clear();
+    event::add_event_listener(
+        &outer,
+        "pong",
+        ListenerOptions {
+            capture: true,
+            ..Default::default()
+        },
+        |event| event.stop_immediate_propagation(),
+    );
+    record(&outer, "pong", true, &log);
+    record(&inner, "pong", false, &log);
+    event::dispatch_event(&inner, &mut Event::new("pong", true, false));
+    assert!(log.borrow().is_empty());
+}
+
+#[test]
+fn test_prevent_default_and_listener_options() {
+    let document = Parser::new("<p id=p>x</p>").parse();
+    let p = by_id(&document, "p");
+    let calls = Rc::new(RefCell::new(0));
+
+    let counter = Rc::clone(&calls);
+    event::add_event_listener(
+        &p,
+        "submit",
+        ListenerOptions {
+            once: true,
+            ..Default::default()
+        },
+        move |_| *counter.borrow_mut() += 1,
+    );
+    event::add_event_listener(
+        &p,
+        "submit",
+        ListenerOptions {
+            passive: true,
+            ..Default::default()
+        },
+        |event| event.prevent_default(),
+    );
+    let mut event = Event::new("submit", true, true);
+    event::dispatch_event(&p, &mut event);
+    event::dispatch_event(&p, &mut Event::new("submit", true, true));
+    assert_eq!(*calls.borrow(), 1);
+    // passive なリスナーの prevent_default は無視される
+    assert!(!event.default_prevented());
+
+    let id = event::add_event_listener(&p, "submit", ListenerOptions::default(), |event| {
+        event.prevent_default()
+    });
+    let mut event = Event::new("submit", true, true);
+    event::dispatch_event(&p, &mut event);
+    assert!(event.default_prevented());
+    // 取り消せないイベントは取り消されない
+    let mut event = Event::new("submit", true, false);
+    event::dispatch_event(&p, &mut event);
+    assert!(!event.default_prevented());
+
+    assert!(event::remove_event_listener(&p, id));
+    assert!(!event::remove_event_listener(&p, id));
+    let mut event = Event::new("submit", true, true);
+    event::dispatch_event(&p, &mut event);
+    assert!(!event.default_prevented());
+}
+
+#[test]
+fn test_listeners_may_change_the_tree() {
+    let document = Parser::new("<div id=outer><p id=inner>x</p></div>").parse();
+    let outer = by_id(&document, "outer");
+    let inner = by_id(&document, "inner");
+    let log = Rc::new(RefCell::new(vec![]));
+
+    // 配送中にターゲットを取り除いても、経路は配送開始時のまま
+    event::add_event_listener(&inner, "click", ListenerOptions::default(), |event| {
+        mutation::remove(event.target().unwrap());
+    });
+    record(&outer, "click", false, &log);
+    event::dispatch_event(&inner, &mut Event::new("click", true, true));
+    assert!(inner.borrow().parent().is_none());
+    assert_eq!(*log.borrow(), ["outer:Bubbling"]);
+}
+
+#[test]
+fn test_click_default_actions() {
+    let document = Parser::new(
+        "<a id=link href='/next'><span id=label>go</span></a>\
+         <form id=form action='/send'><button id=send>send</button>\
+         <button id=plain type=button>x</button></form>",
+    )
+    .parse();
+    let label = by_id(&document, "label");
+    let click = || Event::mouse("click", MouseEventData::default());
+
+    match event::dispatch_event(&label, &mut click()) {
+        Some(DefaultAction::FollowLink { element, href }) => {
+            assert!(Rc::ptr_eq(&element, &by_id(&document, "link")));
+            assert_eq!(href, "/next");
+        }
+        action => panic!("unexpected action {action:?}"),
+    }
+    match event::dispatch_event(&by_id(&document, "send"), &mut click()) {
+        Some(DefaultAction::SubmitForm { form, submitter }) => {
+            assert!(Rc::ptr_eq(&form, &by_id(&document, "form")));
+            assert!(Rc::ptr_eq(&submitter, &by_id(&document, "send")));
+        }
+        action => panic!("unexpected action {action:?}"),
+    }
+    assert!(event::dispatch_event(&by_id(&document, "plain"), &mut click()).is_none());
+    // click 以外には既定の動作がない
+    assert!(
+        event::dispatch_event(&label, &mut Event::mouse("mousedown", Default::default())).is_none()
+    );
+
+    // 取り消されたリンクはたどらない
+    event::add_event_listener(&document, "click", ListenerOptions::default(), |event| {
+        event.prevent_default()
+    });
+    assert!(event::dispatch_event(&label, &mut click()).is_none());
+}
+
+#[test]
+fn test_hit_test() {
+    let document = Parser::new("<p>first</p><p>second <a href=x>link</a></p>").parse();
+    let renderer = Renderer::new(800.0, 600.0);
+    let texts: Vec<(f32, f32)> = renderer
+        .generate_draw_commands(&document)
+        .into_iter()
+        .map(|command| match command {
+            orinium_browser::engine::renderer::DrawCommand::DrawText { x, y, .. } => (x, y),
+            command => panic!("unexpected command {command:?}"),
+        })
+        .collect();
+    assert_eq!(texts.len(), 3);
+
+    let (x, y) = texts[2];
+    let hit = renderer.hit_test(&document, x + 1.0, y + 1.0).unwrap();
+    assert_eq!(hit.borrow().tag_name(), Some("a"));
+    let (x, y) = texts[0];
+    let hit = renderer.hit_test(&document, x + 1.0, y + 1.0).unwrap();
+    assert_eq!(hit.borrow().text_content(), "first");
+    assert!(renderer.hit_test(&document, 799.0, 599.0).is_none());
+}
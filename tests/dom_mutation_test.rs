@@ -0,0 +1,238 @@
+use orinium_browser::engine::html::mutation::{self, DomError, MutationRecord};
+use orinium_browser::engine::html::namespace::Namespace;
+use orinium_browser::engine::html::parser::{Node, NodeRef, Parser};
+use std::rc::Rc;
+
+fn by_id(document: &NodeRef, id: &str) -> NodeRef {
+    document.borrow().get_element_by_id(id).unwrap()
+}
+
+fn body_html(document: &NodeRef) -> String {
+    let body = document.borrow().query_selector("body").unwrap().unwrap();
+    let html = body.borrow().inner_html();
+    html
+}
+
+#[test]
+fn test_insert_remove_replace() {
+    let document = Parser::new("<ul id=list><li id=a>a</li><li id=b>b</li></ul>").parse();
+    let list = by_id(&document, "list");
+    let a = by_id(&document, "a");
+    let b = by_id(&document, "b");
+
+    let c = Node::new_element(Namespace::Html, "li", vec![]);
+    mutation::append_child(&c, &Node::new_text("c")).unwrap();
+    mutation::insert_before(&list, &c, Some(&b)).unwrap();
+    assert_eq!(
+        body_html(&document),
+        "<ul id=\"list\"><li id=\"a\">a</li><li>c</li><li id=\"b\">b</li></ul>"
+    );
+    assert!(Rc::ptr_eq(&a.borrow().next_sibling().unwrap(), &c));
+    assert!(Rc::ptr_eq(&b.borrow().previous_sibling().unwrap(), &c));
+
+    // 既存のノードを挿入すると移動する
+    mutation::append_child(&list, &a).unwrap();
+    assert_eq!(list.borrow().text_content(), "cba");
+    assert!(a.borrow().next_sibling().is_none());
+
+    mutation::replace_child(&list, &Node::new_text("x"), &c).unwrap();
+    assert_eq!(list.borrow().text_content(), "xba");
+    assert!(c.borrow().parent().is_none());
+
+    mutation::remove_child(&list, &b).unwrap();
+    mutation::remove(&a);
+    assert_eq!(body_html(&document), "<ul id=\"list\">x</ul>");
+    assert!(b.borrow().previous_sibling().is_none());
+    assert!(b.borrow().next_sibling().is_none());
+}
+
+#[test]
+fn test_insert_document_fragment() {
+    let document = Parser::new("<p id=p>3</p>").parse();
+    let p = by_id(&document, "p");
+    let fragment = Node::new_document_fragment();
+    mutation::append_child(&fragment, &Node::new_text("1")).unwrap();
+    mutation::append_child(&fragment, &Node::new_text("2")).unwrap();
+
+    let first = p.borrow().first_child().unwrap();
+    mutation::insert_before(&p, &fragment, Some(&first)).unwrap();
+    assert_eq!(p.borrow().children().len(), 3);
+    assert_eq!(p.borrow().text_content(), "123");
+    assert!(fragment.borrow().children().is_empty());
+}
+
+#[test]
+fn test_hierarchy_errors() {
+    let document = Parser::new("<div id=outer><div id=inner></div></div><p id=p>").parse();
+    let outer = by_id(&document, "outer");
+    let inner = by_id(&document, "inner");
+    let p = by_id(&document, "p");
+
+    assert_eq!(
+        mutation::append_child(&inner, &outer).unwrap_err(),
+        DomError::HierarchyRequest
+    );
+    assert_eq!(
+        mutation::append_child(&outer, &outer).unwrap_err(),
+        DomError::HierarchyRequest
+    );
+    assert_eq!(
+        mutation::insert_before(&outer, &p, Some(&p)).unwrap_err(),
+        DomError::NotFound
+    );
+    assert_eq!(
+        mutation::remove_child(&outer, &p).unwrap_err(),
+        DomError::NotFound
+    );
+    let text = Node::new_text("t");
+    assert_eq!(
+        mutation::append_child(&text, &p).unwrap_err(),
+        DomError::HierarchyRequest
+    );
+    assert_eq!(
+        mutation::append_child(&document, &text).unwrap_err(),
+        DomError::HierarchyRequest
+    );
+    assert_eq!(
+        mutation::set_attribute(&p, "a b", "").unwrap_err(),
+        DomError::InvalidCharacter
+    );
+    // 失敗した操作はツリーを変えない
+    assert_eq!(
+        body_html(&document),
+        "<div id=\"outer\"><div id=\"inner\"></div></div><p id=\"p\"></p>"
+    );
+}
+
+#[test]
+fn test_index_follows_mutations() {
+    let document = Parser::new("<div id=root></div>").parse();
+    let root = by_id(&document, "root");
+
+    // 文書の外で組み立てた木は、挿入した時点で索引に入る
+    let section = Node::new_element(Namespace::Html, "section", vec![]);
+    let item = Node::new_element(Namespace::Html, "span", vec![]);
+    mutation::set_attribute(&item, "id", "item").unwrap();
+    mutation::set_attribute(&item, "CLASS", "x y").unwrap();
+    mutation::append_child(&section, &item).unwrap();
+    assert!(document.borrow().get_element_by_id("item").is_none());
+    mutation::append_child(&root, &section).unwrap();
+    assert!(Rc::ptr_eq(&by_id(&document, "item"), &item));
+    assert_eq!(document.borrow().get_elements_by_class_name("y").len(), 1);
+
+    mutation::set_attribute(&item, "id", "renamed").unwrap();
+    assert!(document.borrow().get_element_by_id("item").is_none());
+    assert!(Rc::ptr_eq(&by_id(&document, "renamed"), &item));
+    assert_eq!(
+        mutation::remove_attribute(&item, "class"),
+        Some("x y".into())
+    );
+    assert!(document.borrow().get_elements_by_class_name("y").is_empty());
+
+    mutation::remove(&section);
+    assert!(document.borrow().get_element_by_id("renamed").is_none());
+}
+
+#[test]
+fn test_set_text_content() {
+    let document = Parser::new("<p id=p>a<b>b</b><!--c--></p>").parse();
+    let p = by_id(&document, "p");
+    let comment = p.borrow().last_child().unwrap();
+    mutation::set_text_content(&comment, "changed");
+    assert_eq!(p.borrow().inner_html(), "a<b>b</b><!--changed-->");
+
+    mutation::set_text_content(&p, "<new>");
+    assert_eq!(p.borrow().inner_html(), "&lt;new&gt;");
+    mutation::set_text_content(&p, "");
+    assert!(p.borrow().children().is_empty());
+}
+
+#[test]
+fn test_mutation_records() {
+    let document = Parser::new("<ul id=list><li id=a>a</li></ul><ol id=other></ol>").parse();
+    let list = by_id(&document, "list");
+    let other = by_id(&document, "other");
+    let a = by_id(&document, "a");
+
+    // 記録を有効にするまでは何も残らない
+    mutation::set_attribute(&list, "class", "x").unwrap();
+    assert!(document.borrow_mut().take_mutation_records().is_empty());
+
+    document.borrow_mut().set_mutation_recording(true);
+    let b = Node::new_element(Namespace::Html, "li", vec![]);
+    mutation::append_child(&list, &b).unwrap();
+    mutation::set_attribute(&list, "class", "y").unwrap();
+    mutation::append_child(&other, &a).unwrap();
+    let text = a.borrow().first_child().unwrap();
+    mutation::set_text_content(&text, "A");
+
+    let records = document.borrow_mut().take_mutation_records();
+    assert_eq!(records.len(), 5);
+    match &records[0] {
+        MutationRecord::ChildList {
+            target,
+            added_nodes,
+            removed_nodes,
+            previous_sibling,
+            next_sibling,
+        } => {
+            assert!(Rc::ptr_eq(target, &list));
+            assert!(Rc::ptr_eq(&added_nodes[0], &b));
+            assert!(removed_nodes.is_empty());
+            assert!(Rc::ptr_eq(previous_sibling.as_ref().unwrap(), &a));
+            assert!(next_sibling.is_none());
+        }
+        record => panic!("unexpected record {record:?}"),
+    }
+    match &records[1] {
+        MutationRecord::Attributes {
+            target,
+            name,
+            old_value,
+        } => {
+            assert!(Rc::ptr_eq(target, &list));
+            assert_eq!(name, "class");
+            assert_eq!(old_value.as_deref(), Some("x"));
+        }
+        record => panic!("unexpected record {record:?}"),
+    }
+    // 移動は元の親からの削除と、新しい親への追加の2つ
+    match (&records[2], &records[3]) {
+        (
+            MutationRecord::ChildList {
+                target: from,
+                removed_nodes,
+                next_sibling,
+                ..
+            },
+            MutationRecord::ChildList {
+                target: to,
+                added_nodes,
+                ..
+            },
+        ) => {
+            assert!(Rc::ptr_eq(from, &list));
+            assert!(Rc::ptr_eq(&removed_nodes[0], &a));
+            assert!(Rc::ptr_eq(next_sibling.as_ref().unwrap(), &b));
+            assert!(Rc::ptr_eq(to, &other));
+            assert!(Rc::ptr_eq(&added_nodes[0], &a));
+        }
+        records => panic!("unexpected records {records:?}"),
+    }
+    match &records[4] {
+        MutationRecord::CharacterData { target, old_value } => {
+            assert!(Rc::ptr_eq(target, &text));
+            assert_eq!(old_value, "a");
+        }
+        record => panic!("unexpected record {record:?}"),
+    }
+
+    // 文書につながっていないノードへの変更は記録しない
+    let body = document.borrow().query_selector("body").unwrap().unwrap();
+    mutation::set_attribute(&b, "id", "b").unwrap();
+    mutation::remove(&list);
+    mutation::set_attribute(&list, "id", "detached").unwrap();
+    let records = document.borrow_mut().take_mutation_records();
+    assert_eq!(records.len(), 2);
+    assert!(Rc::ptr_eq(records[1].target(), &body));
+}
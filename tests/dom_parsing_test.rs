@@ -23,7 +23,7 @@ fn test_dom_parse() {
 "#;
 
     html.to_string();
-    let mut parser = parser::Parser::new(&html);
+    let mut parser = parser::Parser::new(html);
     let dom = parser.parse();
     println!("DOM Tree:\n{}", dom.borrow());
 }
@@ -32,7 +32,7 @@ fn test_dom_parse() {
 fn test_dom_parse_malformed() {
     let html = r#"<html><head><title>Test</title></head><body><p>Paragraph 1<p>Paragraph 2<div>Div content"#;
 
-    let mut parser = parser::Parser::new(&html);
+    let mut parser = parser::Parser::new(html);
     let dom = parser.parse();
     println!("DOM Tree:\n{}", dom.borrow());
 }
@@ -0,0 +1,196 @@
+use orinium_browser::engine::html::parser::{NodeRef, Parser};
+use std::rc::Rc;
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>Query</title></head>
+<body>
+  <nav id="nav" class="menu top">
+    <a href="/" class="active">Home</a>
+    <a href="/docs">Docs</a>
+    <a>Disabled</a>
+  </nav>
+  <main id="main">
+    <h1 class="title">Heading</h1>
+    <p class="note">First <b>bold</b> text</p>
+    <p>Second</p>
+    <ul><li>1<li class="odd">2<li>3<li class="odd">4</ul>
+    <svg><foreignObject><p id="inner">svg</p></foreignObject></svg>
+    <template><p id="in-template" class="note">x</p></template>
+  </main>
+  <p id="main">duplicate id</p>
+</body>
+</html>"#;
+
+fn texts(nodes: &[NodeRef]) -> Vec<String> {
+    nodes
+        .iter()
+        .map(|node| node.borrow().text_content().trim().to_string())
+        .collect()
+}
+
+#[test]
+fn test_get_element_by_id() {
+    let document = Parser::new(PAGE).parse();
+    let document = document.borrow();
+    let main = document.get_element_by_id("main").unwrap();
+    // 同じ ID の要素が複数あれば文書順で最初のもの
+    assert_eq!(main.borrow().tag_name(), Some("main"));
+    assert_eq!(
+        document
+            .get_element_by_id("inner")
+            .unwrap()
+            .borrow()
+            .text_content(),
+        "svg"
+    );
+    // テンプレートの内容は文書につながっていない
+    assert!(document.get_element_by_id("in-template").is_none());
+    assert!(document.get_element_by_id("missing").is_none());
+    assert!(document.get_element_by_id("").is_none());
+
+    // 文書ノード以外からは子孫だけを探す
+    let nav = document.get_element_by_id("nav").unwrap();
+    assert!(nav.borrow().get_element_by_id("main").is_none());
+    assert!(Rc::ptr_eq(
+        &main.borrow().get_element_by_id("inner").unwrap(),
+        &document.get_element_by_id("inner").unwrap()
+    ));
+}
+
+#[test]
+fn test_get_elements_by_tag_and_class_name() {
+    let document = Parser::new(PAGE).parse();
+    let document = document.borrow();
+    assert_eq!(
+        texts(&document.get_elements_by_tag_name("A")),
+        ["Home", "Docs", "Disabled"]
+    );
+    assert_eq!(
+        texts(&document.get_elements_by_tag_name("p")),
+        ["First bold text", "Second", "svg", "duplicate id"]
+    );
+    // SVG の要素は大文字小文字を区別する
+    assert_eq!(document.get_elements_by_tag_name("foreignObject").len(), 1);
+    assert!(document
+        .get_elements_by_tag_name("foreignobject")
+        .is_empty());
+    assert_eq!(document.get_elements_by_tag_name("*").len(), 23);
+
+    assert_eq!(
+        texts(&document.get_elements_by_class_name("odd")),
+        ["2", "4"]
+    );
+    assert_eq!(
+        texts(&document.get_elements_by_class_name(" top  menu ")),
+        ["Home\n    Docs\n    Disabled"]
+    );
+    assert!(document.get_elements_by_class_name("menu odd").is_empty());
+    assert!(document.get_elements_by_class_name("").is_empty());
+
+    let main = document.get_element_by_id("main").unwrap();
+    assert_eq!(
+        texts(&main.borrow().get_elements_by_class_name("note")),
+        ["First bold text"]
+    );
+}
+
+#[test]
+fn test_index_follows_tree_construction() {
+    // 2つ目の <body> の属性は既存の body に追加され、索引にも反映される
+    let document = Parser::new("<p class=a>1</p><body id=b class='a c'>").parse();
+    let document = document.borrow();
+    let body = document.get_element_by_id("b").unwrap();
+    assert_eq!(body.borrow().tag_name(), Some("body"));
+    assert_eq!(document.get_elements_by_class_name("a").len(), 2);
+    assert_eq!(document.get_elements_by_class_name("c").len(), 1);
+
+    // 書式要素の作り直しで移動した要素も引ける
+    let document = Parser::new("<b><p id=x class=y>a</b>b").parse();
+    let x = document.borrow().get_element_by_id("x").unwrap();
+    assert_eq!(x.borrow().text_content(), "ab");
+    assert_eq!(document.borrow().get_elements_by_class_name("y").len(), 1);
+}
+
+#[test]
+fn test_traversal_and_attributes() {
+    let document = Parser::new(PAGE).parse();
+    let document = document.borrow();
+    let b = document.query_selector("b").unwrap().unwrap();
+    let ancestors: Vec<String> = b
+        .borrow()
+        .ancestors()
+        .filter_map(|node| node.borrow().tag_name().map(str::to_string))
+        .collect();
+    assert_eq!(ancestors, ["p", "main", "body", "html"]);
+
+    let p = document.query_selector("p.note").unwrap().unwrap();
+    let p = p.borrow();
+    assert_eq!(p.text_content(), "First bold text");
+    assert_eq!(p.descendants().count(), 4);
+    assert!(p.has_class("note"));
+    assert_eq!(p.class_names(), ["note"]);
+    assert_eq!(p.id(), None);
+
+    let nav = document.get_element_by_id("nav").unwrap();
+    let nav = nav.borrow();
+    assert_eq!(nav.id(), Some("nav"));
+    assert_eq!(nav.class_names(), ["menu", "top"]);
+    assert!(nav.has_attribute("class"));
+    assert!(!nav.has_attribute("href"));
+    assert_eq!(nav.attributes().len(), 2);
+    assert!(document.attributes().is_empty());
+}
+
+#[test]
+fn test_query_selector() {
+    let document = Parser::new(PAGE).parse();
+    let document = document.borrow();
+    let query = |selectors: &str| texts(&document.query_selector_all(selectors).unwrap());
+
+    assert_eq!(query("nav > a[href]"), ["Home", "Docs"]);
+    assert_eq!(query("a:not([href])"), ["Disabled"]);
+    assert_eq!(query("#nav a.active, h1.title"), ["Home", "Heading"]);
+    assert_eq!(query("a[href^='/d']"), ["Docs"]);
+    assert_eq!(query("[class~=top] :last-child"), ["Disabled"]);
+    assert_eq!(query("li:nth-child(odd)"), ["1", "3"]);
+    assert_eq!(query("li:nth-last-child(-n+2)"), ["3", "4"]);
+    assert_eq!(query("h1 + p"), ["First bold text"]);
+    assert_eq!(query("h1 ~ p"), ["First bold text", "Second"]);
+    assert_eq!(query("main p:first-of-type"), ["First bold text", "svg"]);
+    assert_eq!(query("svg foreignObject > p"), ["svg"]);
+    assert_eq!(query("main > :is(h1, ul) :only-child, b"), ["bold"]);
+    assert_eq!(query("main > p:has(> b)"), ["First bold text"]);
+    assert_eq!(query("main > :has(+ ul)"), ["Second"]);
+    assert_eq!(query(":root > head > title"), ["Query"]);
+    assert_eq!(query("A:LINK"), ["Home", "Docs"]);
+
+    let first = document.query_selector("li.odd").unwrap().unwrap();
+    assert_eq!(first.borrow().text_content(), "2");
+    assert!(first.borrow().matches_selector("ul > li").unwrap());
+    assert!(document.query_selector("table").unwrap().is_none());
+}
+
+#[test]
+fn test_invalid_selector() {
+    let document = Parser::new(PAGE).parse();
+    let document = document.borrow();
+    for selector in [
+        "",
+        "a,",
+        "div >",
+        "p::before",
+        "a:hover",
+        "[href",
+        "li:nth-child(x)",
+        "a b)",
+    ] {
+        assert!(
+            document.query_selector(selector).is_err(),
+            "{selector:?} should be rejected"
+        );
+    }
+    let error = document.query_selector("p::before").unwrap_err();
+    assert_eq!(error.position, 3);
+    assert_eq!(error.to_string(), "3: unsupported pseudo-element ::before");
+}
@@ -0,0 +1,108 @@
+use orinium_browser::engine::html::parser::{NodeRef, Parser};
+use std::rc::Rc;
+
+/// 最初に見つかった`tag_name`要素を返す
+fn find(node: &NodeRef, tag_name: &str) -> Option<NodeRef> {
+    if node.borrow().tag_name() == Some(tag_name) {
+        return Some(node.clone());
+    }
+    node.borrow()
+        .children()
+        .iter()
+        .find_map(|c| find(c, tag_name))
+}
+
+/// 親・兄弟のリンクが`children`の並びと一致しているか確かめる
+fn assert_links(node: &NodeRef) {
+    let node_ref = node.borrow();
+    let children = node_ref.children();
+    for (i, child) in children.iter().enumerate() {
+        let child_ref = child.borrow();
+        assert!(Rc::ptr_eq(&child_ref.parent().unwrap(), node));
+        match child_ref.previous_sibling() {
+            Some(previous) => assert!(Rc::ptr_eq(&previous, &children[i - 1])),
+            None => assert_eq!(i, 0),
+        }
+        match child_ref.next_sibling() {
+            Some(next) => assert!(Rc::ptr_eq(&next, &children[i + 1])),
+            None => assert_eq!(i, children.len() - 1),
+        }
+        drop(child_ref);
+        assert_links(child);
+    }
+    if let Some(content) = node_ref.template_content() {
+        assert_links(&content);
+    }
+}
+
+#[test]
+fn test_document_is_freed() {
+    let document = Parser::new("<!DOCTYPE html><title>t</title><div><p>a<b>b</b></div>").parse();
+    let p = find(&document, "p").unwrap();
+    let weak_document = Rc::downgrade(&document);
+    let weak_p = Rc::downgrade(&p);
+    drop(p);
+    drop(document);
+    assert!(weak_document.upgrade().is_none());
+    assert!(weak_p.upgrade().is_none());
+}
+
+#[test]
+fn test_parent_is_not_kept_alive_by_children() {
+    let document = Parser::new("<p>text").parse();
+    let p = find(&document, "p").unwrap();
+    assert_eq!(
+        p.borrow().parent().unwrap().borrow().tag_name(),
+        Some("body")
+    );
+    drop(document);
+    // 子だけを持っていても祖先は解放される
+    assert!(p.borrow().parent().is_none());
+    assert_eq!(p.borrow().inner_html(), "text");
+}
+
+#[test]
+fn test_sibling_links() {
+    let document = Parser::new("<ul><li>a<li>b<li>c</ul>").parse();
+    let ul = find(&document, "ul").unwrap();
+    let first = ul.borrow().first_child().unwrap();
+    let second = first.borrow().next_sibling().unwrap();
+    let third = second.borrow().next_sibling().unwrap();
+    assert_eq!(third.borrow().inner_html(), "c");
+    assert!(third.borrow().next_sibling().is_none());
+    assert!(Rc::ptr_eq(&ul.borrow().last_child().unwrap(), &third));
+    assert!(Rc::ptr_eq(
+        &second.borrow().previous_sibling().unwrap(),
+        &first
+    ));
+    assert!(first.borrow().previous_sibling().is_none());
+}
+
+#[test]
+fn test_links_after_tree_fixups() {
+    // 書式要素の作り直し（adoption agency）、テーブルの前への挿入（foster parenting）、
+    // テンプレートの内容でもリンクが保たれる
+    for html in [
+        "<p>1<b>2<i>3<div>4</b>5</i>6</div>7",
+        "<table>a<tr>b<td>c</td>d</tr>e</table>f",
+        "<a><table><a>x</table>y",
+        "<template><tr><td>1</td></tr>2</template><select><option>a<option>b</select>",
+    ] {
+        let document = Parser::new(html).parse();
+        assert_links(&document);
+    }
+}
+
+#[test]
+fn test_fragment_nodes_have_no_siblings() {
+    let document = Parser::new("<div></div>").parse();
+    let div = find(&document, "div").unwrap();
+    let nodes = Parser::new_fragment("a<b>b</b><i>c</i>", &div).parse_fragment();
+    assert_eq!(nodes.len(), 3);
+    for node in &nodes {
+        let node = node.borrow();
+        assert!(node.parent().is_none());
+        assert!(node.previous_sibling().is_none());
+        assert!(node.next_sibling().is_none());
+    }
+}
@@ -0,0 +1,113 @@
+use orinium_browser::engine::html::namespace::Namespace;
+use orinium_browser::engine::html::parser::{NodeRef, NodeType, Parser};
+use orinium_browser::engine::html::tokenizer::{Token, Tokenizer};
+
+/// 最初に見つかった`tag_name`要素を返す
+fn find(node: &NodeRef, tag_name: &str) -> Option<NodeRef> {
+    if node.borrow().tag_name() == Some(tag_name) {
+        return Some(node.clone());
+    }
+    node.borrow()
+        .children()
+        .iter()
+        .find_map(|c| find(c, tag_name))
+}
+
+fn error_codes(html: &str) -> Vec<&'static str> {
+    let (_, errors) = Parser::new(html).parse_with_errors();
+    errors.iter().map(|e| e.kind.code()).collect()
+}
+
+#[test]
+fn test_foreign_element_namespaces() {
+    let document = Parser::new(
+        "<!DOCTYPE html><svg viewbox='0 0 1 1'><clippath/><foreignobject><p>x</p></foreignobject></svg><math><mi>y</mi></math>",
+    )
+    .parse();
+
+    let svg = find(&document, "svg").unwrap();
+    assert_eq!(svg.borrow().namespace(), Some(Namespace::Svg));
+    assert_eq!(svg.borrow().attribute("viewBox"), Some("0 0 1 1"));
+    assert!(find(&document, "clipPath")
+        .unwrap()
+        .borrow()
+        .children()
+        .is_empty());
+
+    // foreignObject の中身はHTMLとして扱う
+    let foreign_object = find(&document, "foreignObject").unwrap();
+    let p = foreign_object.borrow().children()[0].clone();
+    assert_eq!(p.borrow().tag_name(), Some("p"));
+    assert_eq!(p.borrow().namespace(), Some(Namespace::Html));
+
+    let mi = find(&document, "mi").unwrap();
+    assert_eq!(mi.borrow().namespace(), Some(Namespace::MathMl));
+    assert_eq!(
+        find(&document, "math").unwrap().borrow().namespace(),
+        Some(Namespace::MathMl)
+    );
+}
+
+#[test]
+fn test_foreign_attribute_namespaces() {
+    let document =
+        Parser::new("<svg><a xlink:href='#x' xml:space=preserve href='#y'></a></svg>").parse();
+    let a = find(&document, "a").unwrap();
+    let a = a.borrow();
+    let NodeType::Element { attributes, .. } = a.node_type() else {
+        unreachable!()
+    };
+    let namespaces: Vec<_> = attributes
+        .iter()
+        .map(|attr| (attr.name.as_ref(), attr.namespace))
+        .collect();
+    assert_eq!(
+        namespaces,
+        [
+            ("xlink:href", Some(Namespace::XLink)),
+            ("xml:space", Some(Namespace::Xml)),
+            ("href", None),
+        ]
+    );
+}
+
+#[test]
+fn test_cdata_sections() {
+    // HTMLの中ではコメントになる
+    let mut tokenizer = Tokenizer::new("<![CDATA[x]]>");
+    assert_eq!(
+        tokenizer.next_token(),
+        Some(Token::Comment("[CDATA[x]]".into()))
+    );
+    assert_eq!(tokenizer.errors()[0].kind.code(), "cdata-in-html-content");
+
+    let mut tokenizer = Tokenizer::new_streaming();
+    tokenizer.set_cdata_allowed(true);
+    tokenizer.feed(b"<![CDATA[a<b>]");
+    assert_eq!(tokenizer.next_token(), Some(Token::Text("a<b>".into())));
+    assert_eq!(tokenizer.next_token(), None);
+    tokenizer.feed(b"]>c<![CDATA[d");
+    tokenizer.finish();
+    assert_eq!(tokenizer.next_token(), Some(Token::Text("c".into())));
+    assert_eq!(tokenizer.next_token(), Some(Token::Text("d".into())));
+    let codes: Vec<_> = tokenizer.errors().iter().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, ["eof-in-cdata"]);
+}
+
+#[test]
+fn test_html_breaks_out_of_foreign_content() {
+    let document = Parser::new("<!DOCTYPE html><svg><g><p>text</p></g></svg>").parse();
+    let p = find(&document, "p").unwrap();
+    let parent = p.borrow().parent().unwrap();
+    assert_eq!(parent.borrow().tag_name(), Some("body"));
+
+    assert_eq!(
+        error_codes("<!DOCTYPE html><svg><g><p>text</p></g></svg>"),
+        [
+            "unexpected-start-tag",
+            "unexpected-end-tag",
+            "unexpected-end-tag"
+        ]
+    );
+    assert!(error_codes("<!DOCTYPE html><svg><path d=''/><g></G></svg>").is_empty());
+}
@@ -0,0 +1,69 @@
+use orinium_browser::engine::html::namespace::Namespace;
+use orinium_browser::engine::html::parser::{Node, NodeRef, NodeType, Parser};
+
+/// 最初に見つかった`tag_name`要素を返す
+fn find(node: &NodeRef, tag_name: &str) -> Option<NodeRef> {
+    if node.borrow().tag_name() == Some(tag_name) {
+        return Some(node.clone());
+    }
+    node.borrow()
+        .children()
+        .iter()
+        .find_map(|c| find(c, tag_name))
+}
+
+fn names(nodes: &[NodeRef]) -> Vec<String> {
+    nodes
+        .iter()
+        .map(|node| match node.borrow().node_type() {
+            NodeType::Element { tag_name, .. } => tag_name.clone(),
+            NodeType::Text(text) => format!("{text:?}"),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_fragment_in_table_context() {
+    let tbody = Node::new_element(Namespace::Html, "tbody", vec![]);
+    let mut parser = Parser::new_fragment("<tr><td>1<tr><td>2", &tbody);
+    let nodes = parser.parse_fragment();
+    assert_eq!(names(&nodes), ["tr", "tr"]);
+    // 返されたノードは親を持たない
+    assert!(nodes.iter().all(|node| node.borrow().parent().is_none()));
+    assert!(parser.errors().is_empty());
+
+    // 文脈がなければ body の中として扱われ、表の要素は無視される
+    let div = Node::new_element(Namespace::Html, "div", vec![]);
+    let nodes = Parser::new_fragment("<tr><td>1", &div).parse_fragment();
+    assert_eq!(names(&nodes), ["\"1\""]);
+}
+
+#[test]
+fn test_fragment_tokenizer_state_follows_context() {
+    for (context, expected) in [
+        ("textarea", "\"<b>&</b>\""),
+        ("style", "\"<b>&amp;</b>\""),
+        ("script", "\"<b>&amp;</b>\""),
+        ("plaintext", "\"<b>&amp;</b>\""),
+        ("div", "b"),
+    ] {
+        let context = Node::new_element(Namespace::Html, context, vec![]);
+        let nodes = Parser::new_fragment("<b>&amp;</b>", &context).parse_fragment();
+        assert_eq!(names(&nodes), [expected]);
+    }
+}
+
+#[test]
+fn test_fragment_inside_form() {
+    // 文脈要素の祖先の form は form 要素ポインタになる
+    let document = Parser::new("<form><div></div></form>").parse();
+    let div = find(&document, "div").unwrap();
+    let nodes = Parser::new_fragment("<form><input>", &div).parse_fragment();
+    assert_eq!(names(&nodes), ["input"]);
+
+    let svg = Node::new_element(Namespace::Svg, "svg", vec![]);
+    let nodes = Parser::new_fragment("<clippath/><![CDATA[x]]>", &svg).parse_fragment();
+    assert_eq!(names(&nodes), ["clipPath", "\"x\""]);
+    assert_eq!(nodes[0].borrow().namespace(), Some(Namespace::Svg));
+}
@@ -0,0 +1,449 @@
+//! html5lib-tests 形式の適合性テスト
+//!
+//! `tests/html5lib/tokenizer/*.test`（字句解析）と `tests/html5lib/tree-construction/*.dat`
+//! （ツリー構築）を読み込んで実行し、ファイルごとの結果を表示します。
+//! `tests/html5lib/expected-failures.txt` に載っていないテストが失敗した場合と、
+//! 載っているテストが成功した場合（一覧から削除する必要がある）にテストが失敗します。
+//! 比較するのはトークン列と DOM ツリーだけで、期待されるパースエラー（`#errors`）は比較しません。
+//!
+//! 結果の表示: `cargo test --test html5lib_test -- --nocapture`
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use orinium_browser::engine::html::namespace::Namespace;
+use orinium_browser::engine::html::parser::{Node, NodeRef, NodeType, Parser};
+use orinium_browser::engine::html::tokenizer::{Token, Tokenizer, TokenizerState};
+use serde_json::{json, Map, Value};
+
+const ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/html5lib");
+
+enum Outcome {
+    Pass,
+    Fail(String),
+    /// 未対応の機能（フラグメント解析など）を使うテスト
+    Skip,
+}
+
+#[derive(Default)]
+struct Report {
+    lines: Vec<String>,
+    unexpected_failures: Vec<String>,
+    unexpected_passes: Vec<String>,
+}
+
+impl Report {
+    /// 1ファイル分の結果を記録します
+    ///
+    /// # 引数
+    /// * `file` - `ROOT` からの相対パス（テストIDの接頭辞になる）
+    /// * `outcomes` - ファイル内のテストの結果（順番がテスト番号になる）
+    fn record(&mut self, file: &str, outcomes: Vec<Outcome>, expected: &BTreeSet<String>) {
+        let (mut passed, mut failed, mut expected_failed, mut skipped) = (0, 0, 0, 0);
+        for (index, outcome) in outcomes.into_iter().enumerate() {
+            let id = format!("{file}:{}", index + 1);
+            match outcome {
+                Outcome::Pass => {
+                    passed += 1;
+                    if expected.contains(&id) {
+                        self.unexpected_passes.push(id);
+                    }
+                }
+                Outcome::Fail(message) => {
+                    failed += 1;
+                    if expected.contains(&id) {
+                        expected_failed += 1;
+                    } else {
+                        self.unexpected_failures.push(format!("{id}\n{message}"));
+                    }
+                }
+                Outcome::Skip => skipped += 1,
+            }
+        }
+        self.lines.push(format!(
+            "{file}: {passed} passed, {failed} failed ({expected_failed} expected), {skipped} skipped"
+        ));
+    }
+
+    fn finish(self) {
+        for line in &self.lines {
+            println!("{line}");
+        }
+        for failure in &self.unexpected_failures {
+            println!("\nUNEXPECTED FAILURE: {failure}");
+        }
+        for pass in &self.unexpected_passes {
+            println!("\nUNEXPECTED PASS (remove from expected-failures.txt): {pass}");
+        }
+        assert!(
+            self.unexpected_failures.is_empty() && self.unexpected_passes.is_empty(),
+            "{} unexpected failures, {} unexpected passes",
+            self.unexpected_failures.len(),
+            self.unexpected_passes.len()
+        );
+    }
+}
+
+/// 既知の失敗の一覧を読み込みます（`#` 以降はコメント）
+fn expected_failures() -> BTreeSet<String> {
+    let text = fs::read_to_string(Path::new(ROOT).join("expected-failures.txt")).unwrap();
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// ディレクトリ内の指定した拡張子のファイルを名前順に返します
+fn test_files(dir: &str, extension: &str) -> Vec<PathBuf> {
+    let mut files: Vec<_> = fs::read_dir(Path::new(ROOT).join(dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == extension))
+        .collect();
+    files.sort();
+    files
+}
+
+fn relative_name(dir: &str, path: &Path) -> String {
+    format!("{dir}/{}", path.file_name().unwrap().to_string_lossy())
+}
+
+// -------------------------------------------------------------------------
+// 字句解析
+// -------------------------------------------------------------------------
+
+#[test]
+fn tokenizer() {
+    let expected = expected_failures();
+    let mut report = Report::default();
+    for path in test_files("tokenizer", "test") {
+        let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let outcomes = json["tests"]
+            .as_array()
+            .map(|tests| tests.iter().map(run_tokenizer_test).collect())
+            .unwrap_or_default();
+        report.record(&relative_name("tokenizer", &path), outcomes, &expected);
+    }
+    report.finish();
+}
+
+fn run_tokenizer_test(test: &Value) -> Outcome {
+    let double_escaped = test["doubleEscaped"].as_bool().unwrap_or(false);
+    let mut input = test["input"].as_str().unwrap_or_default().to_string();
+    let mut expected = test["output"].clone();
+    if double_escaped {
+        input = unescape(&input);
+        unescape_json(&mut expected);
+    }
+    let expected = coalesce_characters(expected.as_array().cloned().unwrap_or_default());
+
+    let states = match test["initialStates"].as_array() {
+        Some(states) => states.iter().filter_map(Value::as_str).collect(),
+        None => vec!["Data state"],
+    };
+    for state_name in states {
+        let state = match state_name {
+            "Data state" => TokenizerState::Data,
+            "PLAINTEXT state" => TokenizerState::Plaintext,
+            "RCDATA state" => TokenizerState::Rcdata,
+            "RAWTEXT state" => TokenizerState::Rawtext,
+            "Script data state" => TokenizerState::ScriptData,
+            _ => return Outcome::Skip,
+        };
+        let mut tokenizer = Tokenizer::new(&input);
+        tokenizer.switch_to(state);
+        if let Some(last_start_tag) = test["lastStartTag"].as_str() {
+            tokenizer.set_last_start_tag(last_start_tag);
+        }
+        let actual = coalesce_characters(
+            std::iter::from_fn(|| tokenizer.next_token())
+                .map(token_to_json)
+                .collect(),
+        );
+        if actual != expected {
+            return Outcome::Fail(format!(
+                "{} ({state_name})\ninput:    {input:?}\nexpected: {}\nactual:   {}",
+                test["description"].as_str().unwrap_or_default(),
+                Value::Array(expected),
+                Value::Array(actual)
+            ));
+        }
+    }
+    Outcome::Pass
+}
+
+fn token_to_json(token: Token) -> Value {
+    match token {
+        Token::Doctype {
+            name,
+            public_id,
+            system_id,
+            force_quirks,
+        } => json!(["DOCTYPE", name, public_id, system_id, !force_quirks]),
+        Token::StartTag {
+            name,
+            attributes,
+            self_closing,
+        } => {
+            // 重複した属性は最初のものだけが有効
+            let mut attrs = Map::new();
+            for attribute in attributes {
+                attrs
+                    .entry(attribute.name)
+                    .or_insert(Value::String(attribute.value.into_owned()));
+            }
+            let mut token = vec![json!("StartTag"), json!(name), Value::Object(attrs)];
+            if self_closing {
+                token.push(json!(true));
+            }
+            Value::Array(token)
+        }
+        Token::EndTag { name } => json!(["EndTag", name]),
+        Token::Comment(data) => json!(["Comment", data]),
+        Token::Text(text) => json!(["Character", text]),
+        Token::Eof => Value::Null,
+    }
+}
+
+/// 連続する文字トークンを1つにまとめます（トークンの区切り方は比較しない）
+fn coalesce_characters(tokens: Vec<Value>) -> Vec<Value> {
+    let mut result: Vec<Value> = vec![];
+    for token in tokens {
+        if let (Some(last), Some("Character")) = (result.last_mut(), token[0].as_str()) {
+            if last[0] == "Character" {
+                let text = format!(
+                    "{}{}",
+                    last[1].as_str().unwrap_or_default(),
+                    token[1].as_str().unwrap_or_default()
+                );
+                last[1] = Value::String(text);
+                continue;
+            }
+        }
+        result.push(token);
+    }
+    result
+}
+
+/// `doubleEscaped` なテストの `\uXXXX` を展開します
+fn unescape(s: &str) -> String {
+    let mut units = vec![];
+    let mut rest = s;
+    while let Some(index) = rest.find("\\u") {
+        units.extend(rest[..index].encode_utf16());
+        match rest
+            .get(index + 2..index + 6)
+            .map(|hex| u16::from_str_radix(hex, 16))
+        {
+            Some(Ok(unit)) => {
+                units.push(unit);
+                rest = &rest[index + 6..];
+            }
+            _ => {
+                units.extend("\\u".encode_utf16());
+                rest = &rest[index + 2..];
+            }
+        }
+    }
+    units.extend(rest.encode_utf16());
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn unescape_json(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = unescape(s),
+        Value::Array(values) => values.iter_mut().for_each(unescape_json),
+        Value::Object(map) => {
+            let entries = std::mem::take(map);
+            for (key, mut value) in entries {
+                unescape_json(&mut value);
+                map.insert(unescape(&key), value);
+            }
+        }
+        _ => {}
+    }
+}
+
+// -------------------------------------------------------------------------
+// ツリー構築
+// -------------------------------------------------------------------------
+
+#[derive(Default)]
+struct TreeTest {
+    data: String,
+    document: String,
+    fragment_context: Option<String>,
+    scripting: Option<bool>,
+}
+
+#[test]
+fn tree_construction() {
+    let expected = expected_failures();
+    let mut report = Report::default();
+    for path in test_files("tree-construction", "dat") {
+        let outcomes = parse_dat(&fs::read_to_string(&path).unwrap())
+            .iter()
+            .map(run_tree_test)
+            .collect();
+        report.record(
+            &relative_name("tree-construction", &path),
+            outcomes,
+            &expected,
+        );
+    }
+    report.finish();
+}
+
+/// `.dat` 形式のファイルを読み込みます
+///
+/// テストは `#data` から始まり、空行の後の次の `#data` までが1つのテストです
+/// （テキストノードが空行を含むことがあるため、空行だけでは区切りません）。
+fn parse_dat(text: &str) -> Vec<TreeTest> {
+    let mut tests = vec![];
+    let mut sections: Vec<(String, Vec<&str>)> = vec![];
+    let mut previous_blank = true;
+    for line in text.lines() {
+        if line == "#data" && previous_blank {
+            tests.extend(tree_test_from_sections(std::mem::take(&mut sections)));
+        }
+        previous_blank = line.is_empty();
+        if line.starts_with('#') && !line.starts_with("#!") {
+            sections.push((line.to_string(), vec![]));
+        } else if let Some((_, lines)) = sections.last_mut() {
+            lines.push(line);
+        }
+    }
+    tests.extend(tree_test_from_sections(sections));
+    tests
+}
+
+fn tree_test_from_sections(sections: Vec<(String, Vec<&str>)>) -> Option<TreeTest> {
+    if sections.is_empty() {
+        return None;
+    }
+    let mut test = TreeTest::default();
+    for (name, mut lines) in sections {
+        match name.as_str() {
+            "#data" => test.data = lines.join("\n"),
+            "#document" => {
+                // テスト間の区切りの空行を取り除く
+                if lines.last() == Some(&"") {
+                    lines.pop();
+                }
+                test.document = lines.join("\n");
+            }
+            "#document-fragment" => test.fragment_context = lines.first().map(|s| s.to_string()),
+            "#script-on" => test.scripting = Some(true),
+            "#script-off" => test.scripting = Some(false),
+            _ => {}
+        }
+    }
+    Some(test)
+}
+
+fn run_tree_test(test: &TreeTest) -> Outcome {
+    // スクリプト有効時の解析は未対応
+    if test.scripting == Some(true) {
+        return Outcome::Skip;
+    }
+    let nodes = match &test.fragment_context {
+        Some(context) => {
+            // 文脈要素は `td` や `svg path` のように書かれる
+            let (namespace, name) = match context.split_once(' ') {
+                Some(("svg", name)) => (Namespace::Svg, name),
+                Some(("math", name)) => (Namespace::MathMl, name),
+                _ => (Namespace::Html, context.as_str()),
+            };
+            let context = Node::new_element(namespace, name, vec![]);
+            Parser::new_fragment(&test.data, &context).parse_fragment()
+        }
+        None => Parser::new(&test.data).parse().borrow().children().to_vec(),
+    };
+    let mut lines = vec![];
+    for node in &nodes {
+        serialize_tree(node, 0, &mut lines);
+    }
+    let actual = lines.join("\n");
+    if actual == test.document {
+        Outcome::Pass
+    } else {
+        Outcome::Fail(format!(
+            "#data\n{}\n#expected\n{}\n#actual\n{actual}",
+            test.data, test.document
+        ))
+    }
+}
+
+/// html5lib のテスト形式でノードを書き出します
+fn serialize_tree(node: &NodeRef, depth: usize, lines: &mut Vec<String>) {
+    let node = node.borrow();
+    let indent = "  ".repeat(depth);
+    match node.node_type() {
+        NodeType::Document | NodeType::DocumentFragment => {}
+        NodeType::Doctype {
+            name,
+            public_id,
+            system_id,
+        } => {
+            let name = name.as_deref().unwrap_or_default();
+            if public_id.is_some() || system_id.is_some() {
+                lines.push(format!(
+                    "| {indent}<!DOCTYPE {name} \"{}\" \"{}\">",
+                    public_id.as_deref().unwrap_or_default(),
+                    system_id.as_deref().unwrap_or_default()
+                ));
+            } else {
+                lines.push(format!("| {indent}<!DOCTYPE {name}>"));
+            }
+        }
+        NodeType::Element {
+            tag_name,
+            attributes,
+            namespace,
+            template_content,
+        } => {
+            // 外部コンテンツの要素は `<svg foreignObject>` のように名前空間の接頭辞を付ける
+            match namespace {
+                Namespace::Html => lines.push(format!("| {indent}<{tag_name}>")),
+                _ => lines.push(format!("| {indent}<{} {tag_name}>", namespace.prefix())),
+            }
+            let mut attributes: Vec<_> = attributes
+                .iter()
+                .map(|attribute| {
+                    // 名前空間を持つ属性は `xlink href` のように書く
+                    let name = match attribute.namespace {
+                        Some(namespace) => {
+                            let local_name = attribute
+                                .name
+                                .split_once(':')
+                                .map_or(attribute.name.as_ref(), |(_, local)| local);
+                            format!("{} {local_name}", namespace.prefix())
+                        }
+                        None => attribute.name.to_string(),
+                    };
+                    (name, &attribute.value)
+                })
+                .collect();
+            attributes.sort();
+            for (name, value) in attributes {
+                lines.push(format!("| {indent}  {name}=\"{value}\""));
+            }
+            // template の内容は `content` の下に書く
+            if let Some(content) = template_content {
+                lines.push(format!("| {indent}  content"));
+                for child in content.borrow().children() {
+                    serialize_tree(child, depth + 2, lines);
+                }
+            }
+        }
+        NodeType::Text(text) => lines.push(format!("| {indent}\"{text}\"")),
+        NodeType::Comment(data) => lines.push(format!("| {indent}<!-- {data} -->")),
+    }
+    for child in node.children() {
+        serialize_tree(child, depth + 1, lines);
+    }
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{http_response, spawn_http_server};
+use orinium_browser::platform::network::{
+    Middleware, NetworkCore, NetworkError, Request, RequestAction, Response,
+};
+
+/// 受け取ったリクエストヘッダーをそのままボディとして返すサーバー
+async fn spawn_echo_server() -> u16 {
+    spawn_http_server(|head, _| http_response("200 OK", &[], head)).await
+}
+
+struct HeaderInjector;
+
+impl Middleware for HeaderInjector {
+    fn name(&self) -> &str {
+        "header-injector"
+    }
+
+    fn on_request(&self, request: &mut Request) -> RequestAction {
+        request.set_header("X-Injected", "yes");
+        RequestAction::Continue
+    }
+}
+
+struct Blocker;
+
+impl Middleware for Blocker {
+    fn name(&self) -> &str {
+        "blocker"
+    }
+
+    fn on_request(&self, request: &mut Request) -> RequestAction {
+        if request.url.path().starts_with("/ads/") {
+            RequestAction::Block("ad".to_string())
+        } else {
+            RequestAction::Continue
+        }
+    }
+}
+
+struct Mock;
+
+impl Middleware for Mock {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn on_request(&self, request: &mut Request) -> RequestAction {
+        if request.url.path() == "/mocked" {
+            RequestAction::Respond(Response {
+                http_version: "HTTP/1.1".to_string(),
+                status_code: 200,
+                reason_phrase: "OK".to_string(),
+                headers: vec![],
+                body: b"mock body".to_vec(),
+            })
+        } else {
+            RequestAction::Continue
+        }
+    }
+}
+
+/// 呼び出し順を記録するミドルウェア
+struct Recorder {
+    label: &'static str,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl Middleware for Recorder {
+    fn name(&self) -> &str {
+        self.label
+    }
+
+    fn on_request(&self, _request: &mut Request) -> RequestAction {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("request:{}", self.label));
+        RequestAction::Continue
+    }
+
+    fn on_response(&self, _request: &Request, response: &mut Response) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("response:{}", self.label));
+        response
+            .headers
+            .push(("X-Seen-By".to_string(), self.label.to_string()));
+    }
+}
+
+#[tokio::test]
+async fn test_middleware_rewrites_request_headers() {
+    let port = spawn_echo_server().await;
+    let net = NetworkCore::with_middlewares(vec![Arc::new(HeaderInjector)]).unwrap();
+
+    let resp = net
+        .fetch(&format!("http://127.0.0.1:{port}/"))
+        .await
+        .unwrap();
+    let echoed = String::from_utf8_lossy(&resp.body);
+    assert!(echoed.contains("X-Injected: yes"), "{echoed}");
+}
+
+#[tokio::test]
+async fn test_middleware_blocks_request() {
+    let port = spawn_echo_server().await;
+    let net = NetworkCore::with_middlewares(vec![Arc::new(Blocker)]).unwrap();
+
+    let err = net
+        .fetch(&format!("http://127.0.0.1:{port}/ads/banner.js"))
+        .await
+        .unwrap_err();
+    let NetworkError::Blocked(blocked) = err else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(blocked.middleware, "blocker");
+    assert_eq!(blocked.reason, "ad");
+
+    assert!(net
+        .fetch(&format!("http://127.0.0.1:{port}/app.js"))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_middleware_short_circuits_with_synthetic_response() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let net = NetworkCore::with_middlewares(vec![
+        Arc::new(Recorder {
+            label: "outer",
+            calls: Arc::clone(&calls),
+        }),
+        Arc::new(Mock),
+        Arc::new(Recorder {
+            label: "inner",
+            calls: Arc::clone(&calls),
+        }),
+    ])
+    .unwrap();
+
+    // 接続先は存在しないが、Mock が応答するため通信は発生しない
+    let resp = net.fetch("http://127.0.0.1:1/mocked").await.unwrap();
+    assert_eq!(resp.body, b"mock body");
+
+    // Mock より後ろのミドルウェアはリクエストもレスポンスも見ない
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec!["request:outer".to_string(), "response:outer".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_middleware_response_order() {
+    let port = spawn_echo_server().await;
+    let calls = Arc::new(Mutex::new(vec![]));
+    let net = NetworkCore::with_middlewares(vec![
+        Arc::new(Recorder {
+            label: "first",
+            calls: Arc::clone(&calls),
+        }),
+        Arc::new(Recorder {
+            label: "second",
+            calls: Arc::clone(&calls),
+        }),
+    ])
+    .unwrap();
+
+    let resp = net
+        .post(&format!("http://127.0.0.1:{port}/"), vec![], "text/plain")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            "request:first".to_string(),
+            "request:second".to_string(),
+            "response:second".to_string(),
+            "response:first".to_string(),
+        ]
+    );
+    let seen_by = resp
+        .headers
+        .iter()
+        .filter(|(k, _)| k == "X-Seen-By")
+        .map(|(_, v)| v.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(seen_by, vec!["second", "first"]);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use std::error::Error;
+use std::time::Duration;
+
+use common::spawn_http_server;
+use orinium_browser::platform::network::{
+    CertificateProblem, NetworkCore, NetworkError, TcpConnection, TlsConnection, TlsErrorKind,
+};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn test_invalid_url() {
+    let net = NetworkCore::new().unwrap();
+    let err = net.fetch("not a url").await.unwrap_err();
+    assert!(matches!(err, NetworkError::InvalidUrl { ref url, .. } if url == "not a url"));
+    // 原因の url::ParseError をたどれる
+    assert!(err
+        .source()
+        .and_then(|e| e.downcast_ref::<url::ParseError>())
+        .is_some());
+}
+
+#[tokio::test]
+async fn test_connection_refused_has_host_and_port() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let err = TcpConnection::connect("127.0.0.1", port, Duration::from_secs(1))
+        .await
+        .unwrap_err();
+    match err {
+        NetworkError::ConnectionRefused {
+            ref host,
+            port: p,
+            ref source,
+        } => {
+            assert_eq!(host, "127.0.0.1");
+            assert_eq!(p, port);
+            assert_eq!(source.kind(), std::io::ErrorKind::ConnectionRefused);
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+}
+
+#[tokio::test]
+async fn test_malformed_status_line_is_protocol_error() {
+    let port = spawn_http_server(|_, _| "garbage\r\n\r\n".to_string()).await;
+    let net = NetworkCore::new().unwrap();
+    let err = net
+        .fetch(&format!("http://127.0.0.1:{port}/"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, NetworkError::Protocol { .. }), "{err}");
+}
+
+#[tokio::test]
+async fn test_tls_handshake_with_non_tls_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let _ = socket.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+        }
+    });
+
+    let err = TlsConnection::connect("localhost", port, Duration::from_secs(2))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            NetworkError::Tls {
+                kind: TlsErrorKind::Handshake,
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+#[ignore] // インターネット接続が必要
+async fn test_expired_certificate() {
+    let err = TlsConnection::connect("expired.badssl.com", 443, Duration::from_secs(10))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        NetworkError::Tls {
+            kind: TlsErrorKind::Certificate(CertificateProblem::Expired),
+            ..
+        }
+    ));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{http_response, spawn_http_server};
+use orinium_browser::platform::network::NetworkCore;
+
+async fn spawn_server() -> u16 {
+    spawn_http_server(|_, _| http_response("200 OK", &[("Content-Type", "text/html")], "hello"))
+        .await
+}
+
+#[tokio::test]
+async fn test_network_log_disabled_by_default() {
+    let port = spawn_server().await;
+    let net = NetworkCore::new().unwrap();
+
+    net.fetch(&format!("http://127.0.0.1:{port}/"))
+        .await
+        .unwrap();
+    assert!(net.network_log.is_empty().await);
+}
+
+#[tokio::test]
+async fn test_network_log_records_requests() {
+    let port = spawn_server().await;
+    let net = NetworkCore::new().unwrap();
+    net.config.write().await.enable_network_log = true;
+
+    let url = format!("http://127.0.0.1:{port}/index.html");
+    net.fetch(&url).await.unwrap();
+    // 2回目はキャッシュから応答される
+    net.fetch(&url).await.unwrap();
+    net.post(
+        &format!("http://127.0.0.1:{port}/form"),
+        b"a=1".to_vec(),
+        "text/plain",
+    )
+    .await
+    .unwrap();
+
+    let entries = net.network_log.entries().await;
+    assert_eq!(entries.len(), 3);
+
+    let first = &entries[0];
+    assert_eq!(first.method, "GET");
+    assert_eq!(first.status_code, 200);
+    assert_eq!(first.response_body_size, 5);
+    assert_eq!(first.response_header("content-type"), Some("text/html"));
+    assert!(!first.cache_hit);
+    assert!(!first.connection_reused);
+    assert!(first.timings.dns.is_some());
+    assert!(first.timings.connect.is_some());
+    assert!(first.timings.tls.is_none());
+
+    assert!(entries[1].cache_hit);
+
+    let post = &entries[2];
+    assert_eq!(post.method, "POST");
+    assert_eq!(post.request_body_size, 3);
+    assert!(post.connection_reused);
+    assert!(post.timings.dns.is_none());
+
+    let form = net.network_log.filter(|e| e.url.path() == "/form").await;
+    assert_eq!(form.len(), 1);
+    assert_eq!(net.network_log.entries_for_host("127.0.0.1").await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_network_log_har_export() {
+    let port = spawn_server().await;
+    let net = NetworkCore::new().unwrap();
+    net.config.write().await.enable_network_log = true;
+
+    net.fetch(&format!("http://127.0.0.1:{port}/search?q=orinium"))
+        .await
+        .unwrap();
+
+    let har: serde_json::Value = serde_json::from_str(&net.network_log.to_har().await).unwrap();
+    assert_eq!(har["log"]["version"], "1.2");
+
+    let entry = &har["log"]["entries"][0];
+    assert_eq!(entry["request"]["method"], "GET");
+    assert_eq!(entry["request"]["queryString"][0]["name"], "q");
+    assert_eq!(entry["request"]["queryString"][0]["value"], "orinium");
+    assert_eq!(entry["response"]["status"], 200);
+    assert_eq!(entry["response"]["content"]["mimeType"], "text/html");
+    assert_eq!(entry["response"]["content"]["size"], 5);
+    assert_eq!(entry["timings"]["ssl"], -1.0);
+    assert!(entry["startedDateTime"].as_str().unwrap().ends_with('Z'));
+
+    net.network_log.clear().await;
+    assert!(net.network_log.is_empty().await);
+}
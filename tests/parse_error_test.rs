@@ -0,0 +1,233 @@
+use orinium_browser::engine::html::error::ParseErrorKind;
+use orinium_browser::engine::html::parser::{NodeRef, NodeType, Parser};
+use orinium_browser::engine::html::tokenizer::{Token, Tokenizer};
+
+fn error_codes(html: &str) -> Vec<&'static str> {
+    let mut tokenizer = Tokenizer::new(html);
+    while tokenizer.next_token().is_some() {}
+    tokenizer.errors().iter().map(|e| e.kind.code()).collect()
+}
+
+/// 最初に見つかった`tag_name`要素を返す
+fn find(node: &NodeRef, tag_name: &str) -> Option<NodeRef> {
+    if node.borrow().tag_name() == Some(tag_name) {
+        return Some(node.clone());
+    }
+    node.borrow()
+        .children()
+        .iter()
+        .find_map(|c| find(c, tag_name))
+}
+
+#[test]
+fn test_token_spans() {
+    let html = "a<b x=1>\n&amp;</b><!--c-->";
+    let mut tokenizer = Tokenizer::new(html);
+    let spans: Vec<_> = std::iter::from_fn(|| tokenizer.next_token_with_span()).collect();
+    let slices: Vec<_> = spans
+        .iter()
+        .map(|(_, span)| span.slice(html).unwrap())
+        .collect();
+    assert_eq!(slices, ["a", "<b x=1>", "\n&amp;", "</b>", "<!--c-->"]);
+    assert_eq!(spans[2].0, Token::Text("\n&".into()));
+
+    // 行と列は1始まり、列は文字単位
+    let end_tag = spans[3].1;
+    assert_eq!((end_tag.start.line, end_tag.start.column), (2, 6));
+    let mut tokenizer = Tokenizer::new("あい<br>");
+    tokenizer.next_token();
+    let (_, span) = tokenizer.next_token_with_span().unwrap();
+    assert_eq!((span.start.offset, span.start.column), (6, 3));
+
+    // 同じ行の中で前後する位置（トークンの範囲とエラーの位置）も正しく数える
+    let html = "x\nあい<b x=1 x=2>う\0え</b>&amp;お<p\0>";
+    let mut tokenizer = Tokenizer::new(html);
+    let spans: Vec<_> = std::iter::from_fn(|| tokenizer.next_token_with_span()).collect();
+    let positions = spans
+        .iter()
+        .flat_map(|(_, span)| [span.start, span.end])
+        .chain(tokenizer.errors().iter().map(|e| e.position));
+    for position in positions {
+        let line_start = html[..position.offset].rfind('\n').map_or(0, |i| i + 1);
+        let column = html[line_start..position.offset].chars().count() + 1;
+        assert_eq!(position.column, column, "{position:?}");
+    }
+}
+
+#[test]
+fn test_tokenizer_errors() {
+    assert_eq!(error_codes("a\0b"), ["unexpected-null-character"]);
+    assert_eq!(error_codes("<div class=x"), ["eof-in-tag"]);
+    assert_eq!(error_codes("a</>b"), ["missing-end-tag-name"]);
+    assert_eq!(
+        error_codes("<?xml?>"),
+        ["unexpected-question-mark-instead-of-tag-name"]
+    );
+    assert_eq!(error_codes("<!-- x"), ["eof-in-comment"]);
+    assert_eq!(error_codes("<!-->"), ["abrupt-closing-of-empty-comment"]);
+    assert_eq!(error_codes("<!-- a --!>"), ["incorrectly-closed-comment"]);
+    assert_eq!(error_codes("<!-- <!-- a -->"), ["nested-comment"]);
+    assert_eq!(error_codes("<!DOCTYPE>"), ["missing-doctype-name"]);
+    assert_eq!(error_codes("</p x>"), ["end-tag-with-attributes"]);
+    assert_eq!(
+        error_codes("&unknown; &amp &#0; &#x110000;"),
+        [
+            "unknown-named-character-reference",
+            "missing-semicolon-after-character-reference",
+            "null-character-reference",
+            "character-reference-outside-unicode-range",
+        ]
+    );
+    assert!(error_codes("<!DOCTYPE html><p class=\"a\">&amp;</p>").is_empty());
+}
+
+#[test]
+fn test_comments_close_like_browsers() {
+    let tokens = |html| {
+        let mut tokenizer = Tokenizer::new(html);
+        std::iter::from_fn(|| tokenizer.next_token()).collect::<Vec<_>>()
+    };
+    let img = || Token::StartTag {
+        name: "img".into(),
+        attributes: Vec::new(),
+        self_closing: false,
+    };
+    // `<!-->`、`<!--->`、`--->`、`--!>` はいずれもコメントを閉じる
+    assert_eq!(
+        tokens("<!--><img>-->"),
+        [Token::Comment("".into()), img(), Token::Text("-->".into())]
+    );
+    assert_eq!(
+        tokens("<!---><img>")[..2],
+        [Token::Comment("".into()), img()]
+    );
+    assert_eq!(
+        tokens("<!-- x ---><img>")[..2],
+        [Token::Comment(" x -".into()), img()]
+    );
+    assert_eq!(
+        tokens("<!-- a --!><img>")[..2],
+        [Token::Comment(" a ".into()), img()]
+    );
+    // 閉じない並びはそのままコメントの内容になる
+    assert_eq!(
+        tokens("<!-- a --!b <!-- c -- d -->"),
+        [Token::Comment(" a --!b <!-- c -- d ".into())]
+    );
+    assert_eq!(tokens("<!-x>"), [Token::Comment("-x".into())]);
+}
+
+#[test]
+fn test_unexpected_characters_stay_in_attribute_names() {
+    let attribute_names = |html| {
+        let mut tokenizer = Tokenizer::new(html);
+        let Some(Token::StartTag { attributes, .. }) = tokenizer.next_token() else {
+            panic!("{html}");
+        };
+        let names: Vec<_> = attributes.iter().map(|a| a.name.to_string()).collect();
+        let errors: Vec<_> = tokenizer.errors().iter().map(|e| e.kind.code()).collect();
+        (names, errors)
+    };
+    assert_eq!(
+        attribute_names("<p a\"b=1 c'd<e>"),
+        (
+            vec!["a\"b".to_string(), "c'd<e".to_string()],
+            vec!["unexpected-character-in-attribute-name"; 3]
+        )
+    );
+    assert_eq!(
+        attribute_names("<p =x \"y>"),
+        (
+            vec!["=x".to_string(), "\"y".to_string()],
+            vec![
+                "unexpected-equals-sign-before-attribute-name",
+                "unexpected-character-in-attribute-name",
+            ]
+        )
+    );
+    assert_eq!(
+        attribute_names("<p a <b>"),
+        (
+            vec!["a".to_string(), "<b".to_string()],
+            vec!["unexpected-character-in-attribute-name"]
+        )
+    );
+}
+
+#[test]
+fn test_duplicate_attributes_are_dropped() {
+    let mut tokenizer = Tokenizer::new("<a href=1 HREF=2>");
+    match tokenizer.next_token() {
+        Some(Token::StartTag { attributes, .. }) => {
+            assert_eq!(attributes.len(), 1);
+            assert_eq!(attributes[0].value, "1");
+        }
+        other => panic!("unexpected token: {other:?}"),
+    }
+    assert_eq!(
+        tokenizer.errors()[0].kind,
+        ParseErrorKind::DuplicateAttribute
+    );
+
+    // 空白の後に続く属性や `=` も読み落とさない
+    let mut tokenizer = Tokenizer::new("<input disabled value = 1 checked>");
+    match tokenizer.next_token() {
+        Some(Token::StartTag { attributes, .. }) => {
+            let names: Vec<_> = attributes.iter().map(|a| a.name.as_ref()).collect();
+            assert_eq!(names, ["disabled", "value", "checked"]);
+            assert_eq!(attributes[1].value, "1");
+        }
+        other => panic!("unexpected token: {other:?}"),
+    }
+}
+
+#[test]
+fn test_node_spans() {
+    let html = "<!DOCTYPE html>\n<p class=x>hi <b>there</b></p>";
+    let document = Parser::new(html).parse();
+
+    let p = find(&document, "p").unwrap();
+    let span = p.borrow().span.unwrap();
+    assert_eq!(span.slice(html), Some("<p class=x>"));
+    assert_eq!((span.start.line, span.start.column), (2, 1));
+
+    let text = p.borrow().children()[0].clone();
+    assert!(matches!(text.borrow().node_type(), NodeType::Text(_)));
+    assert_eq!(text.borrow().span.unwrap().slice(html), Some("hi "));
+
+    let doctype = document.borrow().children()[0].clone();
+    assert_eq!(
+        doctype.borrow().span.unwrap().slice(html),
+        Some("<!DOCTYPE html>")
+    );
+
+    // 暗黙に補われた要素には範囲がない
+    assert!(find(&document, "body").unwrap().borrow().span.is_none());
+}
+
+#[test]
+fn test_tree_construction_errors() {
+    let (_, errors) = Parser::new("<div><span></div>x").parse_with_errors();
+    let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        ["1:1: missing-doctype", "1:12: misnested-tag </div>"]
+    );
+
+    let (_, errors) = Parser::new("<!DOCTYPE html>\n<table><tr>x</table></i>").parse_with_errors();
+    let kinds: Vec<_> = errors.iter().map(|e| e.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        [
+            ParseErrorKind::FosterParenting,
+            ParseErrorKind::UnexpectedEndTag("i".to_string()),
+        ]
+    );
+    assert_eq!(errors[0].position.line, 2);
+
+    // 字句解析のエラーもソース上の順に含まれる
+    let mut parser = Parser::new("<!DOCTYPE html><p a a>\0");
+    parser.parse();
+    let codes: Vec<_> = parser.errors().iter().map(|e| e.kind.code()).collect();
+    assert_eq!(codes, ["duplicate-attribute", "unexpected-null-character"]);
+}
@@ -0,0 +1,109 @@
+use orinium_browser::engine::html::parser::{NodeRef, NodeType, Parser};
+use orinium_browser::engine::html::tokenizer::{Token, Tokenizer, TokenizerState};
+
+/// 開始タグの直後に`state`へ切り替えて、残りのトークンを返す
+fn tokens_after_start_tag(input: &str, state: TokenizerState) -> Vec<Token<'_>> {
+    let mut tokenizer = Tokenizer::new(input);
+    assert!(matches!(
+        tokenizer.next_token(),
+        Some(Token::StartTag { .. })
+    ));
+    tokenizer.switch_to(state);
+    std::iter::from_fn(|| tokenizer.next_token()).collect()
+}
+
+fn text(s: &str) -> Token<'_> {
+    Token::Text(s.into())
+}
+
+fn end_tag(name: &str) -> Token<'static> {
+    Token::EndTag {
+        name: name.to_string().into(),
+    }
+}
+
+/// 最初に見つかった`tag_name`要素の子ノードを返す
+fn children_of(node: &NodeRef, tag_name: &str) -> Option<Vec<NodeRef>> {
+    let n = node.borrow();
+    if n.tag_name() == Some(tag_name) {
+        return Some(n.children().to_vec());
+    }
+    n.children().iter().find_map(|c| children_of(c, tag_name))
+}
+
+fn only_text_of(html: &str, tag_name: &str) -> String {
+    let document = Parser::new(html).parse();
+    let children = children_of(&document, tag_name).expect("element not found");
+    assert_eq!(children.len(), 1, "expected a single text node");
+    let child = children[0].borrow();
+    match child.node_type() {
+        NodeType::Text(data) => data.clone(),
+        other => panic!("unexpected node: {other:?}"),
+    }
+}
+
+#[test]
+fn test_rawtext() {
+    assert_eq!(
+        tokens_after_start_tag(
+            "<style>a > b { content: '&amp;</p>' }</style>x",
+            TokenizerState::Rawtext
+        ),
+        vec![
+            text("a > b { content: '&amp;</p>' }"),
+            end_tag("style"),
+            text("x")
+        ]
+    );
+    // 大文字小文字を区別せず、属性付きの終了タグでも終わる
+    assert_eq!(
+        tokens_after_start_tag("<style>x</STYLE foo>", TokenizerState::Rawtext),
+        vec![text("x"), end_tag("style")]
+    );
+    // 閉じられていない場合は末尾まで
+    assert_eq!(
+        tokens_after_start_tag("<style>a</sty", TokenizerState::Rawtext),
+        vec![text("a</sty")]
+    );
+}
+
+#[test]
+fn test_rcdata_decodes_character_references() {
+    assert_eq!(
+        tokens_after_start_tag("<title>a &lt;b&gt; <i></title>", TokenizerState::Rcdata),
+        vec![text("a <b> <i>"), end_tag("title")]
+    );
+    assert_eq!(
+        only_text_of("<textarea>\n<p>&amp;</p></textarea>", "textarea"),
+        "<p>&</p>"
+    );
+}
+
+#[test]
+fn test_script_data() {
+    assert_eq!(
+        only_text_of("<script>if (a < b && c) { x = '</p>'; }</script>", "script"),
+        "if (a < b && c) { x = '</p>'; }"
+    );
+    // エスケープされた中の `</script>` では終わる
+    assert_eq!(
+        only_text_of("<script><!-- a </script>b", "script"),
+        "<!-- a "
+    );
+    // 二重エスケープ（`<!--` の中の `<script>`）の間は `</script>` で終わらない
+    assert_eq!(
+        only_text_of(
+            "<script><!--<script>x</script>y--></script><p>after",
+            "script"
+        ),
+        "<!--<script>x</script>y-->"
+    );
+}
+
+#[test]
+fn test_plaintext() {
+    assert_eq!(
+        only_text_of("<plaintext><b>x</plaintext>&amp;", "plaintext"),
+        "<b>x</plaintext>&amp;"
+    );
+}
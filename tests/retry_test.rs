@@ -0,0 +1,189 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::{http_response, spawn_http_server};
+use orinium_browser::platform::network::{NetworkCore, NetworkError, RetryCondition, RetryPolicy};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+fn fast_policy() -> RetryPolicy {
+    RetryPolicy {
+        initial_backoff: Duration::from_millis(20),
+        jitter: false,
+        ..RetryPolicy::default()
+    }
+}
+
+/// 最初の `drops` 回の接続はリクエストを読んだ後に応答せず切断するサーバー
+async fn spawn_flaky_server(drops: usize, connections: Arc<AtomicUsize>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let n = connections.fetch_add(1, Ordering::SeqCst);
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            if n < drops {
+                continue;
+            }
+            let response = http_response("200 OK", &[], "ok");
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    port
+}
+
+#[test]
+fn test_backoff_grows_exponentially_up_to_max() {
+    let policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(300),
+        backoff_multiplier: 2.0,
+        jitter: false,
+        ..RetryPolicy::default()
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(300));
+    assert_eq!(policy.backoff(10), Duration::from_millis(300));
+
+    let jittered = RetryPolicy {
+        jitter: true,
+        ..policy
+    };
+    for _ in 0..20 {
+        let delay = jittered.backoff(1);
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_only_idempotent_methods_retry_after_send() {
+    let policy = RetryPolicy::default();
+    assert!(policy.allows(RetryCondition::ConnectionReset, "get"));
+    assert!(!policy.allows(RetryCondition::ConnectionReset, "POST"));
+    // 接続前の失敗ならリクエストは送られていない
+    assert!(policy.allows(RetryCondition::ConnectFailed, "POST"));
+}
+
+#[tokio::test]
+async fn test_dropped_connection_is_retried() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let port = spawn_flaky_server(1, Arc::clone(&connections)).await;
+    let net = NetworkCore::new().unwrap();
+    net.config.write().await.retry_policy = fast_policy();
+
+    let resp = net
+        .fetch(&format!("http://127.0.0.1:{port}/"))
+        .await
+        .unwrap();
+    assert_eq!(resp.body, b"ok");
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_post_is_not_retried_after_send() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let port = spawn_flaky_server(1, Arc::clone(&connections)).await;
+    let net = NetworkCore::new().unwrap();
+    net.config.write().await.retry_policy = fast_policy();
+
+    let result = net
+        .post(
+            &format!("http://127.0.0.1:{port}/"),
+            b"x".to_vec(),
+            "text/plain",
+        )
+        .await;
+    assert!(result.is_err());
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_retry_disabled() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let port = spawn_flaky_server(1, Arc::clone(&connections)).await;
+    let net = NetworkCore::new().unwrap();
+    net.config.write().await.retry_policy = RetryPolicy::none();
+
+    assert!(net
+        .fetch(&format!("http://127.0.0.1:{port}/"))
+        .await
+        .is_err());
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_connection_refused_backs_off() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let net = NetworkCore::new().unwrap();
+    net.config.write().await.retry_policy = fast_policy();
+
+    let start = Instant::now();
+    let err = net
+        .fetch(&format!("http://127.0.0.1:{port}/"))
+        .await
+        .unwrap_err();
+    // 20ms + 40ms 待ってから3回目で諦める
+    assert!(start.elapsed() >= Duration::from_millis(60));
+    assert!(matches!(err, NetworkError::ConnectionRefused { port: p, .. } if p == port));
+}
+
+#[tokio::test]
+async fn test_closed_keep_alive_connection_is_replaced() {
+    // 1つの接続で1回だけ応答して切断するサーバー
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            let response = http_response("200 OK", &[], "fresh");
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+
+    // 再試行を無効にしていても、プールの古い接続は新しい接続に置き換えられる
+    let net = NetworkCore::new().unwrap();
+    net.config.write().await.retry_policy = RetryPolicy::none();
+    for path in ["/a", "/b", "/c"] {
+        let resp = net.fetch(&format!("http://127.0.0.1:{port}{path}")).await;
+        assert_eq!(resp.unwrap().body, b"fresh");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_offline_mode_serves_only_from_cache() {
+    let port = spawn_http_server(|_, _| {
+        http_response("200 OK", &[("Cache-Control", "max-age=0")], "cached")
+    })
+    .await;
+    let net = NetworkCore::new().unwrap();
+    let cached_url = format!("http://127.0.0.1:{port}/page");
+    net.fetch(&cached_url).await.unwrap();
+
+    net.config.write().await.offline = true;
+
+    // 期限切れでもオフライン時はキャッシュから応答する
+    let resp = net.fetch(&cached_url).await.unwrap();
+    assert_eq!(resp.body, b"cached");
+
+    let err = net
+        .fetch(&format!("http://127.0.0.1:{port}/other"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, NetworkError::Offline { ref url } if url.path() == "/other"));
+
+    let err = net
+        .post(&cached_url, vec![], "text/plain")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, NetworkError::Offline { .. }));
+}
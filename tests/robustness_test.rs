@@ -0,0 +1,220 @@
+//! 任意の入力でトークナイザー・パーサー・セレクターが panic しないことを確かめるプロパティテスト
+//!
+//! `fuzz/`のファジングで見つかった入力は、ここに固定のケースとして追加します。
+
+use orinium_browser::engine::css::selector::{SelectorErrorKind, SelectorList};
+use orinium_browser::engine::html::namespace::Namespace;
+use orinium_browser::engine::html::parser::{Node, NodeRef, Parser};
+use orinium_browser::engine::html::tokenizer::Tokenizer;
+use proptest::prelude::*;
+
+/// 木の構築規則を通りやすい、HTML の断片を並べた入力
+fn html_like() -> impl Strategy<Value = String> {
+    const TAGS: &[&str] = &[
+        "html",
+        "head",
+        "body",
+        "table",
+        "caption",
+        "colgroup",
+        "col",
+        "tbody",
+        "tr",
+        "td",
+        "th",
+        "select",
+        "option",
+        "optgroup",
+        "template",
+        "form",
+        "p",
+        "div",
+        "a",
+        "b",
+        "i",
+        "nobr",
+        "svg",
+        "math",
+        "mtext",
+        "mglyph",
+        "malignmark",
+        "annotation-xml",
+        "foreignObject",
+        "desc",
+        "title",
+        "script",
+        "style",
+        "textarea",
+        "plaintext",
+        "xmp",
+        "iframe",
+        "noscript",
+        "frameset",
+        "frame",
+        "li",
+        "dd",
+        "h1",
+        "button",
+        "image",
+        "input",
+        "hr",
+        "br",
+        "rt",
+        "rp",
+        "ruby",
+        "applet",
+        "marquee",
+        "object",
+        "listing",
+        "pre",
+        "font",
+        "center",
+        "search",
+    ];
+    let tag = prop::sample::select(TAGS);
+    let piece = prop_oneof![
+        tag.clone().prop_map(|name| format!("<{name}>")),
+        tag.clone().prop_map(|name| format!("</{name}>")),
+        tag.prop_map(|name| format!("<{name} a='1' b=x c>")),
+        Just("<!DOCTYPE html>".to_string()),
+        Just("<!--".to_string()),
+        Just("-->".to_string()),
+        Just("<![CDATA[".to_string()),
+        Just("]]>".to_string()),
+        Just("<svg/>".to_string()),
+        Just("<math><mi>".to_string()),
+        Just("<annotation-xml encoding=text/html>".to_string()),
+        Just("<font color=red>".to_string()),
+        Just("&amp;&#x110000;&#0;&notin;&no".to_string()),
+        Just("\0\r\n\t ".to_string()),
+        "[a-zA-Z<>/=\"'!&#;\\- ]{0,8}",
+    ];
+    prop::collection::vec(piece, 0..48).prop_map(|pieces| pieces.concat())
+}
+
+/// 入力を`chunk_sizes`の大きさに区切って順に与える（UTF-8 の途中で区切ることもある）
+fn feed_in_chunks(bytes: &[u8], chunk_sizes: &[usize]) {
+    let mut parser = Parser::new_streaming();
+    let mut rest = bytes;
+    for &size in chunk_sizes.iter().cycle() {
+        if rest.is_empty() {
+            break;
+        }
+        let (chunk, tail) = rest.split_at(size.min(rest.len()));
+        parser.feed(chunk);
+        rest = tail;
+    }
+    let document = parser.finish();
+    let html = document.borrow().inner_html();
+    Parser::new(&html).parse();
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn arbitrary_bytes_never_panic(
+        bytes in prop::collection::vec(any::<u8>(), 0..1024),
+        chunk_sizes in prop::collection::vec(1usize..64, 1..8),
+    ) {
+        feed_in_chunks(&bytes, &chunk_sizes);
+    }
+
+    #[test]
+    fn html_like_input_never_panics(
+        input in html_like(),
+        chunk_sizes in prop::collection::vec(1usize..64, 1..8),
+    ) {
+        let document = Parser::new(&input).parse();
+        // 書き出した HTML も解析できる
+        let html = document.borrow().inner_html();
+        Parser::new(&html).parse();
+        feed_in_chunks(input.as_bytes(), &chunk_sizes);
+
+        let mut tokenizer = Tokenizer::new(&input);
+        while tokenizer.next_token().is_some() {}
+    }
+
+    #[test]
+    fn fragments_never_panic(
+        input in html_like(),
+        context in prop::sample::select(&[
+            "body", "table", "tr", "select", "template", "title", "script", "textarea",
+            "plaintext", "html", "frameset", "svg",
+        ][..]),
+    ) {
+        let namespace = if context == "svg" { Namespace::Svg } else { Namespace::Html };
+        let context = Node::new_element(namespace, context, vec![]);
+        Parser::new_fragment(&input, &context).parse_fragment();
+    }
+
+    #[test]
+    fn selectors_never_panic(selector in "[a-z0-9 #.*>+~:()\\[\\]=\"'|^$-]{0,32}") {
+        let document = Parser::new("<div id=a class='b c'><p>x</p><p lang=en>y</p></div>").parse();
+        if let Ok(list) = SelectorList::parse(&selector) {
+            for element in document.borrow().descendant_elements() {
+                list.matches(&element.borrow());
+            }
+        }
+    }
+}
+
+/// 木の深さ（文書ノードを 0 とし、`<template>`の内容は`<template>`要素の 1 つ下として数える）
+fn max_depth(node: &Node) -> usize {
+    let mut deepest = 0;
+    let mut pending: Vec<(NodeRef, usize)> = node
+        .children()
+        .iter()
+        .map(|child| (child.clone(), 1))
+        .collect();
+    while let Some((node, depth)) = pending.pop() {
+        deepest = deepest.max(depth);
+        let node = node.borrow();
+        pending.extend(
+            node.children()
+                .iter()
+                .map(|child| (child.clone(), depth + 1)),
+        );
+        if let Some(content) = node.template_content() {
+            pending.extend(
+                content
+                    .borrow()
+                    .children()
+                    .iter()
+                    .map(|child| (child.clone(), depth + 2)),
+            );
+        }
+    }
+    deepest
+}
+
+#[test]
+fn deeply_nested_input_is_flattened() {
+    for unit in [
+        "<div>",
+        "<template>",
+        "<template><div>",
+        "<table><td>",
+        "<p><b><i><a>",
+        "<svg><foreignObject>",
+        "<math><mtext>",
+    ] {
+        let input = unit.repeat(1000);
+        let document = Parser::new(&input).parse();
+        assert!(max_depth(&document.borrow()) <= 513, "{unit}");
+        // 再帰でたどる処理もスタックを使い切らない
+        let html = document.borrow().inner_html();
+        document.borrow().clone_node(true);
+        Parser::new(&html).parse();
+    }
+}
+
+#[test]
+fn deeply_nested_selectors_are_rejected() {
+    let nested = |depth: usize| format!("{}a{}", ":is(".repeat(depth), ")".repeat(depth));
+    assert!(SelectorList::parse(&nested(32)).is_ok());
+    let error = SelectorList::parse(&nested(10_000)).unwrap_err();
+    assert_eq!(error.kind, SelectorErrorKind::NestingTooDeep);
+    let error = SelectorList::parse(&":not(".repeat(10_000)).unwrap_err();
+    assert_eq!(error.kind, SelectorErrorKind::NestingTooDeep);
+}
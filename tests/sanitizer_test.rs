@@ -0,0 +1,229 @@
+use orinium_browser::engine::html::parser::Parser;
+use orinium_browser::engine::html::sanitizer::SanitizerPolicy;
+
+fn sanitize(html: &str) -> String {
+    SanitizerPolicy::default().sanitize(html)
+}
+
+/// 出力に、スクリプトを実行しうる要素・属性・URL がないことを確かめる
+///
+/// 検査対象のパーサーで解析し直すと同じ誤りを見逃すため、文字列のまま検査します。
+/// コメントはブラウザと同じく最も早く閉じる位置（`<!-->`、`<!--->`、`-->`、`--!>`）で閉じ、
+/// それ以外の`<`はすべてタグとして許可リストと照らし合わせます（テキストと属性値の`<`・`>`は
+/// エスケープされているはずです）。
+fn assert_safe(output: &str) {
+    let allowed = SanitizerPolicy::default().allowed_elements;
+    let mut rest = output;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            let end = if comment.starts_with('>') {
+                1
+            } else if comment.starts_with("->") {
+                2
+            } else {
+                [("-->", 3), ("--!>", 4)]
+                    .iter()
+                    .filter_map(|(close, len)| comment.find(close).map(|i| i + len))
+                    .min()
+                    .unwrap_or(comment.len())
+            };
+            rest = &comment[end..];
+            continue;
+        }
+
+        let tag_end = rest
+            .find('>')
+            .unwrap_or_else(|| panic!("unclosed tag: {output}"));
+        let tag = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+        let tag = tag.strip_prefix('/').unwrap_or(tag);
+        let name_end = tag.find([' ', '/']).unwrap_or(tag.len());
+        let (name, attributes) = tag.split_at(name_end);
+        assert!(allowed.contains(name), "<{name}> in {output}");
+        let mut attributes = attributes;
+        loop {
+            let attribute = attributes.trim_start_matches([' ', '/']);
+            if attribute.is_empty() {
+                break;
+            }
+            let name_end = attribute.find([' ', '/', '=']).unwrap_or(attribute.len());
+            let (attribute_name, after_name) = attribute.split_at(name_end);
+            assert!(
+                !attribute_name.to_ascii_lowercase().starts_with("on"),
+                "{attribute_name} in {output}"
+            );
+            let Some(value) = after_name.strip_prefix('=') else {
+                attributes = after_name;
+                continue;
+            };
+            let (value, after_value) = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let quoted = &value[1..];
+                    let end = quoted.find(quote).unwrap_or(quoted.len());
+                    (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+                }
+                _ => value.split_at(value.find(' ').unwrap_or(value.len())),
+            };
+            // URL の解析ではタブと改行が取り除かれる
+            let url: String = value
+                .chars()
+                .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+                .collect();
+            assert!(
+                !url.to_ascii_lowercase().contains("javascript:"),
+                "{value} in {output}"
+            );
+            attributes = after_value;
+        }
+    }
+}
+
+#[test]
+fn test_allowed_markup_is_kept() {
+    assert_eq!(
+        sanitize("<p lang=en>Hello <b>bold</b> <a href='/x' title=t>link</a></p>"),
+        "<p lang=\"en\">Hello <b>bold</b> \
+         <a href=\"/x\" title=\"t\" rel=\"noopener noreferrer\">link</a></p>"
+    );
+    assert_eq!(
+        sanitize("<table><tr><td colspan=2>1</td></tr></table>"),
+        "<table><tbody><tr><td colspan=\"2\">1</td></tr></tbody></table>"
+    );
+    assert_eq!(sanitize("a &lt; b &amp; c"), "a &lt; b &amp; c");
+}
+
+#[test]
+fn test_disallowed_elements_and_attributes() {
+    // 許可されていない要素は子を残し、危険な要素は中身ごと取り除く
+    assert_eq!(
+        sanitize("<custom-el><b>bold</b></custom-el><form><input value=x>text</form>"),
+        "<b>bold</b>text"
+    );
+    assert_eq!(
+        sanitize("<div><script>alert(1)</script>ok<style>*{}</style></div>"),
+        "<div>ok</div>"
+    );
+    assert_eq!(
+        sanitize("<div id=x class=y style='color:red' onclick=alert(1)>x</div>"),
+        "<div>x</div>"
+    );
+    assert_eq!(sanitize("<p>a<!-- comment --></p>"), "<p>a</p>");
+    assert_eq!(sanitize("<title>t</title><textarea>x</textarea>"), "");
+}
+
+#[test]
+fn test_url_schemes() {
+    for href in [
+        "javascript:alert(1)",
+        "JaVaScRiPt:alert(1)",
+        " \u{1}javascript:alert(1)",
+        "java&#x09;script:alert(1)",
+        "java&#10;script:alert(1)",
+        "&#106;avascript:alert(1)",
+        "vbscript:msgbox(1)",
+        "data:text/html,<script>alert(1)</script>",
+        "http://[::1",
+    ] {
+        let output = sanitize(&format!("<a href=\"{href}\">x</a>"));
+        assert_eq!(output, "<a>x</a>", "{href}");
+    }
+    for href in [
+        "https://example.com/",
+        "mailto:a@example.com",
+        "page.html",
+        "#top",
+        "?q=1",
+    ] {
+        let output = sanitize(&format!("<a href=\"{href}\">x</a>"));
+        assert!(output.contains("href="), "{href}");
+    }
+
+    let mut policy = SanitizerPolicy::default();
+    policy.allowed_url_schemes.insert("data".to_string());
+    policy.link_rel = None;
+    assert_eq!(
+        policy.sanitize("<img src='data:image/png;base64,AA=='><a href=javascript:x>x</a>"),
+        "<img src=\"data:image/png;base64,AA==\"><a>x</a>"
+    );
+}
+
+#[test]
+fn test_mutation_xss_vectors() {
+    let vectors = [
+        "<img src=x onerror=alert(1)>",
+        "<noscript><p title=\"</noscript><img src=x onerror=alert(1)>\">",
+        "<svg><style><img src=x onerror=alert(1)></style></svg>",
+        "<math><mtext><table><mglyph><style><img src=x onerror=alert(1)>",
+        "<math><mtext><table><mglyph><style><!--</style><img title=\"--&gt;&lt;/mglyph&gt;&lt;img&Tab;src=1&Tab;onerror=alert(1)&gt;\">",
+        "<form><math><mtext></form><form><mglyph><style></math><img src onerror=alert(1)>",
+        "<svg></p><style><a id=\"</style><img src=1 onerror=alert(1)>\">",
+        "<svg><p><style><a id=\"</style><img src=1 onerror=alert(1)>\"></p></svg>",
+        "<table><a href=javascript:alert(1)>x</a><td>y</table>",
+        "<xmp><img src=x onerror=alert(1)></xmp>",
+        "<listing>&lt;img src=x onerror=alert(1)&gt;</listing>",
+        "<template><img src=x onerror=alert(1)></template>",
+        "<p>x<!--<img src=x onerror=alert(1)>--></p>",
+        "<!--><img src=x onerror=alert(1)>-->",
+        "<!---><img src=x onerror=alert(1)>-->",
+        "<!-- x ---> <img src=x onerror=alert(1)> -->",
+        "<!-- a --!> <img src=x onerror=alert(1)> -->",
+        "<!-- a --!x <img src=x onerror=alert(1)> -->",
+        "<iframe srcdoc=\"<script>alert(1)</script>\"></iframe>",
+        "<a href=\"&#x6A;avascript&colon;alert(1)\">x</a>",
+        "<select><template><style><img src=x onerror=alert(1)>",
+        "<plaintext><img src=x onerror=alert(1)>",
+    ];
+    let policies = [
+        SanitizerPolicy::default(),
+        SanitizerPolicy {
+            allow_comments: true,
+            ..Default::default()
+        },
+    ];
+    for vector in vectors {
+        for policy in &policies {
+            let output = policy.sanitize(vector);
+            assert_safe(&output);
+            // サニタイズした結果は、もう一度サニタイズしても変わらない
+            assert_eq!(policy.sanitize(&output), output, "{vector}");
+        }
+    }
+}
+
+#[test]
+fn test_comments_that_could_close_early_are_removed() {
+    let policy = SanitizerPolicy {
+        allow_comments: true,
+        ..Default::default()
+    };
+    assert_eq!(policy.sanitize("<p>a<!-- b --></p>"), "<p>a<!-- b --></p>");
+    for comment in [
+        "<!-- a > b -->",
+        "<!-- a -- b -->",
+        "<!-- <!-x -->",
+        "<!-- a--->",
+    ] {
+        assert_eq!(policy.sanitize(&format!("<p>{comment}</p>")), "<p></p>");
+    }
+}
+
+#[test]
+fn test_sanitize_tree_in_place() {
+    let document = Parser::new("<div id=root><p onclick=x>a<script>b</script></p></div>").parse();
+    let root = document.borrow().get_element_by_id("root").unwrap();
+    SanitizerPolicy::default().sanitize_tree(&root);
+    assert_eq!(
+        root.borrow().outer_html(),
+        "<div id=\"root\"><p>a</p></div>"
+    );
+}
+
+#[test]
+fn test_text_only_policy() {
+    let policy = SanitizerPolicy::text_only();
+    assert_eq!(
+        policy.sanitize("<h1>Title</h1><p>a <b>&lt;b&gt;</b><script>x</script></p>"),
+        "Titlea &lt;b&gt;"
+    );
+}
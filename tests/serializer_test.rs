@@ -0,0 +1,96 @@
+use orinium_browser::engine::html::parser::{NodeRef, Parser};
+
+/// 最初に見つかった`tag_name`要素を返す
+fn find(node: &NodeRef, tag_name: &str) -> Option<NodeRef> {
+    if node.borrow().tag_name() == Some(tag_name) {
+        return Some(node.clone());
+    }
+    node.borrow()
+        .children()
+        .iter()
+        .find_map(|c| find(c, tag_name))
+}
+
+fn serialize(html: &str) -> String {
+    Parser::new(html).parse().borrow().inner_html()
+}
+
+#[test]
+fn test_serialize_document() {
+    assert_eq!(
+        serialize("<!DOCTYPE html><title>a&amp;b</title><p class=x>One<br>Two<!--c-->"),
+        "<!DOCTYPE html><html><head><title>a&amp;b</title></head><body><p class=\"x\">One<br>Two<!--c--></p></body></html>"
+    );
+}
+
+#[test]
+fn test_escaping() {
+    let document = Parser::new(
+        "<p title='&quot;1 &lt; 2&quot; &amp; more'>x &lt; y &gt; z &amp;&nbsp;\"q\"</p>",
+    )
+    .parse();
+    let p = find(&document, "p").unwrap();
+    assert_eq!(
+        p.borrow().outer_html(),
+        "<p title=\"&quot;1 &lt; 2&quot; &amp; more\">x &lt; y &gt; z &amp;&nbsp;\"q\"</p>"
+    );
+
+    // script や style の中はエスケープしない。textarea はエスケープする
+    let document = Parser::new(
+        "<script>if (a < b && c) {}</script><style>a > b {}</style><textarea><b></textarea>",
+    )
+    .parse();
+    let head = find(&document, "head").unwrap();
+    assert_eq!(
+        head.borrow().inner_html(),
+        "<script>if (a < b && c) {}</script><style>a > b {}</style>"
+    );
+    let script = find(&document, "script").unwrap();
+    let text = script.borrow().children()[0].clone();
+    assert_eq!(text.borrow().outer_html(), "if (a < b && c) {}");
+    let textarea = find(&document, "textarea").unwrap();
+    assert_eq!(textarea.borrow().inner_html(), "&lt;b&gt;");
+}
+
+#[test]
+fn test_serialize_foreign_and_template() {
+    let document = Parser::new(
+        "<svg viewbox='0 0 1 1'><foreignobject><img src=a.png></foreignobject><use xlink:href='#a'/></svg><template><td>x</td></template>",
+    )
+    .parse();
+    let body = find(&document, "body").unwrap();
+    assert_eq!(
+        body.borrow().inner_html(),
+        "<svg viewBox=\"0 0 1 1\"><foreignObject><img src=\"a.png\"></foreignObject><use xlink:href=\"#a\"></use></svg><template><td>x</td></template>"
+    );
+    let template = find(&document, "template").unwrap();
+    assert_eq!(template.borrow().inner_html(), "<td>x</td>");
+}
+
+#[test]
+fn test_round_trip() {
+    for html in [
+        "<!DOCTYPE html><table><tr><td>1<td>2</table><ul><li>a<li>b</ul>",
+        "<p>a<b>b<i>c</b>d</i>e</p><pre>x\n</pre><select><option selected>1</select>",
+        "<math><mi>x</mi><annotation-xml encoding='text/html'><div>&amp;</div></annotation-xml></math>",
+    ] {
+        let once = serialize(html);
+        assert_eq!(serialize(&once), once, "{html:?}");
+    }
+}
+
+#[test]
+fn test_leading_newline_in_pre_is_not_added() {
+    // ブラウザと同じく、開始タグ直後の改行を補わない。
+    // 解析時に読み飛ばされるため、書き出したものを再び解析すると先頭の改行は失われる
+    let html = "<pre>\n\nx</pre><textarea>\n\ny</textarea>";
+    let once = serialize(html);
+    assert_eq!(
+        once,
+        "<html><head></head><body><pre>\nx</pre><textarea>\ny</textarea></body></html>"
+    );
+    assert_eq!(
+        serialize(&once),
+        "<html><head></head><body><pre>x</pre><textarea>y</textarea></body></html>"
+    );
+}
@@ -0,0 +1,123 @@
+use orinium_browser::engine::html::parser::{NodeRef, NodeType, Parser};
+use orinium_browser::engine::html::tokenizer::{Token, Tokenizer};
+
+/// ノードの種類、内容、範囲をすべて書き出す
+fn dump(node: &NodeRef, out: &mut String) {
+    let node = node.borrow();
+    let span = node.span.map(|s| (s.start.offset, s.end.offset));
+    match node.node_type() {
+        NodeType::Document => out.push_str("#document"),
+        NodeType::DocumentFragment => out.push_str("#document-fragment"),
+        NodeType::Element {
+            tag_name,
+            attributes,
+            ..
+        } => {
+            out.push_str(&format!("<{tag_name}"));
+            for attr in attributes {
+                out.push_str(&format!(" {}={:?}", attr.name, attr.value));
+            }
+            out.push('>');
+        }
+        NodeType::Text(text) => out.push_str(&format!("{text:?}")),
+        NodeType::Comment(data) => out.push_str(&format!("<!--{data}-->")),
+        NodeType::Doctype { name, .. } => out.push_str(&format!("<!DOCTYPE {name:?}>")),
+    }
+    out.push_str(&format!("{span:?}("));
+    for child in node.children() {
+        dump(child, out);
+    }
+    if let Some(content) = node.template_content() {
+        dump(&content, out);
+    }
+    out.push(')');
+}
+
+fn parse_in_chunks(chunks: &[&[u8]]) -> String {
+    let mut parser = Parser::new_streaming();
+    for chunk in chunks {
+        parser.feed(chunk);
+    }
+    let document = parser.finish();
+    let mut out = String::new();
+    dump(&document, &mut out);
+    for error in parser.errors() {
+        out.push_str(&format!("\n{error}"));
+    }
+    out
+}
+
+const DOCUMENTS: &[&str] = &[
+    "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\">\n<title>a &amp; b</title><p class='x' id=y>caf\u{e9} &eacute;&#x41;&#66;&notit; \u{1F600}</p>",
+    "<table><tr><td>1<td>2</table>x<!-- comment --><br/>&lt",
+    "<script>if (a < b) { document.write('</p>'); }</script><textarea>\nvalue</textarea><pre>\n\ntext</pre>",
+    "<!doctype html><body><a href=\"?a=1&copy=2\">link</a><b><i>x</b>y</i><plaintext></plaintext>&amp;",
+    "<template><tr><td>1</template><table><template>x</template></table>",
+    "<svg viewbox='0 0 1 1'><![CDATA[a]b]]c]]><foreignobject><p>x</svg><math><mi/></math><![CDATA[y]]>",
+    "<DIV Data-Role=Main CLASS=\"a b\" x-Custom_attr:1='q&quot;'><My-Element lang=EN\u{3000}x>t\0ext</MY-element><!-- a - b -- c --></div>",
+    "<!--><!---><!-- a ---><!-- b --!><!-- <!-- c --!x --><!-x><p>",
+];
+
+#[test]
+fn test_every_split_point_matches_whole_input() {
+    for html in DOCUMENTS {
+        let bytes = html.as_bytes();
+        let expected = parse_in_chunks(&[bytes]);
+        for split in 0..=bytes.len() {
+            let (a, b) = bytes.split_at(split);
+            assert_eq!(
+                parse_in_chunks(&[a, b]),
+                expected,
+                "split at {split} in {html:?}"
+            );
+        }
+        let single_bytes: Vec<&[u8]> = bytes.chunks(1).collect();
+        assert_eq!(parse_in_chunks(&single_bytes), expected, "{html:?}");
+    }
+}
+
+#[test]
+fn test_partial_document() {
+    let mut parser = Parser::new_streaming();
+    parser.feed(b"<!DOCTYPE html><ul><li>one<li>tw");
+    let mut out = String::new();
+    dump(&parser.document(), &mut out);
+    // 届いた分のテキストは途中でも挿入される
+    assert!(
+        out.contains(
+            r#"<li>Some((19, 23))("one"Some((23, 26))())<li>Some((26, 30))("tw"Some((30, 32))())"#
+        ),
+        "{out}"
+    );
+
+    parser.feed(b"o</ul>");
+    let document = parser.finish();
+    let mut out = String::new();
+    dump(&document, &mut out);
+    assert!(
+        out.contains(r#"<li>Some((26, 30))("two"Some((30, 33))())"#),
+        "{out}"
+    );
+}
+
+#[test]
+fn test_tokenizer_waits_for_incomplete_input() {
+    let mut tokenizer = Tokenizer::new_streaming();
+    tokenizer.feed(b"x<di");
+    assert_eq!(tokenizer.next_token(), Some(Token::Text("x".into())));
+    assert_eq!(tokenizer.next_token(), None);
+    tokenizer.feed(b"v>&am");
+    assert!(matches!(tokenizer.next_token(), Some(Token::StartTag { name, .. }) if name == "div"));
+    assert_eq!(tokenizer.next_token(), None);
+    // 文字の途中で途切れたバイト列は続きを待つ
+    tokenizer.feed(&"p;\u{3042}".as_bytes()[..4]);
+    assert_eq!(tokenizer.next_token(), Some(Token::Text("&".into())));
+    tokenizer.feed(&"\u{3042}".as_bytes()[2..]);
+    tokenizer.feed(b"\xFF");
+    tokenizer.finish();
+    assert_eq!(
+        tokenizer.next_token(),
+        Some(Token::Text("\u{3042}\u{FFFD}".into()))
+    );
+    assert_eq!(tokenizer.next_token(), None);
+}
@@ -0,0 +1,76 @@
+use orinium_browser::engine::html::mutation;
+use orinium_browser::engine::html::parser::{NodeRef, NodeType, Parser};
+use std::rc::Rc;
+
+/// 最初に見つかった`tag_name`要素を返す（template の内容も探す）
+fn find(node: &NodeRef, tag_name: &str) -> Option<NodeRef> {
+    if node.borrow().tag_name() == Some(tag_name) {
+        return Some(node.clone());
+    }
+    let node = node.borrow();
+    node.children()
+        .iter()
+        .chain(node.template_content().as_ref())
+        .find_map(|c| find(c, tag_name))
+}
+
+fn text_content(node: &NodeRef) -> String {
+    match node.borrow().node_type() {
+        NodeType::Text(text) => text.clone(),
+        _ => node.borrow().children().iter().map(text_content).collect(),
+    }
+}
+
+#[test]
+fn test_template_content_is_a_separate_fragment() {
+    let document =
+        Parser::new("<!DOCTYPE html><template id=row><tr><td>cell</td></tr></template><p>after")
+            .parse();
+    let template = find(&document, "template").unwrap();
+    let head = template.borrow().parent().unwrap();
+    assert_eq!(head.borrow().tag_name(), Some("head"));
+
+    // 内容は子ではなく DocumentFragment に入る
+    assert!(template.borrow().children().is_empty());
+    let content = template.borrow().template_content().unwrap();
+    assert!(matches!(
+        content.borrow().node_type(),
+        NodeType::DocumentFragment
+    ));
+    let tr = content.borrow().children()[0].clone();
+    assert_eq!(tr.borrow().tag_name(), Some("tr"));
+    assert!(Rc::ptr_eq(&tr.borrow().parent().unwrap(), &content));
+    assert_eq!(text_content(&content), "cell");
+
+    // テンプレートの後は通常どおり body に入る
+    let p = find(&document, "p").unwrap();
+    let parent = p.borrow().parent().unwrap();
+    assert_eq!(parent.borrow().tag_name(), Some("body"));
+
+    // template 以外の要素は内容を持たない
+    assert!(p.borrow().template_content().is_none());
+}
+
+#[test]
+fn test_clone_template_content() {
+    let document = Parser::new("<template><b>x</b>y</template>").parse();
+    let template = find(&document, "template").unwrap();
+    let content = template.borrow().template_content().unwrap();
+
+    let clone = content.borrow().clone_node(true);
+    assert!(clone.borrow().parent().is_none());
+    assert_eq!(text_content(&clone), "xy");
+    // 複製を変更しても元の内容は変わらない
+    mutation::set_text_content(&clone, "");
+    assert_eq!(text_content(&content), "xy");
+
+    // template 要素を深く複製すると内容も複製される
+    let template_clone = template.borrow().clone_node(true);
+    let cloned_content = template_clone.borrow().template_content().unwrap();
+    assert!(!Rc::ptr_eq(&cloned_content, &content));
+    assert_eq!(text_content(&cloned_content), "xy");
+
+    let shallow = template.borrow().clone_node(false);
+    let shallow_content = shallow.borrow().template_content().unwrap();
+    assert!(shallow_content.borrow().children().is_empty());
+}
@@ -0,0 +1,196 @@
+use orinium_browser::engine::html::parser::{
+    is_void_element, NodeRef, NodeType, Parser, QuirksMode,
+};
+
+/// 要素とテキストだけを `html(head,body(p("x")))` の形に書き出す
+fn outline(node: &NodeRef) -> String {
+    let node = node.borrow();
+    let children = || {
+        node.children()
+            .iter()
+            .map(outline)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    match node.node_type() {
+        NodeType::Document | NodeType::DocumentFragment => children(),
+        NodeType::Element { tag_name, .. } => {
+            let inner = children();
+            if inner.is_empty() {
+                tag_name.clone()
+            } else {
+                format!("{tag_name}({inner})")
+            }
+        }
+        NodeType::Text(text) if text.trim().is_empty() => String::new(),
+        NodeType::Text(text) => format!("{text:?}"),
+        NodeType::Comment(_) | NodeType::Doctype { .. } => String::new(),
+    }
+}
+
+fn parse(html: &str) -> String {
+    outline(&Parser::new(html).parse())
+}
+
+#[test]
+fn test_implied_html_head_body() {
+    assert_eq!(parse(""), "html(head,body)");
+    assert_eq!(parse("Hello"), r#"html(head,body("Hello"))"#);
+    assert_eq!(
+        parse("<title>t</title><p>x"),
+        r#"html(head(title("t")),body(p("x")))"#
+    );
+    // 大文字のタグ名は小文字になる
+    assert_eq!(parse("<HTML><BODY><DIV>x"), r#"html(head,body(div("x")))"#);
+}
+
+#[test]
+fn test_p_is_closed_implicitly() {
+    assert_eq!(
+        parse("<p>a<p>b<div>c</div>"),
+        r#"html(head,body(p("a"),p("b"),div("c")))"#
+    );
+    assert_eq!(parse("<p>a</p></p>"), r#"html(head,body(p("a"),p))"#);
+    assert_eq!(
+        parse("<ul><li>a<li>b<ul><li>c</ul></ul>"),
+        r#"html(head,body(ul(li("a"),li("b",ul(li("c"))))))"#
+    );
+    assert_eq!(
+        parse("<dl><dt>a<dd>b<dt>c</dl>"),
+        r#"html(head,body(dl(dt("a"),dd("b"),dt("c"))))"#
+    );
+}
+
+#[test]
+fn test_void_elements() {
+    assert_eq!(
+        parse("<p>a<br>b<img src=x>c</p>"),
+        r#"html(head,body(p("a",br,"b",img,"c")))"#
+    );
+    // `</br>` は `<br>` として扱う
+    assert_eq!(parse("a</br>b"), r#"html(head,body("a",br,"b"))"#);
+}
+
+#[test]
+fn test_adoption_agency() {
+    assert_eq!(parse("<b><p>x</b>y"), r#"html(head,body(b,p(b("x"),"y")))"#);
+    assert_eq!(
+        parse("<b>1<i>2</b>3</i>4"),
+        r#"html(head,body(b("1",i("2")),i("3"),"4"))"#
+    );
+    // 閉じられていない書式要素は次の段落で作り直される
+    assert_eq!(
+        parse("<p><b>a<p>b"),
+        r#"html(head,body(p(b("a")),p(b("b"))))"#
+    );
+}
+
+#[test]
+fn test_tables() {
+    assert_eq!(
+        parse("<table><td>x</table>"),
+        r#"html(head,body(table(tbody(tr(td("x"))))))"#
+    );
+    // テーブル内の不正な内容はテーブルの前に移動する
+    assert_eq!(
+        parse("<table>a<tr><td>b</td>c</tr></table>"),
+        r#"html(head,body("ac",table(tbody(tr(td("b"))))))"#
+    );
+    assert_eq!(
+        parse("<table><div>x</div><tr><td>y</table>"),
+        r#"html(head,body(div("x"),table(tbody(tr(td("y"))))))"#
+    );
+}
+
+#[test]
+fn test_quirks_mode() {
+    let quirks_mode = |html: &str| {
+        let mut parser = Parser::new(html);
+        parser.parse();
+        parser.quirks_mode()
+    };
+    assert_eq!(quirks_mode("<!DOCTYPE html><p>"), QuirksMode::NoQuirks);
+    assert_eq!(quirks_mode("<p>"), QuirksMode::Quirks);
+}
+
+#[test]
+fn test_void_elements_do_not_take_children() {
+    assert_eq!(
+        parse("<head><meta charset=utf-8><link rel=x><title>t</title></head>"),
+        r#"html(head(meta,link,title("t")),body)"#
+    );
+    assert_eq!(
+        parse("<input>a<hr>b<wbr>c<embed>d<area>e"),
+        r#"html(head,body(input,"a",hr,"b",wbr,"c",embed,"d",area,"e"))"#
+    );
+    // 空要素の終了タグは無視する
+    assert_eq!(parse("<img>a</img>b"), r#"html(head,body(img,"ab"))"#);
+    // 空要素でない要素の `/>` は無視する
+    assert_eq!(parse("<div/>a"), r#"html(head,body(div("a")))"#);
+    assert_eq!(
+        parse("<table><col><col></table>"),
+        r#"html(head,body(table(colgroup(col,col))))"#
+    );
+    assert!(is_void_element("br"));
+    assert!(is_void_element("source"));
+    assert!(!is_void_element("div"));
+}
+
+#[test]
+fn test_stray_br_end_tag_before_body() {
+    // head の中の `</br>` は head と body を暗黙に生成する
+    assert_eq!(parse("<head></br>x"), r#"html(head,body(br,"x"))"#);
+    // その他の余分な終了タグは無視する
+    assert_eq!(parse("</div></p>x"), r#"html(head,body("x"))"#);
+    assert_eq!(parse("<body></div></span>x"), r#"html(head,body("x"))"#);
+    // body の中の `</p>` だけは空の段落を作る
+    assert_eq!(parse("<body></p>x"), r#"html(head,body(p,"x"))"#);
+}
+
+#[test]
+fn test_implied_end_tags() {
+    assert_eq!(
+        parse("<select><option>a<option>b<optgroup><option>c<optgroup><option>d</select>"),
+        r#"html(head,body(select(option("a"),option("b"),optgroup(option("c")),optgroup(option("d")))))"#
+    );
+    assert_eq!(
+        parse("<ruby>a<rb>b<rt>c<rp>(<rt>d</ruby>"),
+        r#"html(head,body(ruby("a",rb("b"),rt("c"),rp("("),rt("d"))))"#
+    );
+    assert_eq!(
+        parse("<table><tr><td>a<td>b<tr><th>c</table>"),
+        r#"html(head,body(table(tbody(tr(td("a"),td("b")),tr(th("c"))))))"#
+    );
+    // 終了タグは暗黙に閉じられる要素を越えて閉じる
+    assert_eq!(
+        parse("<div><p>a<li>b</div>c"),
+        r#"html(head,body(div(p("a"),li("b")),"c"))"#
+    );
+    // リスト項目の終了タグはリストの外には届かない
+    assert_eq!(
+        parse("<li><ul><li>a</li></ul>b</li>"),
+        r#"html(head,body(li(ul(li("a")),"b")))"#
+    );
+}
+
+#[test]
+fn test_tag_and_attribute_name_characters() {
+    // 空白・`/`・`>` 以外はすべて名前の一部（小文字にするのは ASCII だけなので、
+    // `</CAFÉ>` は `<café>` を閉じない）
+    assert_eq!(
+        parse("<my.el>t</my.el><café>x</CAFÉ><P\u{3000}x>y"),
+        "html(head,body(my.el(\"t\"),café(\"x\",p\u{3000}x(\"y\"))))"
+    );
+    assert_eq!(parse("<p\x0Cclass=a\r>x"), r#"html(head,body(p("x")))"#);
+
+    let document = Parser::new("<div foo.bar=1 @click=go :x=2 a\0b=3 CAFÉ=4>").parse();
+    let body = document.borrow().children()[0].borrow().children()[1].clone();
+    let div = body.borrow().children()[0].clone();
+    let div = div.borrow();
+    let NodeType::Element { attributes, .. } = div.node_type() else {
+        panic!();
+    };
+    let names: Vec<_> = attributes.iter().map(|a| a.name.as_ref()).collect();
+    assert_eq!(names, ["foo.bar", "@click", ":x", "a\u{FFFD}b", "cafÉ"]);
+}